@@ -0,0 +1,120 @@
+use crate::precision::Real;
+use crate::{pixel_to_point, progress, render, render_bands, Renderer};
+use num::Complex;
+
+/// Pixels of an image with dimensions `bounds`, `channels` bytes each, that differ from one of
+/// their neighbors by more than `threshold` in any channel: the edges that supersampling
+/// smooths, unlike the flat regions inside and far outside the set.
+pub fn edge_pixels(
+    pixels: &[u8],
+    bounds: (usize, usize),
+    channels: usize,
+    threshold: u8,
+) -> Vec<bool> {
+    let at = |x: usize, y: usize| &pixels[(y * bounds.0 + x) * channels..][..channels];
+    let differs = |a: &[u8], b: &[u8]| a.iter().zip(b).any(|(a, b)| a.abs_diff(*b) > threshold);
+    (0..bounds.0 * bounds.1)
+        .map(|i| {
+            let (x, y) = (i % bounds.0, i / bounds.0);
+            let pixel = at(x, y);
+            (x > 0 && differs(pixel, at(x - 1, y)))
+                || (x + 1 < bounds.0 && differs(pixel, at(x + 1, y)))
+                || (y > 0 && differs(pixel, at(x, y - 1)))
+                || (y + 1 < bounds.1 && differs(pixel, at(x, y + 1)))
+        })
+        .collect()
+}
+
+#[test]
+fn test_edge_pixels() {
+    let pixels = [0, 0, 10, 200, 0, 0];
+    assert_eq!(
+        edge_pixels(&pixels, (3, 2), 1, 16),
+        [true, false, false, true, true, false]
+    );
+    assert!(edge_pixels(&pixels, (3, 2), 1, 255).iter().all(|&e| !e));
+    // Two pixels of three channels each, differing in blue only.
+    assert_eq!(
+        edge_pixels(&[1, 2, 3, 1, 2, 90], (2, 1), 3, 16),
+        [true, true]
+    );
+}
+
+/// Render again, as `renderer` describes, the pixels marked in `refine` of the image `pixels`
+/// with dimensions `bounds` between `upper_left` and `lower_right`, on `threads` threads. Meant
+/// for rendering the edges found by `edge_pixels` in an image taken at a single sample per pixel
+/// with all of `renderer.samples`. Returns the chunks of rows that failed, as `render_bands`
+/// does.
+#[allow(clippy::too_many_arguments)]
+pub fn refine<R: Real>(
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<R>,
+    lower_right: Complex<R>,
+    renderer: &Renderer,
+    refine: &[bool],
+    threads: usize,
+    progress: Option<&progress::Progress>,
+) -> Vec<(usize, usize)> {
+    let channels = renderer.channels();
+    let mut slots: Vec<_> = pixels.chunks_mut(channels).zip(refine).collect();
+    render_bands(
+        &mut slots,
+        bounds,
+        upper_left,
+        lower_right,
+        threads,
+        progress,
+        None,
+        |band, band_bounds, band_upper_left, band_lower_right| {
+            for (i, (pixel, &refine)) in band.iter_mut().enumerate() {
+                if refine {
+                    let (x, y) = (i % band_bounds.0, i / band_bounds.0);
+                    let corner = |x, y| {
+                        pixel_to_point(band_bounds, (x, y), band_upper_left, band_lower_right)
+                    };
+                    render(pixel, (1, 1), corner(x, y), corner(x + 1, y + 1), renderer);
+                }
+            }
+        },
+    )
+}
+
+#[test]
+fn test_refine() {
+    let renderer = Renderer {
+        samples: (3, 3),
+        ..Renderer::default()
+    };
+    let single = Renderer::default();
+    let bounds = (60, 40);
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let (mut pixels, _) = single.render_image(bounds, upper_left, lower_right, 2);
+    let edges = edge_pixels(&pixels, bounds, 1, 8);
+    let refined = edges.iter().filter(|&&e| e).count();
+    assert!(0 < refined && refined < bounds.0 * bounds.1);
+    let failed = refine(
+        &mut pixels,
+        bounds,
+        upper_left,
+        lower_right,
+        &renderer,
+        &edges,
+        2,
+        None,
+    );
+    assert!(failed.is_empty());
+    // The others are left alone, and the refined pixels are those of a supersampled render,
+    // but for the odd sample whose point rounds differently when found from the pixel's corner.
+    let (full, _) = renderer.render_image(bounds, upper_left, lower_right, 2);
+    let (flat, _) = single.render_image(bounds, upper_left, lower_right, 2);
+    let mut mismatched = 0;
+    for (i, &edge) in edges.iter().enumerate() {
+        if edge {
+            mismatched += (pixels[i] != full[i]) as usize;
+        } else {
+            assert_eq!(pixels[i], flat[i]);
+        }
+    }
+    assert!(mismatched * 20 < refined);
+}
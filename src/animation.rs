@@ -0,0 +1,129 @@
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use std::fs::File;
+use std::io::{BufWriter, Error, ErrorKind};
+
+/// File formats that hold a whole animation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Container {
+    Gif,
+    Apng,
+}
+
+impl Container {
+    /// The format for a file name ending in `.gif`, or in `.apng` or `.png`.
+    pub fn from_filename(filename: &str) -> Option<Container> {
+        let extension = std::path::Path::new(filename).extension()?.to_str()?;
+        match extension.to_ascii_lowercase().as_str() {
+            "gif" => Some(Container::Gif),
+            "apng" | "png" => Some(Container::Apng),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Container::Gif => "GIF",
+            Container::Apng => "APNG",
+        }
+    }
+}
+
+#[test]
+fn test_container_from_filename() {
+    assert_eq!(Container::from_filename("zoom.GIF"), Some(Container::Gif));
+    assert_eq!(Container::from_filename("zoom.apng"), Some(Container::Apng));
+    assert_eq!(Container::from_filename("zoom.png"), Some(Container::Apng));
+    assert_eq!(Container::from_filename("zoom.mp4"), None);
+}
+
+/// Write `frames`, each the pixels of an image with dimensions `bounds` as `write_image` takes
+/// them, to the file named `filename` as an animation in `container` playing `fps` frames per
+/// second and looping forever.
+pub fn write_animation(
+    filename: &str,
+    container: Container,
+    frames: &[Vec<u8>],
+    bounds: (usize, usize),
+    fps: u16,
+) -> Result<(), Error> {
+    let output = BufWriter::new(File::create(filename)?);
+    let channels = frames
+        .first()
+        .map_or(1, |pixels| pixels.len() / (bounds.0 * bounds.1).max(1));
+    match container {
+        Container::Gif => {
+            // Sampling every tenth pixel to pick each frame's colors is much faster than looking
+            // at all of them, and hardly worse.
+            let mut encoder = GifEncoder::new_with_speed(output, 10);
+            let gif_error = |e: image::ImageError| Error::other(e);
+            encoder.set_repeat(Repeat::Infinite).map_err(gif_error)?;
+            for pixels in frames {
+                let rgba = pixels
+                    .chunks(channels)
+                    .flat_map(|pixel| match *pixel {
+                        [gray] => [gray, gray, gray, 255],
+                        [gray, alpha] => [gray, gray, gray, alpha],
+                        [red, green, blue] => [red, green, blue, 255],
+                        [red, green, blue, alpha, ..] => [red, green, blue, alpha],
+                        [] => [0; 4],
+                    })
+                    .collect();
+                let image = RgbaImage::from_raw(bounds.0 as u32, bounds.1 as u32, rgba)
+                    .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "frame size mismatch"))?;
+                let delay = Delay::from_numer_denom_ms(1000, fps as u32);
+                encoder
+                    .encode_frame(Frame::from_parts(image, 0, 0, delay))
+                    .map_err(gif_error)?;
+            }
+            Ok(())
+        }
+        Container::Apng => {
+            let mut encoder = png::Encoder::new(output, bounds.0 as u32, bounds.1 as u32);
+            encoder.set_color(match channels {
+                2 => png::ColorType::GrayscaleAlpha,
+                3 => png::ColorType::Rgb,
+                4 => png::ColorType::Rgba,
+                _ => png::ColorType::Grayscale,
+            });
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_animated(frames.len() as u32, 0)?;
+            encoder.set_frame_delay(1, fps)?;
+            let mut writer = encoder.write_header()?;
+            for pixels in frames {
+                writer.write_image_data(pixels)?;
+            }
+            writer.finish()?;
+            Ok(())
+        }
+    }
+}
+
+#[test]
+fn test_write_animation() {
+    let frames: Vec<Vec<u8>> = (0..3).map(|i| vec![i * 100; 4 * 3]).collect();
+    let gif = std::env::temp_dir().join("rust-experiments-test-animation.gif");
+    let gif = gif.to_str().unwrap();
+    write_animation(gif, Container::Gif, &frames, (4, 3), 10).unwrap();
+    let decoder = image::codecs::gif::GifDecoder::new(File::open(gif).unwrap()).unwrap();
+    let decoded = image::AnimationDecoder::into_frames(decoder)
+        .collect_frames()
+        .unwrap();
+    assert_eq!(decoded.len(), 3);
+    assert_eq!(decoded[2].buffer().get_pixel(0, 0).0, [200, 200, 200, 255]);
+    std::fs::remove_file(gif).unwrap();
+
+    let apng = std::env::temp_dir().join("rust-experiments-test-animation.png");
+    let apng = apng.to_str().unwrap();
+    write_animation(apng, Container::Apng, &frames, (4, 3), 10).unwrap();
+    let mut reader = png::Decoder::new(File::open(apng).unwrap())
+        .read_info()
+        .unwrap();
+    assert_eq!(reader.info().animation_control().unwrap().num_frames, 3);
+    let mut decoded = vec![0; reader.output_buffer_size()];
+    for frame in &frames {
+        reader.next_frame(&mut decoded).unwrap();
+        assert_eq!(&decoded, frame);
+    }
+    std::fs::remove_file(apng).unwrap();
+}
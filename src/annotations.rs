@@ -0,0 +1,159 @@
+use crate::draw::Canvas;
+use num::Complex;
+use std::str::FromStr;
+
+/// Width of the complex plane shown at zoom 1, which takes in the whole Mandelbrot set.
+pub const FULL_WIDTH: f64 = 3.0;
+
+/// Half the length of each arm of a marker, in pixels.
+const MARKER_RADIUS: f64 = 4.0;
+
+/// A labeled point of interest.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Annotation {
+    pub name: String,
+    pub center: Complex<f64>,
+    /// Magnification relative to a view of the whole set.
+    pub zoom: f64,
+}
+
+impl Annotation {
+    /// The upper left and lower right corners of a view of this point, for an image with
+    /// dimensions `bounds`.
+    pub fn viewport(&self, bounds: (usize, usize)) -> (Complex<f64>, Complex<f64>) {
+        let width = FULL_WIDTH / self.zoom;
+        let height = width * bounds.1 as f64 / bounds.0.max(1) as f64;
+        let half = Complex {
+            re: width / 2.0,
+            im: -height / 2.0,
+        };
+        (self.center - half, self.center + half)
+    }
+}
+
+/// Parse an annotation file, with one point per line given as its name, its coordinates as
+/// `re,im`, and its zoom, separated by whitespace. Blank lines and lines starting with `#` are
+/// skipped.
+pub fn parse_annotations(text: &str) -> Result<Vec<Annotation>, String> {
+    let mut annotations = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let annotation = match fields[..] {
+            [name, center, zoom] => crate::parse_complex(center).and_then(|center| {
+                let zoom = f64::from_str(zoom)
+                    .ok()
+                    .filter(|&z| z > 0.0 && z.is_finite())?;
+                Some(Annotation {
+                    name: name.to_string(),
+                    center,
+                    zoom,
+                })
+            }),
+            _ => None,
+        };
+        annotations.push(
+            annotation.ok_or_else(|| format!("error parsing annotation on line {}", number + 1))?,
+        );
+    }
+    Ok(annotations)
+}
+
+#[test]
+fn test_parse_annotations() {
+    let text = "# classic spots\n\
+                seahorse-valley-3  -0.745,0.113  200\n\
+                \n\
+                elephant-valley 0.275,0.0 50\n";
+    let annotations = parse_annotations(text).unwrap();
+    assert_eq!(annotations.len(), 2);
+    assert_eq!(annotations[0].name, "seahorse-valley-3");
+    assert_eq!(
+        annotations[0].center,
+        Complex {
+            re: -0.745,
+            im: 0.113
+        }
+    );
+    assert_eq!(annotations[1].zoom, 50.0);
+
+    assert_eq!(
+        parse_annotations("a 0,0 1\nb 0,0\n"),
+        Err("error parsing annotation on line 2".to_string())
+    );
+    assert!(parse_annotations("a 0,0 0").is_err());
+}
+
+#[test]
+fn test_viewport() {
+    let annotation = Annotation {
+        name: "a".to_string(),
+        center: Complex { re: -1.0, im: 0.5 },
+        zoom: 2.0,
+    };
+    let (upper_left, lower_right) = annotation.viewport((300, 200));
+    assert_eq!(upper_left, Complex { re: -1.75, im: 1.0 });
+    assert_eq!(lower_right, Complex { re: -0.25, im: 0.0 });
+}
+
+/// Draw a white cross at each annotated point that falls inside the image `pixels`, which has
+/// dimensions `bounds`, `channels` bytes per pixel, and shows the area between `upper_left` and
+/// `lower_right`.
+pub fn draw_markers(
+    pixels: &mut [u8],
+    channels: usize,
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    annotations: &[Annotation],
+) {
+    let mut canvas = Canvas::new(pixels, channels, bounds);
+    for annotation in annotations {
+        let (x, y) = crate::point_to_sample(bounds, annotation.center, upper_left, lower_right);
+        // Through the middle of the pixel the point falls in.
+        let (x, y) = (x.floor() + 0.5, y.floor() + 0.5);
+        let white = [255; 3];
+        canvas.line((x - MARKER_RADIUS, y), (x + MARKER_RADIUS, y), white);
+        canvas.line((x, y - MARKER_RADIUS), (x, y + MARKER_RADIUS), white);
+    }
+}
+
+#[test]
+fn test_draw_markers() {
+    let annotations = [Annotation {
+        name: "center".to_string(),
+        center: Complex { re: 0.0, im: 0.0 },
+        zoom: 1.0,
+    }];
+    let mut pixels = vec![0; 20 * 20];
+    let (upper_left, lower_right) = (Complex { re: -1.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    draw_markers(
+        &mut pixels,
+        1,
+        (20, 20),
+        upper_left,
+        lower_right,
+        &annotations,
+    );
+    assert_eq!(pixels[10 * 20 + 10], 255);
+    assert_eq!(pixels[10 * 20 + 14], 255);
+    assert_eq!(pixels[6 * 20 + 10], 255);
+    assert_eq!(pixels[11 * 20 + 11], 0);
+    assert_eq!(pixels.iter().filter(|&&p| p == 255).count(), 17);
+
+    // Points outside of the image are clipped.
+    let mut pixels = vec![0; 20 * 20];
+    let (upper_left, lower_right) = (Complex { re: 5.0, im: 1.0 }, Complex { re: 7.0, im: -1.0 });
+    draw_markers(
+        &mut pixels,
+        1,
+        (20, 20),
+        upper_left,
+        lower_right,
+        &annotations,
+    );
+    assert!(pixels.iter().all(|&p| p == 0));
+}
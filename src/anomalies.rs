@@ -0,0 +1,93 @@
+/// A frame whose histogram differs from both of its neighbors by more than this many times as
+/// much as they differ from each other is suspicious.
+const JUMP_FACTOR: f64 = 4.0;
+
+/// Differences between histograms smaller than this, as a fraction of the pixels, are ordinary
+/// motion however they compare to the neighbors'.
+const JUMP_FLOOR: f64 = 0.1;
+
+/// The fraction of the pixels of a frame at each gray level.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram(Vec<f64>);
+
+impl Histogram {
+    pub fn of(pixels: &[u8]) -> Self {
+        let mut counts = vec![0.0; 256];
+        for &pixel in pixels {
+            counts[pixel as usize] += 1.0;
+        }
+        let total = pixels.len().max(1) as f64;
+        Histogram(counts.into_iter().map(|count| count / total).collect())
+    }
+
+    /// Whether every pixel has the same level, as when a frame lands entirely inside the set or
+    /// fails to render at all.
+    pub fn is_blank(&self) -> bool {
+        self.0.contains(&1.0)
+    }
+
+    /// The fraction of the pixels that would have to change level to turn one histogram into the
+    /// other, from 0 for the same histogram to 1 for histograms with no level in common.
+    pub fn distance(&self, other: &Histogram) -> f64 {
+        self.0
+            .iter()
+            .zip(&other.0)
+            .map(|(a, b)| (a - b).abs())
+            .sum::<f64>()
+            / 2.0
+    }
+}
+
+#[test]
+fn test_histogram() {
+    let blank = Histogram::of(&[7; 10]);
+    assert!(blank.is_blank());
+    let half = Histogram::of(&[7, 7, 200, 200]);
+    assert!(!half.is_blank());
+    assert_eq!(blank.distance(&half), 0.5);
+    assert_eq!(half.distance(&half), 0.0);
+    assert_eq!(blank.distance(&Histogram::of(&[8])), 1.0);
+}
+
+/// The indexes of the frames of an animation, given by their histograms in order, that look
+/// broken: blank frames between frames that aren't, and frames that jump away from both of their
+/// neighbors while the neighbors stay close to each other. Re-rendering them, with more precision
+/// or more references, often fixes them; a frame flagged again is likely just what the set looks
+/// like there.
+pub fn suspicious_frames(histograms: &[Histogram]) -> Vec<usize> {
+    (0..histograms.len())
+        .filter(|&i| {
+            let neighbors = [i.checked_sub(1), Some(i + 1)];
+            if histograms[i].is_blank() {
+                // A run of blank frames is a zoom into the interior.
+                return !neighbors
+                    .iter()
+                    .flatten()
+                    .any(|&j| histograms.get(j).is_some_and(Histogram::is_blank));
+            }
+            match (i.checked_sub(1), histograms.get(i + 1)) {
+                (Some(previous), Some(next)) => {
+                    let previous = &histograms[previous];
+                    let jump = histograms[i]
+                        .distance(previous)
+                        .min(histograms[i].distance(next));
+                    jump > JUMP_FLOOR && jump > JUMP_FACTOR * previous.distance(next)
+                }
+                _ => false,
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_suspicious_frames() {
+    let gradient = |shift: u8| Histogram::of(&(0..=200).map(|x| x + shift).collect::<Vec<u8>>());
+    // A steady zoom, with one frame gone dark and one blank.
+    let mut histograms: Vec<_> = (0..8).map(gradient).collect();
+    histograms[3] = Histogram::of(&[0, 0, 0, 1]);
+    histograms[6] = Histogram::of(&[255; 4]);
+    assert_eq!(suspicious_frames(&histograms), [3, 6]);
+    assert!(suspicious_frames(&(0..8).map(gradient).collect::<Vec<_>>()).is_empty());
+    histograms[7] = Histogram::of(&[255; 4]);
+    assert_eq!(suspicious_frames(&histograms), [3]);
+}
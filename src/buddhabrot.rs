@@ -0,0 +1,712 @@
+use crate::random::SplitMix64;
+use num::Complex;
+use rayon::prelude::*;
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Half the side of the square of points `c` sampled, centered on the origin. Every point of the
+/// Mandelbrot set lies within it.
+const SAMPLE_RADIUS: f64 = 2.0;
+
+/// Orbits sampled when `--orbits` isn't given, per pixel of the image.
+const DEFAULT_ORBITS_PER_PIXEL: usize = 10;
+
+/// Most iterations of an orbit when `--iterations` isn't given.
+const DEFAULT_LIMIT: u32 = 1000;
+
+/// What to render and where to write it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BuddhabrotOptions {
+    pub filename: String,
+    pub bounds: (usize, usize),
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+    /// About how many points `c` to trace the orbits of.
+    pub orbits: usize,
+    /// Most iterations of an orbit: orbits that last longer are taken not to escape, and left
+    /// out.
+    pub limit: u32,
+    /// Most iterations of the orbits counted in each of red, green and blue, making a color
+    /// Nebulabrot in place of the gray image `limit` gives.
+    pub nebula: Option<[u32; 3]>,
+    /// Factor each channel's counts are scaled by before tone mapping.
+    pub gains: [f64; 3],
+    pub tone: Tone,
+    pub sampling: Sampling,
+    /// Seed of the sampling. The same seed gives the same image, on any number of threads.
+    pub seed: u64,
+    pub threads: usize,
+    /// Keep adding passes, writing the image every so often, until interrupted.
+    pub snapshot_every: Option<Duration>,
+    /// File to resume the counts from, if it exists, and to save them to with the image.
+    pub state: Option<String>,
+}
+
+/// Parse the arguments following `buddhabrot`: the PNG file to write, the dimensions and corners
+/// of the image, and options.
+pub fn parse_args(args: &[String]) -> Result<BuddhabrotOptions, String> {
+    let (filename, pixels, upper_left, lower_right) = match args {
+        [filename, pixels, upper_left, lower_right, ..] if !lower_right.starts_with("--") => {
+            (filename, pixels, upper_left, lower_right)
+        }
+        _ => return Err("expected FILE PIXELS UPPERLEFT LOWERRIGHT after buddhabrot".to_string()),
+    };
+    let bounds = crate::parse_pair(pixels, 'x')
+        .filter(|&(width, height): &(usize, usize)| width > 0 && height > 0)
+        .ok_or("error parsing image dimensions")?;
+    let mut options = BuddhabrotOptions {
+        filename: filename.clone(),
+        bounds,
+        upper_left: crate::parse_complex(upper_left).ok_or("error parsing upper left corner")?,
+        lower_right: crate::parse_complex(lower_right).ok_or("error parsing lower right corner")?,
+        orbits: bounds.0 * bounds.1 * DEFAULT_ORBITS_PER_PIXEL,
+        limit: DEFAULT_LIMIT,
+        nebula: None,
+        gains: [1.0; 3],
+        tone: Tone::default(),
+        sampling: Sampling::default(),
+        seed: 0,
+        threads: num_cpus::get(),
+        snapshot_every: None,
+        state: None,
+    };
+
+    let mut limit = None;
+    let mut gains = None;
+    let mut args = args[4..].iter();
+    while let Some(name) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {}", name))?;
+        match name.as_str() {
+            "--orbits" => {
+                options.orbits = usize::from_str(value)
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or("error parsing --orbits")?
+            }
+            "--iterations" => {
+                limit = Some(
+                    u32::from_str(value)
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or("error parsing --iterations")?,
+                )
+            }
+            "--nebula" => {
+                let limits: Vec<u32> = value
+                    .split(',')
+                    .map(|n| u32::from_str(n).ok().filter(|&n| n > 0))
+                    .collect::<Option<_>>()
+                    .ok_or("error parsing --nebula")?;
+                options.nebula = Some(limits.try_into().map_err(|_| "error parsing --nebula")?)
+            }
+            "--gain" => {
+                let factors: Vec<f64> = value
+                    .split(',')
+                    .map(|x| f64::from_str(x).ok().filter(|&x| x > 0.0 && x.is_finite()))
+                    .collect::<Option<_>>()
+                    .ok_or("error parsing --gain")?;
+                gains = Some(factors.try_into().map_err(|_| "error parsing --gain")?)
+            }
+            "--tone" => {
+                options.tone =
+                    Tone::parse(value).ok_or_else(|| format!("unknown tone map {}", value))?
+            }
+            "--sampling" => {
+                options.sampling =
+                    Sampling::parse(value).ok_or_else(|| format!("unknown sampling {}", value))?
+            }
+            "--seed" => options.seed = u64::from_str(value).map_err(|_| "error parsing --seed")?,
+            "--snapshot-every" => {
+                let minutes = f64::from_str(value)
+                    .ok()
+                    .filter(|&minutes| minutes > 0.0 && minutes.is_finite())
+                    .ok_or("error parsing --snapshot-every")?;
+                options.snapshot_every = Some(Duration::from_secs_f64(minutes * 60.0))
+            }
+            "--state" => options.state = Some(value.clone()),
+            "--threads" => {
+                options.threads = usize::from_str(value)
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or("error parsing --threads")?
+            }
+            // Read by `main` before the subcommand runs.
+            "--error-format" | "--lang" => {}
+            _ => return Err(format!("unknown option {}", name)),
+        }
+    }
+    if options.nebula.is_some() && limit.is_some() {
+        return Err("--iterations can't be combined with --nebula".to_string());
+    }
+    if options.nebula.is_none() && gains.is_some() {
+        return Err("--gain needs --nebula".to_string());
+    }
+    options.limit = limit.unwrap_or(DEFAULT_LIMIT);
+    options.gains = gains.unwrap_or(options.gains);
+    Ok(options)
+}
+
+#[test]
+fn test_parse_args() {
+    let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+    let options = parse_args(&args("buddha.png 300x200 -2,1 1,-1 --seed 7 --lang ru")).unwrap();
+    assert_eq!(options.bounds, (300, 200));
+    assert_eq!(options.upper_left, Complex { re: -2.0, im: 1.0 });
+    assert_eq!(options.orbits, 600_000);
+    assert_eq!(options.limit, 1000);
+    assert_eq!(options.seed, 7);
+    assert_eq!(options.sampling, Sampling::Stratified);
+    assert_eq!(options.snapshot_every, None);
+    let options = parse_args(&args(
+        "buddha.png 30x20 -2,1 1,-1 --snapshot-every 0.5 --state buddha.state",
+    ))
+    .unwrap();
+    assert_eq!(options.snapshot_every, Some(Duration::from_secs(30)));
+    assert_eq!(options.state.as_deref(), Some("buddha.state"));
+    assert!(parse_args(&args("buddha.png 30x20 -2,1 1,-1 --snapshot-every 0")).is_err());
+    let options = parse_args(&args(
+        "nebula.png 30x20 -2,1 1,-1 --nebula 5000,500,50 --gain 1,1.5,2 --tone reinhard",
+    ))
+    .unwrap();
+    assert_eq!(options.nebula, Some([5000, 500, 50]));
+    assert_eq!(options.limits(), [5000, 500, 50]);
+    assert_eq!(options.gains, [1.0, 1.5, 2.0]);
+    assert_eq!(options.tone, Tone::Reinhard);
+    assert_eq!(
+        parse_args(&args("b.png 30x20 -2,1 1,-1")).unwrap().limits(),
+        [1000]
+    );
+    assert!(parse_args(&args("b.png 30x20 -2,1 1,-1 --nebula 500,50")).is_err());
+    assert!(parse_args(&args("b.png 30x20 -2,1 1,-1 --nebula 9,9,9 --iterations 9")).is_err());
+    assert!(parse_args(&args("b.png 30x20 -2,1 1,-1 --gain 1,2,3")).is_err());
+    assert!(parse_args(&args("b.png 30x20 -2,1 1,-1 --tone filmic")).is_err());
+    let options = parse_args(&args(
+        "buddha.png 30x20 -2,1 1,-1 --orbits 50 --iterations 20",
+    ));
+    assert_eq!(options.as_ref().map(|o| (o.orbits, o.limit)), Ok((50, 20)));
+    assert!(parse_args(&args("buddha.png 30x20 -2,1")).is_err());
+    assert!(parse_args(&args("buddha.png 30x20 -2,1 1,-1 --orbits 0")).is_err());
+    assert!(parse_args(&args("buddha.png 30x20 -2,1 1,-1 --sampling metropolis")).is_err());
+    assert!(parse_args(&args("buddha.png 30x20 -2,1 1,-1 --samples 4x4")).is_err());
+}
+
+/// How the points `c` are spread over the sampled square.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Sampling {
+    /// Evenly, one at random within each cell of a square grid.
+    #[default]
+    Stratified,
+    /// Like `Stratified`, but `BOOST * BOOST` times as densely in the cells of a coarse mask that
+    /// lie near the boundary of the set, where the long orbits that make up the picture start,
+    /// with each orbit counted that many times less. Those orbits are also the slowest to trace,
+    /// so this only pays off with high `--iterations`, where they are most of the picture.
+    Importance,
+}
+
+impl Sampling {
+    pub fn parse(s: &str) -> Option<Sampling> {
+        match s {
+            "stratified" => Some(Sampling::Stratified),
+            "importance" => Some(Sampling::Importance),
+            _ => None,
+        }
+    }
+}
+
+/// How the counts of a channel become its levels.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Tone {
+    /// The square root of each count as a fraction of the largest, which brings out the faint
+    /// outer orbits next to the bright center.
+    #[default]
+    Sqrt,
+    /// The logarithm of each count over that of the largest, which lifts the faint orbits
+    /// further still.
+    Log,
+    /// Each count over its sum with the average count, so that the few brightest pixels no
+    /// longer set the scale of all others.
+    Reinhard,
+}
+
+impl Tone {
+    pub fn parse(s: &str) -> Option<Tone> {
+        match s {
+            "sqrt" => Some(Tone::Sqrt),
+            "log" => Some(Tone::Log),
+            "reinhard" => Some(Tone::Reinhard),
+            _ => None,
+        }
+    }
+}
+
+impl BuddhabrotOptions {
+    /// Most iterations of the orbits counted in each channel of the image.
+    pub fn limits(&self) -> Vec<u32> {
+        match self.nebula {
+            Some(limits) => limits.to_vec(),
+            None => vec![self.limit],
+        }
+    }
+}
+
+/// Cells across and down the coarse mask over the sampled square.
+const MASK_SIDE: usize = 64;
+
+/// How many times as many samples across and down the cells near the boundary take.
+const BOOST: usize = 4;
+
+/// Points of a cell that take at least this many iterations to escape put it near the boundary.
+const NEAR_ESCAPE: u32 = 16;
+
+/// The side of the sampled square's cell number `cell` of the mask, as its upper left corner and
+/// width.
+fn cell_corner(cell: usize) -> (Complex<f64>, f64) {
+    let width = 2.0 * SAMPLE_RADIUS / MASK_SIDE as f64;
+    let (column, row) = (cell % MASK_SIDE, cell / MASK_SIDE);
+    let corner = Complex {
+        re: -SAMPLE_RADIUS + column as f64 * width,
+        im: SAMPLE_RADIUS - row as f64 * width,
+    };
+    (corner, width)
+}
+
+/// For each cell of the mask, row by row, how many times as many samples across and down it
+/// takes as the cells away from the boundary. A cell is near the boundary if, of its corners and
+/// center, some escape within `limit` iterations and some don't, or some take `NEAR_ESCAPE`
+/// iterations or more to escape.
+pub fn cell_densities(sampling: Sampling, limit: u32) -> Vec<usize> {
+    (0..MASK_SIDE * MASK_SIDE)
+        .map(|cell| {
+            if sampling == Sampling::Stratified {
+                return 1;
+            }
+            let (corner, width) = cell_corner(cell);
+            let escapes = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0), (0.5, 0.5)].map(
+                |(across, down)| {
+                    let c = corner + Complex::new(across * width, -down * width);
+                    crate::escape_time(Complex::new(0.0, 0.0), c, limit, 4.0)
+                },
+            );
+            let mixed = escapes.iter().any(Option::is_some) && escapes.iter().any(Option::is_none);
+            let slow = escapes
+                .iter()
+                .any(|&escape| escape.is_some_and(|count| count >= NEAR_ESCAPE));
+            if mixed || slow {
+                BOOST
+            } else {
+                1
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_cell_densities() {
+    let densities = cell_densities(Sampling::Importance, 100);
+    assert_eq!(densities.len(), MASK_SIDE * MASK_SIDE);
+    // The corner cells are far outside the set, the cell with the corner at -0.75 on its
+    // boundary, and the cells on either side of it, inside the main cardioid and the period 2
+    // bulb, are near it too.
+    let cell = |re: f64, im: f64| {
+        let width = 2.0 * SAMPLE_RADIUS / MASK_SIDE as f64;
+        let column = ((re + SAMPLE_RADIUS) / width) as usize;
+        let row = ((SAMPLE_RADIUS - im) / width) as usize;
+        row * MASK_SIDE + column
+    };
+    assert_eq!(densities[0], 1);
+    assert_eq!(densities[MASK_SIDE * MASK_SIDE - 1], 1);
+    assert_eq!(densities[cell(-0.75, 0.01)], BOOST);
+    assert_eq!(densities[cell(-0.2, 0.01)], 1);
+    assert!(cell_densities(Sampling::Stratified, 100)
+        .iter()
+        .all(|&density| density == 1));
+}
+
+/// The points `c` sampled in cell number `cell` of the mask: one placed at random within each
+/// cell of a `side` by `side` grid over it, drawing on stream `cell` of `seed`. Every part of the
+/// cell gets its share of samples, which converges faster than sampling it uniformly at random,
+/// and each cell's samples are the same whichever thread draws them.
+pub fn stratum_cell(seed: u64, cell: usize, side: usize) -> impl Iterator<Item = Complex<f64>> {
+    let mut rng = SplitMix64::for_stream(seed, cell as u64);
+    let (corner, width) = cell_corner(cell);
+    let stratum = width / side as f64;
+    (0..side * side).map(move |i| {
+        let (column, row) = (i % side, i / side);
+        Complex {
+            re: corner.re + (column as f64 + rng.next_f64()) * stratum,
+            im: corner.im - (row as f64 + rng.next_f64()) * stratum,
+        }
+    })
+}
+
+#[test]
+fn test_stratum_cell() {
+    let samples: Vec<_> = stratum_cell(7, MASK_SIDE + 1, 2).collect();
+    assert_eq!(samples.len(), 4);
+    let (corner, width) = cell_corner(MASK_SIDE + 1);
+    assert_eq!(corner, Complex::new(-2.0 + width, 2.0 - width));
+    for (i, c) in samples.iter().enumerate() {
+        // Each sample lies in its own quarter of the cell.
+        let (column, row) = ((i % 2) as f64, (i / 2) as f64);
+        let across = (c.re - corner.re) / (width / 2.0);
+        let down = (corner.im - c.im) / (width / 2.0);
+        assert!((column..column + 1.0).contains(&across));
+        assert!((row..row + 1.0).contains(&down));
+    }
+    assert_eq!(
+        samples,
+        stratum_cell(7, MASK_SIDE + 1, 2).collect::<Vec<_>>()
+    );
+    assert_ne!(
+        samples,
+        stratum_cell(8, MASK_SIDE + 1, 2).collect::<Vec<_>>()
+    );
+}
+
+/// Trace the orbit of 0 under `z = z*z + c` for at most the largest of `limits` iterations,
+/// keeping its points in `orbit`, and if it escapes, count each of them `weight` times in the
+/// pixel of `hits` it falls in, in each channel whose limit it escaped within. Pixels take one
+/// count per channel.
+fn trace(
+    c: Complex<f64>,
+    options: &BuddhabrotOptions,
+    limits: &[u32],
+    weight: u64,
+    orbit: &mut Vec<Complex<f64>>,
+    hits: &mut [u64],
+) {
+    orbit.clear();
+    // Points inside these never escape, however far they are iterated.
+    if crate::in_main_cardioid_or_bulb(c) {
+        return;
+    }
+    let channels = limits.len();
+    let mut z = Complex { re: 0.0, im: 0.0 };
+    for _ in 0..limits.iter().copied().max().unwrap_or(0) {
+        z = z * z + c;
+        if z.norm_sqr() > 4.0 {
+            for &point in orbit.iter() {
+                let pixel = crate::point_to_pixel(
+                    options.bounds,
+                    point,
+                    options.upper_left,
+                    options.lower_right,
+                );
+                if let Some((x, y)) = pixel {
+                    let index = (y * options.bounds.0 + x) * channels;
+                    for (channel, &limit) in limits.iter().enumerate() {
+                        if (orbit.len() as u32) < limit {
+                            hits[index + channel] += weight;
+                        }
+                    }
+                }
+            }
+            return;
+        }
+        orbit.push(z);
+    }
+}
+
+/// Count, for each pixel of the image `options` describes and each of its channels, how many
+/// times the escaping orbits of points sampled by `stratum_cell` pass through it, on
+/// `options.threads` threads. The cells
+/// take `cell_densities` times as many samples across and down as the others, at least one, and
+/// orbits from the sparser cells count as many times more, so that the counts are those of
+/// sampling evenly, only with less noise where it matters.
+pub fn accumulate(options: &BuddhabrotOptions) -> Vec<u64> {
+    let limits = options.limits();
+    let most = limits.iter().copied().max().unwrap_or(0);
+    let densities = cell_densities(options.sampling, most);
+    let squares: usize = densities.iter().map(|density| density * density).sum();
+    let side = (options.orbits as f64 / squares as f64).sqrt().ceil() as usize;
+    let densest = densities.iter().copied().max().unwrap_or(1);
+    let pixels = options.bounds.0 * options.bounds.1 * limits.len();
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.threads.max(1))
+        .build()
+        .expect("Failed to start render threads");
+    // Counts add up the same in any order, so the cells can be shared out freely.
+    pool.install(|| {
+        densities
+            .par_iter()
+            .enumerate()
+            .fold(
+                || (vec![0; pixels], Vec::new()),
+                |(mut hits, mut orbit), (cell, &density)| {
+                    let weight = ((densest / density) * (densest / density)) as u64;
+                    for c in stratum_cell(options.seed, cell, side * density) {
+                        trace(c, options, &limits, weight, &mut orbit, &mut hits);
+                    }
+                    (hits, orbit)
+                },
+            )
+            .map(|(hits, _)| hits)
+            .reduce(
+                || vec![0; pixels],
+                |mut total, hits| {
+                    total
+                        .iter_mut()
+                        .zip(hits)
+                        .for_each(|(total, hits)| *total += hits);
+                    total
+                },
+            )
+    })
+}
+
+#[test]
+fn test_accumulate() {
+    let options = BuddhabrotOptions {
+        filename: String::new(),
+        bounds: (24, 16),
+        upper_left: Complex { re: -2.0, im: 1.0 },
+        lower_right: Complex { re: 1.0, im: -1.0 },
+        orbits: 2000,
+        limit: 50,
+        nebula: None,
+        gains: [1.0; 3],
+        tone: Tone::Sqrt,
+        sampling: Sampling::Importance,
+        seed: 3,
+        threads: 1,
+        snapshot_every: None,
+        state: None,
+    };
+    let hits = accumulate(&options);
+    assert!(hits.iter().any(|&count| count > 0));
+    // The same however many threads share the work.
+    assert_eq!(
+        accumulate(&BuddhabrotOptions {
+            threads: 3,
+            ..options.clone()
+        }),
+        hits
+    );
+    assert_ne!(
+        accumulate(&BuddhabrotOptions {
+            seed: 4,
+            ..options.clone()
+        }),
+        hits
+    );
+    // Sampling evenly counts about as much in all.
+    let even = accumulate(&BuddhabrotOptions {
+        sampling: Sampling::Stratified,
+        ..options.clone()
+    });
+    let total = |hits: &[u64]| hits.iter().sum::<u64>() as f64;
+    assert!((total(&hits) / total(&even) / (BOOST * BOOST) as f64 - 1.0).abs() < 0.1);
+    // A Nebulabrot's channel with the same limit counts the same orbits.
+    let nebula = accumulate(&BuddhabrotOptions {
+        nebula: Some([50, 20, 5]),
+        ..options.clone()
+    });
+    assert_eq!(nebula.len(), 3 * hits.len());
+    assert_eq!(nebula.iter().step_by(3).copied().collect::<Vec<_>>(), hits);
+    assert!(nebula
+        .chunks(3)
+        .all(|counts| counts[0] >= counts[1] && counts[1] >= counts[2]));
+}
+
+/// Levels for the counts of `hits`, as many to a pixel as `options` has channels, each channel
+/// scaled by its gain and mapped to levels by `options.tone`.
+pub fn to_pixels(hits: &[u64], options: &BuddhabrotOptions) -> Vec<u8> {
+    let channels = options.limits().len();
+    let mut pixels = vec![0; hits.len()];
+    for (channel, gain) in options.gains.iter().enumerate().take(channels) {
+        let counts = || {
+            hits.iter()
+                .skip(channel)
+                .step_by(channels)
+                .map(|&n| n as f64)
+        };
+        let most = counts().fold(1.0, f64::max);
+        let lit = counts().filter(|&count| count > 0.0).count();
+        let average = counts().sum::<f64>() / lit.max(1) as f64;
+        let levels = pixels.iter_mut().skip(channel).step_by(channels);
+        for (level, count) in levels.zip(counts()) {
+            let count = gain * count;
+            let value = match options.tone {
+                Tone::Sqrt => (count / most).sqrt(),
+                Tone::Log => count.ln_1p() / most.ln_1p(),
+                Tone::Reinhard => count / (count + average),
+            };
+            *level = (value.min(1.0) * 255.0).round() as u8;
+        }
+    }
+    pixels
+}
+
+#[test]
+fn test_to_pixels() {
+    let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+    let options = |tone| parse_args(&args(&format!("b.png 4x1 -2,1 1,-1 --tone {}", tone)));
+    let gray = options("sqrt").unwrap();
+    assert_eq!(to_pixels(&[0, 1, 4, 16], &gray), [0, 64, 128, 255]);
+    assert_eq!(to_pixels(&[0, 0], &gray), [0, 0]);
+    let log = options("log").unwrap();
+    assert_eq!(to_pixels(&[0, 1, 4, 16], &log), [0, 62, 145, 255]);
+    // The average of the counts that aren't 0 is 7.
+    let reinhard = options("reinhard").unwrap();
+    assert_eq!(to_pixels(&[0, 1, 4, 16], &reinhard), [0, 32, 93, 177]);
+    // Each channel is scaled on its own, then by its gain.
+    let nebula = BuddhabrotOptions {
+        nebula: Some([9, 9, 9]),
+        gains: [1.0, 1.0, 4.0],
+        ..gray
+    };
+    assert_eq!(
+        to_pixels(&[4, 1, 0, 16, 4, 1], &nebula),
+        [128, 128, 0, 255, 255, 255]
+    );
+}
+
+/// Marks the files `write_state` writes, and the version of their layout.
+const STATE_MAGIC: &[u8; 8] = b"BUDDHA1\n";
+
+/// The options of pass number `pass` of a progressive render: those of the render, with a seed
+/// of its own, so that every pass samples new points. The first pass is the render `options`
+/// describe by itself.
+pub fn pass_options(options: &BuddhabrotOptions, pass: u64) -> BuddhabrotOptions {
+    let seed = match pass {
+        0 => options.seed,
+        _ => SplitMix64::for_stream(options.seed, pass).next_u64(),
+    };
+    BuddhabrotOptions {
+        seed,
+        ..options.clone()
+    }
+}
+
+/// The counts of a progressive render, after its first `passes` passes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Accumulation {
+    pub passes: u64,
+    pub hits: Vec<u64>,
+}
+
+impl Accumulation {
+    /// Counts of the render `options` describe, before any pass.
+    pub fn new(options: &BuddhabrotOptions) -> Accumulation {
+        Accumulation {
+            passes: 0,
+            hits: vec![0; options.bounds.0 * options.bounds.1 * options.limits().len()],
+        }
+    }
+
+    /// Run the next pass of the render `options` describe, adding its counts.
+    pub fn step(&mut self, options: &BuddhabrotOptions) {
+        let hits = accumulate(&pass_options(options, self.passes));
+        self.hits
+            .iter_mut()
+            .zip(hits)
+            .for_each(|(total, hits)| *total += hits);
+        self.passes += 1;
+    }
+}
+
+/// What a state file records of the render it belongs to, which resuming has to match.
+fn state_header(options: &BuddhabrotOptions) -> Vec<u8> {
+    let mut header = STATE_MAGIC.to_vec();
+    let (width, height) = options.bounds;
+    for n in [width, height, options.orbits] {
+        header.extend_from_slice(&(n as u64).to_le_bytes());
+    }
+    for limit in options.limits() {
+        header.extend_from_slice(&limit.to_le_bytes());
+    }
+    header.push(options.sampling as u8);
+    header.extend_from_slice(&options.seed.to_le_bytes());
+    for x in [
+        options.upper_left.re,
+        options.upper_left.im,
+        options.lower_right.re,
+        options.lower_right.im,
+    ] {
+        header.extend_from_slice(&x.to_le_bytes());
+    }
+    header
+}
+
+/// Save `accumulation`, the counts of the render `options` describe, to `filename`. The file is
+/// written in full under another name first, so that an interrupted save leaves the last one
+/// intact.
+pub fn write_state(
+    filename: &str,
+    options: &BuddhabrotOptions,
+    accumulation: &Accumulation,
+) -> Result<(), Error> {
+    let mut bytes = state_header(options);
+    bytes.extend_from_slice(&accumulation.passes.to_le_bytes());
+    for count in &accumulation.hits {
+        bytes.extend_from_slice(&count.to_le_bytes());
+    }
+    let partial = format!("{}.partial", filename);
+    std::fs::write(&partial, bytes)?;
+    std::fs::rename(&partial, filename)
+}
+
+/// Load the counts of the render `options` describe from `filename`, as `write_state` saved
+/// them. Fails with `ErrorKind::InvalidData` if the file was saved for another render.
+pub fn read_state(filename: &str, options: &BuddhabrotOptions) -> Result<Accumulation, Error> {
+    let bytes = std::fs::read(filename)?;
+    let header = state_header(options);
+    let pixels = options.bounds.0 * options.bounds.1 * options.limits().len();
+    if !bytes.starts_with(&header) || bytes.len() != header.len() + 8 * (pixels + 1) {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "it was saved for another render",
+        ));
+    }
+    let mut words = bytes[header.len()..]
+        .chunks_exact(8)
+        .map(|word| u64::from_le_bytes(word.try_into().unwrap()));
+    Ok(Accumulation {
+        passes: words.next().unwrap(),
+        hits: words.collect(),
+    })
+}
+
+#[test]
+fn test_state() {
+    let options = BuddhabrotOptions {
+        filename: String::new(),
+        bounds: (12, 8),
+        upper_left: Complex { re: -2.0, im: 1.0 },
+        lower_right: Complex { re: 1.0, im: -1.0 },
+        orbits: 500,
+        limit: 50,
+        nebula: None,
+        gains: [1.0; 3],
+        tone: Tone::Sqrt,
+        sampling: Sampling::Stratified,
+        seed: 3,
+        threads: 1,
+        snapshot_every: None,
+        state: None,
+    };
+    let mut resumed = Accumulation::new(&options);
+    resumed.step(&options);
+    assert_eq!(resumed.hits, accumulate(&options));
+    let filename = std::env::temp_dir().join("rust-experiments-test-buddhabrot.state");
+    let filename = filename.to_str().unwrap();
+    write_state(filename, &options, &resumed).unwrap();
+    let mut resumed = read_state(filename, &options).unwrap();
+    resumed.step(&options);
+    // Picking up where the saved counts left off is the same as never stopping.
+    let mut whole = Accumulation::new(&options);
+    whole.step(&options);
+    whole.step(&options);
+    assert_eq!(resumed, whole);
+    assert_ne!(pass_options(&options, 1).seed, options.seed);
+    let other = BuddhabrotOptions {
+        limit: 60,
+        ..options
+    };
+    let error = read_state(filename, &other).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+    std::fs::remove_file(filename).unwrap();
+}
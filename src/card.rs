@@ -0,0 +1,150 @@
+use crate::draw::{text_size, Canvas, GLYPH_HEIGHT};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Space left around the text of the card, in pixels.
+const PADDING: usize = 4;
+
+/// Space left between the fields on a line of the card, in characters.
+const GAP: &str = "  ";
+
+/// Color of the strip under the text, and of the text.
+const BACKGROUND: [u8; 3] = [24, 24, 24];
+const FOREGROUND: [u8; 3] = [230, 230, 230];
+
+/// Join `fields` into lines of text no wider than `width` pixels, keeping each field whole and on
+/// one line, even a field too wide for it on its own.
+pub fn layout(fields: &[String], width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for field in fields {
+        match lines.last_mut() {
+            Some(line) if text_size(&format!("{}{}{}", line, GAP, field)).0 <= width => {
+                line.push_str(GAP);
+                line.push_str(field);
+            }
+            _ => lines.push(field.clone()),
+        }
+    }
+    lines
+}
+
+#[test]
+fn test_layout() {
+    let fields = ["ZOOM 4", "ITERATIONS 255", "2026-10-16"].map(String::from);
+    // ZOOM 4  ITERATIONS 255 is 22 characters, 131 pixels.
+    assert_eq!(
+        layout(&fields, 131),
+        ["ZOOM 4  ITERATIONS 255", "2026-10-16"]
+    );
+    assert_eq!(
+        layout(&fields, 130),
+        ["ZOOM 4", "ITERATIONS 255", "2026-10-16"]
+    );
+    assert_eq!(
+        layout(&fields, 10),
+        ["ZOOM 4", "ITERATIONS 255", "2026-10-16"]
+    );
+    assert!(layout(&[], 100).is_empty());
+}
+
+/// Append a strip below the image in `pixels`, with dimensions `bounds` and `channels` bytes to a
+/// pixel, holding `fields` written in the built-in font across as many lines as they need.
+/// Returns the dimensions of the image with the strip.
+pub fn append(
+    pixels: &mut Vec<u8>,
+    channels: usize,
+    bounds: (usize, usize),
+    fields: &[String],
+) -> (usize, usize) {
+    let lines = layout(fields, bounds.0.saturating_sub(2 * PADDING));
+    if lines.is_empty() {
+        return bounds;
+    }
+    let text = lines.join("\n");
+    let height = text_size(&text).1.max(GLYPH_HEIGHT) + 2 * PADDING;
+    let top = pixels.len();
+    pixels.resize(top + bounds.0 * height * channels, 0);
+    let mut canvas = Canvas::new(&mut pixels[top..], channels, (bounds.0, height));
+    for y in 0..height {
+        for x in 0..bounds.0 {
+            canvas.plot(x as i64, y as i64, BACKGROUND);
+        }
+    }
+    canvas.text((PADDING as i64, PADDING as i64), &text, FOREGROUND);
+    (bounds.0, bounds.1 + height)
+}
+
+#[test]
+fn test_append() {
+    let mut pixels = vec![7; 40 * 3 * 4];
+    let fields = ["1", "2"].map(String::from);
+    let bounds = append(&mut pixels, 4, (40, 3), &fields);
+    assert_eq!(bounds, (40, 3 + 15));
+    assert_eq!(pixels.len(), 40 * 18 * 4);
+    // The image is kept as it was, and the strip is opaque.
+    assert!(pixels[..40 * 3 * 4].iter().all(|&p| p == 7));
+    assert_eq!(pixels[40 * 3 * 4..][..4], [24, 24, 24, 255]);
+    // "1  2" starts with the top of the 1 four pixels in from the corner of the strip.
+    let index = ((3 + 4) * 40 + 4 + 2) * 4;
+    assert_eq!(pixels[index..index + 4], [230, 230, 230, 255]);
+
+    let mut pixels = vec![7; 8 * 8];
+    assert_eq!(append(&mut pixels, 1, (8, 8), &[]), (8, 8));
+    assert_eq!(pixels.len(), 64);
+}
+
+/// Today's date, in UTC, as `YYYY-MM-DD`.
+pub fn today() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    date((seconds / 86_400) as i64)
+}
+
+/// The date `days` days after 1970-01-01 as `YYYY-MM-DD`, by the proleptic Gregorian calendar.
+fn date(days: i64) -> String {
+    // Count from 0000-03-01, so that leap days fall at the end of each year, in eras of the 400
+    // years after which the calendar repeats.
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = era * 400 + year_of_era + (month <= 2) as i64;
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[test]
+fn test_date() {
+    assert_eq!(date(0), "1970-01-01");
+    assert_eq!(date(59), "1970-03-01");
+    assert_eq!(date(11_016), "2000-02-29");
+    assert_eq!(date(20_742), "2026-10-16");
+    assert_eq!(date(-1), "1969-12-31");
+}
+
+/// `zoom` written briefly: with up to two decimals, or in scientific notation once large.
+pub fn format_zoom(zoom: f64) -> String {
+    if zoom >= 1e5 {
+        format!("{:.2e}", zoom)
+    } else {
+        let text = format!("{:.2}", zoom);
+        text.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+}
+
+#[test]
+fn test_format_zoom() {
+    assert_eq!(format_zoom(1.0), "1");
+    assert_eq!(format_zoom(2.5), "2.5");
+    assert_eq!(format_zoom(1234.5678), "1234.57");
+    assert_eq!(format_zoom(3.2e12), "3.20e12");
+}
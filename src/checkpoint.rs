@@ -0,0 +1,171 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom, Write};
+
+/// Marks the files `Checkpoint` writes, and the version of their layout.
+const MAGIC: &[u8; 8] = b"MANDEL1\n";
+
+/// The rows of an image finished so far, saved to a file as they are rendered so that a render
+/// that stops partway can pick up where it left off. The file holds the parameters of the render,
+/// then a record for each band of rows: the number of rows, followed by their pixels.
+pub struct Checkpoint {
+    file: File,
+    /// Bytes in a row of the image.
+    row_length: usize,
+    /// Where the pixels of each band saved start in the file, and its number of rows.
+    bands: Vec<(u64, usize)>,
+}
+
+/// What a checkpoint records of the render it belongs to, which resuming has to match.
+fn header(parameters: &str) -> Vec<u8> {
+    let mut header = MAGIC.to_vec();
+    header.extend_from_slice(&(parameters.len() as u64).to_le_bytes());
+    header.extend_from_slice(parameters.as_bytes());
+    header
+}
+
+impl Checkpoint {
+    /// Open the checkpoint `filename` of the render `parameters` describe, an image `height` rows
+    /// of `row_length` bytes, starting it afresh if there is no such file. A band cut short by
+    /// the render stopping partway is dropped, as is any band past the bottom of the image. Fails
+    /// with `ErrorKind::InvalidData` if the file was saved for another render.
+    pub fn open(
+        filename: &str,
+        parameters: &str,
+        (row_length, height): (usize, usize),
+    ) -> Result<Checkpoint, Error> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(filename)?;
+        let length = file.metadata()?.len();
+        let header = header(parameters);
+        if length == 0 {
+            file.write_all(&header)?;
+            file.sync_data()?;
+            return Ok(Checkpoint {
+                file,
+                row_length,
+                bands: Vec::new(),
+            });
+        }
+        let mut saved = vec![0; header.len()];
+        if file.read_exact(&mut saved).is_err() || saved != header {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "it was saved for another render",
+            ));
+        }
+        // Find the bands by their records alone, leaving their pixels on disk.
+        let mut bands = Vec::new();
+        let mut end = header.len() as u64;
+        let mut saved_rows = 0;
+        let mut rows = [0; 8];
+        while file.read_exact(&mut rows).is_ok() {
+            let rows = u64::from_le_bytes(rows);
+            let start = end + 8;
+            // A count of rows too large to add up is as good as cut short.
+            let band_end = rows
+                .checked_mul(row_length as u64)
+                .and_then(|size| start.checked_add(size))
+                .filter(|&band_end| band_end <= length);
+            let Some(band_end) = band_end else {
+                break;
+            };
+            let rows = rows as usize;
+            if rows > height - saved_rows {
+                break;
+            }
+            saved_rows += rows;
+            bands.push((start, rows));
+            end = file.seek(SeekFrom::Start(band_end))?;
+        }
+        // Leave off whatever follows the last whole band, to write the next one in its place.
+        file.set_len(end)?;
+        Ok(Checkpoint {
+            file,
+            row_length,
+            bands,
+        })
+    }
+
+    /// Number of rows saved, from the top of the image down.
+    pub fn rows(&self) -> usize {
+        self.bands.iter().map(|&(_, rows)| rows).sum()
+    }
+
+    /// Number of bands of rows saved.
+    pub fn bands(&self) -> usize {
+        self.bands.len()
+    }
+
+    /// Read the pixels of the band saved `index`th.
+    pub fn read_band(&mut self, index: usize) -> Result<Vec<u8>, Error> {
+        let (start, rows) = self.bands[index];
+        let mut pixels = vec![0; rows * self.row_length];
+        self.file.seek(SeekFrom::Start(start))?;
+        self.file.read_exact(&mut pixels)?;
+        Ok(pixels)
+    }
+
+    /// Save the `pixels` of the next band of rows, after those already saved, once they are
+    /// on disk.
+    pub fn append(&mut self, pixels: &[u8]) -> Result<(), Error> {
+        let rows = pixels.len() / self.row_length.max(1);
+        let mut record = (rows as u64).to_le_bytes().to_vec();
+        record.extend_from_slice(pixels);
+        let start = self.file.seek(SeekFrom::End(0))? + 8;
+        self.file.write_all(&record)?;
+        self.file.sync_data()?;
+        self.bands.push((start, rows));
+        Ok(())
+    }
+}
+
+#[test]
+fn test_checkpoint() {
+    let filename = std::env::temp_dir().join("rust-experiments-test.checkpoint");
+    let filename = filename.to_str().unwrap();
+    let _ = std::fs::remove_file(filename);
+    let mut checkpoint = Checkpoint::open(filename, "width 2", (6, 4)).unwrap();
+    assert_eq!(checkpoint.rows(), 0);
+    checkpoint.append(&[1; 12]).unwrap();
+    checkpoint.append(&[2; 6]).unwrap();
+    drop(checkpoint);
+
+    // A band cut short is dropped, and the next one written over it.
+    let mut file = OpenOptions::new().append(true).open(filename).unwrap();
+    file.write_all(&[3, 0, 0, 0, 0, 0, 0, 0, 4, 4]).unwrap();
+    drop(file);
+    let mut checkpoint = Checkpoint::open(filename, "width 2", (6, 4)).unwrap();
+    assert_eq!((checkpoint.bands(), checkpoint.rows()), (2, 3));
+    assert_eq!(checkpoint.read_band(0).unwrap(), [1; 12]);
+    checkpoint.append(&[5; 6]).unwrap();
+    drop(checkpoint);
+    let checkpoint = Checkpoint::open(filename, "width 2", (6, 4)).unwrap();
+    assert_eq!(checkpoint.rows(), 4);
+    drop(checkpoint);
+
+    // So is one whose count of rows is garbage.
+    let mut file = OpenOptions::new().append(true).open(filename).unwrap();
+    file.write_all(&u64::MAX.to_le_bytes()).unwrap();
+    drop(file);
+    let mut checkpoint = Checkpoint::open(filename, "width 2", (6, 4)).unwrap();
+    assert_eq!(checkpoint.rows(), 4);
+    assert_eq!(checkpoint.read_band(1).unwrap(), [2; 6]);
+    assert_eq!(checkpoint.read_band(2).unwrap(), [5; 6]);
+    drop(checkpoint);
+
+    // And one past the bottom of the image, however whole.
+    let mut file = OpenOptions::new().append(true).open(filename).unwrap();
+    file.write_all(&[1, 0, 0, 0, 0, 0, 0, 0, 6, 6, 6, 6, 6, 6])
+        .unwrap();
+    drop(file);
+    let checkpoint = Checkpoint::open(filename, "width 2", (6, 4)).unwrap();
+    assert_eq!((checkpoint.bands(), checkpoint.rows()), (3, 4));
+
+    let error = Checkpoint::open(filename, "width 3", (9, 4)).err().unwrap();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+    std::fs::remove_file(filename).unwrap();
+}
@@ -0,0 +1,196 @@
+use crate::color::srgb_to_linear;
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufWriter, Seek, Write};
+use tiff::encoder::{colortype, TiffEncoder, TiffValue};
+use tiff::tags::{Tag, Type};
+
+/// The TIFF tag an ICC profile is embedded in.
+const ICC_PROFILE_TAG: u16 = 34675;
+
+/// Black coverage above which the rich black recipe starts being mixed in.
+const RICH_BLACK_THRESHOLD: f64 = 0.8;
+
+/// How gray levels are separated into process inks.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Profile {
+    /// Black ink only, without dot gain compensation.
+    BlackOnly,
+    /// Coated stock: 15% dot gain, shadows deepened with C60 M40 Y40.
+    Coated,
+    /// Uncoated stock: 25% dot gain, shadows deepened with C40 M30 Y30.
+    Uncoated,
+}
+
+impl Profile {
+    /// Parse the name of a profile, as given on the command line.
+    pub fn parse(s: &str) -> Option<Profile> {
+        match s {
+            "k" => Some(Profile::BlackOnly),
+            "coated" => Some(Profile::Coated),
+            "uncoated" => Some(Profile::Uncoated),
+            _ => None,
+        }
+    }
+
+    /// How much a 50% dot grows on press, as a fraction of the full tone range.
+    fn dot_gain(self) -> f64 {
+        match self {
+            Profile::BlackOnly => 0.0,
+            Profile::Coated => 0.15,
+            Profile::Uncoated => 0.25,
+        }
+    }
+
+    /// Cyan, magenta and yellow laid under solid black.
+    fn rich_black(self) -> [f64; 3] {
+        match self {
+            Profile::BlackOnly => [0.0; 3],
+            Profile::Coated => [0.6, 0.4, 0.4],
+            Profile::Uncoated => [0.4, 0.3, 0.3],
+        }
+    }
+
+    /// Separate an sRGB encoded gray level into cyan, magenta, yellow and black coverage.
+    ///
+    /// The gray level is decoded to linear light first, so that the printed coverage matches
+    /// the reflectance the pixel stands for.
+    pub fn separate(self, gray: u8) -> [u8; 4] {
+        let target = 1.0 - srgb_to_linear(gray);
+        // Dot gain is modelled as a parabola peaking at 50%, printed = a + 4g·a(1 - a), which is
+        // solved for the coverage `a` to put on the plate.
+        let g = self.dot_gain();
+        let black = if g == 0.0 {
+            target
+        } else {
+            let b = 1.0 + 4.0 * g;
+            (b - (b * b - 16.0 * g * target).sqrt()) / (8.0 * g)
+        };
+        let rich = ((black - RICH_BLACK_THRESHOLD) / (1.0 - RICH_BLACK_THRESHOLD)).clamp(0.0, 1.0);
+        let [c, m, y] = self.rich_black().map(|ink| ink * rich);
+        [c, m, y, black].map(|ink| (ink.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+}
+
+#[test]
+fn test_separate() {
+    for profile in [Profile::BlackOnly, Profile::Coated, Profile::Uncoated] {
+        assert_eq!(profile.separate(255), [0, 0, 0, 0]);
+        assert_eq!(profile.separate(0)[3], 255);
+    }
+    assert_eq!(Profile::BlackOnly.separate(0), [0, 0, 0, 255]);
+    assert_eq!(Profile::Coated.separate(0), [153, 102, 102, 255]);
+
+    // Middle gray is about a fifth of the light, and the dot gain is held back on the plate.
+    let flat = Profile::BlackOnly.separate(128)[3];
+    assert!(flat > 195 && flat < 205);
+    assert!(Profile::Coated.separate(128)[3] < flat);
+    assert!(Profile::Uncoated.separate(128)[3] < Profile::Coated.separate(128)[3]);
+    assert_eq!(Profile::Coated.separate(128)[..3], [0, 0, 0]);
+}
+
+/// An ICC profile, as the opaque bytes the TIFF tag holds.
+struct IccProfile<'a>(&'a [u8]);
+
+impl TiffValue for IccProfile<'_> {
+    const BYTE_LEN: u8 = 1;
+    const FIELD_TYPE: Type = Type::UNDEFINED;
+
+    fn count(&self) -> usize {
+        self.0.len()
+    }
+
+    fn data(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.0)
+    }
+}
+
+/// Write `samples` as an image of color type `C` with dimensions `bounds`, tagged with the ICC
+/// profile `icc` if one is given.
+fn write_samples<C: colortype::ColorType<Inner = u8>, W: Write + Seek>(
+    encoder: &mut TiffEncoder<W>,
+    (width, height): (u32, u32),
+    samples: &[u8],
+    icc: Option<&[u8]>,
+) -> tiff::TiffResult<()> {
+    let mut image = encoder.new_image::<C>(width, height)?;
+    if let Some(icc) = icc {
+        image
+            .encoder()
+            .write_tag(Tag::Unknown(ICC_PROFILE_TAG), IccProfile(icc))?;
+    }
+    image.write_data(samples)
+}
+
+/// Write the buffer `pixels`, whose dimensions are given by `bounds`, to the file named
+/// `filename` as a TIFF image. Gray pixels are separated into CMYK according to `profile` if one
+/// is given; pixels of three bytes are written as RGB. The ICC profile `icc` of gray or RGB pixels
+/// is embedded if given.
+pub fn write_tiff(
+    filename: &str,
+    pixels: &[u8],
+    bounds: (usize, usize),
+    profile: Option<Profile>,
+    icc: Option<&[u8]>,
+) -> Result<(), std::io::Error> {
+    let output = BufWriter::new(File::create(filename)?);
+    let size = (bounds.0 as u32, bounds.1 as u32);
+    let write = || -> tiff::TiffResult<()> {
+        let mut encoder = TiffEncoder::new(output)?;
+        match profile {
+            Some(profile) => {
+                let inks: Vec<u8> = pixels.iter().flat_map(|&p| profile.separate(p)).collect();
+                write_samples::<colortype::CMYK8, _>(&mut encoder, size, &inks, None)
+            }
+            None if pixels.len() == bounds.0 * bounds.1 * 3 => {
+                write_samples::<colortype::RGB8, _>(&mut encoder, size, pixels, icc)
+            }
+            None => write_samples::<colortype::Gray8, _>(&mut encoder, size, pixels, icc),
+        }
+    };
+    match write() {
+        Ok(()) => Ok(()),
+        Err(tiff::TiffError::IoError(e)) => Err(e),
+        Err(e) => Err(std::io::Error::other(format!(
+            "Failed to write TIFF image: {:?}",
+            e
+        ))),
+    }
+}
+
+#[test]
+fn test_write_tiff() {
+    let filename = std::env::temp_dir().join("rust-experiments-test.tiff");
+    let filename = filename.to_str().unwrap();
+    write_tiff(
+        filename,
+        &[0, 255, 128, 64],
+        (2, 2),
+        Some(Profile::Coated),
+        None,
+    )
+    .unwrap();
+
+    let mut decoder = tiff::decoder::Decoder::new(File::open(filename).unwrap()).unwrap();
+    assert_eq!(decoder.colortype().unwrap(), tiff::ColorType::CMYK(8));
+    match decoder.read_image().unwrap() {
+        tiff::decoder::DecodingResult::U8(inks) => {
+            assert_eq!(inks.len(), 16);
+            assert_eq!(inks[..8], [153, 102, 102, 255, 0, 0, 0, 0]);
+        }
+        _ => panic!("expected 8-bit samples"),
+    }
+
+    write_tiff(filename, &[0, 255, 128, 64], (2, 2), None, None).unwrap();
+    let mut decoder = tiff::decoder::Decoder::new(File::open(filename).unwrap()).unwrap();
+    assert_eq!(decoder.colortype().unwrap(), tiff::ColorType::Gray(8));
+
+    write_tiff(filename, &[0; 12], (2, 2), None, Some(b"icc profile")).unwrap();
+    let mut decoder = tiff::decoder::Decoder::new(File::open(filename).unwrap()).unwrap();
+    assert_eq!(decoder.colortype().unwrap(), tiff::ColorType::RGB(8));
+    let icc = decoder
+        .get_tag_u8_vec(Tag::Unknown(ICC_PROFILE_TAG))
+        .unwrap();
+    assert_eq!(icc, b"icc profile");
+    std::fs::remove_file(filename).unwrap();
+}
@@ -0,0 +1,59 @@
+/// Convert an sRGB encoded 8-bit value to linear light in `[0, 1]`.
+pub fn srgb_to_linear(value: u8) -> f64 {
+    let v = value as f64 / 255.0;
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Convert linear light in `[0, 1]` to an sRGB encoded 8-bit value.
+pub fn linear_to_srgb(linear: f64) -> u8 {
+    let l = linear.clamp(0.0, 1.0);
+    let v = if l <= 0.0031308 {
+        l * 12.92
+    } else {
+        1.055 * l.powf(1.0 / 2.4) - 0.055
+    };
+    (v * 255.0).round() as u8
+}
+
+#[test]
+fn test_srgb_round_trip() {
+    for value in 0..=255 {
+        assert_eq!(linear_to_srgb(srgb_to_linear(value)), value);
+    }
+    assert_eq!(srgb_to_linear(0), 0.0);
+    assert_eq!(srgb_to_linear(255), 1.0);
+    // Half the light is much brighter than half the code values.
+    assert_eq!(linear_to_srgb(0.5), 188);
+}
+
+/// Check that `profile` looks like an ICC profile: long enough for the profile header, with the
+/// size it declares matching its length and the `acsp` signature in place.
+pub fn validate_icc(profile: &[u8]) -> Result<(), String> {
+    if profile.len() < 128 {
+        return Err("ICC profile is too short".to_string());
+    }
+    let size = u32::from_be_bytes([profile[0], profile[1], profile[2], profile[3]]);
+    if size as usize != profile.len() {
+        return Err("ICC profile size doesn't match its header".to_string());
+    }
+    if &profile[36..40] != b"acsp" {
+        return Err("ICC profile signature is missing".to_string());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_validate_icc() {
+    let mut profile = vec![0; 132];
+    profile[..4].copy_from_slice(&132u32.to_be_bytes());
+    profile[36..40].copy_from_slice(b"acsp");
+    assert!(validate_icc(&profile).is_ok());
+    assert!(validate_icc(&profile[..100]).is_err());
+    assert!(validate_icc(&profile[..130]).is_err());
+    profile[36] = b'x';
+    assert!(validate_icc(&profile).is_err());
+}
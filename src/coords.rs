@@ -0,0 +1,174 @@
+/// How to write coordinates of the plane as text.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CoordFormat {
+    /// The fewest decimal digits that read back as the same f64.
+    #[default]
+    Shortest,
+    /// Rounded to this many significant decimal digits.
+    Digits(usize),
+    /// Every decimal digit of the f64's binary value, however many it takes.
+    Exact,
+    /// The f64's binary value in C99 hexadecimal notation, like `-0x1.8p-1`.
+    Hex,
+}
+
+impl CoordFormat {
+    pub fn parse(s: &str) -> Option<CoordFormat> {
+        match s {
+            "shortest" => Some(CoordFormat::Shortest),
+            "exact" => Some(CoordFormat::Exact),
+            "hex" => Some(CoordFormat::Hex),
+            _ => s
+                .parse()
+                .ok()
+                .filter(|&digits| (1..=17).contains(&digits))
+                .map(CoordFormat::Digits),
+        }
+    }
+
+    /// Write `x` in this format. Every format but `Digits` reads back as exactly `x`.
+    pub fn format(self, x: f64) -> String {
+        if !x.is_finite() {
+            return x.to_string();
+        }
+        match self {
+            CoordFormat::Shortest => x.to_string(),
+            CoordFormat::Digits(digits) => {
+                let magnitude = if x == 0.0 {
+                    0
+                } else {
+                    x.abs().log10().floor() as i32
+                };
+                let decimals = (digits as i32 - 1 - magnitude).max(0) as usize;
+                format!("{:.*}", decimals, x)
+            }
+            CoordFormat::Exact => {
+                // A binary fraction with its lowest set bit at 2^-n has exactly n decimals.
+                let (mantissa, exponent) = decompose(x);
+                let decimals = if mantissa == 0 {
+                    0
+                } else {
+                    (-(exponent + mantissa.trailing_zeros() as i32)).max(0) as usize
+                };
+                format!("{:.*}", decimals, x)
+            }
+            CoordFormat::Hex => hex(x),
+        }
+    }
+
+    /// Write the complex number `z` as `re,im`, the way coordinates are given on the command line.
+    pub fn format_complex(self, z: num::Complex<f64>) -> String {
+        format!("{},{}", self.format(z.re), self.format(z.im))
+    }
+}
+
+/// Split the finite `x` into an integer mantissa and a power of two, `x = ±mantissa * 2^exponent`.
+fn decompose(x: f64) -> (u64, i32) {
+    let bits = x.to_bits();
+    let biased = ((bits >> 52) & 0x7ff) as i32;
+    let fraction = bits & ((1 << 52) - 1);
+    if biased == 0 {
+        (fraction, -1074)
+    } else {
+        (fraction | 1 << 52, biased - 1075)
+    }
+}
+
+/// Write the finite `x` in C99 hexadecimal notation.
+fn hex(x: f64) -> String {
+    let sign = if x.is_sign_negative() { "-" } else { "" };
+    let bits = x.to_bits();
+    let biased = ((bits >> 52) & 0x7ff) as i32;
+    let fraction = bits & ((1 << 52) - 1);
+    let (lead, exponent) = match (biased, fraction) {
+        (0, 0) => return format!("{}0x0p+0", sign),
+        (0, _) => (0, -1022),
+        _ => (1, biased - 1023),
+    };
+    let digits = format!("{:013x}", fraction);
+    let digits = digits.trim_end_matches('0');
+    let point = if digits.is_empty() { "" } else { "." };
+    format!("{}0x{}{}{}p{:+}", sign, lead, point, digits, exponent)
+}
+
+#[test]
+fn test_format() {
+    assert_eq!(CoordFormat::Shortest.format(-0.75), "-0.75");
+    assert_eq!(CoordFormat::Shortest.format(0.1), "0.1");
+    assert_eq!(CoordFormat::Digits(4).format(-0.75), "-0.7500");
+    assert_eq!(CoordFormat::Digits(3).format(123.456), "123");
+    assert_eq!(CoordFormat::Digits(2).format(0.001234), "0.0012");
+    assert_eq!(CoordFormat::Exact.format(-0.75), "-0.75");
+    assert_eq!(CoordFormat::Exact.format(2.0), "2");
+    assert_eq!(
+        CoordFormat::Exact.format(0.1),
+        "0.1000000000000000055511151231257827021181583404541015625"
+    );
+    assert_eq!(CoordFormat::Hex.format(-0.75), "-0x1.8p-1");
+    assert_eq!(CoordFormat::Hex.format(1.0), "0x1p+0");
+    assert_eq!(CoordFormat::Hex.format(0.1), "0x1.999999999999ap-4");
+    assert_eq!(CoordFormat::Hex.format(-0.0), "-0x0p+0");
+    assert_eq!(CoordFormat::Hex.format(5e-324), "0x0.0000000000001p-1022");
+    assert_eq!(CoordFormat::parse("12"), Some(CoordFormat::Digits(12)));
+    assert_eq!(CoordFormat::parse("0"), None);
+    assert_eq!(CoordFormat::parse("octal"), None);
+}
+
+/// Parse a real number written in decimal, like `"-0.75"`, or in C99 hexadecimal notation, like
+/// `"-0x1.8p-1"`, as `CoordFormat` writes them.
+pub fn parse_real(s: &str) -> Option<f64> {
+    let (negative, unsigned) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let Some(hex) = unsigned
+        .strip_prefix("0x")
+        .or_else(|| unsigned.strip_prefix("0X"))
+    else {
+        return s.parse().ok();
+    };
+    let (digits, exponent) = hex.split_once(['p', 'P'])?;
+    let exponent: i32 = exponent.parse().ok()?;
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    if whole.is_empty() && fraction.is_empty() {
+        return None;
+    }
+    let mut mantissa: u64 = 0;
+    for c in whole.chars().chain(fraction.chars()) {
+        mantissa = mantissa
+            .checked_mul(16)?
+            .checked_add(c.to_digit(16)? as u64)?;
+    }
+    // Larger mantissas would be rounded, and the value wouldn't be the one written.
+    if mantissa >= 1 << 53 {
+        return None;
+    }
+    let exponent = exponent.checked_sub(4 * fraction.len() as i32)?;
+    // Scale in two steps, so that subnormal results aren't lost to an intermediate underflow.
+    let half = exponent / 2;
+    let value = mantissa as f64 * 2f64.powi(half) * 2f64.powi(exponent - half);
+    Some(if negative { -value } else { value })
+}
+
+#[test]
+fn test_parse_real() {
+    assert_eq!(parse_real("-0.75"), Some(-0.75));
+    assert_eq!(parse_real("-0x1.8p-1"), Some(-0.75));
+    assert_eq!(parse_real("0X1P+0"), Some(1.0));
+    assert_eq!(parse_real("0x1.999999999999ap-4"), Some(0.1));
+    assert_eq!(parse_real("0x0.0000000000001p-1022"), Some(5e-324));
+    assert_eq!(parse_real("0x1.8"), None);
+    assert_eq!(parse_real("0xp+1"), None);
+    assert_eq!(parse_real("0x1.gp+0"), None);
+    assert_eq!(parse_real("x"), None);
+    for x in [
+        0.1,
+        -1.7976931348623157e308,
+        2.2250738585072014e-308,
+        -1.25e-310,
+    ] {
+        for format in [CoordFormat::Shortest, CoordFormat::Exact, CoordFormat::Hex] {
+            assert_eq!(parse_real(&format.format(x)), Some(x));
+        }
+    }
+}
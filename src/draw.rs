@@ -0,0 +1,350 @@
+/// Width of each glyph of the built-in font, in pixels.
+pub const GLYPH_WIDTH: usize = 5;
+
+/// Height of each glyph of the built-in font, in pixels.
+pub const GLYPH_HEIGHT: usize = 7;
+
+/// Horizontal distance from one glyph to the next, and vertical distance from one line of text to
+/// the next, in pixels.
+const ADVANCE: (usize, usize) = (GLYPH_WIDTH + 1, GLYPH_HEIGHT + 2);
+
+/// A 5×7 bitmap font covering digits, capital letters and the punctuation found in coordinates and
+/// labels. Each row is a byte whose five low bits are its pixels, the highest on the left.
+/// Lowercase letters are drawn as capitals, and anything else as the glyph for `?`.
+#[rustfmt::skip]
+const GLYPHS: [(char, [u8; GLYPH_HEIGHT]); 50] = [
+    (' ', [0, 0, 0, 0, 0, 0, 0]),
+    ('0', [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110]),
+    ('1', [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('2', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111]),
+    ('3', [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110]),
+    ('4', [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010]),
+    ('5', [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110]),
+    ('6', [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110]),
+    ('7', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000]),
+    ('8', [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110]),
+    ('9', [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100]),
+    ('A', [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('B', [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110]),
+    ('C', [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110]),
+    ('D', [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100]),
+    ('E', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111]),
+    ('F', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('G', [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111]),
+    ('H', [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('I', [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('J', [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100]),
+    ('K', [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001]),
+    ('L', [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111]),
+    ('M', [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001]),
+    ('N', [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001]),
+    ('O', [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('P', [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('Q', [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101]),
+    ('R', [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001]),
+    ('S', [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110]),
+    ('T', [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100]),
+    ('U', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('V', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100]),
+    ('W', [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010]),
+    ('X', [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001]),
+    ('Y', [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100]),
+    ('Z', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111]),
+    ('.', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100]),
+    (',', [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000]),
+    ('-', [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000]),
+    ('+', [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000]),
+    (':', [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000]),
+    ('/', [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000]),
+    ('(', [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010]),
+    (')', [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000]),
+    ('=', [0b00000, 0b00000, 0b11111, 0b00000, 0b11111, 0b00000, 0b00000]),
+    ('_', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111]),
+    ('%', [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011]),
+    ('#', [0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010]),
+    ('?', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100]),
+];
+
+/// The rows of the glyph for `c`.
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    let c = c.to_ascii_uppercase();
+    let (_, rows) = GLYPHS
+        .iter()
+        .find(|&&(g, _)| g == c)
+        .unwrap_or(&GLYPHS[GLYPHS.len() - 1]);
+    *rows
+}
+
+/// The width and height of `text` drawn by `Canvas::text`, in pixels.
+pub fn text_size(text: &str) -> (usize, usize) {
+    let columns = text.lines().map(|line| line.chars().count()).max();
+    match columns {
+        None | Some(0) => (0, 0),
+        Some(columns) => (
+            columns * ADVANCE.0 - 1,
+            text.lines().count() * ADVANCE.1 - 2,
+        ),
+    }
+}
+
+#[test]
+fn test_text_size() {
+    assert_eq!(text_size(""), (0, 0));
+    assert_eq!(text_size("1"), (5, 7));
+    assert_eq!(text_size("-0.75"), (29, 7));
+    assert_eq!(text_size("1\nI0"), (11, 16));
+}
+
+/// An image to draw overlays on: `pixels` holds `bounds.0` by `bounds.1` pixels of `channels`
+/// bytes each, gray or RGB, and opaque or with alpha, as `Renderer::channels` gives them.
+///
+/// Positions are in pixels from the upper left corner of the image, like those
+/// `point_to_sample` gives, so pixel `(x, y)` covers the positions from `(x, y)` up to
+/// `(x + 1, y + 1)`. Anything falling outside of the image is clipped.
+pub struct Canvas<'a> {
+    pixels: &'a mut [u8],
+    channels: usize,
+    bounds: (usize, usize),
+}
+
+impl<'a> Canvas<'a> {
+    pub fn new(pixels: &'a mut [u8], channels: usize, bounds: (usize, usize)) -> Canvas<'a> {
+        Canvas {
+            pixels,
+            channels,
+            bounds,
+        }
+    }
+
+    /// Set the pixel in column `x` and row `y` to `color`, opaque. Gray images get its luma.
+    pub fn plot(&mut self, x: i64, y: i64, color: [u8; 3]) {
+        if x < 0 || y < 0 || x >= self.bounds.0 as i64 || y >= self.bounds.1 as i64 {
+            return;
+        }
+        let index = (y as usize * self.bounds.0 + x as usize) * self.channels;
+        let pixel = &mut self.pixels[index..index + self.channels];
+        match pixel.len() {
+            1 | 2 => {
+                let [r, g, b] = color.map(f64::from);
+                pixel[0] = (0.2126 * r + 0.7152 * g + 0.0722 * b).round() as u8;
+            }
+            _ => pixel[..3].copy_from_slice(&color),
+        }
+        if let 2 | 4 = pixel.len() {
+            pixel[pixel.len() - 1] = 255;
+        }
+    }
+
+    /// Draw a line one pixel thick from `from` to `to`, both included.
+    pub fn line(&mut self, from: (f64, f64), to: (f64, f64), color: [u8; 3]) {
+        let (from, to) = match self.clip(from, to) {
+            Some(ends) => ends,
+            None => return,
+        };
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let steps = dx.abs().max(dy.abs()).ceil().max(1.0);
+        for step in 0..=steps as i64 {
+            let t = step as f64 / steps;
+            let (x, y) = (from.0 + t * dx, from.1 + t * dy);
+            self.plot(x.floor() as i64, y.floor() as i64, color);
+        }
+    }
+
+    /// The part of the segment from `from` to `to` that lies over the image, give or take a
+    /// pixel, found by Liang–Barsky clipping. This keeps lines running far off the image from
+    /// stepping through every pixel they would cover.
+    fn clip(&self, from: (f64, f64), to: (f64, f64)) -> Option<((f64, f64), (f64, f64))> {
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let (width, height) = (self.bounds.0 as f64, self.bounds.1 as f64);
+        let (mut enter, mut leave) = (0.0f64, 1.0f64);
+        for (p, q) in [
+            (-dx, from.0 + 1.0),
+            (dx, width + 1.0 - from.0),
+            (-dy, from.1 + 1.0),
+            (dy, height + 1.0 - from.1),
+        ] {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return None;
+                }
+            } else if p < 0.0 {
+                enter = enter.max(q / p);
+            } else {
+                leave = leave.min(q / p);
+            }
+        }
+        (enter <= leave).then_some((
+            (from.0 + enter * dx, from.1 + enter * dy),
+            (from.0 + leave * dx, from.1 + leave * dy),
+        ))
+    }
+
+    /// Draw the outline of a circle of `radius` pixels, rounded to a whole number, around the
+    /// pixel that `center` falls in.
+    pub fn circle(&mut self, center: (f64, f64), radius: f64, color: [u8; 3]) {
+        let (cx, cy) = (center.0.floor() as i64, center.1.floor() as i64);
+        let radius = radius.round() as i64;
+        // Midpoint circle algorithm: walk one eighth of the circle, and mirror it.
+        let (mut x, mut y, mut error) = (radius, 0, 1 - radius);
+        while x >= y {
+            for (dx, dy) in [
+                (x, y),
+                (y, x),
+                (-y, x),
+                (-x, y),
+                (-x, -y),
+                (-y, -x),
+                (y, -x),
+                (x, -y),
+            ] {
+                self.plot(cx + dx, cy + dy, color);
+            }
+            y += 1;
+            if error < 0 {
+                error += 2 * y + 1;
+            } else {
+                x -= 1;
+                error += 2 * (y - x) + 1;
+            }
+        }
+    }
+
+    /// Fill the pixels whose centers lie within `radius` pixels of `center`.
+    pub fn disc(&mut self, center: (f64, f64), radius: f64, color: [u8; 3]) {
+        let rows = (center.1 - radius).floor().max(0.0) as i64
+            ..=(center.1 + radius).ceil().min(self.bounds.1 as f64) as i64;
+        let columns = (center.0 - radius).floor().max(0.0) as i64
+            ..=(center.0 + radius).ceil().min(self.bounds.0 as f64) as i64;
+        for y in rows {
+            for x in columns.clone() {
+                let (dx, dy) = (x as f64 + 0.5 - center.0, y as f64 + 0.5 - center.1);
+                if dx * dx + dy * dy <= radius * radius {
+                    self.plot(x, y, color);
+                }
+            }
+        }
+    }
+
+    /// Write `text` in the built-in font with the upper left corner of its first glyph at pixel
+    /// `at`, starting a new line below it at each line break.
+    pub fn text(&mut self, at: (i64, i64), text: &str, color: [u8; 3]) {
+        for (line, characters) in text.lines().enumerate() {
+            let top = at.1 + (line * ADVANCE.1) as i64;
+            for (column, c) in characters.chars().enumerate() {
+                let left = at.0 + (column * ADVANCE.0) as i64;
+                for (dy, row) in glyph(c).iter().enumerate() {
+                    for dx in 0..GLYPH_WIDTH {
+                        if row & (1 << (GLYPH_WIDTH - 1 - dx)) != 0 {
+                            self.plot(left + dx as i64, top + dy as i64, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_plot() {
+    let mut pixels = vec![0; 2 * 2 * 4];
+    let mut canvas = Canvas::new(&mut pixels, 4, (2, 2));
+    canvas.plot(1, 0, [10, 20, 30]);
+    canvas.plot(-1, 0, [255, 255, 255]);
+    canvas.plot(2, 1, [255, 255, 255]);
+    assert_eq!(
+        pixels,
+        [0, 0, 0, 0, 10, 20, 30, 255, 0, 0, 0, 0, 0, 0, 0, 0]
+    );
+
+    // Gray images get the luma of the color.
+    let mut pixels = vec![0; 2];
+    let mut canvas = Canvas::new(&mut pixels, 2, (1, 1));
+    canvas.plot(0, 0, [0, 255, 0]);
+    assert_eq!(pixels, [182, 255]);
+}
+
+#[test]
+fn test_line() {
+    let white = [255; 3];
+    let mut pixels = vec![0; 10 * 10];
+    let mut canvas = Canvas::new(&mut pixels, 1, (10, 10));
+    canvas.line((0.5, 0.5), (9.5, 9.5), white);
+    for (index, &p) in pixels.iter().enumerate() {
+        assert_eq!(p == 255, index % 11 == 0);
+    }
+
+    // Lines running far off the image are clipped to the part over it.
+    let mut pixels = vec![0; 10 * 10];
+    let mut canvas = Canvas::new(&mut pixels, 1, (10, 10));
+    canvas.line((-1e12, 4.5), (1e12, 4.5), white);
+    assert!(pixels[40..50].iter().all(|&p| p == 255));
+    assert_eq!(pixels.iter().filter(|&&p| p == 255).count(), 10);
+    let mut pixels = vec![0; 10 * 10];
+    let mut canvas = Canvas::new(&mut pixels, 1, (10, 10));
+    canvas.line((-5.0, -5.0), (-5.0, 20.0), white);
+    canvas.line((20.0, -5.0), (30.0, 20.0), white);
+    assert!(pixels.iter().all(|&p| p == 0));
+}
+
+#[test]
+fn test_circle() {
+    let mut pixels = vec![0; 11 * 11];
+    let mut canvas = Canvas::new(&mut pixels, 1, (11, 11));
+    canvas.circle((5.5, 5.5), 4.0, [255; 3]);
+    let lit = |x: usize, y: usize| pixels[y * 11 + x] == 255;
+    assert!(lit(9, 5) && lit(1, 5) && lit(5, 9) && lit(5, 1));
+    assert!(!lit(5, 5) && !lit(0, 5));
+    // Symmetric about both axes and both diagonals.
+    for y in 0..11 {
+        for x in 0..11 {
+            assert_eq!(lit(x, y), lit(10 - x, y));
+            assert_eq!(lit(x, y), lit(x, 10 - y));
+            assert_eq!(lit(x, y), lit(y, x));
+        }
+    }
+}
+
+#[test]
+fn test_disc() {
+    let mut pixels = vec![0; 10 * 10];
+    let mut canvas = Canvas::new(&mut pixels, 1, (10, 10));
+    canvas.disc((5.0, 5.0), 1.0, [255; 3]);
+    // The four pixels around the corner at (5, 5).
+    assert_eq!(pixels.iter().filter(|&&p| p == 255).count(), 4);
+    assert!([44, 45, 54, 55].iter().all(|&i| pixels[i] == 255));
+    let mut pixels = vec![0; 10 * 10];
+    let mut canvas = Canvas::new(&mut pixels, 1, (10, 10));
+    canvas.disc((0.0, 0.0), 100.0, [255; 3]);
+    assert!(pixels.iter().all(|&p| p == 255));
+}
+
+#[test]
+fn test_text() {
+    let mut pixels = vec![0; 12 * 8];
+    let mut canvas = Canvas::new(&mut pixels, 1, (12, 8));
+    canvas.text((1, 1), "-i", [255; 3]);
+    let rows: Vec<String> = pixels
+        .chunks(12)
+        .map(|row| {
+            row.iter()
+                .map(|&p| if p == 255 { '#' } else { '.' })
+                .collect()
+        })
+        .collect();
+    assert_eq!(
+        rows,
+        [
+            "............",
+            "........###.",
+            ".........#..",
+            ".........#..",
+            ".#####...#..",
+            ".........#..",
+            ".........#..",
+            "........###.",
+        ]
+    );
+    // Characters the font lacks are drawn as question marks.
+    assert_eq!(glyph('é'), glyph('?'));
+    assert_eq!(glyph('q'), glyph('Q'));
+}
@@ -0,0 +1,117 @@
+/// What went wrong, each with its own exit status, following the BSD `sysexits.h` codes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorKind {
+    /// The command line is missing required arguments.
+    Usage,
+    /// An argument couldn't be parsed or doesn't make sense with the others.
+    Parse,
+    /// An output file couldn't be written.
+    Io,
+    /// The render would take more memory than allowed.
+    Memory,
+}
+
+impl ErrorKind {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Usage => 64,
+            ErrorKind::Parse => 65,
+            ErrorKind::Io => 74,
+            ErrorKind::Memory => 75,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Usage => "usage",
+            ErrorKind::Parse => "parse",
+            ErrorKind::Io => "io",
+            ErrorKind::Memory => "memory",
+        }
+    }
+}
+
+/// How errors are reported on standard error.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ErrorFormat {
+    /// A line of text for people.
+    #[default]
+    Text,
+    /// A JSON object on a single line, for programs.
+    Json,
+}
+
+impl ErrorFormat {
+    pub fn parse(s: &str) -> Option<ErrorFormat> {
+        match s {
+            "text" => Some(ErrorFormat::Text),
+            "json" => Some(ErrorFormat::Json),
+            _ => None,
+        }
+    }
+
+    /// Find the error format among the command line arguments before they are parsed, so that
+    /// errors parsing them are reported in it too.
+    pub fn from_args(args: &[String]) -> ErrorFormat {
+        args.windows(2)
+            .rev()
+            .find(|pair| pair[0] == "--error-format")
+            .and_then(|pair| ErrorFormat::parse(&pair[1]))
+            .unwrap_or_default()
+    }
+
+    /// Describe an error of the given `kind`.
+    pub fn format(self, kind: ErrorKind, message: &str) -> String {
+        match self {
+            ErrorFormat::Text => format!("error: {}", message),
+            ErrorFormat::Json => format!(
+                r#"{{"error":"{}","code":{},"message":{}}}"#,
+                kind.name(),
+                kind.exit_code(),
+                json_string(message)
+            ),
+        }
+    }
+}
+
+/// Quote `s` as a JSON string.
+pub fn json_string(s: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted += "\\\"",
+            '\\' => quoted += "\\\\",
+            '\n' => quoted += "\\n",
+            c if (c as u32) < 0x20 => quoted += &format!("\\u{:04x}", c as u32),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[test]
+fn test_format() {
+    assert_eq!(
+        ErrorFormat::Text.format(ErrorKind::Parse, "error parsing --dpi"),
+        "error: error parsing --dpi"
+    );
+    assert_eq!(
+        ErrorFormat::Json.format(ErrorKind::Io, "can't write \"a\\b\"\n\t"),
+        r#"{"error":"io","code":74,"message":"can't write \"a\\b\"\n\u0009"}"#
+    );
+}
+
+#[test]
+fn test_from_args() {
+    let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+    assert_eq!(ErrorFormat::from_args(&args("a b")), ErrorFormat::Text);
+    assert_eq!(
+        ErrorFormat::from_args(&args("a --error-format json b")),
+        ErrorFormat::Json
+    );
+    assert_eq!(
+        ErrorFormat::from_args(&args("--error-format xml")),
+        ErrorFormat::Text
+    );
+}
@@ -0,0 +1,376 @@
+use crate::precision::Real;
+use num::Complex;
+use std::str::FromStr;
+
+/// Parse the string `s` as a pair, like `"400x600"` or `"1.0,0.5"`.
+///
+/// # Examples
+/// ```
+/// # use rust_experiments::geometry::parse_pair;
+/// assert_eq!(parse_pair("400x600", 'x'), Some((400, 600)));
+/// assert_eq!(parse_pair("1.0,0.5", ','), Some((1.0, 0.5)));
+/// ```
+pub fn parse_pair<T: FromStr>(s: &str, separator: char) -> Option<(T, T)> {
+    // Find position of separator
+    let index = s.find(separator)?;
+    match (T::from_str(&s[..index]), T::from_str(&s[index + 1..])) {
+        (Ok(l), Ok(r)) => Some((l, r)),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_parse_pair() {
+    assert_eq!(parse_pair::<i32>("", ','), None);
+    assert_eq!(parse_pair::<i32>("10,", ','), None);
+    assert_eq!(parse_pair::<i32>(",10", ','), None);
+    assert_eq!(parse_pair::<i32>("10,20", ','), Some((10, 20)));
+    assert_eq!(parse_pair::<i32>("10,20xy", ','), None);
+    assert_eq!(parse_pair::<f64>("0.5x", 'x'), None);
+    assert_eq!(parse_pair::<f64>("0.5x1.5", 'x'), Some((0.5, 1.5)));
+}
+
+/// Parse a pair of floating-point numbers separated by a comma as a complex number. The numbers
+/// may be decimal or hexadecimal, as `coords::CoordFormat` writes them.
+pub fn parse_complex(s: &str) -> Option<Complex<f64>> {
+    let (re, im) = s.split_once(',')?;
+    Some(Complex {
+        re: crate::coords::parse_real(re)?,
+        im: crate::coords::parse_real(im)?,
+    })
+}
+
+#[test]
+fn test_parse_complex() {
+    assert_eq!(
+        parse_complex("1.25,-0.0625"),
+        Some(Complex {
+            re: 1.25,
+            im: -0.0625
+        })
+    );
+    assert_eq!(parse_complex(",-0.0625"), None);
+    assert_eq!(
+        parse_complex("-0x1.8p-1,0x1p-4"),
+        Some(Complex {
+            re: -0.75,
+            im: 0.0625
+        })
+    );
+}
+
+/// Given the row and column of a pixel in the output image, return the corresponding point on the
+/// complex plane.
+pub fn pixel_to_point<R: Real>(
+    bounds: (usize, usize),
+    pixel: (usize, usize),
+    upper_left: Complex<R>,
+    lower_right: Complex<R>,
+) -> Complex<R> {
+    sample_to_point(
+        bounds,
+        (pixel.0 as f64, pixel.1 as f64),
+        upper_left,
+        lower_right,
+    )
+}
+
+/// Like `pixel_to_point`, but for a position anywhere inside a pixel, like `(10.5, 3.25)`.
+///
+/// The horizontal and vertical scales are independent, so pixels cover a non-square area of the
+/// plane whenever the aspect ratio of the corners differs from that of `bounds`.
+pub fn sample_to_point<R: Real>(
+    bounds: (usize, usize),
+    sample: (f64, f64),
+    upper_left: Complex<R>,
+    lower_right: Complex<R>,
+) -> Complex<R> {
+    let (width, height) = (
+        lower_right.re - upper_left.re,
+        upper_left.im - lower_right.im,
+    );
+    Complex {
+        re: upper_left.re + R::from_f64(sample.0) * width / R::from_f64(bounds.0 as f64),
+        im: upper_left.im - R::from_f64(sample.1) * height / R::from_f64(bounds.1 as f64),
+    }
+}
+
+#[test]
+fn test_sample_to_point() {
+    let (upper_left, lower_right) = (Complex { re: -1.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    assert_eq!(
+        sample_to_point((100, 100), (25.0, 75.0), upper_left, lower_right),
+        pixel_to_point((100, 100), (25, 75), upper_left, lower_right)
+    );
+    // Anamorphic: each pixel is twice as wide as it is high, so the same offset along both axes
+    // lands at different distances.
+    assert_eq!(
+        sample_to_point((100, 200), (25.0, 25.0), upper_left, lower_right),
+        Complex { re: -0.5, im: 0.75 }
+    );
+}
+
+/// The position in an image with dimensions `bounds` between `upper_left` and `lower_right` that
+/// `point` maps to: the inverse of `sample_to_point`. Points outside of the view map to positions
+/// outside of the image, left of or above it when negative, so overlays can be drawn running off
+/// its edges.
+pub fn point_to_sample(
+    bounds: (usize, usize),
+    point: Complex<f64>,
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+) -> (f64, f64) {
+    (
+        (point.re - upper_left.re) / (lower_right.re - upper_left.re) * bounds.0 as f64,
+        (upper_left.im - point.im) / (upper_left.im - lower_right.im) * bounds.1 as f64,
+    )
+}
+
+#[test]
+fn test_point_to_sample() {
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    assert_eq!(
+        point_to_sample(
+            (30, 20),
+            Complex { re: -0.5, im: 0.5 },
+            upper_left,
+            lower_right
+        ),
+        (15.0, 5.0)
+    );
+    assert_eq!(
+        point_to_sample(
+            (30, 20),
+            Complex { re: -2.5, im: 2.0 },
+            upper_left,
+            lower_right
+        ),
+        (-5.0, -10.0)
+    );
+    // Back and forth lands where it started, give or take rounding.
+    let sample = (12.25, 7.5);
+    let point = sample_to_point((30, 20), sample, upper_left, lower_right);
+    let (x, y) = point_to_sample((30, 20), point, upper_left, lower_right);
+    assert!((x - sample.0).abs() < 1e-12 && (y - sample.1).abs() < 1e-12);
+}
+
+/// The pixel of an image with dimensions `bounds` that the position `sample` falls in, if any.
+pub fn sample_to_pixel(bounds: (usize, usize), sample: (f64, f64)) -> Option<(usize, usize)> {
+    let (x, y) = sample;
+    let inside = (0.0..bounds.0 as f64).contains(&x) && (0.0..bounds.1 as f64).contains(&y);
+    inside.then_some((x as usize, y as usize))
+}
+
+#[test]
+fn test_sample_to_pixel() {
+    assert_eq!(sample_to_pixel((30, 20), (0.0, 0.0)), Some((0, 0)));
+    assert_eq!(sample_to_pixel((30, 20), (29.9, 19.5)), Some((29, 19)));
+    assert_eq!(sample_to_pixel((30, 20), (30.0, 10.0)), None);
+    assert_eq!(sample_to_pixel((30, 20), (-0.5, 10.0)), None);
+    assert_eq!(sample_to_pixel((30, 20), (f64::NAN, 10.0)), None);
+}
+
+/// The pixel of an image with dimensions `bounds` between `upper_left` and `lower_right` that
+/// `point` falls in, if any: the inverse of `pixel_to_point`, which gives each pixel's upper left
+/// corner.
+pub fn point_to_pixel(
+    bounds: (usize, usize),
+    point: Complex<f64>,
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+) -> Option<(usize, usize)> {
+    sample_to_pixel(
+        bounds,
+        point_to_sample(bounds, point, upper_left, lower_right),
+    )
+}
+
+#[test]
+fn test_point_to_pixel() {
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let point = |re, im| Complex { re, im };
+    assert_eq!(
+        point_to_pixel((30, 20), upper_left, upper_left, lower_right),
+        Some((0, 0))
+    );
+    assert_eq!(
+        point_to_pixel((30, 20), point(0.95, -0.95), upper_left, lower_right),
+        Some((29, 19))
+    );
+    // The right and bottom edges belong to the pixels beyond the image.
+    assert_eq!(
+        point_to_pixel((30, 20), lower_right, upper_left, lower_right),
+        None
+    );
+    assert_eq!(
+        point_to_pixel((30, 20), point(-2.5, 0.0), upper_left, lower_right),
+        None
+    );
+}
+
+/// Resize the vertical extent of the rectangle between `upper_left` and `lower_right`, keeping its
+/// vertical center, so that each of the pixels in `bounds` covers an area `aspect` times as wide
+/// as it is high. Anamorphic formats stretch such images horizontally when they are displayed.
+pub fn apply_pixel_aspect<R: Real>(
+    bounds: (usize, usize),
+    upper_left: Complex<R>,
+    lower_right: Complex<R>,
+    aspect: f64,
+) -> (Complex<R>, Complex<R>) {
+    let pixel_height =
+        (lower_right.re - upper_left.re) / R::from_f64(bounds.0 as f64) / R::from_f64(aspect);
+    let two = R::from_f64(2.0);
+    let center = (upper_left.im + lower_right.im) / two;
+    let half = pixel_height * R::from_f64(bounds.1 as f64) / two;
+    (
+        Complex {
+            re: upper_left.re,
+            im: center + half,
+        },
+        Complex {
+            re: lower_right.re,
+            im: center - half,
+        },
+    )
+}
+
+#[test]
+fn test_apply_pixel_aspect() {
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.0 }, Complex { re: 2.0, im: -3.0 });
+    assert_eq!(
+        apply_pixel_aspect((400, 200), upper_left, lower_right, 1.0),
+        (Complex { re: -2.0, im: 0.0 }, Complex { re: 2.0, im: -2.0 })
+    );
+    assert_eq!(
+        apply_pixel_aspect((400, 200), upper_left, lower_right, 2.0),
+        (
+            Complex { re: -2.0, im: -0.5 },
+            Complex { re: 2.0, im: -1.5 }
+        )
+    );
+}
+
+/// The upper left and lower right corners of a view centered on `center`, for an image with
+/// dimensions `bounds` whose square pixels are each `pixel_size` wide.
+pub fn viewport<R: Real>(
+    bounds: (usize, usize),
+    center: Complex<R>,
+    pixel_size: f64,
+) -> (Complex<R>, Complex<R>) {
+    let half = Complex {
+        re: R::from_f64(pixel_size * bounds.0 as f64 / 2.0),
+        im: -R::from_f64(pixel_size * bounds.1 as f64 / 2.0),
+    };
+    (center - half, center + half)
+}
+
+#[test]
+fn test_viewport() {
+    assert_eq!(
+        viewport((400, 200), Complex { re: -0.5, im: 0.25 }, 0.01),
+        (
+            Complex { re: -2.5, im: 1.25 },
+            Complex { re: 1.5, im: -0.75 }
+        )
+    );
+}
+
+#[test]
+fn test_pixel_to_point() {
+    assert_eq!(
+        pixel_to_point(
+            (100, 100),
+            (25, 75),
+            Complex { re: -1.0, im: 1.0 },
+            Complex { re: 1.0, im: -1.0 }
+        ),
+        Complex { re: -0.5, im: -0.5 }
+    );
+}
+
+/// Turn and scale `point` about `center` by multiplying its offset from it with `turn`: a turn of
+/// `i` rotates it a quarter turn counterclockwise, one of `2` doubles its distance.
+pub fn turn_about(point: Complex<f64>, center: Complex<f64>, turn: Complex<f64>) -> Complex<f64> {
+    center + (point - center) * turn
+}
+
+#[test]
+fn test_turn_about() {
+    let center = Complex { re: -0.5, im: 0.25 };
+    let point = center + Complex { re: 2.0, im: 1.0 };
+    let i = Complex { re: 0.0, im: 1.0 };
+    assert_eq!(
+        turn_about(point, center, i),
+        center + Complex { re: -1.0, im: 2.0 }
+    );
+    assert_eq!(
+        turn_about(point, center, Complex { re: 2.0, im: 0.0 }),
+        center + Complex { re: 4.0, im: 2.0 }
+    );
+    assert_eq!(turn_about(center, center, i), center);
+}
+
+/// Properties of the mappings between pixels and points, checked on random images and views.
+#[test]
+fn test_mapping_properties() {
+    let mut rng = crate::random::SplitMix64::new(272);
+    for _ in 0..1000 {
+        let bounds = (
+            1 + (rng.next_u64() % 500) as usize,
+            1 + (rng.next_u64() % 500) as usize,
+        );
+        let center = Complex {
+            re: rng.next_f64() * 4.0 - 2.0,
+            im: rng.next_f64() * 4.0 - 2.0,
+        };
+        let pixel_size = 10f64.powf(-12.0 * rng.next_f64());
+        let (upper_left, lower_right) = viewport(bounds, center, pixel_size);
+        // Rounding error allowed in the plane, a few units in the last place of the largest
+        // coordinates involved.
+        let slack =
+            8.0 * f64::EPSILON * (center.norm() + pixel_size * (bounds.0 + bounds.1) as f64);
+
+        // The corners of the image are the corners of the view.
+        assert_eq!(
+            pixel_to_point(bounds, (0, 0), upper_left, lower_right),
+            upper_left
+        );
+        let far = sample_to_point(
+            bounds,
+            (bounds.0 as f64, bounds.1 as f64),
+            upper_left,
+            lower_right,
+        );
+        assert!((far - lower_right).norm() <= slack);
+
+        // Every point inside a pixel maps back to it.
+        let pixel = (
+            (rng.next_u64() % bounds.0 as u64) as usize,
+            (rng.next_u64() % bounds.1 as u64) as usize,
+        );
+        let offset = (0.01 + 0.98 * rng.next_f64(), 0.01 + 0.98 * rng.next_f64());
+        let sample = (pixel.0 as f64 + offset.0, pixel.1 as f64 + offset.1);
+        let point = sample_to_point(bounds, sample, upper_left, lower_right);
+        assert_eq!(
+            point_to_pixel(bounds, point, upper_left, lower_right),
+            Some(pixel)
+        );
+
+        // Sub-pixel offsets move the point by that fraction of a pixel, right and down.
+        let corner = pixel_to_point(bounds, pixel, upper_left, lower_right);
+        let expected = Complex {
+            re: offset.0 * pixel_size,
+            im: -offset.1 * pixel_size,
+        };
+        assert!((point - corner - expected).norm() <= slack);
+
+        // Turning about the center keeps every point at the same distance from it, and turning
+        // back undoes it.
+        let turn = Complex::from_polar(1.0, rng.next_f64() * std::f64::consts::TAU);
+        let turned = turn_about(point, center, turn);
+        let distance = (point - center).norm();
+        assert!(((turned - center).norm() - distance).abs() <= slack);
+        let back = turn_about(turned, center, turn.inv());
+        assert!((back - point).norm() <= 2.0 * slack);
+    }
+}
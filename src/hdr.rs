@@ -0,0 +1,133 @@
+use crate::layers::PointData;
+use std::fs::File;
+use std::io::BufWriter;
+
+/// Peak brightness, in cd/m², that the brightest pixels of a PQ image are mapped to.
+const PQ_PEAK_NITS: f64 = 1000.0;
+
+/// The transfer function used to encode an HDR image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transfer {
+    /// SMPTE ST 2084 perceptual quantizer, encoding absolute display brightness.
+    Pq,
+    /// ARIB STD-B67 hybrid log-gamma, encoding relative scene brightness.
+    Hlg,
+}
+
+impl Transfer {
+    /// The transfer characteristics code point of this function in ITU-T H.273.
+    fn code_point(self) -> u8 {
+        match self {
+            Transfer::Pq => 16,
+            Transfer::Hlg => 18,
+        }
+    }
+
+    /// Encode a linear brightness `l` in `[0, 1]`, relative to the brightest pixel of the image,
+    /// as a signal value in `[0, 1]`.
+    pub fn encode(self, l: f64) -> f64 {
+        match self {
+            Transfer::Pq => {
+                let (m1, m2) = (2610.0 / 16384.0, 2523.0 / 4096.0 * 128.0);
+                let (c1, c2, c3) = (
+                    3424.0 / 4096.0,
+                    2413.0 / 4096.0 * 32.0,
+                    2392.0 / 4096.0 * 32.0,
+                );
+                let y = (l * PQ_PEAK_NITS / 10000.0).powf(m1);
+                ((c1 + c2 * y) / (1.0 + c3 * y)).powf(m2)
+            }
+            Transfer::Hlg => {
+                let (a, b, c) = (0.17883277, 0.28466892, 0.55991073);
+                if l <= 1.0 / 12.0 {
+                    (3.0 * l).sqrt()
+                } else {
+                    a * (12.0 * l - b).ln() + c
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_encode() {
+    assert_eq!(Transfer::Hlg.encode(0.0), 0.0);
+    assert!((Transfer::Hlg.encode(1.0 / 12.0) - 0.5).abs() < 1e-9);
+    assert!((Transfer::Hlg.encode(1.0) - 1.0).abs() < 1e-6);
+    assert!(Transfer::Pq.encode(0.0) < 1e-6);
+    // 1000 cd/m² is at about three quarters of the PQ signal range.
+    assert!((Transfer::Pq.encode(1.0) - 0.7518).abs() < 1e-3);
+}
+
+/// Map the smooth iteration count of a point to a linear brightness in `[0, 1]`, so that the
+/// slowly escaping points near the boundary become the highlights of the image and points that
+/// never escape are black.
+pub fn brightness(data: &PointData, limit: u32) -> f64 {
+    if data.iterations >= limit as f32 {
+        return 0.0;
+    }
+    let t = (data.smooth as f64 / limit as f64).clamp(0.0, 1.0);
+    t * t
+}
+
+/// Write the brightness of `data`, whose dimensions are given by `bounds`, to the file named
+/// `filename` as a 16-bit grayscale PNG tagged with a cICP chunk declaring BT.2020 primaries and
+/// the `transfer` function.
+pub fn write_hdr_png(
+    filename: &str,
+    data: &[PointData],
+    bounds: (usize, usize),
+    limit: u32,
+    transfer: Transfer,
+) -> Result<(), std::io::Error> {
+    let output = BufWriter::new(File::create(filename)?);
+    let mut encoder = png::Encoder::new(output, bounds.0 as u32, bounds.1 as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Sixteen);
+
+    let samples: Vec<u8> = data
+        .iter()
+        .flat_map(|d| {
+            let signal = transfer.encode(brightness(d, limit));
+            ((signal * u16::MAX as f64).round() as u16).to_be_bytes()
+        })
+        .collect();
+
+    let write = || -> Result<(), png::EncodingError> {
+        let mut writer = encoder.write_header()?;
+        // Primaries, transfer function, matrix coefficients (always 0 for PNG) and full range.
+        writer.write_chunk(
+            png::chunk::ChunkType(*b"cICP"),
+            &[9, transfer.code_point(), 0, 1],
+        )?;
+        writer.write_image_data(&samples)?;
+        writer.finish()
+    };
+    write().map_err(|e| std::io::Error::other(format!("Failed to write HDR image: {:?}", e)))
+}
+
+#[test]
+fn test_write_hdr_png() {
+    use num::Complex;
+    let filename = std::env::temp_dir().join("rust-experiments-test-hdr.png");
+    let filename = filename.to_str().unwrap();
+    let data: Vec<_> = [1.0, -1.0]
+        .map(|re| {
+            crate::layers::analyze_point(Complex::default(), Complex { re, im: 0.0 }, 10, 2.0)
+        })
+        .to_vec();
+    write_hdr_png(filename, &data, (2, 1), 10, Transfer::Pq).unwrap();
+    let decoder = png::Decoder::new(File::open(filename).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut buffer).unwrap();
+    assert_eq!(reader.info().color_type, png::ColorType::Grayscale);
+    assert_eq!(reader.info().bit_depth, png::BitDepth::Sixteen);
+    // -1 never escapes, and is black.
+    assert_eq!(buffer[2..], [0, 0]);
+    // The decoder skips the cICP chunk, so look for it among the bytes of the file.
+    let bytes = std::fs::read(filename).unwrap();
+    let chunk = bytes.windows(4).position(|w| w == b"cICP").unwrap();
+    assert_eq!(bytes[chunk + 4..chunk + 8], [9, 16, 0, 1]);
+    std::fs::remove_file(filename).unwrap();
+}
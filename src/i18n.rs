@@ -0,0 +1,761 @@
+/// Languages the command line messages are available in.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Lang {
+    #[default]
+    En,
+    Ru,
+}
+
+impl Lang {
+    pub fn parse(s: &str) -> Option<Lang> {
+        match s {
+            "en" => Some(Lang::En),
+            "ru" => Some(Lang::Ru),
+            _ => None,
+        }
+    }
+
+    /// Pick the language from a POSIX locale name like `ru_RU.UTF-8`, falling back to English.
+    fn from_locale(locale: &str) -> Lang {
+        Lang::parse(&locale.get(..2).unwrap_or("").to_ascii_lowercase()).unwrap_or_default()
+    }
+
+    /// Find the language among the command line arguments before they are parsed, so that
+    /// errors parsing them are reported in it too, or else in the locale's language.
+    pub fn detect(args: &[String]) -> Lang {
+        let given = args
+            .windows(2)
+            .rev()
+            .find(|pair| pair[0] == "--lang")
+            .and_then(|pair| Lang::parse(&pair[1]));
+        given.unwrap_or_else(|| {
+            // The first of these that is set decides, as for any other program.
+            ["LC_ALL", "LC_MESSAGES", "LANG"]
+                .iter()
+                .filter_map(|name| std::env::var(name).ok())
+                .find(|value| !value.is_empty())
+                .map_or(Lang::En, |locale| Lang::from_locale(&locale))
+        })
+    }
+
+    /// The usage message, with `program` as the name of the executable in the example.
+    pub fn usage(self, program: &str) -> String {
+        match self {
+            Lang::En => USAGE_EN,
+            Lang::Ru => USAGE_RU,
+        }
+        .replace("{program}", program)
+    }
+
+    /// Translate an English `message`, matching it against the templates of the catalog, whose
+    /// `{}` placeholders stand for any text, itself translated in turn. Messages the catalog
+    /// doesn't know are left alone.
+    pub fn translate(self, message: &str) -> String {
+        let catalog = match self {
+            Lang::En => return message.to_string(),
+            Lang::Ru => RU,
+        };
+        for (english, translated) in catalog {
+            if let Some(captures) = match_template(english, message) {
+                let mut result = String::new();
+                let mut pieces = translated.split("{}");
+                result += pieces.next().unwrap();
+                for (piece, capture) in pieces.zip(captures) {
+                    result += &self.translate(capture);
+                    result += piece;
+                }
+                return result;
+            }
+        }
+        message.to_string()
+    }
+}
+
+/// Match `message` against `template`, returning the text standing in for each `{}`.
+fn match_template<'a>(template: &str, message: &'a str) -> Option<Vec<&'a str>> {
+    let mut pieces = template.split("{}");
+    let mut rest = message.strip_prefix(pieces.next().unwrap())?;
+    let mut captures = Vec::new();
+    let pieces: Vec<_> = pieces.collect();
+    for (i, piece) in pieces.iter().enumerate() {
+        let end = if i == pieces.len() - 1 {
+            rest.strip_suffix(piece).map(|capture| capture.len())?
+        } else {
+            rest.find(piece)?
+        };
+        captures.push(&rest[..end]);
+        rest = &rest[end + piece.len()..];
+    }
+    rest.is_empty().then_some(captures)
+}
+
+#[test]
+fn test_detect() {
+    let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+    assert_eq!(Lang::detect(&args("a --lang ru b")), Lang::Ru);
+    assert_eq!(Lang::detect(&args("a --lang en b")), Lang::En);
+    assert_eq!(Lang::from_locale("ru_RU.UTF-8"), Lang::Ru);
+    assert_eq!(Lang::from_locale("de_DE.UTF-8"), Lang::En);
+    assert_eq!(Lang::from_locale("C"), Lang::En);
+}
+
+#[test]
+fn test_translate() {
+    assert_eq!(
+        Lang::Ru.translate("error: error parsing --dpi"),
+        "ошибка: ошибка разбора --dpi"
+    );
+    assert_eq!(
+        Lang::Ru.translate("error: error writing PNG file a: b: c"),
+        "ошибка: ошибка записи файла PNG a: b: c"
+    );
+    assert_eq!(
+        Lang::Ru.translate("error: --cmyk needs TIFF output"),
+        "ошибка: для --cmyk нужен вывод в TIFF"
+    );
+    assert_eq!(
+        Lang::Ru
+            .translate("warning: rows 0 to 15 failed in the color stage and were left incomplete"),
+        "предупреждение: строки с 0 по 15 не удались на этапе color и остались незавершёнными"
+    );
+    assert_eq!(
+        Lang::Ru
+            .translate("error: CSV and Parquet output is only supported for the Mandelbrot set"),
+        "ошибка: вывод в CSV и Parquet поддерживается только для множества Мандельброта"
+    );
+    assert_eq!(Lang::Ru.translate("something else"), "something else");
+    assert_eq!(Lang::En.translate("error: x"), "error: x");
+    assert!(Lang::Ru.usage("mandel").contains("mandel mandel.png"));
+}
+
+/// English message templates and their Russian translations. More specific templates come
+/// first, as the first match wins.
+const RU: &[(&str, &str)] = &[
+    ("error: {}", "ошибка: {}"),
+    ("warning: {}", "предупреждение: {}"),
+    (
+        "rows {} to {} failed in the {} stage and were left incomplete",
+        "строки с {} по {} не удались на этапе {} и остались незавершёнными",
+    ),
+    (
+        "error parsing image dimensions",
+        "ошибка разбора размеров изображения",
+    ),
+    (
+        "error parsing upper left corner point",
+        "ошибка разбора левого верхнего угла",
+    ),
+    (
+        "error parsing lower right corner point",
+        "ошибка разбора правого нижнего угла",
+    ),
+    (
+        "expected FILE POINT after sonify",
+        "после sonify ожидаются ФАЙЛ и ТОЧКА",
+    ),
+    (
+        "expected FILE PIXELS UPPERLEFT LOWERRIGHT after buddhabrot",
+        "после buddhabrot ожидаются ФАЙЛ ПИКСЕЛИ ВЕРХНИЙ_ЛЕВЫЙ НИЖНИЙ_ПРАВЫЙ",
+    ),
+    (
+        "expected PIXELS CENTER ZOOM after plan-zoom",
+        "после plan-zoom ожидаются ПИКСЕЛИ ЦЕНТР УВЕЛИЧЕНИЕ",
+    ),
+    (
+        "a zoom of {} is too deep for double-double precision",
+        "увеличение {} слишком глубоко для двойной-двойной точности",
+    ),
+    ("error writing plan: {}", "ошибка записи плана: {}"),
+    (
+        "expected PATTERN PIXELS CENTER ZOOM after zoom",
+        "после zoom ожидаются ШАБЛОН ПИКСЕЛИ ЦЕНТР УВЕЛИЧЕНИЕ",
+    ),
+    (
+        "{} has no frame number like %04d and isn't a GIF or APNG file",
+        "в {} нет номера кадра вида %04d, и это не файл GIF или APNG",
+    ),
+    (
+        "--fps needs a GIF or APNG output",
+        "для --fps нужен вывод в GIF или APNG",
+    ),
+    (
+        "expected FILE PIXELS CENTER ZOOM after loop",
+        "после loop ожидаются ФАЙЛ ПИКСЕЛИ ЦЕНТР УВЕЛИЧЕНИЕ",
+    ),
+    ("{} isn't a GIF or APNG file", "{} не файл GIF или APNG"),
+    (
+        "expected FILE PIXELS UPPERLEFT LOWERRIGHT PARAMETER FROM TO after scan",
+        "после scan ожидаются ФАЙЛ ПИКСЕЛИ ВЕРХНИЙ_ЛЕВЫЙ НИЖНИЙ_ПРАВЫЙ ПАРАМЕТР ОТ ДО",
+    ),
+    (
+        "unknown parameter {}",
+        "неизвестный параметр сканирования {}",
+    ),
+    (
+        "expected FILE TILES after stitch",
+        "после stitch ожидаются ФАЙЛ ПЛИТКИ",
+    ),
+    ("error reading tile {}: {}", "ошибка чтения плитки {}: {}"),
+    (
+        "tile {} isn't 8 bits a sample",
+        "в плитке {} не 8 бит на отсчёт",
+    ),
+    (
+        "tile {} doesn't line up with the others",
+        "плитка {} не совпадает с остальными",
+    ),
+    (
+        "--tiles asks for more tiles than the image has pixels",
+        "--tiles задаёт больше плиток, чем пикселей в изображении",
+    ),
+    (
+        "expected DIRECTORY UPPERLEFT LOWERRIGHT FROM TO after tiles",
+        "после tiles ожидаются КАТАЛОГ ВЕРХНИЙ_ЛЕВЫЙ НИЖНИЙ_ПРАВЫЙ ОТ ДО",
+    ),
+    (
+        "error parsing zoom levels",
+        "ошибка разбора уровней масштаба",
+    ),
+    (
+        "the corners of the region have to differ in both coordinates",
+        "углы области должны различаться по обеим координатам",
+    ),
+    (
+        "error creating directory {}: {}",
+        "ошибка создания каталога {}: {}",
+    ),
+    (
+        "error reading checkpoint file {}: {}",
+        "ошибка чтения файла контрольной точки {}: {}",
+    ),
+    (
+        "error writing checkpoint file {}: {}",
+        "ошибка записи файла контрольной точки {}: {}",
+    ),
+    (
+        "error removing checkpoint file {}: {}",
+        "ошибка удаления файла контрольной точки {}: {}",
+    ),
+    (
+        "--resume needs --seed with --jitter on",
+        "для --resume при --jitter on нужен --seed",
+    ),
+    (
+        "expected edit PALETTE PREVIEW after palette",
+        "после palette ожидаются edit ПАЛИТРА ПРОСМОТР",
+    ),
+    (
+        "unknown color space {}",
+        "неизвестное цветовое пространство {}",
+    ),
+    (
+        "error reading palette {}: {}",
+        "ошибка чтения палитры {}: {}",
+    ),
+    (
+        "error parsing palette on line {}",
+        "ошибка разбора палитры в строке {}",
+    ),
+    (
+        "a palette needs at least two colors",
+        "в палитре должно быть не меньше двух цветов",
+    ),
+    (
+        "error writing palette file {}: {}",
+        "ошибка записи файла палитры {}: {}",
+    ),
+    (
+        "each cycle zooms in {} times and turns {} degrees",
+        "каждый цикл увеличивает в {} раз и поворачивает на {} градусов",
+    ),
+    (
+        "{} doesn't look self-similar, so the loop will jump",
+        "{} не выглядит самоподобной, и цикл будет прерывистым",
+    ),
+    (
+        "frame {} still looks broken after rendering it again",
+        "кадр {} выглядит испорченным и после повторного построения",
+    ),
+    (
+        "rendered {} frames, {}% of the pixels copied from the frame before",
+        "построено кадров: {}, {}% пикселей скопировано из предыдущего кадра",
+    ),
+    ("error parsing point", "ошибка разбора точки"),
+    ("error parsing {}", "ошибка разбора {}"),
+    ("missing value for {}", "не указано значение для {}"),
+    ("unknown option {}", "неизвестный параметр {}"),
+    ("unknown formula {}", "неизвестная формула {}"),
+    ("unknown sampling {}", "неизвестная выборка {}"),
+    ("unknown coloring {}", "неизвестная раскраска {}"),
+    ("unknown algorithm {}", "неизвестный алгоритм {}"),
+    (
+        "unknown tone map {}",
+        "неизвестное тональное отображение {}",
+    ),
+    ("--gain needs --nebula", "для --gain нужен --nebula"),
+    (
+        "error reading state file {}: {}",
+        "ошибка чтения файла состояния {}: {}",
+    ),
+    (
+        "error writing state file {}: {}",
+        "ошибка записи файла состояния {}: {}",
+    ),
+    (
+        "it was saved for another render",
+        "он сохранён для другого рисунка",
+    ),
+    (
+        "wrote a snapshot of {} after {} passes",
+        "снимок {} записан после {} проходов",
+    ),
+    (
+        "unknown anti-aliasing mode {}",
+        "неизвестный режим сглаживания {}",
+    ),
+    (
+        "unknown interior coloring {}",
+        "неизвестная окраска внутренности {}",
+    ),
+    (
+        "unknown output format for {}",
+        "неизвестный формат вывода для {}",
+    ),
+    ("unknown CMYK profile {}", "неизвестный профиль CMYK {}"),
+    ("unknown palette {}", "неизвестная палитра {}"),
+    ("unknown precision {}", "неизвестная точность {}"),
+    (
+        "unknown coordinate format {}",
+        "неизвестный формат координат {}",
+    ),
+    (
+        "unknown verification mode {}",
+        "неизвестный режим проверки {}",
+    ),
+    (
+        "the escape time of pixel {},{} is uncertain",
+        "время выхода пикселя {},{} не определено надёжно",
+    ),
+    (
+        "verified {} boundary pixels, {} uncertain",
+        "проверено граничных пикселей: {}, ненадёжных: {}",
+    ),
+    (
+        "unknown transparent region {}",
+        "неизвестная прозрачная область {}",
+    ),
+    ("unknown channel {}", "неизвестный канал {}"),
+    (
+        "--pair needs two different channels",
+        "для --pair нужны два разных канала",
+    ),
+    (
+        "unknown HDR transfer function {}",
+        "неизвестная передаточная функция HDR {}",
+    ),
+    (
+        "error reading ICC profile {}: {}",
+        "ошибка чтения профиля ICC {}: {}",
+    ),
+    (
+        "error reading annotations {}: {}",
+        "ошибка чтения аннотаций {}: {}",
+    ),
+    (
+        "error parsing annotation on line {}",
+        "ошибка разбора аннотации в строке {}",
+    ),
+    (
+        "unknown point of interest {}",
+        "неизвестная интересная точка {}",
+    ),
+    (
+        "--markers needs --annotations",
+        "для --markers нужен --annotations",
+    ),
+    (
+        "--adaptive needs --samples",
+        "для --adaptive нужен --samples",
+    ),
+    (
+        "--refined needs --adaptive",
+        "для --refined нужен --adaptive",
+    ),
+    (
+        "--dynamics-iterations needs --interior dynamics",
+        "для --dynamics-iterations нужен --interior dynamics",
+    ),
+    (
+        "--zoom and --scale need --center",
+        "для --zoom и --scale нужен --center",
+    ),
+    ("ICC profile is too short", "профиль ICC слишком короткий"),
+    (
+        "ICC profile size doesn't match its header",
+        "размер профиля ICC не совпадает с заголовком",
+    ),
+    (
+        "ICC profile signature is missing",
+        "нет подписи профиля ICC",
+    ),
+    (
+        "--newton needs a polynomial of degree 1 or more",
+        "для --newton нужен многочлен степени 1 или выше",
+    ),
+    (
+        "--denominator requires --numerator",
+        "для --denominator нужен --numerator",
+    ),
+    (
+        "CSV and Parquet output is only supported for the Mandelbrot set",
+        "вывод в CSV и Parquet поддерживается только для множества Мандельброта",
+    ),
+    (
+        "{} output is only supported for the Mandelbrot set",
+        "вывод в {} поддерживается только для множества Мандельброта",
+    ),
+    (
+        "{} is only supported for the Mandelbrot and Julia sets",
+        "{} поддерживается только для множеств Мандельброта и Жюлиа",
+    ),
+    (
+        "{} is only supported for the Mandelbrot set",
+        "{} поддерживается только для множества Мандельброта",
+    ),
+    (
+        "{} output can't be combined with {}",
+        "вывод в {} нельзя сочетать с {}",
+    ),
+    ("{} can't be combined with {}", "{} нельзя сочетать с {}"),
+    ("{} needs TIFF output", "для {} нужен вывод в TIFF"),
+    (
+        "a {} render needs about {} {}, more than the {} {} allowed by --max-memory",
+        "построению {} нужно около {} {}, больше разрешённых --max-memory {} {}",
+    ),
+    ("MiB", "МиБ"),
+    ("KiB", "КиБ"),
+    ("bytes", "байт"),
+    (
+        "error writing {} file {}: {}",
+        "ошибка записи файла {} {}: {}",
+    ),
+    (
+        "{}: {}% done, {} pixels a second, about {} s left",
+        "{}: готово {}%, {} пикселей в секунду, осталось около {} с",
+    ),
+];
+
+const USAGE_EN: &str = "\
+Usage: mandelbrot FILE PIXELS UPPERLEFT LOWERRIGHT [OPTIONS]
+       mandelbrot FILE PIXELS --annotations FILE --goto NAME [OPTIONS]
+       mandelbrot FILE PIXELS --center RE,IM [--zoom Z | --scale S] [OPTIONS]
+       mandelbrot sonify FILE.wav POINT [--julia RE,IM] [--iterations N]
+                         [--note-ms MS]
+       mandelbrot buddhabrot FILE PIXELS UPPERLEFT LOWERRIGHT [--orbits N]
+                             [--iterations N] [--sampling stratified|importance]
+                             [--seed N] [--threads N] [--snapshot-every MINUTES]
+                             [--state FILE] [--nebula R,G,B] [--gain R,G,B]
+                             [--tone sqrt|log|reinhard]
+       mandelbrot plan-zoom PIXELS CENTER ZOOM [--start-zoom Z] [--frames N]
+       mandelbrot zoom PATTERN PIXELS CENTER ZOOM [--start-zoom Z] [--frames N]
+                       [--fps N] [--palette NAME] [--threads N]
+       mandelbrot loop FILE.gif PIXELS CENTER ZOOM [--frames N] [--fps N]
+                       [--palette NAME] [--threads N]
+       mandelbrot scan FILE.gif PIXELS UPPERLEFT LOWERRIGHT power|bailout FROM TO
+                       [--frames N] [--fps N] [--julia RE,IM] [--palette NAME]
+                       [--threads N]
+       mandelbrot palette edit PALETTE PREVIEW.png [--reverse on|off]
+                               [--rotate N] [--resample N]
+                               [--convert linear|srgb] [--output FILE]
+       mandelbrot stitch FILE TILES
+       mandelbrot tiles DIRECTORY UPPERLEFT LOWERRIGHT FROM TO
+                        [--iterations N] [--julia RE,IM] [--palette NAME]
+                        [--threads N]
+Example: {program} mandel.png 1000x750 -1.20,0.35 -1,0.20
+Options:
+  --julia RE,IM         render the Julia set of z*z + c for this c
+  --formula NAME        map iterated instead of z*z + c: burning-ship, which
+                        squares (|Re z| + i|Im z|), tricorn, which squares
+                        conj(z), or mandelbrot, the default
+  --power D             exponent d of z^d + c in place of squaring, any number
+                        above 1
+  --numerator COEFFS    render the Julia set of the rational map P(z)/Q(z),
+                        with P given as \"re,im;re,im;...\" from z^0 up
+  --denominator COEFFS  coefficients of Q(z), 1 by default
+  --newton COEFFS       color the basins of the roots of a polynomial, given like
+                        --numerator, under Newton's method, or of z^3 - 1 with
+                        default
+  --bailout R           escape radius, above 1; 2 by default (1e6 for rational
+                        maps)
+  --z0 RE,IM            starting value of the Mandelbrot iteration, 0 by default
+  --samples AxD         samples taken across and down each pixel, 1x1 by default
+  --jitter on|off       place samples randomly within their grid cells
+  --aa-mode color|value color the samples and average the colors, the default,
+                        or average the gray levels and color the average
+  --adaptive T          take one sample per pixel first, and all of --samples
+                        only where neighbors differ by more than T gray levels
+  --refined FILE        write a mask of the pixels --adaptive supersampled
+  --algorithm NAME      render every pixel (scanline, the default), or fill
+                        rectangles whose border is all one color (subdivision)
+  --seed N              seed for random sampling, recorded in the PNG; taken
+                        from the clock by default
+  --pixel-aspect R      fit the vertical extent so pixels are R times as wide
+                        as high, for anamorphic formats
+  --precision P         compute points in f64, in arb for about 32 digits for
+                        deep zooms, or by perturb, which iterates a few points
+                        at that precision and the rest in f64 relative to
+                        them, much faster but for the Mandelbrot set only;
+                        auto (the default) picks perturb or arb when the
+                        pixels get too small for f64
+  --coord-format F      write coordinates in the log, manifest, caption and CSV
+                        as the shortest decimal that reads back the same (the
+                        default), N significant digits, the exact decimal
+                        value, or hex floats like -0x1.8p-1, which are also
+                        accepted wherever coordinates are read
+  --focus TOP,BOTTOM    render these rows first and work outward from them,
+                        instead of from the middle row
+  --annotations FILE    read points of interest, one \"name re,im zoom\" per line
+  --goto NAME           render the point of interest NAME instead of corners
+  --center RE,IM        render the view centered here instead of corners, with
+                        square pixels
+  --zoom Z              magnification of the view around --center relative to
+                        the whole set, 1 by default
+  --scale S             width of a pixel around --center, instead of --zoom
+  --markers on|off      mark the points of interest in the image
+  --palette NAME        color the image through the grayscale, fire, ocean or
+                        rainbow palette, writing RGB instead of gray
+  --interior MODE       color the interior flat (default) or by the kind of
+                        dynamics: attracting, parabolic or Siegel-like
+  --iterations N        most iterations to wait for a point to escape, 255 by
+                        default
+  --dynamics-iterations N
+                        most iterations to classify the dynamics of a point
+                        that hasn't escaped within --iterations, for
+                        --interior dynamics only, 4096 by default
+  --auto-range LO,HI    stretch the gray scale over these percentiles of the
+                        escape counts, e.g. 1,99
+  --coloring MODE       map escape counts to gray levels as they are (linear, the
+                        default), or spread them evenly by their histogram
+  --output FILE         also write the image to FILE, as PNG, TIFF, PDF, raw
+                        gray bytes, EXR layers, SVG boundary, or CSV or Parquet
+                        tables of x, y, re, im, iterations, smooth and escaped
+                        per pixel, by extension; may be given several times
+  --threads N           use at most N threads in every stage
+  --stage-threads S=N   threads for the iterate, color and encode stages, e.g.
+                        iterate=6,color=6,encode=2; all cores by default
+  --timings on|off      report the time spent in each stage
+  --error-format F      report errors as text or json; exit codes are 64 for
+                        usage, 65 for bad arguments, 74 for I/O errors and 75
+                        for renders over --max-memory
+  --lang en|ru          language of messages, from the locale by default
+  --max-memory SIZE     refuse renders needing more memory than SIZE, e.g. 4G
+  --log-format F        log events of the render as text or json lines, off by
+                        default
+  --progress MODE       show progress, with the pixels rendered a second and the
+                        time left, as a redrawn bar, as plain lines of text for
+                        screen readers and logs, or not at all (off); auto, the
+                        default, shows the bar if standard error is a terminal
+  --quiet on|off        show no progress, whatever standard error is
+  --exr FILE            also write iteration, smooth, distance, trap, angle and
+                        normal layers to an EXR file for compositing
+  --svg FILE            also trace the boundary into smooth SVG curves
+  --pdf FILE            also write the image to a PDF page for printing
+  --page SIZE           PDF page size: a0-a5, letter, legal, tabloid or WxH
+                        in mm, a4 by default
+  --margin MM           PDF page margins, 10 mm by default
+  --dpi N               PDF print resolution, 300 by default
+  --caption on|off      print the coordinates below the image in the PDF
+  --card on|off         add a strip below the image giving its center, zoom,
+                        iterations, palette and the date
+  --tiff FILE           also write the image as a TIFF for print
+  --cmyk PROFILE        separate the TIFF into CMYK inks for k (black only),
+                        coated or uncoated stock
+  --hdr pq|hlg          write a 16-bit HDR PNG of the smooth iteration count
+  --pair A,B            also write two of the iterations, smooth, distance, trap
+                        and angle (of the escaping z) channels as aligned 16-bit
+                        gray PNGs, FILE-A.png and FILE-B.png, for compositing
+  --tiles COLSxROWS     render the image as this many separate PNG tiles,
+                        FILE-tile-COL-ROW.png, skipping the ones already there,
+                        to put together with stitch FILE COLSxROWS
+  --resume FILE         save the rows of the image to this checkpoint as they
+                        are rendered, and if a render stopped partway left
+                        some there, carry on after them; removed once done
+  --icc FILE            embed this ICC profile instead of tagging the image
+                        as sRGB
+  --manifest FILE       write the version, commit, resolved parameters and
+                        SHA-256 of every output to FILE as JSON, to verify and
+                        reproduce the render
+  --verify interval     re-check up to 1000 pixels along the boundary with
+                        interval arithmetic and report those whose escape
+                        time rounding errors make uncertain; slow
+  --transparent REGION  make the interior or exterior transparent
+";
+
+const USAGE_RU: &str = "\
+Использование: mandelbrot ФАЙЛ ПИКСЕЛИ ВЕРХНИЙ_ЛЕВЫЙ НИЖНИЙ_ПРАВЫЙ [ПАРАМЕТРЫ]
+               mandelbrot ФАЙЛ ПИКСЕЛИ --annotations ФАЙЛ --goto ИМЯ [ПАРАМЕТРЫ]
+               mandelbrot ФАЙЛ ПИКСЕЛИ --center RE,IM [--zoom Z | --scale S]
+                          [ПАРАМЕТРЫ]
+               mandelbrot sonify ФАЙЛ.wav ТОЧКА [--julia RE,IM] [--iterations N]
+                                 [--note-ms МС]
+               mandelbrot buddhabrot ФАЙЛ ПИКСЕЛИ ВЕРХНИЙ_ЛЕВЫЙ НИЖНИЙ_ПРАВЫЙ
+                                     [--orbits N] [--iterations N]
+                                     [--sampling stratified|importance]
+                                     [--seed N] [--threads N]
+                                     [--snapshot-every МИНУТЫ] [--state ФАЙЛ]
+                                     [--nebula R,G,B] [--gain R,G,B]
+                                     [--tone sqrt|log|reinhard]
+               mandelbrot plan-zoom ПИКСЕЛИ ЦЕНТР УВЕЛИЧЕНИЕ [--start-zoom Z]
+                                    [--frames N]
+               mandelbrot zoom ШАБЛОН ПИКСЕЛИ ЦЕНТР УВЕЛИЧЕНИЕ [--start-zoom Z]
+                               [--frames N] [--fps N] [--palette ИМЯ]
+                               [--threads N]
+               mandelbrot loop ФАЙЛ.gif ПИКСЕЛИ ЦЕНТР УВЕЛИЧЕНИЕ [--frames N]
+                               [--fps N] [--palette ИМЯ] [--threads N]
+               mandelbrot scan ФАЙЛ.gif ПИКСЕЛИ ВЕРХНИЙ_ЛЕВЫЙ НИЖНИЙ_ПРАВЫЙ
+                               power|bailout ОТ ДО [--frames N] [--fps N]
+                               [--julia RE,IM] [--palette ИМЯ] [--threads N]
+               mandelbrot palette edit ПАЛИТРА ПРОСМОТР.png
+                                       [--reverse on|off] [--rotate N]
+                                       [--resample N] [--convert linear|srgb]
+                                       [--output ФАЙЛ]
+               mandelbrot stitch ФАЙЛ ПЛИТКИ
+               mandelbrot tiles КАТАЛОГ ВЕРХНИЙ_ЛЕВЫЙ НИЖНИЙ_ПРАВЫЙ ОТ ДО
+                                [--iterations N] [--julia RE,IM]
+                                [--palette ИМЯ] [--threads N]
+Пример: {program} mandel.png 1000x750 -1.20,0.35 -1,0.20
+Параметры:
+  --julia RE,IM         построить множество Жюлиа z*z + c для этого c
+  --formula ИМЯ         итерируемая функция вместо z*z + c: burning-ship,
+                        возводящая в квадрат (|Re z| + i|Im z|), tricorn,
+                        возводящая в квадрат conj(z), или mandelbrot, по
+                        умолчанию
+  --power D             показатель d в z^d + c вместо квадрата, любое число
+                        больше 1
+  --numerator COEFFS    построить множество Жюлиа рациональной функции P(z)/Q(z),
+                        где P задан как \"re,im;re,im;...\" начиная с z^0
+  --denominator COEFFS  коэффициенты Q(z), по умолчанию 1
+  --newton COEFFS       раскрасить бассейны корней многочлена, заданного как в
+                        --numerator, для метода Ньютона, или z^3 - 1 при
+                        default
+  --bailout R           радиус выхода, больше 1; по умолчанию 2 (1e6 для
+                        рациональных функций)
+  --z0 RE,IM            начальное значение итерации Мандельброта, по умолчанию 0
+  --samples AxD         число отсчётов по ширине и высоте пикселя, по умолчанию
+                        1x1
+  --jitter on|off       располагать отсчёты случайно внутри ячеек сетки
+  --aa-mode color|value окрашивать отсчёты и усреднять цвета, по умолчанию,
+                        или усреднять уровни серого и окрашивать среднее
+  --adaptive T          сначала брать один отсчёт на пиксель, а все --samples
+                        только там, где соседи различаются больше чем на T
+                        уровней серого
+  --refined FILE        записать маску пикселей, уточнённых --adaptive
+  --algorithm NAME      считать каждый пиксель (scanline, по умолчанию) или
+                        заливать прямоугольники с одноцветной границей
+                        (subdivision)
+  --seed N              зерно для случайных отсчётов, записывается в PNG; по
+                        умолчанию берётся из часов
+  --pixel-aspect R      подогнать высоту так, чтобы пиксели были в R раз шире,
+                        чем выше, для анаморфных форматов
+  --precision P         вычислять точки в f64, в arb, примерно с 32 знаками,
+                        для глубоких увеличений, или методом perturb, который
+                        вычисляет с такой точностью лишь несколько точек, а
+                        остальные в f64 относительно них, гораздо быстрее, но
+                        только для множества Мандельброта; auto (по
+                        умолчанию) выбирает perturb или arb, когда пиксели
+                        слишком малы для f64
+  --coord-format F      записывать координаты в журнал, манифест, подпись и CSV
+                        кратчайшей десятичной записью, читающейся обратно без
+                        изменений (по умолчанию), N значащими цифрами, точным
+                        десятичным значением или шестнадцатеричными числами
+                        вида -0x1.8p-1, которые также принимаются везде, где
+                        читаются координаты
+  --focus TOP,BOTTOM    строить сначала эти строки и от них к краям, а не от
+                        средней строки
+  --annotations FILE    прочитать интересные точки, по одной \"имя re,im увеличение\"
+                        на строку
+  --goto NAME           построить интересную точку NAME вместо углов
+  --center RE,IM        построить вид с центром в этой точке вместо углов,
+                        с квадратными пикселями
+  --zoom Z              увеличение вида вокруг --center относительно всего
+                        множества, по умолчанию 1
+  --scale S             ширина пикселя вокруг --center, вместо --zoom
+  --markers on|off      отметить интересные точки на изображении
+  --palette NAME        раскрасить изображение палитрой grayscale, fire, ocean
+                        или rainbow, записывая RGB вместо серого
+  --interior MODE       красить внутренность одним цветом (по умолчанию) или по
+                        виду динамики: притягивающая, параболическая или
+                        зигелевская
+  --iterations N        наибольшее число итераций в ожидании ухода точки, по
+                        умолчанию 255
+  --dynamics-iterations N
+                        наибольшее число итераций для определения динамики
+                        точки, не ушедшей за --iterations, только при
+                        --interior dynamics, по умолчанию 4096
+  --auto-range LO,HI    растянуть шкалу серого на эти процентили числа итераций,
+                        например 1,99
+  --coloring MODE       переводить число итераций в уровни серого как есть
+                        (linear, по умолчанию) или равномерно по гистограмме
+  --output FILE         также записать изображение в FILE в формате PNG, TIFF,
+                        PDF, сырых байтов серого, слоёв EXR, границы SVG или
+                        таблиц CSV или Parquet с x, y, re, im, итерациями,
+                        сглаженным счётом и выходом для каждого пикселя по
+                        расширению; можно указать несколько раз
+  --threads N           использовать не больше N потоков на каждом этапе
+  --stage-threads S=N   потоки для этапов iterate, color и encode, например
+                        iterate=6,color=6,encode=2; по умолчанию все ядра
+  --timings on|off      сообщить время, затраченное на каждый этап
+  --error-format F      сообщать об ошибках текстом (text) или в json; коды
+                        выхода: 64 для ошибок вызова, 65 для неверных
+                        аргументов, 74 для ошибок ввода-вывода и 75 для
+                        превышения --max-memory
+  --lang en|ru          язык сообщений, по умолчанию из локали
+  --max-memory SIZE     отказываться от построения, которому нужно больше
+                        памяти, чем SIZE, например 4G
+  --log-format F        журналировать события текстом (text) или строками json,
+                        по умолчанию выключено (off)
+  --progress MODE       показывать ход работы, со скоростью в пикселях в секунду
+                        и оставшимся временем, полосой (bar), простыми строками
+                        текста для экранных чтецов и журналов (plain) или не
+                        показывать (off); auto, по умолчанию, показывает полосу,
+                        если стандартный поток ошибок — терминал
+  --quiet on|off        не показывать ход работы, куда бы ни шёл стандартный
+                        поток ошибок
+  --exr FILE            также записать слои итераций, сглаженного счёта,
+                        расстояния, ловушки, угла и нормалей в EXR для
+                        композитинга
+  --svg FILE            также обвести границу гладкими кривыми SVG
+  --pdf FILE            также записать изображение на страницу PDF для печати
+  --page SIZE           размер страницы PDF: a0-a5, letter, legal, tabloid или
+                        ШxВ в мм, по умолчанию a4
+  --margin MM           поля страницы PDF, по умолчанию 10 мм
+  --dpi N               разрешение печати PDF, по умолчанию 300
+  --caption on|off      напечатать координаты под изображением в PDF
+  --card on|off         добавить под изображением полосу с его центром,
+                        увеличением, числом итераций, палитрой и датой
+  --tiff FILE           также записать изображение в TIFF для печати
+  --cmyk PROFILE        разделить TIFF на краски CMYK для k (только чёрная),
+                        мелованной (coated) или немелованной (uncoated) бумаги
+  --hdr pq|hlg          записать 16-битный HDR PNG сглаженного числа итераций
+  --pair A,B            также записать два из каналов iterations, smooth,
+                        distance, trap и angle (аргумент вышедшего z) в
+                        совмещённые 16-битные серые PNG ФАЙЛ-A.png и ФАЙЛ-B.png
+                        для композитинга
+  --tiles COLSxROWS     построить изображение отдельными плитками PNG
+                        ФАЙЛ-tile-COL-ROW.png, пропуская уже готовые, чтобы
+                        собрать их командой stitch ФАЙЛ COLSxROWS
+  --resume FILE         сохранять строки изображения в эту контрольную точку
+                        по мере построения, а если прерванное построение уже
+                        оставило там строки, продолжить после них; по
+                        окончании файл удаляется
+  --icc FILE            встроить этот профиль ICC вместо пометки изображения
+                        как sRGB
+  --manifest FILE       записать в FILE версию, коммит, итоговые параметры и
+                        SHA-256 каждого выходного файла в JSON, чтобы проверить
+                        и повторить построение
+  --verify interval     перепроверить до 1000 пикселей на границе интервальной
+                        арифметикой и сообщить о тех, чьё время выхода
+                        ненадёжно из-за ошибок округления; медленно
+  --transparent REGION  сделать прозрачной внутренность (interior) или внешность
+                        (exterior)
+";
@@ -0,0 +1,297 @@
+use exr::prelude::{
+    AnyChannel, AnyChannels, Encoding, FlatSamples, Image, ImageAttributes, IntegerBounds, Layer,
+    LayerAttributes, WritableImage,
+};
+use num::Complex;
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::BufWriter;
+
+/// Per-pixel data written to the layers of an EXR file.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PointData {
+    /// Number of iterations before escaping, or the limit for points that don't escape.
+    pub iterations: f32,
+    /// Continuous iteration count, smoothing out the bands between iteration counts.
+    pub smooth: f32,
+    /// Estimated distance to the boundary of the set, 0 for points that don't escape.
+    pub distance: f32,
+    /// Smallest distance of the orbit to the origin.
+    pub trap: f32,
+    /// Argument of the first point of the orbit past the bailout radius, in radians from -π to
+    /// π, 0 for points that don't escape.
+    pub angle: f32,
+    /// Unit surface normal for lighting, pointing towards the viewer for points that don't
+    /// escape.
+    pub normal: [f32; 3],
+}
+
+/// Follow the orbit of `z = z*z + c`, starting from `z0`, for at most `limit` iterations, and
+/// collect everything the EXR layers need to know about it.
+///
+/// Along with `z`, the derivative `dz/dc` is iterated for the distance estimate and the normal.
+pub fn analyze_point(z0: Complex<f64>, c: Complex<f64>, limit: u32, bailout: f64) -> PointData {
+    let mut z = z0;
+    let mut dz = Complex { re: 0.0, im: 0.0 };
+    let mut trap = z.norm();
+    for i in 0..limit {
+        dz = dz * z * 2.0 + 1.0;
+        z = z * z + c;
+        trap = trap.min(z.norm());
+        if z.norm_sqr() > bailout * bailout {
+            let modulus = z.norm();
+            // Renormalize the iteration count by how far past the bailout radius the orbit
+            // landed, so that the count varies continuously across iteration bands.
+            let smooth = i as f64 + 1.0 - (modulus.ln() / bailout.ln()).log2();
+            let distance = 2.0 * modulus * modulus.ln() / dz.norm();
+            let u = z / dz;
+            let u = u / u.norm();
+            let normal = [u.re, u.im, 1.0].map(|x| (x / 2f64.sqrt()) as f32);
+            return PointData {
+                iterations: i as f32,
+                smooth: smooth as f32,
+                distance: distance as f32,
+                trap: trap as f32,
+                angle: z.arg() as f32,
+                normal,
+            };
+        }
+    }
+    PointData {
+        iterations: limit as f32,
+        smooth: limit as f32,
+        distance: 0.0,
+        trap: trap as f32,
+        angle: 0.0,
+        normal: [0.0, 0.0, 1.0],
+    }
+}
+
+#[test]
+fn test_analyze_point() {
+    let zero = Complex { re: 0.0, im: 0.0 };
+    let inside = analyze_point(zero, Complex { re: -1.0, im: 0.0 }, 100, 2.0);
+    assert_eq!(inside.iterations, 100.0);
+    assert_eq!(inside.distance, 0.0);
+    assert_eq!(inside.trap, 0.0);
+
+    // 0, 1, 2, 5: escapes after two iterations, having started at the origin.
+    let outside = analyze_point(zero, Complex { re: 1.0, im: 0.0 }, 100, 2.0);
+    assert_eq!(outside.iterations, 2.0);
+    assert!(outside.smooth > 1.0 && outside.smooth < 3.0);
+    assert!(outside.distance > 0.0);
+    assert_eq!(outside.trap, 0.0);
+    assert_eq!(outside.angle, 0.0);
+    assert_eq!(inside.angle, 0.0);
+    let length: f32 = outside.normal.iter().map(|x| x * x).sum();
+    assert!((length - 1.0).abs() < 1e-6);
+
+    // Points further away are estimated to be further from the set.
+    let further = analyze_point(zero, Complex { re: 3.0, im: 0.0 }, 100, 2.0);
+    assert!(further.distance > outside.distance);
+    // i escapes from -1 + i, which lies at three eighths of a turn.
+    let turned = analyze_point(zero, Complex { re: 0.0, im: 1.0 }, 100, 1.2);
+    assert!((turned.angle as f64 - 0.75 * std::f64::consts::PI).abs() < 1e-6);
+}
+
+/// Write `data`, whose dimensions are given by `bounds`, to the file named `filename` as an EXR
+/// image with one layer for each field of `PointData`.
+pub fn write_layers(
+    filename: &str,
+    data: &[PointData],
+    bounds: (usize, usize),
+) -> Result<(), std::io::Error> {
+    let layer = |name: &str, channels: Vec<(&str, Vec<f32>)>| {
+        let channels = channels
+            .into_iter()
+            .map(|(channel, samples)| AnyChannel::new(channel, FlatSamples::F32(samples)))
+            .collect();
+        Layer::new(
+            bounds,
+            LayerAttributes::named(name),
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(channels),
+        )
+    };
+    let field = |f: fn(&PointData) -> f32| data.iter().map(f).collect::<Vec<_>>();
+
+    let layers = vec![
+        layer("iterations", vec![("Y", field(|d| d.iterations))]),
+        layer("smooth", vec![("Y", field(|d| d.smooth))]),
+        layer("distance", vec![("Y", field(|d| d.distance))]),
+        layer("trap", vec![("Y", field(|d| d.trap))]),
+        layer("angle", vec![("Y", field(|d| d.angle))]),
+        layer(
+            "normal",
+            vec![
+                ("X", field(|d| d.normal[0])),
+                ("Y", field(|d| d.normal[1])),
+                ("Z", field(|d| d.normal[2])),
+            ],
+        ),
+    ];
+    let image = Image::from_layers(
+        ImageAttributes::new(IntegerBounds::from_dimensions(bounds)),
+        layers,
+    );
+
+    match image.write().to_file(filename) {
+        Ok(_) => Ok(()),
+        Err(exr::error::Error::Io(e)) => Err(e),
+        Err(e) => Err(std::io::Error::other(format!(
+            "Failed to write EXR layers: {:?}",
+            e
+        ))),
+    }
+}
+
+#[test]
+fn test_write_layers() {
+    let filename = std::env::temp_dir().join("rust-experiments-test-layers.exr");
+    let filename = filename.to_str().unwrap();
+    let data = vec![analyze_point(Complex::default(), Complex { re: 1.0, im: 0.0 }, 10, 2.0); 6];
+    write_layers(filename, &data, (3, 2)).unwrap();
+
+    let image = exr::prelude::read_all_flat_layers_from_file(filename).unwrap();
+    let names: Vec<_> = image
+        .layer_data
+        .iter()
+        .map(|layer| layer.attributes.layer_name.as_ref().unwrap().to_string())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "iterations",
+            "smooth",
+            "distance",
+            "trap",
+            "angle",
+            "normal"
+        ]
+    );
+    assert_eq!(image.layer_data[5].channel_data.list.len(), 3);
+    std::fs::remove_file(filename).unwrap();
+}
+
+/// A field of `PointData` that can be written out on its own as a gray image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Channel {
+    Iterations,
+    Smooth,
+    Distance,
+    Trap,
+    Angle,
+}
+
+impl Channel {
+    pub fn parse(s: &str) -> Option<Channel> {
+        match s {
+            "iterations" => Some(Channel::Iterations),
+            "smooth" => Some(Channel::Smooth),
+            "distance" => Some(Channel::Distance),
+            "trap" => Some(Channel::Trap),
+            "angle" => Some(Channel::Angle),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Channel::Iterations => "iterations",
+            Channel::Smooth => "smooth",
+            Channel::Distance => "distance",
+            Channel::Trap => "trap",
+            Channel::Angle => "angle",
+        }
+    }
+
+    /// The value of this channel for `data`, scaled to `[0, 1]`: iteration counts over `limit`,
+    /// distances in pixels of `pixel_size` over `DISTANCE_SPAN`, traps over the bailout radius,
+    /// and angles over the whole turn, starting from -π.
+    pub fn level(self, data: &PointData, limit: u32, pixel_size: f64, bailout: f64) -> f64 {
+        let level = match self {
+            Channel::Iterations => data.iterations as f64 / limit as f64,
+            Channel::Smooth => data.smooth as f64 / limit as f64,
+            Channel::Distance => data.distance as f64 / pixel_size / DISTANCE_SPAN,
+            Channel::Trap => data.trap as f64 / bailout,
+            Channel::Angle => (data.angle as f64 + PI) / (2.0 * PI),
+        };
+        level.clamp(0.0, 1.0)
+    }
+}
+
+/// Distance from the set, in pixels, at which `Channel::Distance` reaches its brightest.
+const DISTANCE_SPAN: f64 = 64.0;
+
+#[test]
+fn test_level() {
+    let data = PointData {
+        iterations: 50.0,
+        smooth: 300.0,
+        distance: 0.5,
+        trap: 1.0,
+        angle: 0.0,
+        ..PointData::default()
+    };
+    assert_eq!(Channel::Iterations.level(&data, 100, 0.01, 2.0), 0.5);
+    assert_eq!(Channel::Smooth.level(&data, 100, 0.01, 2.0), 1.0);
+    assert_eq!(Channel::Distance.level(&data, 100, 0.01, 2.0), 50.0 / 64.0);
+    assert_eq!(Channel::Trap.level(&data, 100, 0.01, 2.0), 0.5);
+    assert_eq!(Channel::Angle.level(&data, 100, 0.01, 2.0), 0.5);
+    assert_eq!(Channel::parse(Channel::Trap.name()), Some(Channel::Trap));
+    assert_eq!(Channel::parse("normal"), None);
+}
+
+/// Write `channel` of `data`, whose dimensions are given by `bounds`, to the file named
+/// `filename` as a 16-bit grayscale PNG, at the levels `Channel::level` gives with the other
+/// arguments. Images of different channels of the same data line up pixel for pixel, for
+/// compositing.
+pub fn write_channel_png(
+    filename: &str,
+    data: &[PointData],
+    bounds: (usize, usize),
+    channel: Channel,
+    (limit, pixel_size, bailout): (u32, f64, f64),
+) -> Result<(), std::io::Error> {
+    let output = BufWriter::new(File::create(filename)?);
+    let mut encoder = png::Encoder::new(output, bounds.0 as u32, bounds.1 as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Sixteen);
+    let samples: Vec<u8> = data
+        .iter()
+        .flat_map(|d| {
+            let level = channel.level(d, limit, pixel_size, bailout);
+            ((level * u16::MAX as f64).round() as u16).to_be_bytes()
+        })
+        .collect();
+    let write = || -> Result<(), png::EncodingError> {
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&samples)?;
+        writer.finish()
+    };
+    write().map_err(|e| {
+        std::io::Error::other(format!("Failed to write {} image: {:?}", channel.name(), e))
+    })
+}
+
+#[test]
+fn test_write_channel_png() {
+    let filename = std::env::temp_dir().join("rust-experiments-test-channel.png");
+    let filename = filename.to_str().unwrap();
+    let data: Vec<_> = [1.0, 3.0, -1.0, 2.0]
+        .map(|re| analyze_point(Complex::default(), Complex { re, im: 0.0 }, 10, 2.0))
+        .to_vec();
+    write_channel_png(filename, &data, (2, 2), Channel::Iterations, (10, 0.1, 2.0)).unwrap();
+    let decoder = png::Decoder::new(File::open(filename).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut buffer).unwrap();
+    assert_eq!(reader.info().bit_depth, png::BitDepth::Sixteen);
+    let levels: Vec<u16> = buffer
+        .chunks(2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .collect();
+    // 1 escapes after 2 iterations, 3 after none, -1 never, and 2 after 1.
+    assert_eq!(levels, [13107, 0, 65535, 6554]);
+    std::fs::remove_file(filename).unwrap();
+}
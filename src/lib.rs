@@ -0,0 +1,2151 @@
+//! Rendering of the Mandelbrot set and its relatives, and the file formats to write them to.
+//!
+//! The `mandelbrot` command line tool is a thin layer over this library: [`Renderer`] holds
+//! what to draw and how to sample it, [`render`] fills a buffer of pixels with it, and the
+//! modules write the results out in the various formats.
+
+use num::Complex;
+use num_traits::PrimInt;
+use precision::Real;
+use std::fs::File;
+use std::io::{BufWriter, Error};
+use std::str::FromStr;
+
+pub mod adaptive;
+pub mod animation;
+pub mod annotations;
+pub mod anomalies;
+pub mod buddhabrot;
+pub mod card;
+pub mod checkpoint;
+pub mod cmyk;
+pub mod color;
+pub mod coords;
+pub mod draw;
+pub mod errors;
+pub mod geometry;
+pub mod hdr;
+pub mod i18n;
+pub mod layers;
+pub mod log;
+pub mod manifest;
+pub mod map_tiles;
+pub mod palette;
+pub mod palette_edit;
+pub mod pdf;
+pub mod perturbation;
+pub mod plan;
+pub mod precision;
+pub mod progress;
+pub mod random;
+pub mod records;
+pub mod scan;
+pub mod sonify;
+pub mod stages;
+pub mod stitch;
+pub mod subdivision;
+pub mod svg;
+pub mod verify;
+pub mod zoom;
+pub mod zoom_loop;
+
+pub use geometry::{
+    apply_pixel_aspect, parse_complex, parse_pair, pixel_to_point, point_to_pixel, point_to_sample,
+    sample_to_pixel, sample_to_point, turn_about, viewport,
+};
+
+/// Default radius of the circle that points have to leave to be considered escaped.
+pub const DEFAULT_BAILOUT: f64 = 2.0;
+
+/// Default number of iterations to wait for a point to escape, enough to fill the gray scale.
+pub const DEFAULT_LIMIT: u32 = 255;
+
+/// Find the escape time for a given point in the complex plane.
+/// The escape time is the number of iterations of `z = z*z + c`, starting from `z0`, it takes
+/// for the point to leave the circle of radius `bailout` centered at the origin.
+/// If the point does not escape, return `None`.
+///
+/// The iteration is carried out in whatever precision `R` offers, so that deep zooms can use
+/// `precision::DoubleDouble` where f64 runs out of digits.
+///
+/// # Examples
+/// ```
+/// # use num::Complex;
+/// # use rust_experiments::escape_time;
+/// let zero = Complex { re: 0.0, im: 0.0 };
+/// assert_eq!(escape_time(zero, Complex { re: 1.0, im: 0.0 }, 100, 2.0), Some(2));
+/// assert_eq!(escape_time(zero, Complex { re: 1.0, im: 0.0 }, 1, 2.0), None);
+/// ```
+pub fn escape_time<T: PrimInt, R: Real>(
+    z0: Complex<R>,
+    c: Complex<R>,
+    limit: T,
+    bailout: f64,
+) -> Option<T> {
+    Formula::Mandelbrot.escape_time(Power::default(), z0, c, limit, bailout)
+}
+
+#[test]
+fn test_escape_time() {
+    let zero = Complex { re: 0.0, im: 0.0 };
+    assert_eq!(
+        escape_time(zero, Complex { re: 1.0, im: 0.0 }, 100, 2.0),
+        Some(2)
+    );
+    assert_eq!(
+        escape_time(zero, Complex { re: 1.0, im: 0.0 }, 1, 2.0),
+        None
+    );
+    // 1, 2, 5, 26, 677
+    assert_eq!(
+        escape_time(zero, Complex { re: 1.0, im: 0.0 }, 100, 100.0),
+        Some(4)
+    );
+    // 0 is a fixed point of z*z when starting from it, 1 is not a fixed point of z*z + 0.5.
+    assert_eq!(escape_time(zero, zero, 100, 2.0), None);
+    assert_eq!(
+        escape_time(
+            Complex { re: 1.0, im: 0.0 },
+            Complex { re: 0.5, im: 0.0 },
+            100,
+            2.0
+        ),
+        Some(1)
+    );
+}
+
+/// The exponent `d` of `z = z^d + c`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Power {
+    /// A whole exponent, raised to by repeated multiplication at the precision of `z`.
+    Integer(u32),
+    /// Any other exponent, raised to in polar form, always in f64.
+    Real(f64),
+}
+
+impl Default for Power {
+    fn default() -> Self {
+        Power::Integer(2)
+    }
+}
+
+impl Power {
+    /// Parse an exponent greater than 1, whole or not.
+    pub fn parse(s: &str) -> Option<Power> {
+        Power::from_value(f64::from_str(s).ok()?)
+    }
+
+    /// The exponent `d`, if it is greater than 1, as an `Integer` if it is whole.
+    pub fn from_value(d: f64) -> Option<Power> {
+        if !(d > 1.0 && d.is_finite()) {
+            return None;
+        }
+        Some(if d.fract() == 0.0 && d <= u32::MAX as f64 {
+            Power::Integer(d as u32)
+        } else {
+            Power::Real(d)
+        })
+    }
+
+    pub fn value(self) -> f64 {
+        match self {
+            Power::Integer(n) => n as f64,
+            Power::Real(d) => d,
+        }
+    }
+
+    /// Raise `z` to this power.
+    pub fn apply<R: Real>(self, z: Complex<R>) -> Complex<R> {
+        match self {
+            Power::Integer(n) => (1..n).fold(z, |power, _| power * z),
+            Power::Real(d) => {
+                let z = precision::complex_to_f64(z);
+                if z.norm_sqr() == 0.0 {
+                    precision::complex_from_f64(z)
+                } else {
+                    precision::complex_from_f64(z.powf(d))
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_power() {
+    assert_eq!(Power::parse("3"), Some(Power::Integer(3)));
+    assert_eq!(Power::parse("2.5"), Some(Power::Real(2.5)));
+    assert_eq!(Power::parse("1"), None);
+    assert_eq!(Power::parse("inf"), None);
+    assert_eq!(Power::from_value(4.0), Some(Power::Integer(4)));
+    assert_eq!(Power::from_value(f64::NAN), None);
+    let z = Complex { re: 1.0, im: 1.0 };
+    // (1 + i)^2 = 2i, (1 + i)^3 = -2 + 2i.
+    assert_eq!(Power::Integer(2).apply(z), Complex { re: 0.0, im: 2.0 });
+    assert_eq!(Power::Integer(3).apply(z), Complex { re: -2.0, im: 2.0 });
+    let real = Power::Real(3.0).apply(z);
+    assert!((real - Complex { re: -2.0, im: 2.0 }).norm() < 1e-12);
+    assert_eq!(
+        Power::Real(2.5).apply(Complex { re: 0.0, im: 0.0 }),
+        Complex { re: 0.0, im: 0.0 }
+    );
+    assert!((Power::Real(2.5).apply(Complex { re: 4.0, im: 0.0 }).re - 32.0).abs() < 1e-12);
+}
+
+/// Orbit points this many times the precision apart, or closer, are taken to be the same point of
+/// an attracting cycle. Orbits converge to their cycle until rounding keeps them from getting any
+/// closer, so this leaves some room for the rounding of larger values.
+const PERIOD_TOLERANCE: f64 = 1024.0;
+
+/// The map iterated to find escape times, each a variation on `z = z*z + c`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Formula {
+    /// `z = z*z + c`.
+    #[default]
+    Mandelbrot,
+    /// `z = (|Re z| + i|Im z|)^2 + c`, folding `z` into the first quadrant before squaring.
+    BurningShip,
+    /// `z = conj(z)^2 + c`, also known as the Mandelbar set.
+    Tricorn,
+}
+
+impl Formula {
+    pub fn parse(s: &str) -> Option<Formula> {
+        match s {
+            "mandelbrot" => Some(Formula::Mandelbrot),
+            "burning-ship" => Some(Formula::BurningShip),
+            "tricorn" => Some(Formula::Tricorn),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Formula::Mandelbrot => "mandelbrot",
+            Formula::BurningShip => "burning-ship",
+            Formula::Tricorn => "tricorn",
+        }
+    }
+
+    /// One step of the iteration, from `z` to the next point of the orbit, raising to `power`
+    /// in place of squaring.
+    pub fn step<R: Real>(self, z: Complex<R>, c: Complex<R>, power: Power) -> Complex<R> {
+        let abs = |x: R| if x < R::zero() { -x } else { x };
+        let z = match self {
+            Formula::Mandelbrot => z,
+            Formula::BurningShip => Complex {
+                re: abs(z.re),
+                im: abs(z.im),
+            },
+            Formula::Tricorn => z.conj(),
+        };
+        power.apply(z) + c
+    }
+
+    /// Find the escape time of the orbit starting from `z0` under this formula with the constant
+    /// `c` and exponent `power`, as `escape_time` does for the Mandelbrot set.
+    ///
+    /// Orbits that settle on an attracting cycle never escape, so they are given up on as soon as
+    /// the cycle is found, with Brent's algorithm as in `rational_fate`, instead of running to
+    /// `limit`.
+    pub fn escape_time<T: PrimInt, R: Real>(
+        self,
+        power: Power,
+        z0: Complex<R>,
+        c: Complex<R>,
+        limit: T,
+        bailout: f64,
+    ) -> Option<T> {
+        let bailout_sqr = R::from_f64(bailout * bailout);
+        let period_epsilon = R::from_f64((PERIOD_TOLERANCE * R::EPSILON).powi(2));
+        let mut z = z0;
+        let mut saved = z0;
+        let (mut power_of_two, mut lambda) = (1u64, 0u64);
+        let mut i = T::zero();
+        while i < limit {
+            z = self.step(z, c, power);
+            // If the absolute value of z is greater than the bailout radius, then
+            // the point is unbounded and we return the number of iterations it
+            // took to get there.
+            if z.norm_sqr() > bailout_sqr {
+                return Some(i);
+            }
+            // Back where it was a while ago, to the last bits, the orbit is caught in a cycle.
+            if (z - saved).norm_sqr() < period_epsilon {
+                return None;
+            }
+            lambda += 1;
+            if lambda == power_of_two {
+                saved = z;
+                power_of_two *= 2;
+                lambda = 0;
+            }
+            i = i + T::one();
+        }
+        None
+    }
+}
+
+#[test]
+fn test_formula() {
+    let square = Power::default();
+    let z = Complex { re: -1.0, im: -2.0 };
+    let c = Complex { re: 0.5, im: 0.0 };
+    // (-1 - 2i)^2 = -3 + 4i, (1 + 2i)^2 = -3 + 4i, (-1 + 2i)^2 = -3 - 4i.
+    assert_eq!(
+        Formula::Mandelbrot.step(z, c, square),
+        Complex { re: -2.5, im: 4.0 }
+    );
+    assert_eq!(
+        Formula::BurningShip.step(z, c, square),
+        Complex { re: -2.5, im: 4.0 }
+    );
+    assert_eq!(
+        Formula::BurningShip.step(Complex { re: -1.0, im: 2.0 }, c, square),
+        Complex { re: -2.5, im: 4.0 }
+    );
+    assert_eq!(
+        Formula::Tricorn.step(z, c, square),
+        Complex { re: -2.5, im: -4.0 }
+    );
+    // All three send 0 to -1 and back again for c = -1.
+    let zero = Complex { re: 0.0, im: 0.0 };
+    for formula in [Formula::Mandelbrot, Formula::BurningShip, Formula::Tricorn] {
+        assert_eq!(Formula::parse(formula.name()), Some(formula));
+        assert_eq!(
+            formula.escape_time(square, zero, Complex { re: -1.0, im: 0.0 }, 100u32, 2.0),
+            None
+        );
+    }
+    // Along the imaginary axis, 0.5i stays bounded for the Mandelbrot set but escapes for the
+    // Burning Ship, whose orbit of it spirals out through the first quadrant.
+    let half = Complex { re: 0.0, im: 0.5 };
+    assert_eq!(
+        Formula::Mandelbrot.escape_time(square, zero, half, 100u32, 2.0),
+        None
+    );
+    assert!(Formula::BurningShip
+        .escape_time(square, zero, half, 100u32, 2.0)
+        .is_some());
+}
+
+#[test]
+fn test_escape_time_periodicity() {
+    // Interior orbits are caught in their cycle long before any sensible limit: the fixed point
+    // of c = 0, the 2-cycle of c = -1 and the fixed point near the main cardioid's center, the
+    // last also in double-double precision.
+    let zero = Complex { re: 0.0, im: 0.0 };
+    for c in [
+        zero,
+        Complex { re: -1.0, im: 0.0 },
+        Complex { re: -0.1, im: 0.1 },
+    ] {
+        assert_eq!(escape_time(zero, c, u64::MAX, 2.0), None);
+    }
+    let c = Complex {
+        re: precision::DoubleDouble::from_f64(-0.1),
+        im: precision::DoubleDouble::from_f64(0.1),
+    };
+    let zero = Complex {
+        re: precision::DoubleDouble::from_f64(0.0),
+        im: precision::DoubleDouble::from_f64(0.0),
+    };
+    assert_eq!(escape_time(zero, c, u64::MAX, 2.0), None);
+    // Points just outside still escape at the same iteration as without the check.
+    let zero = Complex { re: 0.0, im: 0.0 };
+    let c = Complex {
+        re: 0.2501,
+        im: 0.0,
+    };
+    let mut z = zero;
+    let mut i = 0;
+    while z.norm_sqr() <= 4.0 {
+        z = z * z + c;
+        i += 1;
+    }
+    assert_eq!(escape_time(zero, c, u64::MAX, 2.0), Some(i - 1));
+}
+
+/// Whether `c` lies in the main cardioid or the period 2 bulb of the Mandelbrot set, where the
+/// orbit of 0 under `z = z*z + c` stays bounded. Testing for them takes a handful of
+/// multiplications, where iterating their points up to the limit takes the most time of all.
+pub fn in_main_cardioid_or_bulb<R: Real>(c: Complex<R>) -> bool {
+    let quarter = R::from_f64(0.25);
+    let y_sqr = c.im * c.im;
+    // Inside the cardioid when q (q + x - 1/4) <= y^2 / 4, with q = (x - 1/4)^2 + y^2.
+    let x = c.re - quarter;
+    let q = x * x + y_sqr;
+    if q * (q + x) <= quarter * y_sqr {
+        return true;
+    }
+    // The bulb is the disk of radius 1/4 around -1.
+    let x = c.re + R::one();
+    x * x + y_sqr <= R::from_f64(0.0625)
+}
+
+#[test]
+fn test_in_main_cardioid_or_bulb() {
+    let point = |re, im| Complex { re, im };
+    for c in [
+        point(0.0, 0.0),
+        point(0.2, 0.0),
+        point(-0.7, 0.2),
+        point(-0.1, 0.6),
+        point(-1.0, 0.0),
+        point(-1.2, 0.1),
+        point(-0.8, 0.0),
+    ] {
+        assert!(in_main_cardioid_or_bulb(c), "{}", c);
+    }
+    for c in [
+        point(0.26, 0.0),
+        point(-0.75, 0.1),
+        point(-1.3, 0.0),
+        point(0.0, 1.0),
+        point(-0.1, 0.66),
+        point(-2.0, 0.0),
+    ] {
+        assert!(!in_main_cardioid_or_bulb(c), "{}", c);
+    }
+    // Every point the test finds stays bounded, here for long enough to be sure.
+    let mut rng = random::SplitMix64::new(274);
+    for _ in 0..1000 {
+        let c = point(rng.next_f64() * 2.5 - 2.0, rng.next_f64() * 2.5 - 1.25);
+        if in_main_cardioid_or_bulb(c) {
+            let zero = point(0.0, 0.0);
+            assert_eq!(escape_time(zero, c, 100_000u32, 2.0), None, "{}", c);
+        }
+    }
+}
+
+/// Default radius beyond which an orbit of a rational map is considered to have escaped to
+/// infinity.
+pub const RATIONAL_BAILOUT: f64 = 1e6;
+
+/// Two orbit points closer than this (squared distance) are considered equal when looking for
+/// attracting cycles.
+const CYCLE_EPSILON: f64 = 1e-12;
+
+/// Evaluate the polynomial with the given coefficients (constant term first) at `z`.
+fn eval_polynomial(coefficients: &[Complex<f64>], z: Complex<f64>) -> Complex<f64> {
+    // Horner's scheme, starting from the highest degree term.
+    coefficients
+        .iter()
+        .rev()
+        .fold(Complex { re: 0.0, im: 0.0 }, |acc, &a| acc * z + a)
+}
+
+#[test]
+fn test_eval_polynomial() {
+    // z^2 - 1
+    let p = [
+        Complex { re: -1.0, im: 0.0 },
+        Complex { re: 0.0, im: 0.0 },
+        Complex { re: 1.0, im: 0.0 },
+    ];
+    assert_eq!(
+        eval_polynomial(&p, Complex { re: 2.0, im: 0.0 }),
+        Complex { re: 3.0, im: 0.0 }
+    );
+    assert_eq!(
+        eval_polynomial(&p, Complex { re: 0.0, im: 1.0 }),
+        Complex { re: -2.0, im: 0.0 }
+    );
+    assert_eq!(
+        eval_polynomial(&[], Complex { re: 5.0, im: 0.0 }),
+        Complex { re: 0.0, im: 0.0 }
+    );
+}
+
+/// Parse a semicolon separated list of complex coefficients, like `"-1,0;0,0;1,0"`.
+pub fn parse_coefficients(s: &str) -> Option<Vec<Complex<f64>>> {
+    s.split(';').map(parse_complex).collect()
+}
+
+#[test]
+fn test_parse_coefficients() {
+    assert_eq!(
+        parse_coefficients("-1,0;0,0.5"),
+        Some(vec![
+            Complex { re: -1.0, im: 0.0 },
+            Complex { re: 0.0, im: 0.5 }
+        ])
+    );
+    assert_eq!(parse_coefficients("1,0;"), None);
+    assert_eq!(parse_coefficients(""), None);
+}
+
+/// A rational map `z -> P(z) / Q(z)`, with the coefficients of both polynomials stored from the
+/// constant term upwards.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RationalMap {
+    pub numerator: Vec<Complex<f64>>,
+    pub denominator: Vec<Complex<f64>>,
+}
+
+impl RationalMap {
+    /// Apply the map to `z`. Returns `None` at a pole of the map.
+    pub fn apply(&self, z: Complex<f64>) -> Option<Complex<f64>> {
+        let q = eval_polynomial(&self.denominator, z);
+        if q.norm_sqr() == 0.0 {
+            return None;
+        }
+        Some(eval_polynomial(&self.numerator, z) / q)
+    }
+
+    /// Whether infinity is a fixed point of the map, so that escaping orbits form a basin of
+    /// their own rather than being ordinary orbits passing through large values.
+    fn fixes_infinity(&self) -> bool {
+        self.numerator.len() > self.denominator.len()
+    }
+}
+
+/// What eventually happens to the orbit of a point under a rational map.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fate {
+    /// The orbit left the escape radius after the given number of iterations.
+    Escaped(u32),
+    /// The orbit settled on an attracting cycle of the given period after the given number of
+    /// iterations.
+    Attracted { period: u32, iterations: u32 },
+    /// Nothing could be decided within the iteration limit; the point is likely on the Julia set.
+    Undecided,
+}
+
+/// Follow the orbit of `z` under `map` for at most `limit` iterations and classify it, treating
+/// orbits that leave the circle of radius `bailout` as escaped.
+///
+/// Attracting cycles are found with Brent's algorithm: a reference point is saved at every
+/// power of two iterations, and the orbit is attracted once it returns close to that point.
+pub fn rational_fate(map: &RationalMap, mut z: Complex<f64>, limit: u32, bailout: f64) -> Fate {
+    let escape = bailout * bailout;
+    let mut saved = z;
+    let mut power = 1;
+    let mut lambda = 0;
+    for i in 0..limit {
+        z = match map.apply(z) {
+            Some(z) => z,
+            // Poles map to infinity.
+            None => return Fate::Escaped(i),
+        };
+        if map.fixes_infinity() && z.norm_sqr() > escape {
+            return Fate::Escaped(i);
+        }
+        lambda += 1;
+        if (z - saved).norm_sqr() < CYCLE_EPSILON {
+            return Fate::Attracted {
+                period: lambda,
+                iterations: i,
+            };
+        }
+        if lambda == power {
+            saved = z;
+            power *= 2;
+            lambda = 0;
+        }
+    }
+    Fate::Undecided
+}
+
+#[test]
+fn test_rational_fate() {
+    // z -> z^2, with the unit circle as its Julia set.
+    let square = RationalMap {
+        numerator: parse_coefficients("0,0;0,0;1,0").unwrap(),
+        denominator: parse_coefficients("1,0").unwrap(),
+    };
+    assert_eq!(
+        rational_fate(&square, Complex { re: 0.5, im: 0.0 }, 100, RATIONAL_BAILOUT),
+        Fate::Attracted {
+            period: 1,
+            iterations: 7
+        }
+    );
+    assert!(matches!(
+        rational_fate(&square, Complex { re: 2.0, im: 0.0 }, 100, RATIONAL_BAILOUT),
+        Fate::Escaped(_)
+    ));
+    assert_eq!(
+        rational_fate(&square, Complex { re: 0.6, im: 0.8 }, 20, RATIONAL_BAILOUT),
+        Fate::Undecided
+    );
+
+    // z -> 1/z^2 swaps 0 and infinity, so infinity is not a basin of its own, but 0 is still a
+    // pole.
+    let inverse = RationalMap {
+        numerator: parse_coefficients("1,0").unwrap(),
+        denominator: parse_coefficients("0,0;0,0;1,0").unwrap(),
+    };
+    assert!(matches!(
+        rational_fate(
+            &inverse,
+            Complex { re: 0.0, im: 0.0 },
+            100,
+            RATIONAL_BAILOUT
+        ),
+        Fate::Escaped(0)
+    ));
+}
+
+/// Pick a gray level for a point attracted to a cycle, so that basins of cycles with different
+/// periods are told apart.
+fn cycle_shade(period: u32) -> u8 {
+    255 - ((period - 1) % 8) as u8 * 24
+}
+
+/// Newton's method steps shorter than this (squared) are taken to have reached a root.
+const ROOT_EPSILON: f64 = 1e-12;
+
+/// Roots closer than this (squared distance) are taken to be the same, repeated root, which the
+/// Durand-Kerner method only finds to about the cube root of its precision.
+const REPEATED_ROOT_EPSILON: f64 = 1e-8;
+
+/// Most iterations of the Durand-Kerner method spent finding the roots of a polynomial.
+const ROOT_ITERATIONS: u32 = 1000;
+
+/// The coefficients of the derivative of the polynomial with the given coefficients.
+fn derivative(coefficients: &[Complex<f64>]) -> Vec<Complex<f64>> {
+    coefficients
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(k, &a)| a * k as f64)
+        .collect()
+}
+
+#[test]
+fn test_derivative() {
+    // z^3 - 1
+    let p = parse_coefficients("-1,0;0,0;0,0;1,0").unwrap();
+    assert_eq!(derivative(&p), parse_coefficients("0,0;0,0;3,0").unwrap());
+    assert_eq!(derivative(&p[..1]), Vec::new());
+}
+
+/// Find the roots of the polynomial with the given coefficients, of degree 1 or more, with the
+/// Durand-Kerner method, each repeated root only once.
+fn polynomial_roots(coefficients: &[Complex<f64>]) -> Vec<Complex<f64>> {
+    let degree = coefficients.len() - 1;
+    let leading = coefficients[degree];
+    let monic: Vec<_> = coefficients.iter().map(|&a| a / leading).collect();
+    // Start from the powers of a point that is neither real nor a root of unity, and improve all
+    // the guesses at once.
+    let seed = Complex { re: 0.4, im: 0.9 };
+    let mut roots: Vec<_> = (0..degree as u32).map(|k| seed.powu(k)).collect();
+    for _ in 0..ROOT_ITERATIONS {
+        let mut moved: f64 = 0.0;
+        for i in 0..degree {
+            let others = (0..degree)
+                .filter(|&j| j != i)
+                .fold(Complex { re: 1.0, im: 0.0 }, |acc, j| {
+                    acc * (roots[i] - roots[j])
+                });
+            let step = eval_polynomial(&monic, roots[i]) / others;
+            roots[i] -= step;
+            moved = moved.max(step.norm_sqr());
+        }
+        if moved < ROOT_EPSILON * ROOT_EPSILON {
+            break;
+        }
+    }
+    let mut distinct: Vec<Complex<f64>> = Vec::new();
+    for root in roots {
+        if distinct
+            .iter()
+            .all(|&other| (other - root).norm_sqr() >= REPEATED_ROOT_EPSILON)
+        {
+            distinct.push(root);
+        }
+    }
+    distinct
+}
+
+#[test]
+fn test_polynomial_roots() {
+    let mut roots = polynomial_roots(&parse_coefficients("-1,0;0,0;0,0;1,0").unwrap());
+    roots.sort_by(|a, b| a.im.total_cmp(&b.im));
+    let half = 3f64.sqrt() / 2.0;
+    for (root, expected) in roots.iter().zip([(-0.5, -half), (1.0, 0.0), (-0.5, half)]) {
+        assert!((root - Complex::new(expected.0, expected.1)).norm() < 1e-9);
+    }
+    // (z - 1)^2 (z + 2) = z^3 - 3z + 2
+    let roots = polynomial_roots(&parse_coefficients("2,0;-3,0;0,0;1,0").unwrap());
+    assert_eq!(roots.len(), 2);
+    // 2z + 1
+    let roots = polynomial_roots(&parse_coefficients("1,0;2,0").unwrap());
+    assert!((roots[0] - Complex::new(-0.5, 0.0)).norm() < 1e-12);
+}
+
+/// Newton's method on a polynomial `P`, `z -> z - P(z)/P'(z)`, whose orbits converge to the
+/// roots of `P`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewtonMap {
+    /// The coefficients of `P`, from the constant term upwards.
+    pub coefficients: Vec<Complex<f64>>,
+    /// The distinct roots of `P`, in no particular order.
+    pub roots: Vec<Complex<f64>>,
+    derivative: Vec<Complex<f64>>,
+}
+
+impl NewtonMap {
+    /// Set up Newton's method on the polynomial with the given coefficients, constant term
+    /// first. Returns `None` for a constant polynomial, which has no roots to converge to.
+    pub fn new(mut coefficients: Vec<Complex<f64>>) -> Option<NewtonMap> {
+        while coefficients.last().is_some_and(|a| a.norm_sqr() == 0.0) {
+            coefficients.pop();
+        }
+        if coefficients.len() < 2 {
+            return None;
+        }
+        Some(NewtonMap {
+            roots: polynomial_roots(&coefficients),
+            derivative: derivative(&coefficients),
+            coefficients,
+        })
+    }
+}
+
+/// Follow Newton's method from `z` for at most `limit` iterations. Returns the index in
+/// `map.roots` of the root the orbit converges to, and the number of iterations it took, or
+/// `None` if it didn't converge within the limit, or hit a critical point, where the method is
+/// undefined.
+pub fn newton_fate(map: &NewtonMap, mut z: Complex<f64>, limit: u32) -> Option<(usize, u32)> {
+    for i in 0..limit {
+        let slope = eval_polynomial(&map.derivative, z);
+        if slope.norm_sqr() == 0.0 {
+            return None;
+        }
+        let step = eval_polynomial(&map.coefficients, z) / slope;
+        z -= step;
+        if !step.norm_sqr().is_finite() {
+            return None;
+        }
+        if step.norm_sqr() < ROOT_EPSILON {
+            let distance = |root: &Complex<f64>| (root - z).norm_sqr();
+            let nearest = (0..map.roots.len())
+                .min_by(|&a, &b| distance(&map.roots[a]).total_cmp(&distance(&map.roots[b])))?;
+            return Some((nearest, i));
+        }
+    }
+    None
+}
+
+#[test]
+fn test_newton_fate() {
+    let cubic = NewtonMap::new(parse_coefficients("-1,0;0,0;0,0;1,0;0,0").unwrap()).unwrap();
+    assert_eq!(cubic.coefficients.len(), 4);
+    assert_eq!(cubic.roots.len(), 3);
+    let (root, iterations) = newton_fate(&cubic, Complex { re: 2.0, im: 0.1 }, 100).unwrap();
+    assert!((cubic.roots[root] - Complex::new(1.0, 0.0)).norm() < 1e-9);
+    assert!(iterations < 10);
+    // The derivative vanishes at 0.
+    assert_eq!(newton_fate(&cubic, Complex { re: 0.0, im: 0.0 }, 100), None);
+    assert_eq!(newton_fate(&cubic, Complex { re: 2.0, im: 0.1 }, 2), None);
+    assert_eq!(NewtonMap::new(parse_coefficients("3,0;0,0").unwrap()), None);
+}
+
+/// Pick a gray level for a point that Newton's method takes to root number `root` of `roots` in
+/// `iterations` iterations: each root gets a band of gray levels of its own, brightest for the
+/// points that converge fastest.
+fn root_shade(root: usize, roots: usize, iterations: u32) -> u8 {
+    let band = (256 / roots.max(1)).max(1);
+    let top = (band * (root + 1)).min(256) - 1;
+    (top - (iterations as usize * 4).min(band / 2)) as u8
+}
+
+#[test]
+fn test_root_shade() {
+    assert_eq!(root_shade(0, 3, 0), 84);
+    assert_eq!(root_shade(2, 3, 0), 254);
+    assert_eq!(root_shade(2, 3, 5), 234);
+    assert_eq!(root_shade(2, 3, 100), 212);
+    assert_eq!(root_shade(300, 300, 0), 255);
+}
+
+/// Default number of iterations spent on each point that doesn't escape when classifying interior
+/// dynamics. Parabolic and Siegel-like orbits only reveal themselves slowly, so this is far above
+/// the escape-time limit.
+pub const DYNAMICS_LIMIT: u32 = 4096;
+
+/// Cycles whose multiplier is at least this close to the unit circle are considered parabolic.
+/// Orbits converge to a parabolic cycle so slowly that the cycle is detected while the orbit is
+/// still noticeably far away from it, so this can't be very strict.
+const PARABOLIC_TOLERANCE: f64 = 1e-2;
+
+/// Rotation number estimates closer than this are considered equal.
+const ROTATION_TOLERANCE: f64 = 1e-3;
+
+/// Largest period tried when matching an estimated rotation number against a rational one.
+const MAX_ROTATION_PERIOD: u32 = 20;
+
+/// How the orbit of the critical point behaves for a given `c` in the Mandelbrot family.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dynamics {
+    /// The orbit escapes after the given number of iterations.
+    Escaping(u32),
+    /// The orbit converges to an attracting cycle of the given period.
+    Attracting { period: u32 },
+    /// The orbit creeps towards a cycle whose multiplier lies on the unit circle, or rotates
+    /// around the fixed point with a rational rotation number of the given period.
+    Parabolic { period: u32 },
+    /// The orbit keeps rotating around the fixed point with a stable irrational rotation
+    /// number, as it would on the boundary of a Siegel disk.
+    SiegelLike,
+    /// The orbit stays bounded without any recognizable behavior.
+    Unknown,
+}
+
+/// Estimate the average number of turns per iteration that `orbit` makes around `center`,
+/// as a number in `[0, 1)`.
+fn rotation_number(orbit: &[Complex<f64>], center: Complex<f64>) -> f64 {
+    let turns: f64 = orbit
+        .windows(2)
+        .map(|w| ((w[1] - center) / (w[0] - center)).arg())
+        .sum::<f64>()
+        / (2.0 * std::f64::consts::PI * (orbit.len() - 1) as f64);
+    turns.rem_euclid(1.0)
+}
+
+#[test]
+fn test_rotation_number() {
+    let center = Complex { re: 1.0, im: 1.0 };
+    let quarter: Vec<_> = (0..9)
+        .map(|k| center + Complex::from_polar(0.5, k as f64 * std::f64::consts::FRAC_PI_2))
+        .collect();
+    assert!((rotation_number(&quarter, center) - 0.25).abs() < 1e-12);
+    let backwards: Vec<_> = quarter.iter().rev().copied().collect();
+    assert!((rotation_number(&backwards, center) - 0.75).abs() < 1e-12);
+}
+
+/// Find the smallest period `q` such that `rho` is within `tolerance` of some fraction `p/q`.
+fn rational_period(rho: f64, tolerance: f64) -> Option<u32> {
+    (1..=MAX_ROTATION_PERIOD).find(|&q| {
+        let q = q as f64;
+        (rho * q - (rho * q).round()).abs() < tolerance * q
+    })
+}
+
+#[test]
+fn test_rational_period() {
+    assert_eq!(rational_period(0.5, 1e-6), Some(2));
+    assert_eq!(rational_period(0.4, 1e-6), Some(5));
+    assert_eq!(rational_period(0.0, 1e-6), Some(1));
+    assert_eq!(rational_period((5f64.sqrt() - 1.0) / 2.0, 1e-4), None);
+}
+
+/// Improve an approximation `z` of a point on a cycle of `z = z*z + c` whose period divides
+/// `period`, by running a few steps of Newton's method on `f^period(z) - z`.
+fn refine_cycle(c: Complex<f64>, mut z: Complex<f64>, period: u32) -> Complex<f64> {
+    let one = Complex { re: 1.0, im: 0.0 };
+    for _ in 0..16 {
+        let (mut w, mut dw) = (z, one);
+        for _ in 0..period {
+            dw = dw * 2.0 * w;
+            w = w * w + c;
+        }
+        let step = (w - z) / (dw - one);
+        if !step.re.is_finite() || !step.im.is_finite() {
+            break;
+        }
+        z -= step;
+        if step.norm_sqr() < 1e-30 {
+            break;
+        }
+    }
+    z
+}
+
+#[test]
+fn test_refine_cycle() {
+    // The period-2 cycle of z*z - 1 is {0, -1}.
+    let z = refine_cycle(
+        Complex { re: -1.0, im: 0.0 },
+        Complex { re: 0.05, im: 0.0 },
+        2,
+    );
+    assert!(z.norm() < 1e-12);
+}
+
+/// Classify the dynamics of `z = z*z + c` by following the orbit of the critical point 0 for at
+/// most `limit` iterations, counting it as escaping once it leaves the circle of radius `bailout`.
+///
+/// This is a heuristic: cycles are detected with Brent's algorithm and told apart by the size of
+/// their multiplier, and orbits that neither escape nor settle are classified by estimating their
+/// rotation number around the fixed point on both halves of the orbit and checking that the two
+/// estimates agree.
+pub fn classify_dynamics(c: Complex<f64>, limit: u32, bailout: f64) -> Dynamics {
+    let mut z = Complex { re: 0.0, im: 0.0 };
+    let mut orbit = Vec::with_capacity(limit as usize);
+    let mut saved = z;
+    let mut power = 1;
+    let mut lambda = 0;
+    for i in 0..limit {
+        z = z * z + c;
+        if z.norm_sqr() > bailout * bailout {
+            return Dynamics::Escaping(i);
+        }
+        orbit.push(z);
+        lambda += 1;
+        if (z - saved).norm_sqr() < CYCLE_EPSILON {
+            // The orbit may have come back after a multiple of the actual period, and may still
+            // be spiralling slowly towards the cycle. Pin the cycle down with Newton's method
+            // first, then walk it once more to find where it first closes. The derivative of the
+            // map along the cycle tells how strongly it attracts.
+            let z = refine_cycle(c, z, lambda);
+            let mut multiplier = Complex { re: 1.0, im: 0.0 };
+            let mut w = z;
+            let mut period = 0;
+            while period < lambda {
+                multiplier = multiplier * 2.0 * w;
+                w = w * w + c;
+                period += 1;
+                if (w - z).norm_sqr() < CYCLE_EPSILON {
+                    break;
+                }
+            }
+            return if multiplier.norm() < 1.0 - PARABOLIC_TOLERANCE {
+                Dynamics::Attracting { period }
+            } else {
+                Dynamics::Parabolic { period }
+            };
+        }
+        if lambda == power {
+            saved = z;
+            power *= 2;
+            lambda = 0;
+        }
+    }
+
+    // Look at how the tail of the orbit turns around the alpha fixed point, which is the center
+    // of any Siegel disk of the main cardioid.
+    let one = Complex { re: 1.0, im: 0.0 };
+    let alpha = (one - (one - c * 4.0).sqrt()) / 2.0;
+    let tail = &orbit[orbit.len() / 2..];
+    let (first, second) = tail.split_at(tail.len() / 2);
+    let (rho1, rho2) = (
+        rotation_number(first, alpha),
+        rotation_number(second, alpha),
+    );
+    if (rho1 - rho2).abs() > ROTATION_TOLERANCE {
+        return Dynamics::Unknown;
+    }
+    match rational_period((rho1 + rho2) / 2.0, ROTATION_TOLERANCE / 10.0) {
+        Some(period) => Dynamics::Parabolic { period },
+        None => Dynamics::SiegelLike,
+    }
+}
+
+#[test]
+fn test_classify_dynamics() {
+    let c = |re, im| Complex { re, im };
+    assert_eq!(
+        classify_dynamics(c(0.0, 0.0), DYNAMICS_LIMIT, DEFAULT_BAILOUT),
+        Dynamics::Attracting { period: 1 }
+    );
+    assert_eq!(
+        classify_dynamics(c(-1.0, 0.0), DYNAMICS_LIMIT, DEFAULT_BAILOUT),
+        Dynamics::Attracting { period: 2 }
+    );
+    assert_eq!(
+        classify_dynamics(c(0.25, 0.0), DYNAMICS_LIMIT, DEFAULT_BAILOUT),
+        Dynamics::Parabolic { period: 1 }
+    );
+    assert!(matches!(
+        classify_dynamics(c(1.0, 0.0), DYNAMICS_LIMIT, DEFAULT_BAILOUT),
+        Dynamics::Escaping(_)
+    ));
+
+    // The golden mean Siegel disk: the fixed point has multiplier exp(2 pi i theta).
+    let theta = (5f64.sqrt() - 1.0) / 2.0;
+    let lambda = Complex::from_polar(1.0, 2.0 * std::f64::consts::PI * theta);
+    assert_eq!(
+        classify_dynamics(
+            lambda / 2.0 - lambda * lambda / 4.0,
+            DYNAMICS_LIMIT,
+            DEFAULT_BAILOUT
+        ),
+        Dynamics::SiegelLike
+    );
+}
+
+/// Pick a gray level for each kind of interior dynamics.
+fn dynamics_shade(dynamics: Dynamics) -> u8 {
+    match dynamics {
+        Dynamics::Escaping(count) => count.min(255) as u8,
+        Dynamics::Attracting { period } => cycle_shade(period),
+        Dynamics::Parabolic { .. } => 48,
+        Dynamics::SiegelLike => 96,
+        Dynamics::Unknown => 16,
+    }
+}
+
+/// The kind of fractal to render.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Fractal {
+    /// The Mandelbrot set, iterating `z = z*z + c` over the pixel's point `c`.
+    #[default]
+    Mandelbrot,
+    /// The Julia set of `z = z*z + c` for a fixed `c`, iterating from the pixel's point.
+    Julia(Complex<f64>),
+    /// The Julia set of a rational map, iterating the map from the pixel's point.
+    Rational(RationalMap),
+    /// The basins of the roots of a polynomial under Newton's method, iterating from the pixel's
+    /// point.
+    Newton(NewtonMap),
+}
+
+/// How to color the points of the Mandelbrot set that don't escape.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Interior {
+    /// A single flat color.
+    #[default]
+    Flat,
+    /// A color for each kind of dynamics found by `classify_dynamics`.
+    Dynamics,
+}
+
+/// How the samples of a pixel are combined.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AaMode {
+    /// Color each sample, then average the colors in linear light.
+    #[default]
+    Color,
+    /// Average the samples' gray levels, then color the average. Thin filaments fade into the
+    /// background's color instead of blending with theirs.
+    Value,
+}
+
+impl AaMode {
+    pub fn parse(s: &str) -> Option<AaMode> {
+        match s {
+            "color" => Some(AaMode::Color),
+            "value" => Some(AaMode::Value),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AaMode::Color => "color",
+            AaMode::Value => "value",
+        }
+    }
+}
+
+/// What to render and how to sample it: everything that decides the gray level of a pixel.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Renderer {
+    pub fractal: Fractal,
+    /// Map iterated for the Mandelbrot and Julia sets, in place of `z = z*z + c`.
+    pub formula: Formula,
+    /// Exponent the formula raises to, in place of squaring.
+    pub power: Power,
+    pub interior: Interior,
+    /// Escape radius, if it differs from the fractal's default.
+    pub bailout: Option<f64>,
+    /// Starting value of the Mandelbrot iteration.
+    pub z0: Complex<f64>,
+    /// Most iterations to wait for an orbit to escape, or to settle for the rational and Newton
+    /// maps.
+    pub limit: u32,
+    /// Most iterations to follow an orbit that hasn't escaped within `limit`, or is known not to,
+    /// to classify its dynamics for `Interior::Dynamics`. Attracting cycles show up in a few
+    /// hundred, and parabolic and Siegel-like orbits take thousands. Unused with
+    /// `Interior::Flat`, where points are found to be interior by the cardioid and bulb test, or
+    /// by the escape time catching their cycle, within `limit`.
+    pub dynamics_limit: u32,
+    /// Number of samples taken across and down each pixel.
+    pub samples: (usize, usize),
+    /// Whether to place samples randomly within their cell of the sampling grid.
+    pub jitter: bool,
+    pub aa_mode: AaMode,
+    /// Seed for every random choice made while rendering, so that renders can be repeated
+    /// exactly.
+    pub seed: Option<u64>,
+    /// Escape counts mapped to black and white, instead of the counts themselves being the gray
+    /// level.
+    pub range: Option<(u32, u32)>,
+    /// Whether to make the interior (`Some(true)`) or the exterior (`Some(false)`) of the set
+    /// transparent.
+    pub transparent: Option<bool>,
+    /// Gray levels to give points outside the set by their escape counts, in place of `range`, as
+    /// `histogram_levels` makes them.
+    pub levels: Option<Vec<(u32, u8)>>,
+    /// Palette to color the gray levels with, making an RGB image instead of a gray one.
+    pub palette: Option<palette::Palette>,
+    /// Reference orbits to render the Mandelbrot set by perturbation with, instead of iterating
+    /// each point. Points are then offsets from the origin of the references.
+    pub perturbation: Option<std::sync::Arc<perturbation::Perturbation>>,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Renderer {
+            fractal: Fractal::Mandelbrot,
+            formula: Formula::Mandelbrot,
+            power: Power::Integer(2),
+            interior: Interior::Flat,
+            bailout: None,
+            z0: Complex { re: 0.0, im: 0.0 },
+            limit: DEFAULT_LIMIT,
+            dynamics_limit: DYNAMICS_LIMIT,
+            samples: (1, 1),
+            jitter: false,
+            aa_mode: AaMode::Color,
+            seed: None,
+            range: None,
+            transparent: None,
+            levels: None,
+            palette: None,
+            perturbation: None,
+        }
+    }
+}
+
+impl Renderer {
+    /// Number of color bytes each pixel takes: red, green and blue with a palette, or else the
+    /// gray level.
+    pub fn colors(&self) -> usize {
+        if self.palette.is_some() {
+            3
+        } else {
+            1
+        }
+    }
+
+    /// Number of bytes each pixel takes: its colors, and the alpha if anything is transparent.
+    pub fn channels(&self) -> usize {
+        self.colors() + self.transparent.is_some() as usize
+    }
+
+    /// Render a whole image with dimensions `bounds` between `upper_left` and `lower_right` on
+    /// `threads` threads, returning its pixels, `channels()` bytes each, along with the chunks of
+    /// rows that failed to render, as `render_bands` returns them.
+    pub fn render_image<R: Real>(
+        &self,
+        bounds: (usize, usize),
+        upper_left: Complex<R>,
+        lower_right: Complex<R>,
+        threads: usize,
+    ) -> (Vec<u8>, Vec<(usize, usize)>) {
+        let mut pixels = vec![0; bounds.0 * bounds.1 * self.channels()];
+        let failed = render_bands(
+            &mut pixels,
+            bounds,
+            upper_left,
+            lower_right,
+            threads,
+            None,
+            None,
+            |band, band_bounds, band_upper_left, band_lower_right| {
+                render(band, band_bounds, band_upper_left, band_lower_right, self)
+            },
+        );
+        (pixels, failed)
+    }
+
+    /// Compute the gray level of a single point of the fractal, along with whether the point
+    /// belongs to the interior, that is, doesn't escape.
+    ///
+    /// Escape times are computed at the precision of `point`; classifying the dynamics and
+    /// rational maps are always done in f64.
+    pub fn render_point<R: Real>(&self, point: Complex<R>) -> (u8, bool) {
+        match self.outcome(point) {
+            Outcome::Escaped(count) => (self.escape_shade(count), false),
+            Outcome::Shade(gray, interior) => (gray, interior),
+        }
+    }
+
+    /// The number of iterations it takes `point` to escape, or `None` if it doesn't escape, or
+    /// isn't colored by how soon it does, as the roots of Newton's method aren't.
+    pub fn escape_count<R: Real>(&self, point: Complex<R>) -> Option<u32> {
+        match self.outcome(point) {
+            Outcome::Escaped(count) => Some(count),
+            Outcome::Shade(..) => None,
+        }
+    }
+
+    /// Pick a gray level for a point escaping after `count` iterations, through `levels` if
+    /// given, or else by `range`.
+    fn escape_shade(&self, count: u32) -> u8 {
+        match &self.levels {
+            Some(levels) => histogram_level(levels, count),
+            None => escape_shade(count, self.range),
+        }
+    }
+
+    /// Find out what becomes of `point`, as `render_point` colors it.
+    fn outcome<R: Real>(&self, point: Complex<R>) -> Outcome {
+        if let Some(perturbation) = &self.perturbation {
+            return match perturbation.escape_time(precision::complex_to_f64(point)) {
+                Some(count) => Outcome::Escaped(count),
+                None => Outcome::Shade(16, true),
+            };
+        }
+        match &self.fractal {
+            // Compute the escape time for that point.
+            Fractal::Mandelbrot => match self.interior {
+                // No need to iterate points inside the largest components of the set.
+                Interior::Flat
+                    if self.formula == Formula::Mandelbrot
+                        && self.power == Power::Integer(2)
+                        && self.z0 == Complex::new(0.0, 0.0)
+                        && in_main_cardioid_or_bulb(point) =>
+                {
+                    Outcome::Shade(16, true)
+                }
+                Interior::Flat => match self.formula.escape_time(
+                    self.power,
+                    precision::complex_from_f64(self.z0),
+                    point,
+                    self.limit,
+                    self.bailout.unwrap_or(DEFAULT_BAILOUT),
+                ) {
+                    Some(count) => Outcome::Escaped(count),
+                    None => Outcome::Shade(16, true),
+                },
+                // Wait for the orbit to escape first, skipping the points known to stay bounded,
+                // and only then spend up to `dynamics_limit` on how it behaves.
+                Interior::Dynamics => {
+                    let c = precision::complex_to_f64(point);
+                    let zero = Complex { re: 0.0, im: 0.0 };
+                    let bailout = self.bailout.unwrap_or(DEFAULT_BAILOUT);
+                    let escaped = if in_main_cardioid_or_bulb(c) {
+                        None
+                    } else {
+                        escape_time(zero, c, self.limit, bailout)
+                    };
+                    let dynamics = match escaped {
+                        Some(count) => Dynamics::Escaping(count),
+                        None => classify_dynamics(c, self.dynamics_limit, bailout),
+                    };
+                    match dynamics {
+                        Dynamics::Escaping(count) => Outcome::Escaped(count),
+                        dynamics => Outcome::Shade(dynamics_shade(dynamics), true),
+                    }
+                }
+            },
+            // Compute the escape time for that point as the start of the orbit.
+            Fractal::Julia(c) => {
+                let c = precision::complex_from_f64(*c);
+                let bailout = self.bailout.unwrap_or(DEFAULT_BAILOUT);
+                match self
+                    .formula
+                    .escape_time(self.power, point, c, self.limit, bailout)
+                {
+                    Some(count) => Outcome::Escaped(count),
+                    None => Outcome::Shade(16, true),
+                }
+            }
+            // Find out where the orbit of that point ends up.
+            Fractal::Rational(map) => {
+                let point = precision::complex_to_f64(point);
+                let bailout = self.bailout.unwrap_or(RATIONAL_BAILOUT);
+                match rational_fate(map, point, self.limit, bailout) {
+                    Fate::Escaped(count) => Outcome::Escaped(count),
+                    Fate::Attracted { period, .. } => Outcome::Shade(cycle_shade(period), true),
+                    Fate::Undecided => Outcome::Shade(16, true),
+                }
+            }
+            // Find the root Newton's method takes that point to.
+            Fractal::Newton(map) => {
+                match newton_fate(map, precision::complex_to_f64(point), self.limit) {
+                    Some((root, iterations)) => {
+                        Outcome::Shade(root_shade(root, map.roots.len(), iterations), false)
+                    }
+                    None => Outcome::Shade(16, true),
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_render_point_dynamics() {
+    let renderer = Renderer {
+        interior: Interior::Dynamics,
+        ..Renderer::default()
+    };
+    let c = Complex { re: 1.0, im: 0.0 };
+    // The orbit 0, 1, 2, 5, 26, ... leaves the circle of radius 2 after 2 iterations, and that of
+    // radius 10 after 3.
+    assert_eq!(renderer.escape_count(c), Some(2));
+    let renderer = Renderer {
+        bailout: Some(10.0),
+        ..renderer
+    };
+    assert_eq!(renderer.escape_count(c), Some(3));
+}
+
+/// What becomes of a point of the fractal: it escapes after a number of iterations, or gets a gray
+/// level of its own, along with whether it belongs to the interior.
+enum Outcome {
+    Escaped(u32),
+    Shade(u8, bool),
+}
+
+/// Pick a gray level for a point escaping after `count` iterations, stretching `range` over the
+/// whole gray scale if given.
+fn escape_shade(count: u32, range: Option<(u32, u32)>) -> u8 {
+    match range {
+        Some((low, high)) => {
+            let t = (count.clamp(low, high) - low) as f64 / (high - low).max(1) as f64;
+            (t * 255.0).round() as u8
+        }
+        None => count.min(255) as u8,
+    }
+}
+
+#[test]
+fn test_escape_shade() {
+    assert_eq!(escape_shade(7, None), 7);
+    assert_eq!(escape_shade(4000, None), 255);
+    assert_eq!(escape_shade(2, Some((4, 8))), 0);
+    assert_eq!(escape_shade(6, Some((4, 8))), 128);
+    assert_eq!(escape_shade(9, Some((4, 8))), 255);
+    assert_eq!(escape_shade(4, Some((4, 4))), 0);
+}
+
+/// Find the escape counts at the `percentiles` (each from 0 to 100) of `counts`, to stretch the
+/// gray scale over. Returns `None` if no point escaped.
+pub fn percentile_range(mut counts: Vec<u32>, percentiles: (f64, f64)) -> Option<(u32, u32)> {
+    if counts.is_empty() {
+        return None;
+    }
+    counts.sort_unstable();
+    let at = |p: f64| counts[(p / 100.0 * (counts.len() - 1) as f64).round() as usize];
+    Some((at(percentiles.0), at(percentiles.1)))
+}
+
+#[test]
+fn test_percentile_range() {
+    let counts: Vec<u32> = (0..=100).rev().collect();
+    assert_eq!(percentile_range(counts.clone(), (1.0, 99.0)), Some((1, 99)));
+    assert_eq!(percentile_range(counts, (0.0, 100.0)), Some((0, 100)));
+    assert_eq!(percentile_range(vec![5], (1.0, 99.0)), Some((5, 5)));
+    assert_eq!(percentile_range(Vec::new(), (1.0, 99.0)), None);
+}
+
+/// Build the table `Renderer::levels` takes from the escape counts of a sample of the exterior:
+/// each count of the sample maps to the fraction of the sample at or below it, so that the levels
+/// of the image end up spread evenly over the whole gray scale, however many iterations they
+/// span. The lowest count of the sample stays black. The table lists the counts in order, with
+/// their levels.
+pub fn histogram_levels(counts: &[u32]) -> Vec<(u32, u8)> {
+    let mut counts = counts.to_vec();
+    counts.sort_unstable();
+    let lowest = counts
+        .iter()
+        .take_while(|&&count| count == counts[0])
+        .count();
+    let spread = counts.len().saturating_sub(lowest).max(1) as f64;
+    let mut levels = Vec::new();
+    for (i, &count) in counts.iter().enumerate() {
+        if counts.get(i + 1) != Some(&count) {
+            let below = i + 1 - lowest;
+            levels.push((count, (below as f64 / spread * 255.0).round() as u8));
+        }
+    }
+    levels
+}
+
+/// Look up the gray level of `count` in `levels`, as `histogram_levels` makes them: that of the
+/// highest count of the sample at or below it, or black below all of them.
+pub fn histogram_level(levels: &[(u32, u8)], count: u32) -> u8 {
+    match levels.partition_point(|&(sampled, _)| sampled <= count) {
+        0 => 0,
+        i => levels[i - 1].1,
+    }
+}
+
+#[test]
+fn test_histogram_levels() {
+    // Counts crowded together are pulled apart.
+    let levels = histogram_levels(&[250, 250, 251, 252, 255]);
+    assert_eq!(histogram_level(&levels, 250), 0);
+    assert_eq!(histogram_level(&levels, 251), 85);
+    assert_eq!(histogram_level(&levels, 252), 170);
+    assert_eq!(histogram_level(&levels, 254), 170);
+    assert_eq!(histogram_level(&levels, 255), 255);
+    assert_eq!(histogram_level(&levels, 0), 0);
+    assert_eq!(histogram_level(&histogram_levels(&[]), 100), 0);
+    assert_eq!(histogram_level(&histogram_levels(&[7, 7]), 7), 0);
+    // Counts past the gray scale are told apart too.
+    let levels = histogram_levels(&[300, 1000, 5000]);
+    assert_eq!(histogram_level(&levels, 1000), 128);
+    assert_eq!(histogram_level(&levels, 4999), 128);
+    assert_eq!(histogram_level(&levels, 9000), 255);
+}
+
+/// Render a rectangle of the fractal described by `renderer` into a buffer of pixels.
+///
+/// Every pixel is sampled on a grid of `renderer.samples` points, which may be denser along one
+/// axis than the other, and the resulting gray levels are averaged in linear light. With
+/// `renderer.jitter`, each sample is placed randomly within its cell of the grid, drawing on
+/// `renderer.seed`.
+///
+/// With `renderer.palette` set, each sample is colored through the palette and the pixel takes
+/// three bytes, red, green and blue, each averaged in linear light.
+///
+/// With `renderer.aa_mode` set to `AaMode::Value`, the gray levels themselves are averaged
+/// instead, and the average colored through the palette.
+///
+/// With `renderer.transparent` set, each pixel takes another byte in `pixels`: the colors are
+/// averaged over the visible samples only, and the fraction of visible samples is the alpha.
+pub fn render<R: Real>(
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<R>,
+    lower_right: Complex<R>,
+    renderer: &Renderer,
+) {
+    let (colors, channels) = (renderer.colors(), renderer.channels());
+    assert!(pixels.len() == bounds.0 * bounds.1 * channels);
+    let (across, down) = renderer.samples;
+
+    // Iterate over the rows of the image.
+    for row in 0..bounds.1 {
+        // Iterate over the columns of the image.
+        for column in 0..bounds.0 {
+            let mut total = [0.0; 3];
+            let mut total_gray = 0.0;
+            let mut visible = 0;
+            let mut rng = renderer.seed.filter(|_| renderer.jitter).map(|seed| {
+                let corner = pixel_to_point(bounds, (column, row), upper_left, lower_right);
+                random::SplitMix64::for_point(seed, precision::complex_to_f64(corner))
+            });
+            for j in 0..down {
+                for i in 0..across {
+                    // Find the point in the complex plane that corresponds to this sample of the
+                    // pixel in the output image, moved randomly within its cell of the sampling
+                    // grid when jittering.
+                    let (dx, dy) = match &mut rng {
+                        Some(rng) => (rng.next_f64(), rng.next_f64()),
+                        None => (0.0, 0.0),
+                    };
+                    let sample = (
+                        column as f64 + (i as f64 + dx) / across as f64,
+                        row as f64 + (j as f64 + dy) / down as f64,
+                    );
+                    let point = sample_to_point(bounds, sample, upper_left, lower_right);
+                    let (gray, interior) = renderer.render_point(point);
+                    if renderer.transparent != Some(interior) {
+                        total_gray += gray as f64;
+                        let color = match renderer.palette {
+                            Some(palette) => palette.color(gray),
+                            None => [gray; 3],
+                        };
+                        for (total, value) in total.iter_mut().zip(color) {
+                            *total += color::srgb_to_linear(value);
+                        }
+                        visible += 1;
+                    }
+                }
+            }
+            let index = (row * bounds.0 + column) * channels;
+            let color = match renderer.aa_mode {
+                AaMode::Color => {
+                    total.map(|total| color::linear_to_srgb(total / visible.max(1) as f64))
+                }
+                AaMode::Value => {
+                    let gray = (total_gray / visible.max(1) as f64).round() as u8;
+                    match renderer.palette {
+                        Some(palette) => palette.color(gray),
+                        None => [gray; 3],
+                    }
+                }
+            };
+            pixels[index..index + colors].copy_from_slice(&color[..colors]);
+            if channels > colors {
+                pixels[index + colors] = (visible * 255 / (across * down)) as u8;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_render() {
+    let mut pixels = [0; 10 * 10];
+    render(
+        &mut pixels,
+        (10, 10),
+        Complex { re: 0.0, im: 0.0 },
+        Complex { re: 0.0, im: 0.0 },
+        &Renderer::default(),
+    );
+    println!("{:?}", pixels);
+    assert_eq!(pixels[0], 16);
+    assert_eq!(pixels[1], 16);
+    assert_eq!(pixels[2], 16);
+    assert_eq!(pixels[3], 16);
+}
+
+#[test]
+fn test_render_julia() {
+    // For c = 0 the Julia set is the unit circle: points inside stay bounded, points outside
+    // escape.
+    let renderer = Renderer {
+        fractal: Fractal::Julia(Complex { re: 0.0, im: 0.0 }),
+        ..Renderer::default()
+    };
+    let mut pixels = [0; 4];
+    render(
+        &mut pixels,
+        (4, 1),
+        Complex { re: -2.0, im: 0.0 },
+        Complex { re: 2.0, im: 0.0 },
+        &renderer,
+    );
+    assert_ne!(pixels[0], 16);
+    assert_eq!(pixels[2], 16);
+}
+
+#[test]
+fn test_render_rational() {
+    // z -> z^2 attracts the inside of the unit circle to 0 and the outside to infinity.
+    let renderer = Renderer {
+        fractal: Fractal::Rational(RationalMap {
+            numerator: parse_coefficients("0,0;0,0;1,0").unwrap(),
+            denominator: parse_coefficients("1,0").unwrap(),
+        }),
+        ..Renderer::default()
+    };
+    let mut pixels = [0; 4 * 4];
+    render(
+        &mut pixels,
+        (4, 4),
+        Complex { re: -2.0, im: 2.0 },
+        Complex { re: 2.0, im: -2.0 },
+        &renderer,
+    );
+    // The upper left corner escapes, the center is attracted to the fixed point at 0.
+    assert!(pixels[0] < 16);
+    assert_eq!(pixels[2 * 4 + 2], cycle_shade(1));
+}
+
+#[test]
+fn test_render_newton() {
+    let map = NewtonMap::new(parse_coefficients("-1,0;0,0;0,0;1,0").unwrap()).unwrap();
+    let one = map
+        .roots
+        .iter()
+        .position(|root| (root - 1.0).norm() < 1e-9)
+        .unwrap();
+    let renderer = Renderer {
+        fractal: Fractal::Newton(map),
+        ..Renderer::default()
+    };
+    let mut pixels = [0; 4 * 4];
+    render(
+        &mut pixels,
+        (4, 4),
+        Complex { re: -2.0, im: 2.0 },
+        Complex { re: 2.0, im: -2.0 },
+        &renderer,
+    );
+    // The root 1 is reached at once, but Newton's method is undefined at the critical point 0.
+    assert_eq!(pixels[2 * 4 + 3], root_shade(one, 3, 0));
+    assert_eq!(pixels[2 * 4 + 2], 16);
+}
+
+#[test]
+fn test_render_levels() {
+    // The left pixel escapes, the right one is in the set, which keeps its level.
+    let (upper_left, lower_right) = (Complex { re: -3.0, im: 0.0 }, Complex { re: 1.0, im: 0.0 });
+    let renderer = Renderer {
+        levels: Some(vec![(0, 200)]),
+        ..Renderer::default()
+    };
+    let mut pixels = [0; 2];
+    render(&mut pixels, (2, 1), upper_left, lower_right, &renderer);
+    assert_eq!(pixels, [200, 16]);
+}
+
+#[test]
+fn test_render_transparent() {
+    // The left half of the image is outside of the set, the right half inside.
+    let bounds = (2, 1);
+    let (upper_left, lower_right) = (Complex { re: -3.0, im: 0.0 }, Complex { re: 1.0, im: 0.0 });
+    let mut pixels = [0; 2 * 2];
+
+    let interior = Renderer {
+        transparent: Some(true),
+        ..Renderer::default()
+    };
+    render(&mut pixels, bounds, upper_left, lower_right, &interior);
+    assert_eq!(pixels[1], 255);
+    assert_eq!(pixels[3], 0);
+
+    let exterior = Renderer {
+        transparent: Some(false),
+        samples: (2, 1),
+        ..Renderer::default()
+    };
+    render(&mut pixels, bounds, upper_left, lower_right, &exterior);
+    // The samples of the left pixel are at -3 and -2, only the second of which is in the set.
+    assert_eq!(pixels[0..2], [16, 127]);
+    assert_eq!(pixels[2..4], [16, 255]);
+}
+
+#[test]
+fn test_render_palette() {
+    // The left pixel escapes at once, the right one is in the set.
+    let (upper_left, lower_right) = (Complex { re: -3.0, im: 0.0 }, Complex { re: 1.0, im: 0.0 });
+    let renderer = Renderer {
+        palette: Some(palette::Palette::Rainbow),
+        ..Renderer::default()
+    };
+    let mut pixels = [0; 2 * 3];
+    render(&mut pixels, (2, 1), upper_left, lower_right, &renderer);
+    assert_eq!(pixels[0..3], palette::Palette::Rainbow.color(0));
+    assert_eq!(pixels[3..6], palette::Palette::Rainbow.color(16));
+
+    let transparent = Renderer {
+        transparent: Some(true),
+        ..renderer
+    };
+    let mut pixels = [0; 2 * 4];
+    render(&mut pixels, (2, 1), upper_left, lower_right, &transparent);
+    assert_eq!(pixels[3], 255);
+    assert_eq!(pixels[7], 0);
+}
+
+#[test]
+fn test_render_aa_mode() {
+    // Two samples, one escaping at once at -3 and one in the set at -1.
+    let (upper_left, lower_right) = (Complex { re: -3.0, im: 0.0 }, Complex { re: 1.0, im: 0.0 });
+    let renderer = Renderer {
+        samples: (2, 1),
+        palette: Some(palette::Palette::Rainbow),
+        ..Renderer::default()
+    };
+    let mut colors = [0; 3];
+    render(&mut colors, (1, 1), upper_left, lower_right, &renderer);
+    let value = Renderer {
+        aa_mode: AaMode::Value,
+        ..renderer
+    };
+    let mut values = [0; 3];
+    render(&mut values, (1, 1), upper_left, lower_right, &value);
+    assert_eq!(values, palette::Palette::Rainbow.color(8));
+    assert_ne!(values, colors);
+}
+
+#[test]
+fn test_render_jitter() {
+    let render_with = |seed| {
+        let renderer = Renderer {
+            samples: (2, 2),
+            jitter: true,
+            seed: Some(seed),
+            ..Renderer::default()
+        };
+        let mut pixels = [0; 8 * 8];
+        render(
+            &mut pixels,
+            (8, 8),
+            Complex { re: -2.0, im: 1.0 },
+            Complex { re: 0.5, im: -1.0 },
+            &renderer,
+        );
+        pixels
+    };
+    // The same seed gives the same image, another seed moves the samples.
+    assert_eq!(render_with(1), render_with(1));
+    assert_ne!(render_with(1), render_with(2));
+}
+
+#[test]
+fn test_render_image() {
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.0 }, Complex { re: 0.5, im: -1.0 });
+    let renderer = Renderer {
+        samples: (2, 2),
+        ..Renderer::default()
+    };
+    let mut pixels = vec![0; 40 * 30];
+    render(&mut pixels, (40, 30), upper_left, lower_right, &renderer);
+    assert_eq!(
+        renderer.render_image((40, 30), upper_left, lower_right, 4),
+        (pixels, Vec::new())
+    );
+}
+
+/// Number of rows in each chunk of work handed out to the threads, between progress updates.
+pub const ROWS_PER_CHUNK: usize = 16;
+
+/// Split `buffer`, which holds the pixels of an image with dimensions `bounds`, into chunks of a
+/// few rows and call `render_band` on each of them on a pool of `threads` threads, along with the
+/// chunk's dimensions and corners. Pixels may take up several consecutive elements of `buffer`.
+///
+/// Chunks are handed out from a shared queue, one at a time to whichever thread is idle, so
+/// that the slow rows near the set don't hold up the whole render. The queue starts with the
+/// chunks crossing the `focus` rows, the middle row if none are given, and works outward from
+/// them, so that whoever watches the progress sees the interesting part of the image first.
+/// Finished chunks are counted in `progress`. A chunk whose rendering panics is tried once more;
+/// if that fails too, it is left as it is and returned as its first row and number of rows, so
+/// that one bad chunk doesn't take down the whole render.
+#[allow(clippy::too_many_arguments)]
+pub fn render_bands<T, R, F>(
+    buffer: &mut [T],
+    bounds: (usize, usize),
+    upper_left: Complex<R>,
+    lower_right: Complex<R>,
+    threads: usize,
+    progress: Option<&progress::Progress>,
+    focus: Option<std::ops::Range<usize>>,
+    render_band: F,
+) -> Vec<(usize, usize)>
+where
+    T: Send,
+    R: Real,
+    F: Fn(&mut [T], (usize, usize), Complex<R>, Complex<R>) + Sync,
+{
+    render_band_rows(
+        buffer,
+        bounds,
+        0..bounds.1,
+        (upper_left, lower_right),
+        threads,
+        progress,
+        focus,
+        render_band,
+    )
+}
+
+/// The pool of `threads` render threads, started on first use and kept for every render after,
+/// so that rendering an image a band at a time doesn't start threads anew for each band.
+fn thread_pool(threads: usize) -> std::sync::Arc<rayon::ThreadPool> {
+    static POOLS: std::sync::Mutex<Vec<(usize, std::sync::Arc<rayon::ThreadPool>)>> =
+        std::sync::Mutex::new(Vec::new());
+    let mut pools = POOLS.lock().unwrap();
+    if let Some((_, pool)) = pools.iter().find(|(n, _)| *n == threads) {
+        return pool.clone();
+    }
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .expect("Failed to start render threads");
+    let pool = std::sync::Arc::new(pool);
+    pools.push((threads, pool.clone()));
+    pool
+}
+
+#[test]
+fn test_thread_pool() {
+    let pool = thread_pool(3);
+    assert_eq!(pool.current_num_threads(), 3);
+    assert!(std::sync::Arc::ptr_eq(&pool, &thread_pool(3)));
+    assert!(!std::sync::Arc::ptr_eq(&pool, &thread_pool(2)));
+}
+
+/// Render only the `rows` of the image with dimensions `bounds` between `upper_left` and
+/// `lower_right` into `buffer`, which holds just those rows, as `render_bands` renders the whole
+/// image. Chunks start every 16 rows of the whole image as they do there, so that rows from the
+/// start of one chunk to the start of another, or to the bottom, come out exactly as they do
+/// there. The rows returned and counted in `progress` are rows of the whole image.
+#[allow(clippy::too_many_arguments)]
+pub fn render_band_rows<T, R, F>(
+    buffer: &mut [T],
+    bounds: (usize, usize),
+    rows: std::ops::Range<usize>,
+    (upper_left, lower_right): (Complex<R>, Complex<R>),
+    threads: usize,
+    progress: Option<&progress::Progress>,
+    focus: Option<std::ops::Range<usize>>,
+    render_band: F,
+) -> Vec<(usize, usize)>
+where
+    T: Send,
+    R: Real,
+    F: Fn(&mut [T], (usize, usize), Complex<R>, Complex<R>) + Sync,
+{
+    let row_length = buffer.len() / rows.len().max(1);
+    let focus = focus.unwrap_or(bounds.1 / 2..bounds.1 / 2 + 1);
+    // Chunks start at the same rows whatever rows are asked for.
+    let mut chunks = Vec::new();
+    let (mut top, mut rest) = (rows.start, buffer);
+    while top < rows.end && !rest.is_empty() {
+        let height = (ROWS_PER_CHUNK - top % ROWS_PER_CHUNK).min(rows.end - top);
+        let (chunk, tail) = rest.split_at_mut(height * row_length);
+        chunks.push((top, chunk));
+        (top, rest) = (top + height, tail);
+    }
+    chunks.sort_by_key(|(top, chunk)| {
+        let bottom = top + chunk.len() / row_length.max(1);
+        // Rows between the chunk and the focus; none if they overlap.
+        focus.start.saturating_sub(bottom) + top.saturating_sub(focus.end)
+    });
+    let queue = std::sync::Mutex::new(chunks.into_iter());
+    let failed = std::sync::Mutex::new(Vec::new());
+    thread_pool(threads.max(1)).scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(|_| loop {
+                let next = queue.lock().unwrap().next();
+                let Some((top, chunk)) = next else {
+                    break;
+                };
+                let height = chunk.len() / row_length.max(1);
+                let chunk_upper_left = pixel_to_point(bounds, (0, top), upper_left, lower_right);
+                let chunk_lower_right =
+                    pixel_to_point(bounds, (bounds.0, top + height), upper_left, lower_right);
+                let mut attempt = || {
+                    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                        render_band(
+                            chunk,
+                            (bounds.0, height),
+                            chunk_upper_left,
+                            chunk_lower_right,
+                        )
+                    }))
+                };
+                if attempt().is_err() && attempt().is_err() {
+                    failed.lock().unwrap().push((top, height));
+                }
+                if let Some(progress) = progress {
+                    progress.advance(top, height);
+                }
+            });
+        }
+    });
+    let mut failed = failed.into_inner().unwrap();
+    failed.sort_unstable();
+    failed
+}
+
+#[test]
+fn test_render_bands_failures() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    let bounds = (2, 40);
+    let (upper_left, lower_right) = (Complex { re: 0.0, im: 1.0 }, Complex { re: 1.0, im: 0.0 });
+    let mut buffer = vec![0u8; 2 * 40];
+
+    // A chunk that fails once is rendered on the second try.
+    let failed_once = AtomicBool::new(false);
+    let failed = render_bands(
+        &mut buffer,
+        bounds,
+        upper_left,
+        lower_right,
+        1,
+        None,
+        None,
+        |band, _, ul, _| {
+            if ul.im < 0.5 && !failed_once.swap(true, Ordering::SeqCst) {
+                panic!("flaky chunk");
+            }
+            band.fill(1);
+        },
+    );
+    assert!(failed.is_empty());
+    assert!(buffer.iter().all(|&b| b == 1));
+
+    // A chunk that always fails is reported and the others are still rendered.
+    buffer.fill(0);
+    let failed = render_bands(
+        &mut buffer,
+        bounds,
+        upper_left,
+        lower_right,
+        1,
+        None,
+        None,
+        |band, _, ul, _| {
+            if ul.im < 0.5 {
+                panic!("bad chunk");
+            }
+            band.fill(1);
+        },
+    );
+    assert_eq!(failed, [(32, 8)]);
+    assert!(buffer[..2 * 32].iter().all(|&b| b == 1));
+    assert!(buffer[2 * 32..].iter().all(|&b| b == 0));
+}
+
+#[test]
+fn test_render_bands_focus() {
+    let bounds = (1, 100);
+    let (upper_left, lower_right) = (Complex { re: 0.0, im: 1.0 }, Complex { re: 1.0, im: 0.0 });
+    let order = |focus| {
+        let tops = std::sync::Mutex::new(Vec::new());
+        render_bands(
+            &mut [0u8; 100],
+            bounds,
+            upper_left,
+            lower_right,
+            1,
+            None,
+            focus,
+            |band, _, ul: Complex<f64>, _| {
+                band.fill(1);
+                tops.lock()
+                    .unwrap()
+                    .push((100.0 * (1.0 - ul.im)).round() as usize);
+            },
+        );
+        tops.into_inner().unwrap()
+    };
+    // Chunks start every 16 rows; the middle row, 50, is in the one starting at 48.
+    assert_eq!(order(None), [48, 32, 64, 16, 80, 0, 96]);
+    assert_eq!(order(Some(90..100)), [80, 96, 64, 48, 32, 16, 0]);
+}
+
+#[test]
+fn test_render_band_rows() {
+    let bounds = (3, 100);
+    let corners = (Complex { re: -1.0, im: 1.0 }, Complex { re: 1.0, im: -0.7 });
+    let fill = |band: &mut [f64], band_bounds: (usize, usize), ul: Complex<f64>, lr| {
+        for (i, value) in band.iter_mut().enumerate() {
+            let pixel = (i % band_bounds.0, i / band_bounds.0);
+            *value = pixel_to_point(band_bounds, pixel, ul, lr).im;
+        }
+    };
+    let mut whole = vec![0.0; 3 * 100];
+    render_bands(
+        &mut whole, bounds, corners.0, corners.1, 2, None, None, fill,
+    );
+    // The rows come out just as they do in the whole image, down to the last bit.
+    for rows in [32..64, 0..16, 96..100] {
+        let mut part = vec![0.0; 3 * rows.len()];
+        let failed = render_band_rows(
+            &mut part,
+            bounds,
+            rows.clone(),
+            corners,
+            2,
+            None,
+            None,
+            fill,
+        );
+        assert!(failed.is_empty());
+        assert_eq!(part, whole[3 * rows.start..3 * rows.end]);
+    }
+}
+
+#[test]
+fn test_render_bands_strips() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 0.1 }, Complex { re: 1.0, im: 0.0 });
+    // Every pixel of a strip one pixel high or wide, or of no pixels at all, is rendered once.
+    for bounds in [(100_000, 1), (1, 100_000), (1, 1), (0, 5), (5, 0)] {
+        let rows = AtomicUsize::new(0);
+        let mut buffer = vec![0u8; bounds.0 * bounds.1];
+        let failed = render_bands(
+            &mut buffer,
+            bounds,
+            upper_left,
+            lower_right,
+            4,
+            None,
+            None,
+            |band, band_bounds, _: Complex<f64>, _| {
+                assert_eq!(band.len(), band_bounds.0 * band_bounds.1);
+                assert!(band_bounds.1 > 0 && band_bounds.1 <= ROWS_PER_CHUNK);
+                band.iter_mut().for_each(|pixel| *pixel += 1);
+                rows.fetch_add(band_bounds.1, Ordering::SeqCst);
+            },
+        );
+        assert!(failed.is_empty());
+        assert!(buffer.iter().all(|&pixel| pixel == 1));
+        let expected = if bounds.0 == 0 { 0 } else { bounds.1 };
+        assert_eq!(rows.into_inner(), expected, "{:?}", bounds);
+    }
+}
+
+/// Write the buffer `pixels`, whose dimensions are given by `bounds`, to the
+/// file named `filename`. Pixels are either a single gray level byte or red, green
+/// and blue bytes, optionally followed by an alpha byte.
+///
+/// The image is tagged as sRGB, unless an `icc` profile is given to embed instead, and carries
+/// the `text` given as keyword and value pairs.
+pub fn write_image(
+    filename: &str,
+    pixels: &[u8],
+    bounds: (usize, usize),
+    icc: Option<&[u8]>,
+    text: &[(&str, String)],
+) -> Result<(), std::io::Error> {
+    let channels = pixels.len() / (bounds.0 * bounds.1).max(1);
+    let mut writer = png_writer(filename, bounds, channels, icc, text)?;
+    writer
+        .write_image_data(pixels)
+        .and_then(|_| writer.finish())
+        .map_err(png_error)
+}
+
+/// Create the PNG file `filename` for an image with dimensions `bounds` and `channels` bytes to a
+/// pixel, and write everything up to its pixels, as `write_image` describes.
+fn png_writer(
+    filename: &str,
+    bounds: (usize, usize),
+    channels: usize,
+    icc: Option<&[u8]>,
+    text: &[(&str, String)],
+) -> Result<png::Writer<BufWriter<File>>, std::io::Error> {
+    // Create a new file.
+    let output = BufWriter::new(File::create(filename)?);
+
+    // Create a new encoder that writes to the file we just created.
+    let mut encoder = png::Encoder::new(output, bounds.0 as u32, bounds.1 as u32);
+    encoder.set_color(match channels {
+        2 => png::ColorType::GrayscaleAlpha,
+        3 => png::ColorType::Rgb,
+        4 => png::ColorType::Rgba,
+        _ => png::ColorType::Grayscale,
+    });
+    encoder.set_depth(png::BitDepth::Eight);
+    if icc.is_none() {
+        encoder.set_srgb(png::SrgbRenderingIntent::Perceptual);
+    }
+
+    let mut writer = encoder.write_header().map_err(png_error)?;
+    if let Some(profile) = icc {
+        writer
+            .write_chunk(png::chunk::iCCP, &icc_chunk(profile)?)
+            .map_err(png_error)?;
+    }
+    for (keyword, value) in text {
+        writer
+            .write_text_chunk(&png::text_metadata::TEXtChunk::new(*keyword, value))
+            .map_err(png_error)?;
+    }
+    Ok(writer)
+}
+
+fn png_error(e: png::EncodingError) -> std::io::Error {
+    Error::other(format!("Failed to write image: {:?}", e))
+}
+
+/// A PNG file written a band of rows at a time, as they are rendered, so that images too large
+/// to hold in memory whole can still be written. The file is laid out as `write_image` lays it
+/// out.
+pub struct PngStream {
+    writer: png::StreamWriter<'static, BufWriter<File>>,
+}
+
+impl PngStream {
+    pub fn create(
+        filename: &str,
+        bounds: (usize, usize),
+        channels: usize,
+        icc: Option<&[u8]>,
+        text: &[(&str, String)],
+    ) -> Result<PngStream, std::io::Error> {
+        let writer = png_writer(filename, bounds, channels, icc, text)?;
+        Ok(PngStream {
+            writer: writer.into_stream_writer().map_err(png_error)?,
+        })
+    }
+
+    /// Write the next rows of the image, a whole number of them.
+    pub fn write_rows(&mut self, rows: &[u8]) -> Result<(), std::io::Error> {
+        std::io::Write::write_all(&mut self.writer, rows)
+    }
+
+    /// Finish the file, once every row has been written.
+    pub fn finish(self) -> Result<(), std::io::Error> {
+        self.writer.finish().map_err(png_error)
+    }
+}
+
+#[test]
+fn test_png_stream() {
+    let filename = std::env::temp_dir().join("rust-experiments-test-png-stream.png");
+    let filename = filename.to_str().unwrap();
+    let pixels: Vec<u8> = (0..60).collect();
+    let mut stream = PngStream::create(filename, (4, 5), 3, None, &[]).unwrap();
+    for band in pixels.chunks(4 * 3 * 2) {
+        stream.write_rows(band).unwrap();
+    }
+    stream.finish().unwrap();
+    let decoder = png::Decoder::new(File::open(filename).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    assert_eq!(reader.info().size(), (4, 5));
+    assert_eq!(reader.info().color_type, png::ColorType::Rgb);
+    let mut decoded = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut decoded).unwrap();
+    assert_eq!(decoded, pixels);
+
+    // Strips a pixel high or wide are written whole.
+    for bounds in [(100_000, 1), (1, 100_000)] {
+        let pixels = vec![200; 100_000];
+        let mut stream = PngStream::create(filename, bounds, 1, None, &[]).unwrap();
+        stream.write_rows(&pixels).unwrap();
+        stream.finish().unwrap();
+        let decoder = png::Decoder::new(File::open(filename).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        assert_eq!(reader.info().size(), (bounds.0 as u32, bounds.1 as u32));
+        let mut decoded = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut decoded).unwrap();
+        assert_eq!(decoded, pixels);
+    }
+
+    // Rows left out are an error.
+    let mut stream = PngStream::create(filename, (4, 5), 3, None, &[]).unwrap();
+    stream.write_rows(&pixels[..12]).unwrap();
+    assert!(stream.finish().is_err());
+}
+
+/// Build the contents of a PNG `iCCP` chunk embedding `profile`.
+fn icc_chunk(profile: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+    // Profile name, null separator and compression method 0 (zlib).
+    let chunk = b"ICC profile\0\0".to_vec();
+    let mut compressor = flate2::write::ZlibEncoder::new(chunk, flate2::Compression::default());
+    std::io::Write::write_all(&mut compressor, profile)?;
+    compressor.finish()
+}
+
+#[test]
+fn test_write_image() {
+    let filename = std::env::temp_dir().join("rust-experiments-test-write-image.png");
+    let filename = filename.to_str().unwrap();
+    let pixels: Vec<u8> = (0..12).collect();
+
+    write_image(
+        filename,
+        &pixels,
+        (4, 3),
+        None,
+        &[("Seed", "42".to_string())],
+    )
+    .unwrap();
+    let decoder = png::Decoder::new(File::open(filename).unwrap());
+    let reader = decoder.read_info().unwrap();
+    assert_eq!(reader.info().size(), (4, 3));
+    assert!(reader.info().srgb.is_some());
+    assert!(reader.info().icc_profile.is_none());
+    let text = &reader.info().uncompressed_latin1_text;
+    assert_eq!(
+        (text[0].keyword.as_str(), text[0].text.as_str()),
+        ("Seed", "42")
+    );
+
+    let mut profile = vec![0; 132];
+    profile[..4].copy_from_slice(&132u32.to_be_bytes());
+    profile[36..40].copy_from_slice(b"acsp");
+    write_image(filename, &pixels, (4, 3), Some(&profile), &[]).unwrap();
+    let decoder = png::Decoder::new(File::open(filename).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    assert!(reader.info().srgb.is_none());
+    // The decoder doesn't hand back the whole profile, so dig it out of the file by hand.
+    let file = std::fs::read(filename).unwrap();
+    let start = file.windows(4).position(|w| w == b"iCCP").unwrap();
+    let length = u32::from_be_bytes(file[start - 4..start].try_into().unwrap()) as usize;
+    let chunk = &file[start + 4..start + 4 + length];
+    assert_eq!(&chunk[..13], b"ICC profile\0\0");
+    let mut embedded = Vec::new();
+    std::io::Read::read_to_end(
+        &mut flate2::read::ZlibDecoder::new(&chunk[13..]),
+        &mut embedded,
+    )
+    .unwrap();
+    assert_eq!(embedded, profile);
+    let mut decoded = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut decoded).unwrap();
+    assert_eq!(decoded, pixels);
+
+    // Three bytes a pixel are red, green and blue.
+    write_image(filename, &pixels, (2, 2), None, &[]).unwrap();
+    let decoder = png::Decoder::new(File::open(filename).unwrap());
+    let reader = decoder.read_info().unwrap();
+    assert_eq!(reader.info().color_type, png::ColorType::Rgb);
+
+    std::fs::remove_file(filename).unwrap();
+}
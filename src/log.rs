@@ -0,0 +1,115 @@
+use crate::errors::json_string;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How events are logged on standard error.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LogFormat {
+    /// Events aren't logged.
+    #[default]
+    Off,
+    /// One line of `key=value` pairs per event.
+    Text,
+    /// One JSON object per line, for log collectors.
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(s: &str) -> Option<LogFormat> {
+        match s {
+            "off" => Some(LogFormat::Off),
+            "text" => Some(LogFormat::Text),
+            "json" => Some(LogFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// The value of a field of an event.
+#[derive(Clone, Copy, Debug)]
+pub enum Field<'a> {
+    Str(&'a str),
+    Int(u64),
+    Float(f64),
+}
+
+/// Logs events of the render as they happen.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Logger {
+    pub format: LogFormat,
+}
+
+impl Logger {
+    pub fn enabled(&self) -> bool {
+        self.format != LogFormat::Off
+    }
+
+    /// Log the event `name` with the given `fields`, stamped with the time in milliseconds
+    /// since the Unix epoch.
+    pub fn event(&self, name: &str, fields: &[(&str, Field)]) {
+        if !self.enabled() {
+            return;
+        }
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let line = format_event(self.format, ts, name, fields);
+        let _ = writeln!(std::io::stderr().lock(), "{}", line);
+    }
+}
+
+fn format_event(format: LogFormat, ts: u64, name: &str, fields: &[(&str, Field)]) -> String {
+    let all = [("ts", Field::Int(ts)), ("event", Field::Str(name))];
+    let fields = all.iter().chain(fields);
+    match format {
+        LogFormat::Json => {
+            let members: Vec<_> = fields
+                .map(|(key, value)| {
+                    let value = match value {
+                        Field::Str(s) => json_string(s),
+                        Field::Int(n) => n.to_string(),
+                        Field::Float(x) if x.is_finite() => x.to_string(),
+                        Field::Float(_) => "null".to_string(),
+                    };
+                    format!("{}:{}", json_string(key), value)
+                })
+                .collect();
+            format!("{{{}}}", members.join(","))
+        }
+        _ => {
+            let pairs: Vec<_> = fields
+                .map(|(key, value)| match value {
+                    Field::Str(s) if s.contains(char::is_whitespace) || s.is_empty() => {
+                        format!("{}={:?}", key, s)
+                    }
+                    Field::Str(s) => format!("{}={}", key, s),
+                    Field::Int(n) => format!("{}={}", key, n),
+                    Field::Float(x) => format!("{}={}", key, x),
+                })
+                .collect();
+            pairs.join(" ")
+        }
+    }
+}
+
+#[test]
+fn test_format_event() {
+    let fields = [
+        ("file", Field::Str("a \"b\".png")),
+        ("rows", Field::Int(16)),
+        ("ms", Field::Float(1.5)),
+    ];
+    assert_eq!(
+        format_event(LogFormat::Json, 7, "encode_finished", &fields),
+        r#"{"ts":7,"event":"encode_finished","file":"a \"b\".png","rows":16,"ms":1.5}"#
+    );
+    assert_eq!(
+        format_event(LogFormat::Text, 7, "encode_finished", &fields),
+        r#"ts=7 event=encode_finished file="a \"b\".png" rows=16 ms=1.5"#
+    );
+    assert_eq!(
+        format_event(LogFormat::Json, 0, "x", &[("ms", Field::Float(f64::NAN))]),
+        r#"{"ts":0,"event":"x","ms":null}"#
+    );
+}
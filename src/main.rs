@@ -1,236 +1,3179 @@
-use image::codecs::png::PngEncoder;
-use image::{ColorType, ImageEncoder};
 use num::Complex;
-use num_traits::PrimInt;
-use std::fs::File;
-use std::io::{Error, ErrorKind, Write};
+use rust_experiments::{
+    adaptive, animation, annotations, anomalies, apply_pixel_aspect, buddhabrot, card, checkpoint,
+    cmyk, color, coords, errors, hdr, histogram_levels, i18n, layers, log, manifest, map_tiles,
+    palette, palette_edit, parse_coefficients, parse_complex, parse_pair, pdf, percentile_range,
+    perturbation, pixel_to_point, plan, precision, progress, random, records, render,
+    render_band_rows, render_bands, scan, sonify, stages, stitch, subdivision, svg, verify,
+    viewport, write_image, zoom, zoom_loop, AaMode, Formula, Fractal, Interior, NewtonMap,
+    PngStream, Power, RationalMap, Renderer, DEFAULT_BAILOUT, DEFAULT_LIMIT, DYNAMICS_LIMIT,
+    RATIONAL_BAILOUT, ROWS_PER_CHUNK,
+};
 use std::str::FromStr;
 
-/// Find the escape time for a given point in the complex plane.
-/// The escape time is the number of iterations it takes for the point to leave the
-/// circle of radius 2 centered at the origin.
-/// If the point does not escape, return `None`.
-///
-/// # Examples
-/// ```
-/// assert_eq!(escape_time(Complex { re: 1.0, im: 0.0 }, 100), Some(0));
-/// assert_eq!(escape_time(Complex { re: 1.0, im: 0.0 }, 1), None);
-/// ```
-fn escape_time<T: PrimInt>(c: Complex<f64>, limit: T) -> Option<T> {
-    let mut z = Complex { re: 0.0, im: 0.0 };
-    let mut i = T::zero();
-    while i < limit {
-        z = z * z + c;
-        // If the absolute value of z is greater than 2, then the point is
-        // unbounded and we return the number of iterations it took to get
-        // there.
-        if z.norm_sqr() > 4.0 {
-            return Some(i);
-        }
-        i = i + T::one();
-    }
-    None
+use errors::{ErrorFormat, ErrorKind};
+use i18n::Lang;
+use log::Field;
+use precision::{DoubleDouble, Precision, Real};
+use stages::Stage;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::{Duration, Instant};
+
+/// Warn about the chunks of rows that `stage` failed to render, as returned by `render_bands`.
+fn report_failures(stage: Stage, failed: &[(usize, usize)], logger: log::Logger, lang: Lang) {
+    for &(top, rows) in failed {
+        logger.event(
+            "band_failed",
+            &[
+                ("stage", Field::Str(stage.name())),
+                ("top", Field::Int(top as u64)),
+                ("rows", Field::Int(rows as u64)),
+            ],
+        );
+        eprintln!(
+            "{}",
+            lang.translate(&format!(
+                "warning: rows {} to {} failed in the {} stage and were left incomplete",
+                top,
+                top + rows - 1,
+                stage.name()
+            ))
+        );
+    }
+}
+
+/// File formats that `--output` can write, chosen by the extension of the file name.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Png,
+    Tiff,
+    Pdf,
+    /// The gray levels (and alpha, if any) as bytes, row by row, without a header.
+    Raw,
+    Exr,
+    Svg,
+    /// A table of per-pixel records, for data analysis.
+    Csv,
+    Parquet,
 }
 
-#[test]
-fn test_escape_time() {
-    assert_eq!(escape_time(Complex { re: 1.0, im: 0.0 }, 100), Some(2));
-    assert_eq!(escape_time(Complex { re: 1.0, im: 0.0 }, 1), None);
-}
-
-/// Parse the string `s` as a pair, like `"400x600"` or `"1.0,0.5"`.
-///
-/// # Examples
-/// ```
-/// assert_eq!(parse_pair("400x600", 'x'), Ok((400, 600)));
-/// assert_eq!(parse_pair("1.0,0.5", ','), Ok((1.0, 0.5)));
-/// ```
-fn parse_pair<T: FromStr>(s: &str, separator: char) -> Option<(T, T)> {
-    // Find position of separator
-    match s.find(separator) {
-        None => None,
-        Some(index) => match (T::from_str(&s[..index]), T::from_str(&s[index + 1..])) {
-            (Ok(l), Ok(r)) => Some((l, r)),
+impl Format {
+    fn from_filename(filename: &str) -> Option<Format> {
+        let extension = std::path::Path::new(filename).extension()?.to_str()?;
+        match extension.to_ascii_lowercase().as_str() {
+            "png" => Some(Format::Png),
+            "tif" | "tiff" => Some(Format::Tiff),
+            "pdf" => Some(Format::Pdf),
+            "raw" => Some(Format::Raw),
+            "exr" => Some(Format::Exr),
+            "svg" => Some(Format::Svg),
+            "csv" => Some(Format::Csv),
+            "parquet" => Some(Format::Parquet),
             _ => None,
-        },
+        }
     }
 }
 
 #[test]
-fn test_parse_pair() {
-    assert_eq!(parse_pair::<i32>("", ','), None);
-    assert_eq!(parse_pair::<i32>("10,", ','), None);
-    assert_eq!(parse_pair::<i32>(",10", ','), None);
-    assert_eq!(parse_pair::<i32>("10,20", ','), Some((10, 20)));
-    assert_eq!(parse_pair::<i32>("10,20xy", ','), None);
-    assert_eq!(parse_pair::<f64>("0.5x", 'x'), None);
-    assert_eq!(parse_pair::<f64>("0.5x1.5", 'x'), Some((0.5, 1.5)));
+fn test_format_from_filename() {
+    assert_eq!(Format::from_filename("a.png"), Some(Format::Png));
+    assert_eq!(Format::from_filename("dir.d/a.TIF"), Some(Format::Tiff));
+    assert_eq!(Format::from_filename("a.raw"), Some(Format::Raw));
+    assert_eq!(Format::from_filename("a.parquet"), Some(Format::Parquet));
+    assert_eq!(Format::from_filename("a.jpg"), None);
+    assert_eq!(Format::from_filename("png"), None);
+}
+
+/// How far a view given by its center is zoomed in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Scale {
+    /// Magnification relative to a view of the whole set, as for points of interest.
+    Zoom(f64),
+    /// Width of a pixel in the plane.
+    UnitsPerPixel(f64),
 }
 
-/// Parse a pair of floating-point numbers separated by a comma as a complex number.
-fn parse_complex(s: &str) -> Option<Complex<f64>> {
-    match parse_pair(s, ',') {
-        Some((re, im)) => Some(Complex { re, im }),
-        None => None,
+impl Scale {
+    /// Width of a pixel in the plane, for an image with dimensions `bounds`.
+    fn pixel_size(self, bounds: (usize, usize)) -> f64 {
+        match self {
+            Scale::Zoom(zoom) => annotations::FULL_WIDTH / zoom / bounds.0.max(1) as f64,
+            Scale::UnitsPerPixel(size) => size,
+        }
     }
 }
 
 #[test]
-fn test_parse_complex() {
+fn test_scale() {
+    assert_eq!(Scale::Zoom(2.0).pixel_size((300, 200)), 0.005);
+    assert_eq!(Scale::UnitsPerPixel(1e-3).pixel_size((300, 200)), 1e-3);
+}
+
+/// How the escape counts become gray levels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Coloring {
+    /// The counts themselves, or stretched over `--auto-range`.
+    Linear,
+    /// Spread evenly over the gray scale by their histogram, measured before rendering.
+    Histogram,
+}
+
+impl Coloring {
+    fn parse(s: &str) -> Option<Coloring> {
+        match s {
+            "linear" => Some(Coloring::Linear),
+            "histogram" => Some(Coloring::Histogram),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Coloring::Linear => "linear",
+            Coloring::Histogram => "histogram",
+        }
+    }
+}
+
+/// How the pixels of the image are worked through.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Algorithm {
+    /// Every pixel, row by row.
+    Scanline,
+    /// Rectangles filled from their border when it is all one color, by `subdivision::render`.
+    Subdivision,
+}
+
+impl Algorithm {
+    fn parse(s: &str) -> Option<Algorithm> {
+        match s {
+            "scanline" => Some(Algorithm::Scanline),
+            "subdivision" => Some(Algorithm::Subdivision),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Algorithm::Scanline => "scanline",
+            Algorithm::Subdivision => "subdivision",
+        }
+    }
+}
+
+/// Optional settings given as `--name value` pairs after the positional arguments.
+#[derive(Debug)]
+struct Options {
+    /// What to render and how to sample it.
+    renderer: Renderer,
+    /// Width of the area each pixel covers relative to its height, if the corners should be
+    /// adjusted to it.
+    pixel_aspect: Option<f64>,
+    /// Precision to compute the points of the image with.
+    precision: Precision,
+    /// Percentiles of the escape counts to stretch over the gray scale, to be measured before
+    /// rendering.
+    auto_range: Option<(f64, f64)>,
+    coloring: Coloring,
+    algorithm: Algorithm,
+    /// Number of threads each stage of the render runs on.
+    threads: stages::StageThreads,
+    /// Whether to report the time spent in each stage of the render.
+    timings: bool,
+    /// How to report errors.
+    error_format: ErrorFormat,
+    /// How to show the progress of the render.
+    progress: progress::ProgressMode,
+    /// How to log the events of the render.
+    log_format: log::LogFormat,
+    /// Largest amount of memory, in bytes, the render may take.
+    max_memory: Option<u64>,
+    /// Language of messages for people.
+    lang: Lang,
+    /// Additional files to write from the same render, in the format given by their extension.
+    outputs: Vec<(Format, String)>,
+    /// File to write the iteration data layers to, as an EXR image.
+    exr: Option<String>,
+    /// File to write the image to as a PDF page, laid out according to `page`.
+    pdf: Option<String>,
+    /// Page layout of the PDF output.
+    page: pdf::PageLayout,
+    /// Whether to print the coordinates of the image below it on the PDF page.
+    caption: bool,
+    /// Whether to add a strip describing the view below the image.
+    card: bool,
+    /// File to write the image to as a TIFF, for print.
+    tiff: Option<String>,
+    /// Profile to separate the TIFF output into CMYK inks with, instead of leaving it gray.
+    cmyk: Option<cmyk::Profile>,
+    /// File to write the traced boundary of the set to, as an SVG image.
+    svg: Option<String>,
+    /// Transfer function to write a 16-bit HDR image with, instead of an 8-bit one.
+    hdr: Option<hdr::Transfer>,
+    /// Two per-pixel channels to also write as aligned 16-bit images, named after the image.
+    pair: Option<(layers::Channel, layers::Channel)>,
+    /// Number of tiles across and down to render the image as, each to a file of its own.
+    tiles: Option<(usize, usize)>,
+    /// Checkpoint file to save the rows of the image to as they are rendered, and to pick up from
+    /// if an earlier run of the same render left some there.
+    resume: Option<String>,
+    /// Labeled points of interest.
+    annotations: Vec<annotations::Annotation>,
+    /// Point of interest to render instead of the corners given on the command line.
+    goto: Option<annotations::Annotation>,
+    /// Center of the view to render instead of the corners given on the command line.
+    center: Option<Complex<DoubleDouble>>,
+    /// How far the view around `center` is zoomed in.
+    scale: Scale,
+    /// Whether to mark the points of interest in the image.
+    markers: bool,
+    /// ICC profile to embed in the image instead of tagging it as sRGB.
+    icc: Option<Vec<u8>>,
+    /// File to write the reproducibility manifest of the render to.
+    manifest: Option<String>,
+    /// Difference between neighboring pixels, taken at a single sample each, past which they are
+    /// supersampled; all pixels are when not set.
+    adaptive: Option<u8>,
+    /// File to write a mask of the pixels supersampled by `adaptive` to, as a PNG image.
+    refined: Option<String>,
+    /// Rows to render first, working outward from them; the middle row by default.
+    focus: Option<std::ops::Range<usize>>,
+    /// Whether to check the escape times of boundary pixels with interval arithmetic.
+    verify: bool,
+    /// How to write coordinates in the log, manifest, caption and CSV output.
+    coord_format: coords::CoordFormat,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            renderer: Renderer::default(),
+            pixel_aspect: None,
+            precision: Precision::Auto,
+            auto_range: None,
+            coloring: Coloring::Linear,
+            algorithm: Algorithm::Scanline,
+            threads: stages::StageThreads::default(),
+            timings: false,
+            error_format: ErrorFormat::Text,
+            progress: progress::ProgressMode::Auto,
+            log_format: log::LogFormat::Off,
+            max_memory: None,
+            lang: Lang::En,
+            outputs: Vec::new(),
+            exr: None,
+            pdf: None,
+            page: pdf::PageLayout::default(),
+            caption: false,
+            card: false,
+            tiff: None,
+            cmyk: None,
+            svg: None,
+            hdr: None,
+            pair: None,
+            tiles: None,
+            resume: None,
+            annotations: Vec::new(),
+            goto: None,
+            center: None,
+            scale: Scale::Zoom(1.0),
+            markers: false,
+            icc: None,
+            manifest: None,
+            adaptive: None,
+            refined: None,
+            focus: None,
+            verify: false,
+            coord_format: coords::CoordFormat::Shortest,
+        }
+    }
+}
+
+/// Largest number of columns and rows sampled to measure the escape counts for `--auto-range`
+/// and `--coloring histogram`.
+const AUTO_RANGE_PREVIEW: usize = 256;
+
+/// Parse a strictly positive, finite floating-point number.
+fn parse_positive(s: &str) -> Option<f64> {
+    f64::from_str(s).ok().filter(|&x| x > 0.0 && x.is_finite())
+}
+
+/// Parse an amount of memory in bytes, with an optional binary `K`, `M`, `G` or `T` suffix,
+/// like `"512M"` or `"1.5G"`.
+fn parse_size(s: &str) -> Option<u64> {
+    let upper = s.to_ascii_uppercase();
+    let digits = upper.trim_end_matches(['B', 'I']);
+    let (number, shift) = match digits.chars().last()? {
+        'K' => (&digits[..digits.len() - 1], 10),
+        'M' => (&digits[..digits.len() - 1], 20),
+        'G' => (&digits[..digits.len() - 1], 30),
+        'T' => (&digits[..digits.len() - 1], 40),
+        _ if digits.len() == upper.len() => (digits, 0),
+        _ => return None,
+    };
+    let bytes = parse_positive(number)? * (1u64 << shift) as f64;
+    (bytes < u64::MAX as f64).then_some(bytes as u64)
+}
+
+#[test]
+fn test_parse_size() {
+    assert_eq!(parse_size("1000"), Some(1000));
+    assert_eq!(parse_size("512M"), Some(512 << 20));
+    assert_eq!(parse_size("1.5g"), Some(3 << 29));
+    assert_eq!(parse_size("2GiB"), Some(2 << 30));
+    assert_eq!(parse_size("4KB"), Some(4 << 10));
+    assert_eq!(parse_size("12B"), None);
+    assert_eq!(parse_size("M"), None);
+    assert_eq!(parse_size("-1G"), None);
+}
+
+/// The name of the file to write `channel` of `--pair` to, for an image written to `filename`:
+/// `mandel-angle.png` for `mandel.png`.
+fn channel_filename(filename: &str, channel: layers::Channel) -> String {
+    let path = std::path::Path::new(filename);
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("");
+    path.with_file_name(format!("{}-{}.png", stem, channel.name()))
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[test]
+fn test_channel_filename() {
     assert_eq!(
-        parse_complex("1.25,-0.0625"),
-        Some(Complex {
-            re: 1.25,
-            im: -0.0625
-        })
+        channel_filename("mandel.png", layers::Channel::Angle),
+        "mandel-angle.png"
+    );
+    assert_eq!(
+        channel_filename("out.d/deep.zoom.tiff", layers::Channel::Iterations),
+        "out.d/deep.zoom-iterations.png"
     );
-    assert_eq!(parse_complex(",-0.0625"), None);
 }
 
-/// Given the row and column of a pixel in the output image, return the corresponding point on the
-/// complex plane.
-fn pixel_to_point(
-    bounds: (usize, usize),
-    pixel: (usize, usize),
-    upper_left: Complex<f64>,
-    lower_right: Complex<f64>,
-) -> Complex<f64> {
-    let (width, height) = (
-        lower_right.re - upper_left.re,
-        upper_left.im - lower_right.im,
+/// Whether the image can be written to its files a band of rows at a time as it is rendered,
+/// rather than once it is all there: they are all PNG or raw files, and nothing else needs the
+/// whole of it at once.
+fn streams(options: &Options) -> bool {
+    let page = |&(format, _): &(Format, String)| matches!(format, Format::Tiff | Format::Pdf);
+    options.tiff.is_none()
+        && options.pdf.is_none()
+        && !options.outputs.iter().any(page)
+        && options.adaptive.is_none()
+        && !options.markers
+}
+
+/// Estimate the peak memory, in bytes, that rendering an image with dimensions `bounds` as
+/// described by `options` takes: the buffers the render fills, plus what the encoders of the
+/// output files hold on to at the same time.
+fn estimate_memory(bounds: (usize, usize), options: &Options) -> u64 {
+    let pixels = bounds.0 as u64 * bounds.1 as u64;
+    let has_output = |format| options.outputs.iter().any(|&(f, _)| f == format);
+    let mut total = 0;
+    let exr = options.exr.is_some() || has_output(Format::Exr);
+    let svg = options.svg.is_some() || has_output(Format::Svg);
+    let records = has_output(Format::Csv) || has_output(Format::Parquet);
+    if exr || svg || records || options.hdr.is_some() || options.pair.is_some() {
+        total += pixels * std::mem::size_of::<layers::PointData>() as u64;
+    }
+    if exr {
+        // The layers are copied into separate channel buffers.
+        total += pixels * 8 * 4;
+    }
+    if options.pair.is_some() {
+        // Both images are encoded at once, two bytes a sample.
+        total += pixels * 2 * 2;
+    }
+    if svg {
+        total += pixels * 8;
+    }
+    if has_output(Format::Parquet) {
+        // A row group of records, and one of its columns encoded.
+        let rows = pixels.min(1 << 20);
+        total += rows * std::mem::size_of::<records::Record>() as u64 + rows * 8;
+    }
+    if options.hdr.is_some() {
+        total += pixels * 2;
+    }
+    let channels = options.renderer.channels() as u64;
+    let gray = options.hdr.is_none()
+        || [Format::Png, Format::Tiff, Format::Pdf, Format::Raw]
+            .into_iter()
+            .any(has_output)
+        || options.tiff.is_some()
+        || options.pdf.is_some();
+    // Tiles are rendered, and held in memory, one at a time.
+    let bounds = match options.tiles {
+        Some((across, down)) => (bounds.0.div_ceil(across), bounds.1.div_ceil(down)),
+        None => bounds,
+    };
+    if gray && streams(options) {
+        // A band of rows, and what the encoders hold back of it.
+        let rows = bounds.1.min(stream_rows(bounds.0));
+        total += bounds.0 as u64 * rows as u64 * channels * 2;
+    } else if gray {
+        total += pixels * channels;
+        // Encoders may hold a compressed copy, or a CMYK one four times the size.
+        let tiff = options.tiff.is_some() || has_output(Format::Tiff);
+        total += pixels
+            * if tiff && options.cmyk.is_some() {
+                4
+            } else {
+                channels
+            };
+    }
+    total
+}
+
+#[test]
+fn test_estimate_memory() {
+    let gray = estimate_memory((1000, 1000), &Options::default());
+    assert_eq!(gray, 2_000_000);
+    // Larger images are streamed to their PNG file a band of rows at a time.
+    let band = estimate_memory((10_000, 10_000), &Options::default());
+    assert_eq!(band, 10_000 * 208 * 2);
+    let options = Options {
+        tiff: Some("print.tiff".to_string()),
+        ..Options::default()
+    };
+    assert_eq!(estimate_memory((10_000, 10_000), &options), 200_000_000);
+    let options = Options {
+        exr: Some("layers.exr".to_string()),
+        ..Options::default()
+    };
+    assert!(estimate_memory((1000, 1000), &options) > gray + 28_000_000);
+    let options = Options {
+        tiles: Some((10, 10)),
+        ..Options::default()
+    };
+    assert_eq!(estimate_memory((10_000, 10_000), &options), 1000 * 1000 * 2);
+}
+
+/// Express the memory a render `needed` and the `limit` it exceeds in the largest of MiB, KiB
+/// and bytes that the limit comes to a whole one of, rounding what is needed up and the limit
+/// down, so that the one always reads as more than the other.
+fn memory_sizes(needed: u64, limit: u64) -> (u64, u64, &'static str) {
+    let (unit, size) = [("MiB", 1 << 20), ("KiB", 1 << 10), ("bytes", 1)]
+        .into_iter()
+        .find(|&(_, size)| limit >= size)
+        .unwrap_or(("bytes", 1));
+    (needed.div_ceil(size), limit / size, unit)
+}
+
+#[test]
+fn test_memory_sizes() {
+    assert_eq!(memory_sizes(3 << 20, 1 << 30), (3, 1024, "MiB"));
+    assert_eq!(memory_sizes((1 << 20) + 1, 1 << 20), (2, 1, "MiB"));
+    assert_eq!(memory_sizes(2_000_000, 1 << 20), (2, 1, "MiB"));
+    assert_eq!(memory_sizes(2_000, 1_500), (2, 1, "KiB"));
+    assert_eq!(memory_sizes(128, 1), (128, 1, "bytes"));
+    assert_eq!(memory_sizes(128, 0), (128, 0, "bytes"));
+}
+
+/// Parse the optional `--name value` arguments that follow the positional ones.
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut numerator = None;
+    let mut denominator = None;
+    let mut julia = None;
+    let mut newton = None;
+    let mut goto = None;
+    let mut scale = None;
+    let mut threads = None;
+    let mut quiet = false;
+
+    let mut args = args.iter();
+    while let Some(name) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {}", name))?;
+        match name.as_str() {
+            "--numerator" => {
+                numerator = Some(parse_coefficients(value).ok_or("error parsing --numerator")?)
+            }
+            "--denominator" => {
+                denominator = Some(parse_coefficients(value).ok_or("error parsing --denominator")?)
+            }
+            "--julia" => julia = Some(parse_complex(value).ok_or("error parsing --julia")?),
+            "--newton" => {
+                // z^3 - 1, with its three basins.
+                let value = if value == "default" {
+                    "-1,0;0,0;0,0;1,0"
+                } else {
+                    value
+                };
+                newton = Some(parse_coefficients(value).ok_or("error parsing --newton")?)
+            }
+            "--power" => {
+                options.renderer.power = Power::parse(value).ok_or("error parsing --power")?
+            }
+            "--formula" => {
+                options.renderer.formula =
+                    Formula::parse(value).ok_or_else(|| format!("unknown formula {}", value))?
+            }
+            "--interior" => {
+                options.renderer.interior = match value.as_str() {
+                    "flat" => Interior::Flat,
+                    "dynamics" => Interior::Dynamics,
+                    _ => return Err(format!("unknown interior coloring {}", value)),
+                }
+            }
+            "--iterations" => {
+                options.renderer.limit = u32::from_str(value)
+                    .ok()
+                    .filter(|&limit| limit > 0)
+                    .ok_or("error parsing --iterations")?
+            }
+            "--dynamics-iterations" => {
+                options.renderer.dynamics_limit = u32::from_str(value)
+                    .ok()
+                    .filter(|&limit| limit > 0)
+                    .ok_or("error parsing --dynamics-iterations")?
+            }
+            "--bailout" => {
+                // The smooth count and distance estimate take the logarithm of the radius.
+                options.renderer.bailout = Some(
+                    parse_positive(value)
+                        .filter(|&radius| radius > 1.0)
+                        .ok_or("error parsing --bailout")?,
+                )
+            }
+            "--z0" => options.renderer.z0 = parse_complex(value).ok_or("error parsing --z0")?,
+            "--samples" => {
+                options.renderer.samples = parse_pair(value, 'x')
+                    .filter(|&(across, down)| across > 0 && down > 0)
+                    .ok_or("error parsing --samples")?
+            }
+            "--adaptive" => {
+                options.adaptive =
+                    Some(u8::from_str(value).map_err(|_| "error parsing --adaptive")?)
+            }
+            "--refined" => options.refined = Some(value.clone()),
+            "--aa-mode" => {
+                options.renderer.aa_mode = AaMode::parse(value)
+                    .ok_or_else(|| format!("unknown anti-aliasing mode {}", value))?
+            }
+            "--jitter" => {
+                options.renderer.jitter = match value.as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err("error parsing --jitter".to_string()),
+                }
+            }
+            "--seed" => {
+                options.renderer.seed =
+                    Some(u64::from_str(value).map_err(|_| "error parsing --seed")?)
+            }
+            "--pixel-aspect" => {
+                options.pixel_aspect =
+                    Some(parse_positive(value).ok_or("error parsing --pixel-aspect")?)
+            }
+            "--precision" => {
+                options.precision =
+                    Precision::parse(value).ok_or_else(|| format!("unknown precision {}", value))?
+            }
+            "--auto-range" => {
+                options.auto_range = Some(
+                    parse_pair(value, ',')
+                        .filter(|&(low, high)| 0.0 <= low && low < high && high <= 100.0)
+                        .ok_or("error parsing --auto-range")?,
+                )
+            }
+            "--algorithm" => {
+                options.algorithm =
+                    Algorithm::parse(value).ok_or_else(|| format!("unknown algorithm {}", value))?
+            }
+            "--coloring" => {
+                options.coloring =
+                    Coloring::parse(value).ok_or_else(|| format!("unknown coloring {}", value))?
+            }
+            "--threads" => {
+                threads = Some(
+                    usize::from_str(value)
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or("error parsing --threads")?,
+                )
+            }
+            "--stage-threads" => options
+                .threads
+                .parse(value)
+                .ok_or("error parsing --stage-threads")?,
+            "--timings" => {
+                options.timings = match value.as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err("error parsing --timings".to_string()),
+                }
+            }
+            "--error-format" => {
+                options.error_format =
+                    ErrorFormat::parse(value).ok_or("error parsing --error-format")?
+            }
+            "--progress" => {
+                options.progress =
+                    progress::ProgressMode::parse(value).ok_or("error parsing --progress")?
+            }
+            "--quiet" => {
+                quiet = match value.as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err("error parsing --quiet".to_string()),
+                }
+            }
+            "--log-format" => {
+                options.log_format =
+                    log::LogFormat::parse(value).ok_or("error parsing --log-format")?
+            }
+            "--max-memory" => {
+                options.max_memory = Some(parse_size(value).ok_or("error parsing --max-memory")?)
+            }
+            "--lang" => options.lang = Lang::parse(value).ok_or("error parsing --lang")?,
+            "--output" => {
+                let format = Format::from_filename(value)
+                    .ok_or_else(|| format!("unknown output format for {}", value))?;
+                options.outputs.push((format, value.clone()))
+            }
+            "--exr" => options.exr = Some(value.clone()),
+            "--svg" => options.svg = Some(value.clone()),
+            "--pdf" => options.pdf = Some(value.clone()),
+            "--tiff" => options.tiff = Some(value.clone()),
+            "--manifest" => options.manifest = Some(value.clone()),
+            "--coord-format" => {
+                options.coord_format = coords::CoordFormat::parse(value)
+                    .ok_or_else(|| format!("unknown coordinate format {}", value))?
+            }
+            "--verify" => {
+                options.verify = match value.as_str() {
+                    "interval" => true,
+                    _ => return Err(format!("unknown verification mode {}", value)),
+                }
+            }
+            "--cmyk" => {
+                options.cmyk = Some(
+                    cmyk::Profile::parse(value)
+                        .ok_or_else(|| format!("unknown CMYK profile {}", value))?,
+                )
+            }
+            "--page" => {
+                options.page.size = pdf::parse_page_size(value).ok_or("error parsing --page")?
+            }
+            "--margin" => match f64::from_str(value) {
+                Ok(mm) if mm >= 0.0 && mm.is_finite() => options.page.margin = mm * 72.0 / 25.4,
+                _ => return Err("error parsing --margin".to_string()),
+            },
+            "--dpi" => options.page.dpi = parse_positive(value).ok_or("error parsing --dpi")?,
+            "--caption" => {
+                options.caption = match value.as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err("error parsing --caption".to_string()),
+                }
+            }
+            "--card" => {
+                options.card = match value.as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err("error parsing --card".to_string()),
+                }
+            }
+            "--transparent" => {
+                options.renderer.transparent = match value.as_str() {
+                    "interior" => Some(true),
+                    "exterior" => Some(false),
+                    _ => return Err(format!("unknown transparent region {}", value)),
+                }
+            }
+            "--annotations" => {
+                let text = std::fs::read_to_string(value)
+                    .map_err(|e| format!("error reading annotations {}: {}", value, e))?;
+                options.annotations = annotations::parse_annotations(&text)?;
+            }
+            "--focus" => {
+                let (top, bottom): (usize, usize) = parse_pair(value, ',')
+                    .filter(|(top, bottom)| top < bottom)
+                    .ok_or("error parsing --focus")?;
+                options.focus = Some(top..bottom);
+            }
+            "--goto" => goto = Some(value.clone()),
+            "--center" => {
+                options.center =
+                    Some(precision::parse_complex(value).ok_or("error parsing --center")?)
+            }
+            "--zoom" | "--scale" => {
+                let x = parse_positive(value).ok_or_else(|| format!("error parsing {}", name))?;
+                if scale.is_some() {
+                    return Err("--zoom can't be combined with --scale".to_string());
+                }
+                scale = Some(if name == "--zoom" {
+                    Scale::Zoom(x)
+                } else {
+                    Scale::UnitsPerPixel(x)
+                });
+            }
+            "--markers" => {
+                options.markers = match value.as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err("error parsing --markers".to_string()),
+                }
+            }
+            "--icc" => {
+                let profile = std::fs::read(value)
+                    .map_err(|e| format!("error reading ICC profile {}: {}", value, e))?;
+                color::validate_icc(&profile)?;
+                options.icc = Some(profile);
+            }
+            "--palette" => {
+                options.renderer.palette = Some(
+                    palette::Palette::parse(value)
+                        .ok_or_else(|| format!("unknown palette {}", value))?,
+                )
+            }
+            "--tiles" => {
+                options.tiles = Some(
+                    parse_pair(value, 'x')
+                        .filter(|&(across, down)| across > 0 && down > 0)
+                        .ok_or("error parsing --tiles")?,
+                )
+            }
+            "--resume" => options.resume = Some(value.clone()),
+            "--pair" => {
+                let (first, second) = value.split_once(',').ok_or("error parsing --pair")?;
+                let channel = |name: &str| {
+                    layers::Channel::parse(name).ok_or_else(|| format!("unknown channel {}", name))
+                };
+                let pair = (channel(first)?, channel(second)?);
+                if pair.0 == pair.1 {
+                    return Err("--pair needs two different channels".to_string());
+                }
+                options.pair = Some(pair);
+            }
+            "--hdr" => {
+                options.hdr = match value.as_str() {
+                    "pq" => Some(hdr::Transfer::Pq),
+                    "hlg" => Some(hdr::Transfer::Hlg),
+                    _ => return Err(format!("unknown HDR transfer function {}", value)),
+                }
+            }
+            _ => return Err(format!("unknown option {}", name)),
+        }
+    }
+
+    if newton.is_some() {
+        let conflicts = [
+            ("--julia", julia.is_some()),
+            ("--numerator", numerator.is_some()),
+            ("--formula", options.renderer.formula != Formula::Mandelbrot),
+            ("--power", options.renderer.power != Power::default()),
+            ("--z0", options.renderer.z0 != Complex::default()),
+            ("--bailout", options.renderer.bailout.is_some()),
+        ];
+        if let Some((name, _)) = conflicts.iter().find(|(_, given)| *given) {
+            return Err(format!("{} can't be combined with --newton", name));
+        }
+    }
+    options.renderer.fractal = match (numerator, denominator) {
+        (None, None) => match julia {
+            Some(c) => Fractal::Julia(c),
+            None => Fractal::Mandelbrot,
+        },
+        (Some(_), _) if julia.is_some() => {
+            return Err("--julia can't be combined with --numerator".to_string())
+        }
+        (Some(_), _) if options.renderer.formula != Formula::Mandelbrot => {
+            return Err("--formula can't be combined with --numerator".to_string())
+        }
+        (Some(_), _) if options.renderer.power != Power::default() => {
+            return Err("--power can't be combined with --numerator".to_string())
+        }
+        (Some(_), _) if options.renderer.z0 != Complex::default() => {
+            return Err("--z0 can't be combined with --numerator".to_string())
+        }
+        (Some(numerator), denominator) => Fractal::Rational(RationalMap {
+            numerator,
+            denominator: denominator.unwrap_or(vec![Complex { re: 1.0, im: 0.0 }]),
+        }),
+        (None, Some(_)) => return Err("--denominator requires --numerator".to_string()),
+    };
+    if let Some(coefficients) = newton {
+        options.renderer.fractal = Fractal::Newton(
+            NewtonMap::new(coefficients)
+                .ok_or("--newton needs a polynomial of degree 1 or more")?,
+        );
+    }
+    if let Fractal::Julia(_) = options.renderer.fractal {
+        if options.renderer.z0 != Complex::default() {
+            return Err("--z0 can't be combined with --julia".to_string());
+        }
+        if options.renderer.interior != Interior::Flat {
+            return Err("--interior dynamics is only supported for the Mandelbrot set".to_string());
+        }
+    }
+    // Everything beyond the escape time iterates `z = z*z + c` itself.
+    let squares = options.renderer.formula == Formula::Mandelbrot
+        && options.renderer.power == Power::default();
+    let mandelbrot_set = options.renderer.fractal == Fractal::Mandelbrot && squares;
+    let other_fractal = matches!(
+        options.renderer.fractal,
+        Fractal::Newton(_) | Fractal::Rational(_)
     );
-    Complex {
-        re: upper_left.re + pixel.0 as f64 * width / bounds.0 as f64,
-        im: upper_left.im - pixel.1 as f64 * height / bounds.1 as f64,
+    if options.renderer.interior != Interior::Flat && (!squares || other_fractal) {
+        return Err("--interior dynamics is only supported for the Mandelbrot set".to_string());
+    }
+    // Classifying the dynamics follows the orbit of the critical point 0.
+    if options.renderer.interior == Interior::Dynamics && options.renderer.z0 != Complex::default()
+    {
+        return Err("--z0 can't be combined with --interior dynamics".to_string());
+    }
+    if options.renderer.dynamics_limit != DYNAMICS_LIMIT
+        && options.renderer.interior != Interior::Dynamics
+    {
+        return Err("--dynamics-iterations needs --interior dynamics".to_string());
     }
+    if let Some(threads) = threads {
+        options.threads.cap(threads);
+    }
+    // Tiles are only written as PNG images of their own, for `stitch` to put together.
+    if options.tiles.is_some() {
+        let conflicts = [
+            ("--output", !options.outputs.is_empty()),
+            ("--exr", options.exr.is_some()),
+            ("--svg", options.svg.is_some()),
+            ("--pdf", options.pdf.is_some()),
+            ("--tiff", options.tiff.is_some()),
+            ("--hdr", options.hdr.is_some()),
+            ("--pair", options.pair.is_some()),
+            ("--adaptive", options.adaptive.is_some()),
+            ("--markers", options.markers),
+            ("--card", options.card),
+            ("--icc", options.icc.is_some()),
+            ("--resume", options.resume.is_some()),
+        ];
+        if let Some((name, _)) = conflicts.iter().find(|(_, given)| *given) {
+            return Err(format!("{} can't be combined with --tiles", name));
+        }
+    }
+    // Only the rows of the 8-bit image are saved to the checkpoint, and they have to come out the
+    // same when resumed.
+    if options.resume.is_some() {
+        if options.hdr.is_some() {
+            return Err("--hdr can't be combined with --resume".to_string());
+        }
+        if options.renderer.jitter && options.renderer.seed.is_none() {
+            return Err("--resume needs --seed with --jitter on".to_string());
+        }
+    }
+    if quiet {
+        if !matches!(
+            options.progress,
+            progress::ProgressMode::Auto | progress::ProgressMode::Off
+        ) {
+            return Err("--progress can't be combined with --quiet".to_string());
+        }
+        options.progress = progress::ProgressMode::Off;
+    }
+    if let Some(name) = goto {
+        let annotation = options.annotations.iter().find(|a| a.name == name);
+        options.goto = Some(
+            annotation
+                .ok_or_else(|| format!("unknown point of interest {}", name))?
+                .clone(),
+        );
+    }
+    if options.center.is_some() && options.goto.is_some() {
+        return Err("--center can't be combined with --goto".to_string());
+    }
+    if let Some(scale) = scale {
+        if options.center.is_none() {
+            return Err("--zoom and --scale need --center".to_string());
+        }
+        options.scale = scale;
+    }
+    if options.adaptive.is_some() && options.renderer.samples == (1, 1) {
+        return Err("--adaptive needs --samples".to_string());
+    }
+    if options.refined.is_some() && options.adaptive.is_none() {
+        return Err("--refined needs --adaptive".to_string());
+    }
+    if options.coloring == Coloring::Histogram && options.auto_range.is_some() {
+        return Err("--coloring histogram can't be combined with --auto-range".to_string());
+    }
+    if options.markers && options.annotations.is_empty() {
+        return Err("--markers needs --annotations".to_string());
+    }
+    let has_output = |format| options.outputs.iter().any(|&(f, _)| f == format);
+    if (options.exr.is_some() || has_output(Format::Exr)) && !mandelbrot_set {
+        return Err("EXR output is only supported for the Mandelbrot set".to_string());
+    }
+    if (options.svg.is_some() || has_output(Format::Svg)) && !mandelbrot_set {
+        return Err("SVG output is only supported for the Mandelbrot set".to_string());
+    }
+    if (has_output(Format::Csv) || has_output(Format::Parquet)) && !mandelbrot_set {
+        return Err("CSV and Parquet output is only supported for the Mandelbrot set".to_string());
+    }
+    if options.hdr.is_some() && !mandelbrot_set {
+        return Err("--hdr is only supported for the Mandelbrot set".to_string());
+    }
+    if options.pair.is_some() && !mandelbrot_set {
+        return Err("--pair is only supported for the Mandelbrot set".to_string());
+    }
+    let escapes = !matches!(
+        options.renderer.fractal,
+        Fractal::Rational(_) | Fractal::Newton(_)
+    );
+    if options.verify && (!escapes || !squares) {
+        return Err("--verify is only supported for the Mandelbrot and Julia sets".to_string());
+    }
+    if options.verify && matches!(options.precision, Precision::Arb | Precision::Perturb) {
+        return Err(format!(
+            "--verify can't be combined with --precision {}",
+            options.precision.name()
+        ));
+    }
+    if options.precision == Precision::Perturb {
+        if !mandelbrot_set {
+            return Err("--precision perturb is only supported for the Mandelbrot set".to_string());
+        }
+        if options.renderer.interior == Interior::Dynamics {
+            return Err(
+                "--precision perturb can't be combined with --interior dynamics".to_string(),
+            );
+        }
+    }
+    // The HDR image is gray and opaque.
+    if options.hdr.is_some() {
+        let conflicts = [
+            ("--transparent", options.renderer.transparent.is_some()),
+            ("--palette", options.renderer.palette.is_some()),
+        ];
+        if let Some((name, _)) = conflicts.iter().find(|(_, given)| *given) {
+            return Err(format!("{} can't be combined with --hdr", name));
+        }
+    }
+    if (options.pdf.is_some() || has_output(Format::Pdf)) && options.renderer.transparent.is_some()
+    {
+        return Err("PDF output can't be combined with --transparent".to_string());
+    }
+    if (options.tiff.is_some() || has_output(Format::Tiff))
+        && options.renderer.transparent.is_some()
+    {
+        return Err("TIFF output can't be combined with --transparent".to_string());
+    }
+    if options.cmyk.is_some() && options.renderer.palette.is_some() {
+        return Err("--cmyk can't be combined with --palette".to_string());
+    }
+    // The profile describes gray or RGB pixels, not the separated inks.
+    if options.cmyk.is_some() && options.icc.is_some() {
+        return Err("--icc can't be combined with --cmyk".to_string());
+    }
+    if options.cmyk.is_some() && options.tiff.is_none() && !has_output(Format::Tiff) {
+        return Err("--cmyk needs TIFF output".to_string());
+    }
+
+    Ok(options)
 }
 
 #[test]
-fn test_pixel_to_point() {
+fn test_parse_options() {
+    let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+    assert_eq!(
+        parse_options(&[]).unwrap().renderer.fractal,
+        Fractal::Mandelbrot
+    );
+    assert_eq!(
+        parse_options(&args("--numerator 0,0;0,0;1,0"))
+            .unwrap()
+            .renderer
+            .fractal,
+        Fractal::Rational(RationalMap {
+            numerator: parse_coefficients("0,0;0,0;1,0").unwrap(),
+            denominator: parse_coefficients("1,0").unwrap(),
+        })
+    );
+    assert_eq!(
+        parse_options(&args("--julia -0.8,0.156"))
+            .unwrap()
+            .renderer
+            .fractal,
+        Fractal::Julia(Complex {
+            re: -0.8,
+            im: 0.156
+        })
+    );
+    assert!(parse_options(&args("--julia -0.8")).is_err());
+    let newton = parse_options(&args("--newton default"))
+        .unwrap()
+        .renderer
+        .fractal;
+    assert_eq!(
+        newton,
+        Fractal::Newton(NewtonMap::new(parse_coefficients("-1,0;0,0;0,0;1,0").unwrap()).unwrap())
+    );
+    assert!(parse_options(&args("--newton 1,0;2,0")).is_ok());
+    assert!(parse_options(&args("--newton 1,0;0,0")).is_err());
+    assert!(parse_options(&args("--newton default --julia 0,1")).is_err());
+    assert!(parse_options(&args("--newton default --power 3")).is_err());
+    assert!(parse_options(&args("--newton default --interior dynamics")).is_err());
+    assert!(parse_options(&args("--numerator 0,0;0,0;1,0 --interior dynamics")).is_err());
+    assert!(parse_options(&args("--newton default --verify interval")).is_err());
+    assert!(parse_options(&args("--julia 0,1 --numerator 0,0;0,0;1,0")).is_err());
+    assert!(parse_options(&args("--julia 0,1 --z0 0.1,0")).is_err());
+    assert!(parse_options(&args("--julia 0,1 --interior dynamics")).is_err());
+    assert!(parse_options(&args("--julia 0,1 --hdr pq")).is_err());
+    assert_eq!(
+        parse_options(&args("--pair iterations,angle"))
+            .unwrap()
+            .pair,
+        Some((layers::Channel::Iterations, layers::Channel::Angle))
+    );
+    assert!(parse_options(&args("--pair angle,angle")).is_err());
+    assert!(parse_options(&args("--pair angle,hue")).is_err());
+    assert!(parse_options(&args("--pair angle")).is_err());
+    assert!(parse_options(&args("--julia 0,1 --pair iterations,angle")).is_err());
+    assert_eq!(
+        parse_options(&args("--tiles 4x3")).unwrap().tiles,
+        Some((4, 3))
+    );
+    assert!(parse_options(&args("--tiles 0x3")).is_err());
+    assert!(parse_options(&args("--tiles 4x3 --tiff print.tiff")).is_err());
+    assert_eq!(
+        parse_options(&args("--resume mandel.checkpoint"))
+            .unwrap()
+            .resume
+            .as_deref(),
+        Some("mandel.checkpoint")
+    );
+    assert!(parse_options(&args("--resume mandel.checkpoint --tiles 4x3")).is_err());
+    assert!(parse_options(&args("--resume mandel.checkpoint --jitter on")).is_err());
+    assert!(parse_options(&args("--resume c --jitter on --seed 1")).is_ok());
+    assert_eq!(
+        parse_options(&args("--interior dynamics"))
+            .unwrap()
+            .renderer
+            .interior,
+        Interior::Dynamics
+    );
+    assert!(parse_options(&args("--interior fancy")).is_err());
+    let options = parse_options(&args(
+        "--iterations 1000 --interior dynamics --dynamics-iterations 500",
+    ))
+    .unwrap();
+    assert_eq!(options.renderer.limit, 1000);
+    assert_eq!(options.renderer.dynamics_limit, 500);
+    assert!(parse_options(&args("--iterations 0")).is_err());
+    assert!(parse_options(&args("--dynamics-iterations 500")).is_err());
+    assert_eq!(
+        parse_options(&args("--formula burning-ship"))
+            .unwrap()
+            .renderer
+            .formula,
+        Formula::BurningShip
+    );
+    assert!(parse_options(&args("--formula julia")).is_err());
+    assert!(parse_options(&args("--formula tricorn --numerator 0,0;0,0;1,0")).is_err());
+    assert!(parse_options(&args("--formula tricorn --interior dynamics")).is_err());
+    assert!(parse_options(&args("--z0 0.1,0 --interior dynamics")).is_err());
+    assert!(parse_options(&args("--numerator 0,0;0,0;1,0 --z0 0.3,0.2")).is_err());
+    assert!(parse_options(&args("--formula tricorn --precision perturb")).is_err());
+    assert_eq!(
+        parse_options(&args("--power 3")).unwrap().renderer.power,
+        Power::Integer(3)
+    );
+    assert!(parse_options(&args("--power 0.5")).is_err());
+    assert!(parse_options(&args("--power 2.5 --interior dynamics")).is_err());
+    assert!(parse_options(&args("--power 3 --verify interval")).is_err());
+    assert_eq!(
+        parse_options(&args("--bailout 1e6"))
+            .unwrap()
+            .renderer
+            .bailout,
+        Some(1e6)
+    );
+    assert!(parse_options(&args("--bailout -1")).is_err());
+    assert!(parse_options(&args("--bailout 0.5")).is_err());
+    assert!(parse_options(&args("--bailout 1")).is_err());
+    assert!(parse_options(&args("--bailout inf")).is_err());
+    assert_eq!(
+        parse_options(&args("--z0 0.5,-0.25")).unwrap().renderer.z0,
+        Complex { re: 0.5, im: -0.25 }
+    );
+    assert!(parse_options(&args("--z0 0.5")).is_err());
+    assert_eq!(
+        parse_options(&args("--samples 4x2"))
+            .unwrap()
+            .renderer
+            .samples,
+        (4, 2)
+    );
+    assert!(parse_options(&args("--samples 0x2")).is_err());
+    assert_eq!(
+        parse_options(&args("--pixel-aspect 1.33"))
+            .unwrap()
+            .pixel_aspect,
+        Some(1.33)
+    );
+    assert!(parse_options(&args("--pixel-aspect 0")).is_err());
+    assert_eq!(
+        parse_options(&args("--precision arb")).unwrap().precision,
+        Precision::Arb
+    );
+    assert!(parse_options(&args("--precision f128")).is_err());
+    assert_eq!(
+        parse_options(&args("--exr layers.exr")).unwrap().exr,
+        Some("layers.exr".to_string())
+    );
+    assert!(parse_options(&args("--exr layers.exr --numerator 1,0")).is_err());
+    assert_eq!(
+        parse_options(&args("--svg boundary.svg")).unwrap().svg,
+        Some("boundary.svg".to_string())
+    );
+    assert!(parse_options(&args("--svg boundary.svg --numerator 1,0")).is_err());
+    let options = parse_options(&args(
+        "--pdf poster.pdf --page 254x127 --margin 0 --dpi 72 --caption on",
+    ))
+    .unwrap();
+    assert_eq!(options.pdf, Some("poster.pdf".to_string()));
+    assert_eq!(options.page.margin, 0.0);
+    assert_eq!(options.page.dpi, 72.0);
+    assert!(options.caption);
+    assert!(parse_options(&args("--page b5")).is_err());
+    assert!(parse_options(&args("--margin -1")).is_err());
+    assert!(parse_options(&args("--caption yes")).is_err());
+    assert!(parse_options(&args("--card on")).unwrap().card);
+    assert!(parse_options(&args("--card yes")).is_err());
+    assert!(parse_options(&args("--pdf poster.pdf --transparent interior")).is_err());
+    let options = parse_options(&args("--samples 4x4 --jitter on --seed 42")).unwrap();
+    assert!(options.renderer.jitter);
+    assert_eq!(options.renderer.seed, Some(42));
+    assert!(parse_options(&args("--seed -1")).is_err());
+    assert!(parse_options(&args("--jitter 1")).is_err());
+    let options = parse_options(&args("--samples 4x4 --aa-mode value")).unwrap();
+    assert_eq!(options.renderer.aa_mode, AaMode::Value);
+    assert!(parse_options(&args("--aa-mode median")).is_err());
+    let options = parse_options(&args("--auto-range 1,99")).unwrap();
+    assert_eq!(options.auto_range, Some((1.0, 99.0)));
+    assert!(parse_options(&args("--auto-range 99,1")).is_err());
+    assert!(parse_options(&args("--auto-range 0,101")).is_err());
+    let options = parse_options(&args("--coloring histogram")).unwrap();
+    assert_eq!(options.coloring, Coloring::Histogram);
+    assert!(parse_options(&args("--coloring cubic")).is_err());
+    assert!(parse_options(&args("--coloring histogram --auto-range 1,99")).is_err());
+    let options = parse_options(&args("--algorithm subdivision")).unwrap();
+    assert_eq!(options.algorithm, Algorithm::Subdivision);
+    assert!(parse_options(&args("--algorithm quadtree")).is_err());
+    let options = parse_options(&args("--tiff print.tiff --cmyk uncoated")).unwrap();
+    assert_eq!(options.tiff, Some("print.tiff".to_string()));
+    assert_eq!(options.cmyk, Some(cmyk::Profile::Uncoated));
+    assert!(parse_options(&args("--tiff print.tiff --cmyk fogra39")).is_err());
+    assert!(parse_options(&args("--cmyk coated")).is_err());
+    assert!(parse_options(&args("--tiff print.tiff --transparent exterior")).is_err());
+    let options = parse_options(&args(
+        "--output gray.png --output print.tif --output dump.raw --cmyk k",
+    ))
+    .unwrap();
+    assert_eq!(
+        options.outputs,
+        [
+            (Format::Png, "gray.png".to_string()),
+            (Format::Tiff, "print.tif".to_string()),
+            (Format::Raw, "dump.raw".to_string()),
+        ]
+    );
+    assert!(parse_options(&args("--output image.jpg")).is_err());
+    assert_eq!(parse_options(&args("--lang ru")).unwrap().lang, Lang::Ru);
+    assert_eq!(
+        parse_options(&args("--progress plain")).unwrap().progress,
+        progress::ProgressMode::Plain
+    );
+    assert!(parse_options(&args("--progress fancy")).is_err());
+    assert_eq!(
+        parse_options(&[]).unwrap().progress,
+        progress::ProgressMode::Auto
+    );
+    assert_eq!(
+        parse_options(&args("--quiet on")).unwrap().progress,
+        progress::ProgressMode::Off
+    );
+    assert!(parse_options(&args("--quiet on --progress bar")).is_err());
+    assert_eq!(
+        parse_options(&args("--log-format json"))
+            .unwrap()
+            .log_format,
+        log::LogFormat::Json
+    );
+    assert!(parse_options(&args("--log-format xml")).is_err());
+    assert_eq!(
+        parse_options(&args("--max-memory 4G")).unwrap().max_memory,
+        Some(4 << 30)
+    );
+    assert!(parse_options(&args("--max-memory lots")).is_err());
+    let annotations = std::env::temp_dir().join("rust-experiments-test-annotations.txt");
+    std::fs::write(&annotations, "seahorse -0.745,0.113 200\n").unwrap();
+    let annotations = annotations.to_str().unwrap();
+    let options = parse_options(&args(&format!(
+        "--annotations {} --goto seahorse --markers on",
+        annotations
+    )))
+    .unwrap();
+    assert_eq!(options.goto.unwrap().zoom, 200.0);
+    assert!(options.markers);
+    assert!(parse_options(&args(&format!(
+        "--annotations {} --goto nowhere",
+        annotations
+    )))
+    .is_err());
+    assert!(parse_options(&args("--goto seahorse")).is_err());
+    assert!(parse_options(&args("--markers on")).is_err());
+    let options = parse_options(&args("--center -0.75,0.1 --zoom 4")).unwrap();
+    assert_eq!(
+        options.center.map(precision::complex_to_f64),
+        Some(Complex { re: -0.75, im: 0.1 })
+    );
+    assert_eq!(options.scale, Scale::Zoom(4.0));
+    let options = parse_options(&args("--center 0x1p-2,0 --scale 1e-20")).unwrap();
+    assert_eq!(options.scale, Scale::UnitsPerPixel(1e-20));
+    assert!(parse_options(&args("--center -0.75 --zoom 4")).is_err());
+    assert!(parse_options(&args("--center 0,0 --zoom 4 --scale 0.01")).is_err());
+    assert!(parse_options(&args("--center 0,0 --zoom -4")).is_err());
+    assert!(parse_options(&args("--zoom 4")).is_err());
+    assert_eq!(
+        parse_options(&args("--focus 100,200")).unwrap().focus,
+        Some(100..200)
+    );
+    assert!(parse_options(&args("--focus 200,100")).is_err());
+    let options = parse_options(&args("--samples 4x4 --adaptive 8 --refined mask.png")).unwrap();
+    assert_eq!(options.adaptive, Some(8));
+    assert_eq!(options.refined.as_deref(), Some("mask.png"));
+    assert!(parse_options(&args("--adaptive 8")).is_err());
+    assert!(parse_options(&args("--samples 4x4 --adaptive 300")).is_err());
+    assert!(parse_options(&args("--refined mask.png")).is_err());
+    std::fs::remove_file(annotations).unwrap();
+    assert!(parse_options(&args("--lang de")).is_err());
+    let options = parse_options(&args("--error-format json")).unwrap();
+    assert_eq!(options.error_format, ErrorFormat::Json);
+    assert!(parse_options(&args("--error-format xml")).is_err());
+    let options = parse_options(&args("--stage-threads iterate=3,encode=1 --timings on")).unwrap();
+    assert_eq!(options.threads.get(Stage::Iterate), 3);
+    assert_eq!(options.threads.get(Stage::Encode), 1);
+    assert!(options.timings);
+    assert!(parse_options(&args("--stage-threads post=2")).is_err());
+    let options = parse_options(&args("--threads 2 --stage-threads iterate=3,encode=1")).unwrap();
+    assert_eq!(options.threads.get(Stage::Iterate), 2);
+    assert_eq!(options.threads.get(Stage::Encode), 1);
+    assert!(parse_options(&args("--threads 0")).is_err());
+    assert!(parse_options(&args("--output layers.exr --numerator 1,0")).is_err());
+    assert!(parse_options(&args("--output pixels.parquet --julia 0,1")).is_err());
+    assert!(parse_options(&args("--output page.pdf --transparent interior")).is_err());
+    assert_eq!(
+        parse_options(&args("--hdr hlg")).unwrap().hdr,
+        Some(hdr::Transfer::Hlg)
+    );
+    assert!(parse_options(&args("--hdr sdr")).is_err());
+    assert_eq!(
+        parse_options(&args("--palette fire"))
+            .unwrap()
+            .renderer
+            .palette,
+        Some(palette::Palette::Fire)
+    );
+    assert!(parse_options(&args("--palette sunset")).is_err());
+    assert!(parse_options(&args("--palette ocean --tiff print.tiff --cmyk k")).is_err());
+    assert_eq!(
+        parse_options(&args("--manifest render.json"))
+            .unwrap()
+            .manifest,
+        Some("render.json".to_string())
+    );
+    assert!(parse_options(&args("--verify interval")).unwrap().verify);
+    assert_eq!(
+        parse_options(&args("--coord-format 12"))
+            .unwrap()
+            .coord_format,
+        coords::CoordFormat::Digits(12)
+    );
+    assert!(parse_options(&args("--coord-format octal")).is_err());
+    assert!(parse_options(&args("--verify exact")).is_err());
+    assert!(parse_options(&args("--verify interval --precision arb")).is_err());
+    assert!(parse_options(&args("--precision perturb --julia 0,1")).is_err());
+    assert!(parse_options(&args("--precision perturb --interior dynamics")).is_err());
+    assert!(parse_options(&args("--icc /nonexistent/profile.icc")).is_err());
+    let icc = std::env::temp_dir().join("rust-experiments-test-options.icc");
+    let mut profile = vec![0; 128];
+    profile[..4].copy_from_slice(&128u32.to_be_bytes());
+    profile[36..40].copy_from_slice(b"acsp");
+    std::fs::write(&icc, profile).unwrap();
+    let icc = icc.to_str().unwrap();
+    assert!(parse_options(&args(&format!("--icc {} --tiff a.tiff", icc))).is_ok());
+    assert!(parse_options(&args(&format!("--icc {} --tiff a.tiff --cmyk k", icc))).is_err());
+    std::fs::remove_file(icc).unwrap();
     assert_eq!(
-        pixel_to_point(
-            (100, 100),
-            (25, 75),
-            Complex { re: -1.0, im: 1.0 },
-            Complex { re: 1.0, im: -1.0 }
+        parse_options(&args("--transparent exterior"))
+            .unwrap()
+            .renderer
+            .transparent,
+        Some(false)
+    );
+    assert!(parse_options(&args("--transparent both")).is_err());
+    assert!(parse_options(&args("--transparent interior --hdr pq")).is_err());
+    assert!(parse_options(&args("--palette fire --hdr pq")).is_err());
+    assert!(parse_options(&args("--denominator 1,0")).is_err());
+    assert!(parse_options(&args("--numerator")).is_err());
+    assert!(parse_options(&args("--numerator 1;2")).is_err());
+    assert!(parse_options(&args("--frobnicate 1")).is_err());
+}
+
+/// Report an error of the given `kind` on standard error and exit with its status. Errors for
+/// people are translated to `lang`, errors for programs are left in English.
+fn fail(format: ErrorFormat, lang: Lang, kind: ErrorKind, message: &str) -> ! {
+    match format {
+        ErrorFormat::Text => eprintln!("{}", lang.translate(&format.format(kind, message))),
+        ErrorFormat::Json => eprintln!("{}", format.format(kind, message)),
+    }
+    std::process::exit(kind.exit_code());
+}
+
+/// Wrap writing `filename` in `format` into a job for the encode stage, which describes what
+/// failed for people and logs when the file is done.
+fn encode_job<'a>(
+    logger: log::Logger,
+    format: &'static str,
+    filename: &'a str,
+    write: impl FnOnce() -> Result<(), std::io::Error> + Send + 'a,
+) -> Box<dyn FnOnce() -> Result<(), String> + Send + 'a> {
+    Box::new(move || {
+        let start = Instant::now();
+        write().map_err(|e| format!("error writing {} file {}: {}", format, filename, e))?;
+        logger.event(
+            "encode_finished",
+            &[
+                ("format", Field::Str(format)),
+                ("file", Field::Str(filename)),
+                ("ms", Field::Float(start.elapsed().as_secs_f64() * 1e3)),
+            ],
+        );
+        Ok(())
+    })
+}
+
+/// Write the manifest of a render of an image with dimensions `bounds` between the corners
+/// `upper_left` and `lower_right`, written as `re,im`, as resolved from the command line `args`
+/// into `options`, to `filename`, with the digests of the `written` files.
+fn write_manifest(
+    filename: &str,
+    args: &[String],
+    bounds: (usize, usize),
+    corners: (&str, &str),
+    options: &Options,
+    written: &[&str],
+) -> Result<(), std::io::Error> {
+    with_parameters(bounds, corners, options, |parameters| {
+        manifest::write_manifest(filename, args, parameters, written)
+    })
+}
+
+/// Hand `f` the parameters of a render of an image with dimensions `bounds` between the corners
+/// `upper_left` and `lower_right`, written as `re,im`, as resolved into `options`: what its
+/// manifest records, and a checkpoint has to match to be resumed.
+fn with_parameters<T>(
+    bounds: (usize, usize),
+    (upper_left, lower_right): (&str, &str),
+    options: &Options,
+    f: impl FnOnce(&[(&str, Field)]) -> T,
+) -> T {
+    let renderer = &options.renderer;
+    let point = |c: Complex<f64>| options.coord_format.format_complex(c);
+    let coefficients = |cs: &[Complex<f64>]| cs.iter().map(|&c| point(c)).collect::<Vec<_>>();
+    let fractal = match &renderer.fractal {
+        Fractal::Mandelbrot => renderer.formula.name().to_string(),
+        Fractal::Julia(c) if renderer.formula != Formula::Mandelbrot => {
+            format!("julia {} {}", point(*c), renderer.formula.name())
+        }
+        Fractal::Julia(c) => format!("julia {}", point(*c)),
+        Fractal::Rational(map) => format!(
+            "rational {} / {}",
+            coefficients(&map.numerator).join(";"),
+            coefficients(&map.denominator).join(";")
         ),
-        Complex { re: -0.5, im: -0.5 }
+        Fractal::Newton(map) => format!("newton {}", coefficients(&map.coefficients).join(";")),
+    };
+    let bailout = renderer.bailout.unwrap_or(match renderer.fractal {
+        Fractal::Rational(_) => RATIONAL_BAILOUT,
+        _ => DEFAULT_BAILOUT,
+    });
+    let z0 = point(renderer.z0);
+    let samples = format!("{}x{}", renderer.samples.0, renderer.samples.1);
+    let range = renderer
+        .range
+        .map(|(low, high)| format!("{},{}", low, high));
+    let auto_range = options
+        .auto_range
+        .map(|(low, high)| format!("{},{}", low, high));
+    let mut parameters = vec![
+        ("width", Field::Int(bounds.0 as u64)),
+        ("height", Field::Int(bounds.1 as u64)),
+        ("upper_left", Field::Str(upper_left)),
+        ("lower_right", Field::Str(lower_right)),
+        ("precision", Field::Str(options.precision.name())),
+        ("fractal", Field::Str(&fractal)),
+        (
+            "interior",
+            Field::Str(match renderer.interior {
+                Interior::Flat => "flat",
+                Interior::Dynamics => "dynamics",
+            }),
+        ),
+        ("bailout", Field::Float(bailout)),
+        ("z0", Field::Str(&z0)),
+        ("samples", Field::Str(&samples)),
+        (
+            "jitter",
+            Field::Str(if renderer.jitter { "on" } else { "off" }),
+        ),
+        ("aa_mode", Field::Str(renderer.aa_mode.name())),
+        ("coloring", Field::Str(options.coloring.name())),
+        ("algorithm", Field::Str(options.algorithm.name())),
+    ];
+    if renderer.power != Power::default() {
+        parameters.push(("power", Field::Float(renderer.power.value())));
+    }
+    if renderer.limit != DEFAULT_LIMIT {
+        parameters.push(("iterations", Field::Int(renderer.limit as u64)));
+    }
+    if renderer.dynamics_limit != DYNAMICS_LIMIT {
+        let limit = renderer.dynamics_limit as u64;
+        parameters.push(("dynamics_iterations", Field::Int(limit)));
+    }
+    if let Some(seed) = renderer.seed {
+        parameters.push(("seed", Field::Int(seed)));
+    }
+    if let Some(range) = &range {
+        parameters.push(("range", Field::Str(range)));
+    }
+    if let Some(percentiles) = &auto_range {
+        parameters.push(("auto_range", Field::Str(percentiles)));
+    }
+    if let Some(threshold) = options.adaptive {
+        parameters.push(("adaptive", Field::Int(threshold as u64)));
+    }
+    if let Some(interior) = renderer.transparent {
+        let region = if interior { "interior" } else { "exterior" };
+        parameters.push(("transparent", Field::Str(region)));
+    }
+    if let Some(palette) = renderer.palette {
+        parameters.push(("palette", Field::Str(palette.name())));
+    }
+    if let Some(transfer) = options.hdr {
+        let transfer = match transfer {
+            hdr::Transfer::Pq => "pq",
+            hdr::Transfer::Hlg => "hlg",
+        };
+        parameters.push(("hdr", Field::Str(transfer)));
+    }
+    f(&parameters)
+}
+
+#[test]
+fn test_with_parameters() {
+    let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+    let parameters = |s: &str| {
+        let options = parse_options(&args(s)).unwrap();
+        with_parameters(
+            (4, 4),
+            ("-2,1", "1,-1"),
+            &options,
+            manifest::parameters_json,
+        )
+    };
+    // The manifest records every setting that changes the pixels.
+    assert!(parameters("--samples 2x2 --adaptive 8").contains("\"adaptive\": 8"));
+    assert!(parameters("--auto-range 1,99").contains("\"auto_range\": \"1,99\""));
+    // A checkpoint saved with one setting can't be resumed with another.
+    assert_ne!(
+        parameters("--samples 2x2 --adaptive 8"),
+        parameters("--samples 2x2 --adaptive 16")
+    );
+    assert_ne!(
+        parameters("--samples 2x2"),
+        parameters("--samples 2x2 --adaptive 8")
+    );
+    assert_ne!(
+        parameters("--auto-range 1,99"),
+        parameters("--auto-range 5,95")
     );
 }
 
-/// Render a rectangle of the Mandelbrot set into a buffer of pixels.
-fn render(
-    pixels: &mut [u8],
+/// Pixels rendered at a time when the image is streamed to its files.
+const STREAM_PIXELS: usize = 1 << 21;
+
+/// Rows of an image `width` pixels wide to render at a time when streaming it to its files: about
+/// `STREAM_PIXELS` pixels however narrow or wide it is, in a whole number of the chunks
+/// `render_bands` hands out, so that the pixels are the same as when it is rendered whole.
+fn stream_rows(width: usize) -> usize {
+    (STREAM_PIXELS / width.max(1) / ROWS_PER_CHUNK).max(1) * ROWS_PER_CHUNK
+}
+
+#[test]
+fn test_stream_rows() {
+    assert_eq!(stream_rows(8000), 256);
+    assert_eq!(stream_rows(1), STREAM_PIXELS);
+    assert_eq!(stream_rows(100_000), ROWS_PER_CHUNK);
+    assert_eq!(stream_rows(0), STREAM_PIXELS);
+}
+
+/// The files an image is written to a band of rows at a time as `render_pixels` renders it, for
+/// when nothing needs the whole image at once.
+struct Streams<'a> {
+    png: Vec<(&'a str, PngStream)>,
+    raw: Vec<(&'a str, BufWriter<File>)>,
+    /// Rows to add below the image once it is done, from `--card`.
+    card: Vec<u8>,
+    /// Time spent writing to each file, PNG files first.
+    spent: Vec<Duration>,
+}
+
+impl<'a> Streams<'a> {
+    /// Create the `png_files` and `raw_files` for an image with dimensions `bounds` and `channels`
+    /// bytes to a pixel, followed by the rows of `card`.
+    fn create(
+        png_files: &[&'a String],
+        raw_files: &[&'a String],
+        (bounds, channels): ((usize, usize), usize),
+        card: Vec<u8>,
+        icc: Option<&[u8]>,
+        text: &[(&str, String)],
+    ) -> Result<Streams<'a>, String> {
+        let height = bounds.1 + card.len() / (bounds.0 * channels).max(1);
+        let mut png = Vec::new();
+        for filename in png_files {
+            let stream = PngStream::create(filename, (bounds.0, height), channels, icc, text)
+                .map_err(|e| format!("error writing PNG file {}: {}", filename, e))?;
+            png.push((filename.as_str(), stream));
+        }
+        let mut raw = Vec::new();
+        for filename in raw_files {
+            let file = File::create(filename)
+                .map_err(|e| format!("error writing raw file {}: {}", filename, e))?;
+            raw.push((filename.as_str(), BufWriter::new(file)));
+        }
+        let spent = vec![Duration::ZERO; png.len() + raw.len()];
+        Ok(Streams {
+            png,
+            raw,
+            card,
+            spent,
+        })
+    }
+
+    /// Write the next `rows` of the image to every file.
+    fn write_rows(&mut self, rows: &[u8]) -> Result<(), String> {
+        let spent = &mut self.spent;
+        for (i, (filename, stream)) in self.png.iter_mut().enumerate() {
+            let start = Instant::now();
+            stream
+                .write_rows(rows)
+                .map_err(|e| format!("error writing PNG file {}: {}", filename, e))?;
+            spent[i] += start.elapsed();
+        }
+        let spent = &mut spent[self.png.len()..];
+        for (i, (filename, file)) in self.raw.iter_mut().enumerate() {
+            let start = Instant::now();
+            file.write_all(rows)
+                .map_err(|e| format!("error writing raw file {}: {}", filename, e))?;
+            spent[i] += start.elapsed();
+        }
+        Ok(())
+    }
+
+    /// Write the card and finish every file, once the whole image has been written.
+    fn finish(mut self, logger: log::Logger) -> Result<(), String> {
+        let card = std::mem::take(&mut self.card);
+        self.write_rows(&card)?;
+        let mut spent = self.spent.into_iter();
+        let mut finished = |format, filename, start: Instant| {
+            let ms = (spent.next().unwrap_or_default() + start.elapsed()).as_secs_f64() * 1e3;
+            logger.event(
+                "encode_finished",
+                &[
+                    ("format", Field::Str(format)),
+                    ("file", Field::Str(filename)),
+                    ("ms", Field::Float(ms)),
+                ],
+            );
+        };
+        for (filename, stream) in self.png {
+            let start = Instant::now();
+            stream
+                .finish()
+                .map_err(|e| format!("error writing PNG file {}: {}", filename, e))?;
+            finished("PNG", filename, start);
+        }
+        for (filename, mut file) in self.raw {
+            let start = Instant::now();
+            file.flush()
+                .map_err(|e| format!("error writing raw file {}: {}", filename, e))?;
+            finished("raw", filename, start);
+        }
+        Ok(())
+    }
+}
+
+/// The result of `render_pixels`.
+struct Rendered {
+    /// The image, or nothing if it was streamed to its files instead.
+    pixels: Vec<u8>,
+    /// Range of escape counts the pixels were colored with.
+    range: Option<(u32, u32)>,
+    /// Which pixels were supersampled, with `--adaptive`.
+    refined: Option<Vec<bool>>,
+}
+
+/// Render the image with dimensions `bounds` between `upper_left` and `lower_right` as resolved
+/// into `options`, at the precision of the corners, as `render_pixels` does, or with `--tiles`,
+/// tile by tile into the files `stitch::tile_filename` names for the image `filename`. Tiles
+/// already written, by an earlier run that stopped partway, are left as they are.
+#[allow(clippy::too_many_arguments)]
+fn render_view<R: Real>(
+    filename: &str,
     bounds: (usize, usize),
-    upper_left: Complex<f64>,
-    lower_right: Complex<f64>,
-) {
-    assert!(pixels.len() == bounds.0 * bounds.1);
-
-    // Iterate over the rows of the image.
-    for row in 0..bounds.1 {
-        // Iterate over the columns of the image.
-        for column in 0..bounds.0 {
-            // Find the point in the complex plane that corresponds to this pixel in the output image.
-            let point = pixel_to_point(bounds, (column, row), upper_left, lower_right);
-            // Compute the escape time for that point.
-            pixels[row * bounds.0 + column] = match escape_time(point, 255u8) {
-                None => 16,
-                Some(count) => count,
-            };
+    (upper_left, lower_right): (Complex<R>, Complex<R>),
+    options: &Options,
+    streams: Option<Streams>,
+    resume: Option<(&str, checkpoint::Checkpoint)>,
+    timings: &mut stages::Timings,
+    logger: log::Logger,
+) -> Rendered {
+    let corners = (upper_left, lower_right);
+    let renderer = measure_counts(bounds, corners, options, timings, logger);
+    let Some(tiles) = options.tiles else {
+        return render_pixels(
+            bounds, corners, options, &renderer, streams, resume, timings, logger,
+        );
+    };
+    let text: Vec<_> = renderer
+        .seed
+        .iter()
+        .map(|seed| ("Seed", seed.to_string()))
+        .collect();
+    for row in 0..tiles.1 {
+        for column in 0..tiles.0 {
+            let tile_filename = stitch::tile_filename(filename, (column, row));
+            if std::path::Path::new(&tile_filename).exists() {
+                logger.event("tile_skipped", &[("file", Field::Str(&tile_filename))]);
+                continue;
+            }
+            let ((left, top), (right, bottom)) = stitch::tile_pixels(bounds, tiles, (column, row));
+            let tile_bounds = (right - left, bottom - top);
+            let tile_corners = (
+                pixel_to_point(bounds, (left, top), upper_left, lower_right),
+                pixel_to_point(bounds, (right, bottom), upper_left, lower_right),
+            );
+            // Only a whole tile gets its name, so that one cut short is rendered again.
+            let partial = format!("{}.partial", tile_filename);
+            let streams = Streams::create(
+                &[&partial],
+                &[],
+                (tile_bounds, renderer.channels()),
+                Vec::new(),
+                None,
+                &text,
+            )
+            .unwrap_or_else(|e| fail(options.error_format, options.lang, ErrorKind::Io, &e));
+            let streams = Some(streams);
+            render_pixels(
+                tile_bounds,
+                tile_corners,
+                options,
+                &renderer,
+                streams,
+                None,
+                timings,
+                logger,
+            );
+            std::fs::rename(&partial, &tile_filename).unwrap_or_else(|e| {
+                fail(
+                    options.error_format,
+                    options.lang,
+                    ErrorKind::Io,
+                    &format!("error writing PNG file {}: {}", tile_filename, e),
+                )
+            });
+            logger.event("tile_written", &[("file", Field::Str(&tile_filename))]);
         }
     }
+    Rendered {
+        pixels: Vec::new(),
+        range: renderer.range,
+        refined: None,
+    }
+}
+
+/// The renderer of `options`, with the escape counts of the image with dimensions `bounds`
+/// between `upper_left` and `lower_right` measured for `--auto-range` and `--coloring histogram`
+/// if asked to.
+fn measure_counts<R: Real>(
+    bounds: (usize, usize),
+    (upper_left, lower_right): (Complex<R>, Complex<R>),
+    options: &Options,
+    timings: &mut stages::Timings,
+    logger: log::Logger,
+) -> Renderer {
+    let threads = options.threads;
+    let mut renderer = options.renderer.clone();
+    // Measure the escape counts on a coarse grid, to stretch the gray scale over or spread it by.
+    if options.auto_range.is_some() || options.coloring == Coloring::Histogram {
+        let preview_bounds = (
+            bounds.0.min(AUTO_RANGE_PREVIEW),
+            bounds.1.min(AUTO_RANGE_PREVIEW),
+        );
+        let mut preview = vec![None; preview_bounds.0 * preview_bounds.1];
+        let failed = timings.time(Stage::Iterate, || {
+            render_bands(
+                &mut preview,
+                preview_bounds,
+                upper_left,
+                lower_right,
+                threads.get(Stage::Iterate),
+                None,
+                None,
+                |band, band_bounds, band_upper_left, band_lower_right| {
+                    for (i, point) in band.iter_mut().enumerate() {
+                        let pixel = (i % band_bounds.0, i / band_bounds.0);
+                        let c =
+                            pixel_to_point(band_bounds, pixel, band_upper_left, band_lower_right);
+                        *point = renderer.escape_count(c);
+                    }
+                },
+            )
+        });
+        report_failures(Stage::Iterate, &failed, logger, options.lang);
+        let counts: Vec<u32> = preview.into_iter().flatten().collect();
+        if let Some(percentiles) = options.auto_range {
+            renderer.range = percentile_range(counts.clone(), percentiles);
+        }
+        if options.coloring == Coloring::Histogram {
+            renderer.levels = Some(histogram_levels(&counts));
+        }
+    }
+    renderer
 }
 
 #[test]
-fn test_render() {
-    let mut pixels = [0; 10 * 10];
-    render(
-        &mut pixels,
-        (10, 10),
-        Complex { re: 0.0, im: 0.0 },
-        Complex { re: 0.0, im: 0.0 },
-    );
-    println!("{:?}", pixels);
-    assert_eq!(pixels[0], 16);
-    assert_eq!(pixels[1], 16);
-    assert_eq!(pixels[2], 16);
-    assert_eq!(pixels[3], 16);
-}
-
-/// Write the buffer `pixels`, whose dimensions are given by `bounds`, to the
-/// file named `filename`.
-fn write_image(
+fn test_measure_counts() {
+    let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+    let options = parse_options(&args("--iterations 5000 --auto-range 1,99")).unwrap();
+    // Near the boundary of the set, where orbits take well over 255 iterations to escape.
+    let corners = (
+        Complex {
+            re: -0.75,
+            im: 0.11,
+        },
+        Complex { re: -0.74, im: 0.1 },
+    );
+    let logger = log::Logger {
+        format: log::LogFormat::Off,
+    };
+    let renderer = measure_counts(
+        (64, 64),
+        corners,
+        &options,
+        &mut stages::Timings::default(),
+        logger,
+    );
+    assert!(renderer.range.unwrap().1 > 255);
+}
+
+/// Read the bands of rows saved in `checkpoint`, from the file `filename`, handing them to `f` in
+/// turn from the top of the image down.
+fn for_each_saved_band(
     filename: &str,
-    pixels: &[u8],
+    checkpoint: &mut checkpoint::Checkpoint,
+    options: &Options,
+    mut f: impl FnMut(Vec<u8>),
+) {
+    for index in 0..checkpoint.bands() {
+        let band = checkpoint.read_band(index).unwrap_or_else(|e| {
+            fail(
+                options.error_format,
+                options.lang,
+                ErrorKind::Io,
+                &format!("error reading checkpoint file {}: {}", filename, e),
+            )
+        });
+        f(band);
+    }
+}
+
+/// Color every pixel of the image with dimensions `bounds` between `upper_left` and
+/// `lower_right` with `renderer`, as measured by `measure_counts`, supersampling only the edges
+/// with `--adaptive`. Given `streams`, the pixels are written to them a band at a time instead of
+/// kept. Given the checkpoint of `--resume` and its file, the rows saved in it are taken as they
+/// are, and the rest are rendered a band at a time and saved to it in turn.
+#[allow(clippy::too_many_arguments)]
+fn render_pixels<R: Real>(
     bounds: (usize, usize),
-) -> Result<(), std::io::Error> {
-    // Create a new file.
-    let output = File::create(filename)?;
+    (upper_left, lower_right): (Complex<R>, Complex<R>),
+    options: &Options,
+    renderer: &Renderer,
+    streams: Option<Streams>,
+    mut resume: Option<(&str, checkpoint::Checkpoint)>,
+    timings: &mut stages::Timings,
+    logger: log::Logger,
+) -> Rendered {
+    let threads = options.threads;
+    // Render the Mandelbrot set into a buffer of pixels.
+    let channels = renderer.channels();
+    let row_length = bounds.0 * channels;
+    let saved = resume
+        .as_ref()
+        .map_or(0, |(_, checkpoint)| checkpoint.rows());
+    let color_progress = progress::Progress::new(
+        options.progress,
+        Stage::Color.name(),
+        options.lang,
+        logger,
+        (bounds.0, bounds.1 - saved),
+    );
+    let first_pass = match options.adaptive {
+        Some(_) => Renderer {
+            samples: (1, 1),
+            jitter: false,
+            ..renderer.clone()
+        },
+        None => renderer.clone(),
+    };
+    // Render the `rows` of the image into `pixels`, returning the chunks that failed.
+    let render_rows =
+        |pixels: &mut [u8], rows: std::ops::Range<usize>, focus: Option<std::ops::Range<usize>>| {
+            render_band_rows(
+                pixels,
+                bounds,
+                rows,
+                (upper_left, lower_right),
+                threads.get(Stage::Color),
+                Some(&color_progress),
+                focus,
+                |band, band_bounds, band_upper_left, band_lower_right| match options.algorithm {
+                    Algorithm::Scanline => render(
+                        band,
+                        band_bounds,
+                        band_upper_left,
+                        band_lower_right,
+                        &first_pass,
+                    ),
+                    // Each chunk of rows is a rectangle of its own, subdivided on its thread.
+                    Algorithm::Subdivision => subdivision::render(
+                        band,
+                        band_bounds,
+                        band_upper_left,
+                        band_lower_right,
+                        &first_pass,
+                    ),
+                },
+            )
+        };
+
+    // Save a band of rows just rendered to the checkpoint, if there is one.
+    let save = |resume: &mut Option<(&str, checkpoint::Checkpoint)>, band: &[u8]| {
+        if let Some((filename, checkpoint)) = resume {
+            checkpoint.append(band).unwrap_or_else(|e| {
+                fail(
+                    options.error_format,
+                    options.lang,
+                    ErrorKind::Io,
+                    &format!("error writing checkpoint file {}: {}", filename, e),
+                )
+            });
+        }
+    };
+    let band_rows = stream_rows(bounds.0);
+
+    // Hand the image to its files a band at a time, holding no more of it than that.
+    if let Some(mut streams) = streams {
+        if let Some((filename, checkpoint)) = &mut resume {
+            for_each_saved_band(filename, checkpoint, options, |band| {
+                timings
+                    .time(Stage::Encode, || streams.write_rows(&band))
+                    .unwrap_or_else(|e| {
+                        fail(options.error_format, options.lang, ErrorKind::Io, &e)
+                    });
+            });
+        }
+        let mut band = Vec::new();
+        for top in (saved..bounds.1).step_by(band_rows) {
+            let rows = band_rows.min(bounds.1 - top);
+            band.resize(row_length * rows, 0);
+            let failed = timings.time(Stage::Color, || {
+                render_rows(&mut band, top..top + rows, None)
+            });
+            report_failures(Stage::Color, &failed, logger, options.lang);
+            timings
+                .time(Stage::Encode, || streams.write_rows(&band))
+                .unwrap_or_else(|e| fail(options.error_format, options.lang, ErrorKind::Io, &e));
+            save(&mut resume, &band);
+        }
+        timings
+            .time(Stage::Encode, || streams.finish(logger))
+            .unwrap_or_else(|e| fail(options.error_format, options.lang, ErrorKind::Io, &e));
+        return Rendered {
+            pixels: Vec::new(),
+            range: renderer.range,
+            refined: None,
+        };
+    }
+
+    let mut pixels = vec![0; bounds.0 * bounds.1 * channels];
+    if resume.is_none() {
+        let failed = timings.time(Stage::Color, || {
+            render_rows(&mut pixels, 0..bounds.1, options.focus.clone())
+        });
+        report_failures(Stage::Color, &failed, logger, options.lang);
+    } else {
+        // Take the rows saved, and render the rest from the top down, saving them as they come.
+        if let Some((filename, checkpoint)) = &mut resume {
+            let mut start = 0;
+            for_each_saved_band(filename, checkpoint, options, |band| {
+                pixels[start..start + band.len()].copy_from_slice(&band);
+                start += band.len();
+            });
+        }
+        for top in (saved..bounds.1).step_by(band_rows) {
+            let rows = band_rows.min(bounds.1 - top);
+            let band = &mut pixels[top * row_length..(top + rows) * row_length];
+            let failed = timings.time(Stage::Color, || render_rows(band, top..top + rows, None));
+            report_failures(Stage::Color, &failed, logger, options.lang);
+            save(&mut resume, band);
+        }
+    }
+
+    let refined = options.adaptive.map(|threshold| {
+        let edges = adaptive::edge_pixels(&pixels, bounds, channels, threshold);
+        let failed = timings.time(Stage::Color, || {
+            adaptive::refine(
+                &mut pixels,
+                bounds,
+                upper_left,
+                lower_right,
+                renderer,
+                &edges,
+                threads.get(Stage::Color),
+                None,
+            )
+        });
+        report_failures(Stage::Color, &failed, logger, options.lang);
+        logger.event(
+            "pixels_refined",
+            &[(
+                "pixels",
+                Field::Int(edges.iter().filter(|&&edge| edge).count() as u64),
+            )],
+        );
+        edges
+    });
+    Rendered {
+        pixels,
+        range: renderer.range,
+        refined,
+    }
+}
 
-    // Create a new encoder that writes to the file we just created.
-    let encoder = PngEncoder::new(output);
-    match encoder.write_image(pixels, bounds.0 as u32, bounds.1 as u32, ColorType::L8) {
-        Ok(_) => (),
-        Err(e) => {
-            return Err(Error::new(
-                ErrorKind::Other,
-                format!("Failed to write image: {:?}", e),
-            ));
+/// Write the orbit of a point as audio, for the `sonify` subcommand given `args` after it.
+fn run_sonify(args: &[String], error_format: ErrorFormat, lang: Lang) {
+    let options = sonify::parse_args(args).unwrap_or_else(|e| {
+        let kind = if args.len() < 2 {
+            ErrorKind::Usage
+        } else {
+            ErrorKind::Parse
+        };
+        fail(error_format, lang, kind, &e)
+    });
+    let zero = Complex { re: 0.0, im: 0.0 };
+    let orbit = match options.julia {
+        Some(c) => sonify::orbit(options.point, c, options.iterations),
+        None => sonify::orbit(zero, options.point, options.iterations),
+    };
+    let samples = sonify::synthesize(&orbit, options.note_ms);
+    sonify::write_wav(&options.filename, &samples).unwrap_or_else(|e| {
+        fail(
+            error_format,
+            lang,
+            ErrorKind::Io,
+            &format!("error writing WAV file {}: {}", options.filename, e),
+        )
+    });
+}
+
+/// Render the Buddhabrot, the density of the escaping orbits, for the `buddhabrot` subcommand
+/// given `args` after it.
+fn run_buddhabrot(args: &[String], error_format: ErrorFormat, lang: Lang) {
+    let options = buddhabrot::parse_args(args).unwrap_or_else(|e| {
+        let kind = if args.len() < 4 {
+            ErrorKind::Usage
+        } else {
+            ErrorKind::Parse
+        };
+        fail(error_format, lang, kind, &e)
+    });
+    let mut accumulation = match &options.state {
+        Some(state) => match buddhabrot::read_state(state, &options) {
+            Ok(accumulation) => accumulation,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                buddhabrot::Accumulation::new(&options)
+            }
+            Err(e) => fail(
+                error_format,
+                lang,
+                ErrorKind::Io,
+                &format!("error reading state file {}: {}", state, e),
+            ),
+        },
+        None => buddhabrot::Accumulation::new(&options),
+    };
+    // Write the image of the counts so far, and the counts themselves to resume from.
+    let save = |accumulation: &buddhabrot::Accumulation| {
+        let pixels = buddhabrot::to_pixels(&accumulation.hits, &options);
+        write_image(&options.filename, &pixels, options.bounds, None, &[]).unwrap_or_else(|e| {
+            fail(
+                error_format,
+                lang,
+                ErrorKind::Io,
+                &format!("error writing PNG file {}: {}", options.filename, e),
+            )
+        });
+        if let Some(state) = &options.state {
+            buddhabrot::write_state(state, &options, accumulation).unwrap_or_else(|e| {
+                fail(
+                    error_format,
+                    lang,
+                    ErrorKind::Io,
+                    &format!("error writing state file {}: {}", state, e),
+                )
+            });
         }
     };
+    let Some(interval) = options.snapshot_every else {
+        accumulation.step(&options);
+        save(&accumulation);
+        return;
+    };
+    // Quality keeps improving with every pass, so keep going until interrupted.
+    loop {
+        let start = std::time::Instant::now();
+        while start.elapsed() < interval {
+            accumulation.step(&options);
+        }
+        save(&accumulation);
+        eprintln!(
+            "{}",
+            lang.translate(&format!(
+                "wrote a snapshot of {} after {} passes",
+                options.filename, accumulation.passes
+            ))
+        );
+    }
+}
 
-    Ok(())
+/// Print the frames of a zoom with the precision and estimated cost of each, for the
+/// `plan-zoom` subcommand given `args` after it.
+fn run_plan_zoom(args: &[String], error_format: ErrorFormat, lang: Lang) {
+    let options = plan::parse_args(args).unwrap_or_else(|e| {
+        let kind = if args.len() < 3 {
+            ErrorKind::Usage
+        } else {
+            ErrorKind::Parse
+        };
+        fail(error_format, lang, kind, &e)
+    });
+    let frames = plan::plan(&options);
+    plan::write_plan(&mut std::io::stdout().lock(), &frames).unwrap_or_else(|e| {
+        fail(
+            error_format,
+            lang,
+            ErrorKind::Io,
+            &format!("error writing plan: {}", e),
+        )
+    });
 }
 
-fn main() {
-    let args: Vec<String> = std::env::args().collect();
+/// Render one frame of a zoom at the precision planned for it, as `zoom::render_frame` does.
+fn render_zoom_frame(
+    frame: &plan::Frame,
+    options: &zoom::ZoomOptions,
+    renderer: &Renderer,
+    previous: Option<(&[u8], usize)>,
+) -> (Vec<u8>, usize, Vec<(usize, usize)>) {
+    let (bounds, threads) = (options.plan.bounds, options.threads);
+    match frame.precision {
+        Precision::Arb => zoom::render_frame(
+            bounds,
+            frame.upper_left,
+            frame.lower_right,
+            renderer,
+            threads,
+            previous,
+            None,
+        ),
+        Precision::Perturb => {
+            // The frames share their center, which makes it the natural origin.
+            let origin = options.plan.center;
+            let offsets = (
+                precision::complex_to_f64(frame.upper_left - origin),
+                precision::complex_to_f64(frame.lower_right - origin),
+            );
+            let bailout = renderer.bailout.unwrap_or(DEFAULT_BAILOUT);
+            let perturbation = perturbation::Perturbation::for_view(
+                origin,
+                bounds,
+                offsets.0,
+                offsets.1,
+                renderer.z0,
+                255,
+                bailout,
+            );
+            let renderer = Renderer {
+                perturbation: Some(std::sync::Arc::new(perturbation)),
+                ..renderer.clone()
+            };
+            zoom::render_frame(
+                bounds, offsets.0, offsets.1, &renderer, threads, previous, None,
+            )
+        }
+        Precision::Auto | Precision::F64 => zoom::render_frame(
+            bounds,
+            precision::complex_to_f64(frame.upper_left),
+            precision::complex_to_f64(frame.lower_right),
+            renderer,
+            threads,
+            previous,
+            None,
+        ),
+    }
+}
+
+/// Render the frames of a zoom to PNG files, for the `zoom` subcommand given `args` after it.
+/// Frames that look broken next to their neighbors are rendered again at double-double
+/// precision.
+fn run_zoom(args: &[String], error_format: ErrorFormat, lang: Lang) {
+    let options = zoom::parse_args(args).unwrap_or_else(|e| {
+        let kind = if args.len() < 4 {
+            ErrorKind::Usage
+        } else {
+            ErrorKind::Parse
+        };
+        fail(error_format, lang, kind, &e)
+    });
+    let renderer = Renderer {
+        palette: options.palette,
+        ..Renderer::default()
+    };
+    let bounds = options.plan.bounds;
+    let logger = log::Logger {
+        format: log::LogFormat::Off,
+    };
+    // The frames of an animation are kept until the end, to be written together.
+    let mut animation_frames = Vec::new();
+    let mut write_frame = |i: usize, pixels: &[u8]| {
+        if options.animation.is_some() {
+            match animation_frames.get_mut(i) {
+                Some(frame) => *frame = pixels.to_vec(),
+                None => animation_frames.push(pixels.to_vec()),
+            }
+            return;
+        }
+        let filename = zoom::frame_filename(&options.pattern, i).unwrap();
+        write_image(&filename, pixels, bounds, None, &[]).unwrap_or_else(|e| {
+            fail(
+                error_format,
+                lang,
+                ErrorKind::Io,
+                &format!("error writing PNG file {}: {}", filename, e),
+            )
+        });
+    };
 
-    // Check that we have the right number of arguments.
-    if args.len() != 5 {
-        writeln!(
-            std::io::stderr(),
-            "Usage: mandelbrot FILE PIXELS UPPERLEFT LOWERRIGHT"
+    let frames = plan::plan(&options.plan);
+    let mut histograms = Vec::with_capacity(frames.len());
+    let mut previous: Option<Vec<u8>> = None;
+    let mut reused = 0;
+    for (i, frame) in frames.iter().enumerate() {
+        let factor = i
+            .checked_sub(1)
+            .and_then(|before| zoom::whole_factor(frames[before].zoom, frame.zoom));
+        let (pixels, copied, failed) =
+            render_zoom_frame(frame, &options, &renderer, previous.as_deref().zip(factor));
+        report_failures(Stage::Color, &failed, logger, lang);
+        write_frame(i, &pixels);
+        histograms.push(anomalies::Histogram::of(&pixels));
+        reused += copied;
+        previous = Some(pixels);
+    }
+
+    let suspicious = anomalies::suspicious_frames(&histograms);
+    for &i in &suspicious {
+        let frame = plan::Frame {
+            precision: Precision::Arb,
+            ..frames[i].clone()
+        };
+        let (pixels, _, failed) = render_zoom_frame(&frame, &options, &renderer, None);
+        report_failures(Stage::Color, &failed, logger, lang);
+        write_frame(i, &pixels);
+        histograms[i] = anomalies::Histogram::of(&pixels);
+    }
+    // Frames that still stand out are likely just what the set looks like there.
+    for i in anomalies::suspicious_frames(&histograms) {
+        if suspicious.contains(&i) {
+            eprintln!(
+                "{}",
+                lang.translate(&format!(
+                    "warning: frame {} still looks broken after rendering it again",
+                    i
+                ))
+            );
+        }
+    }
+    if let Some(container) = options.animation {
+        animation::write_animation(
+            &options.pattern,
+            container,
+            &animation_frames,
+            bounds,
+            options.fps,
         )
-        .unwrap();
-        writeln!(
-            std::io::stderr(),
-            "Example: {} mandel.png 1000x750 -1.20,0.35 -1,0.20",
-            args[0]
+        .unwrap_or_else(|e| {
+            fail(
+                error_format,
+                lang,
+                ErrorKind::Io,
+                &format!(
+                    "error writing {} file {}: {}",
+                    container.name(),
+                    options.pattern,
+                    e
+                ),
+            )
+        });
+    }
+    eprintln!(
+        "{}",
+        lang.translate(&format!(
+            "rendered {} frames, {}% of the pixels copied from the frame before",
+            frames.len(),
+            reused * 100 / (frames.len() * bounds.0 * bounds.1).max(1)
+        ))
+    );
+}
+
+/// Render one cycle of a zoom into a self-similar point that loops seamlessly, for the `loop`
+/// subcommand given `args` after it. The zoom factor and turn of a cycle are those that best
+/// match the picture near the point against itself further out.
+fn run_loop(args: &[String], error_format: ErrorFormat, lang: Lang) {
+    let options = zoom_loop::parse_args(args).unwrap_or_else(|e| {
+        let kind = if args.len() < 4 {
+            ErrorKind::Usage
+        } else {
+            ErrorKind::Parse
+        };
+        fail(error_format, lang, kind, &e)
+    });
+    let logger = log::Logger {
+        format: log::LogFormat::Off,
+    };
+    let rings = (zoom_loop::MAX_OCTAVES + zoom_loop::WINDOW_OCTAVES) * zoom_loop::RINGS_PER_OCTAVE;
+    let strip = zoom_loop::log_polar_strip(
+        &Renderer::default(),
+        options.center,
+        options.radius(),
+        rings,
+    );
+    let period = zoom_loop::find_period(&strip);
+    eprintln!(
+        "{}",
+        lang.translate(&format!(
+            "each cycle zooms in {:.3} times and turns {:.1} degrees",
+            period.factor,
+            period.angle.to_degrees()
+        ))
+    );
+    if period.mismatch > zoom_loop::POOR_MATCH {
+        eprintln!(
+            "{}",
+            lang.translate(&format!(
+                "warning: {} doesn't look self-similar, so the loop will jump",
+                options.center
+            ))
+        );
+    }
+
+    let renderer = Renderer {
+        palette: options.palette,
+        ..Renderer::default()
+    };
+    let frames: Vec<Vec<u8>> = (0..options.frames)
+        .map(|frame| {
+            let (pixels, failed) = zoom_loop::render_frame(
+                options.bounds,
+                options.center,
+                options.radius(),
+                period,
+                frame,
+                options.frames,
+                &renderer,
+                options.threads,
+                None,
+            );
+            report_failures(Stage::Color, &failed, logger, lang);
+            pixels
+        })
+        .collect();
+    animation::write_animation(
+        &options.filename,
+        options.animation,
+        &frames,
+        options.bounds,
+        options.fps,
+    )
+    .unwrap_or_else(|e| {
+        fail(
+            error_format,
+            lang,
+            ErrorKind::Io,
+            &format!(
+                "error writing {} file {}: {}",
+                options.animation.name(),
+                options.filename,
+                e
+            ),
         )
-        .unwrap();
-        std::process::exit(1);
+    });
+}
+
+/// Render the frames of a parameter scan and write them as an animation, for the `scan`
+/// subcommand given `args` after it.
+fn run_scan(args: &[String], error_format: ErrorFormat, lang: Lang) {
+    let options = scan::parse_args(args).unwrap_or_else(|e| {
+        let kind = if args.len() < 7 {
+            ErrorKind::Usage
+        } else {
+            ErrorKind::Parse
+        };
+        fail(error_format, lang, kind, &e)
+    });
+    let logger = log::Logger {
+        format: log::LogFormat::Off,
+    };
+    let frames: Vec<Vec<u8>> = (0..options.frames)
+        .map(|frame| {
+            let (pixels, failed) = options.renderer(frame).render_image(
+                options.bounds,
+                options.upper_left,
+                options.lower_right,
+                options.threads,
+            );
+            report_failures(Stage::Color, &failed, logger, lang);
+            pixels
+        })
+        .collect();
+    animation::write_animation(
+        &options.filename,
+        options.animation,
+        &frames,
+        options.bounds,
+        options.fps,
+    )
+    .unwrap_or_else(|e| {
+        fail(
+            error_format,
+            lang,
+            ErrorKind::Io,
+            &format!(
+                "error writing {} file {}: {}",
+                options.animation.name(),
+                options.filename,
+                e
+            ),
+        )
+    });
+}
+
+/// Render a pyramid of web map tiles, for the `tiles` subcommand given `args` after it.
+fn run_map_tiles(args: &[String], error_format: ErrorFormat, lang: Lang) {
+    let options = map_tiles::parse_args(args).unwrap_or_else(|e| {
+        let kind = if args.len() < 5 {
+            ErrorKind::Usage
+        } else {
+            ErrorKind::Parse
+        };
+        fail(error_format, lang, kind, &e)
+    });
+    let logger = log::Logger {
+        format: log::LogFormat::Off,
+    };
+    let renderer = options.renderer();
+    let bounds = (map_tiles::TILE_SIZE, map_tiles::TILE_SIZE);
+    for zoom in options.zooms.0..=options.zooms.1 {
+        let (across, down) = options.grid(zoom);
+        for x in 0..across {
+            let path = map_tiles::tile_path(&options.directory, zoom, (x, 0));
+            let column = path
+                .parent()
+                .expect("tiles are in a directory of their column");
+            std::fs::create_dir_all(column).unwrap_or_else(|e| {
+                fail(
+                    error_format,
+                    lang,
+                    ErrorKind::Io,
+                    &format!("error creating directory {}: {}", column.display(), e),
+                )
+            });
+            for y in 0..down {
+                let (upper_left, lower_right) = options.corners(zoom, (x, y));
+                let (pixels, failed) =
+                    renderer.render_image(bounds, upper_left, lower_right, options.threads);
+                report_failures(Stage::Color, &failed, logger, lang);
+                let path = map_tiles::tile_path(&options.directory, zoom, (x, y));
+                let filename = path.to_string_lossy();
+                write_image(&filename, &pixels, bounds, None, &[]).unwrap_or_else(|e| {
+                    fail(
+                        error_format,
+                        lang,
+                        ErrorKind::Io,
+                        &format!("error writing PNG file {}: {}", filename, e),
+                    )
+                });
+            }
+        }
+    }
+}
+
+/// Put together the tiles rendered with `--tiles`, for the `stitch` subcommand given `args` after
+/// it.
+fn run_stitch(args: &[String], error_format: ErrorFormat, lang: Lang) {
+    let options = stitch::parse_args(args).unwrap_or_else(|e| {
+        let kind = if args.len() < 2 {
+            ErrorKind::Usage
+        } else {
+            ErrorKind::Parse
+        };
+        fail(error_format, lang, kind, &e)
+    });
+    stitch::stitch(&options.filename, options.tiles)
+        .unwrap_or_else(|e| fail(error_format, lang, ErrorKind::Io, &e));
+}
+
+/// Edit a palette file and preview it, for the `palette` subcommand given `args` after it.
+fn run_palette(args: &[String], error_format: ErrorFormat, lang: Lang) {
+    let options = palette_edit::parse_args(args).unwrap_or_else(|e| {
+        let kind = if args.len() < 3 {
+            ErrorKind::Usage
+        } else {
+            ErrorKind::Parse
+        };
+        fail(error_format, lang, kind, &e)
+    });
+    // Start from a built-in palette by name, or read the file.
+    let builtin = palette::Palette::parse(&options.palette);
+    let mut gradient = match builtin {
+        Some(palette) => palette::Gradient::from(palette),
+        None => {
+            let text = std::fs::read_to_string(&options.palette).unwrap_or_else(|e| {
+                fail(
+                    error_format,
+                    lang,
+                    ErrorKind::Io,
+                    &format!("error reading palette {}: {}", options.palette, e),
+                )
+            });
+            palette::Gradient::parse(&text)
+                .unwrap_or_else(|e| fail(error_format, lang, ErrorKind::Parse, &e))
+        }
+    };
+    for operation in &options.operations {
+        operation.apply(&mut gradient);
+    }
+
+    // Save the changes back to the file they came from, unless asked to put them elsewhere.
+    let destination = match (&options.output, builtin) {
+        (Some(output), _) => Some(output),
+        (None, None) if !options.operations.is_empty() => Some(&options.palette),
+        (None, _) => None,
+    };
+    if let Some(destination) = destination {
+        std::fs::write(destination, gradient.to_text()).unwrap_or_else(|e| {
+            fail(
+                error_format,
+                lang,
+                ErrorKind::Io,
+                &format!("error writing palette file {}: {}", destination, e),
+            )
+        });
+    }
+    let (pixels, bounds) = palette_edit::preview(&gradient);
+    write_image(&options.preview, &pixels, bounds, None, &[]).unwrap_or_else(|e| {
+        fail(
+            error_format,
+            lang,
+            ErrorKind::Io,
+            &format!("error writing PNG file {}: {}", options.preview, e),
+        )
+    });
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    let error_format = ErrorFormat::from_args(&args);
+    let lang = Lang::detect(&args);
+
+    if args.get(1).map(String::as_str) == Some("sonify") {
+        run_sonify(&args[2..], error_format, lang);
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("buddhabrot") {
+        run_buddhabrot(&args[2..], error_format, lang);
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("plan-zoom") {
+        run_plan_zoom(&args[2..], error_format, lang);
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("zoom") {
+        run_zoom(&args[2..], error_format, lang);
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("loop") {
+        run_loop(&args[2..], error_format, lang);
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("scan") {
+        run_scan(&args[2..], error_format, lang);
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("palette") {
+        run_palette(&args[2..], error_format, lang);
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("tiles") {
+        run_map_tiles(&args[2..], error_format, lang);
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("stitch") {
+        run_stitch(&args[2..], error_format, lang);
+        return;
+    }
+
+    // Check that we have the right number of arguments. The corners may be left out when the
+    // options say where to go instead.
+    let corners_given = args.len() >= 5 && !args[3].starts_with("--");
+    let view_given = args.iter().any(|arg| arg == "--goto" || arg == "--center");
+    if args.len() < 3 || !corners_given && !view_given {
+        if error_format == ErrorFormat::Json {
+            fail(
+                error_format,
+                lang,
+                ErrorKind::Usage,
+                "expected FILE PIXELS UPPERLEFT LOWERRIGHT",
+            );
+        }
+        eprint!("{}", lang.usage(&args[0]));
+        std::process::exit(ErrorKind::Usage.exit_code());
     }
 
     // Parse the arguments.
-    let bounds = parse_pair(&args[2], 'x').expect("error parsing image dimensions");
-    let upper_left = parse_complex(&args[3]).expect("error parsing upper left corner point");
-    let lower_right = parse_complex(&args[4]).expect("error parsing lower right corner point");
+    let bounds = parse_pair(&args[2], 'x')
+        .filter(|&(width, height): &(usize, usize)| width > 0 && height > 0)
+        .unwrap_or_else(|| {
+            fail(
+                error_format,
+                lang,
+                ErrorKind::Parse,
+                "error parsing image dimensions",
+            )
+        });
+    let mut options = parse_options(&args[if corners_given { 5 } else { 3 }..])
+        .unwrap_or_else(|e| fail(error_format, lang, ErrorKind::Parse, &e));
+    if let Some((across, down)) = options.tiles {
+        if across > bounds.0 || down > bounds.1 {
+            fail(
+                error_format,
+                lang,
+                ErrorKind::Parse,
+                "--tiles asks for more tiles than the image has pixels",
+            );
+        }
+    }
+    // Keep every digit of the corners or center given, for deep zooms past what f64 can tell
+    // apart.
+    let centered = options
+        .center
+        .map(|center| viewport(bounds, center, options.scale.pixel_size(bounds)));
+    let (upper_left, lower_right) = match (&options.goto, centered) {
+        (Some(annotation), _) => annotation.viewport(bounds),
+        (None, Some((upper_left, lower_right))) => (
+            precision::complex_to_f64(upper_left),
+            precision::complex_to_f64(lower_right),
+        ),
+        (None, None) => (
+            parse_complex(&args[3]).unwrap_or_else(|| {
+                fail(
+                    error_format,
+                    lang,
+                    ErrorKind::Parse,
+                    "error parsing upper left corner point",
+                )
+            }),
+            parse_complex(&args[4]).unwrap_or_else(|| {
+                fail(
+                    error_format,
+                    lang,
+                    ErrorKind::Parse,
+                    "error parsing lower right corner point",
+                )
+            }),
+        ),
+    };
+    if options.renderer.jitter && options.renderer.seed.is_none() {
+        options.renderer.seed = Some(random::clock_seed());
+    }
+    let parse_deep = |s: &str, fallback| {
+        precision::parse_complex(s).unwrap_or_else(|| precision::complex_from_f64(fallback))
+    };
+    let deep = match (&options.goto, centered) {
+        (Some(_), _) => (
+            precision::complex_from_f64(upper_left),
+            precision::complex_from_f64(lower_right),
+        ),
+        (None, Some(corners)) => corners,
+        (None, None) => (
+            parse_deep(&args[3], upper_left),
+            parse_deep(&args[4], lower_right),
+        ),
+    };
+    let (upper_left, lower_right) = match options.pixel_aspect {
+        Some(aspect) => apply_pixel_aspect(bounds, upper_left, lower_right, aspect),
+        None => (upper_left, lower_right),
+    };
+    let deep = match options.pixel_aspect {
+        Some(aspect) => apply_pixel_aspect(bounds, deep.0, deep.1, aspect),
+        None => deep,
+    };
+    // Interval arithmetic checks f64 renders, so a verified render stays in f64.
+    if options.precision == Precision::Auto && options.verify {
+        options.precision = Precision::F64;
+    }
+    if options.precision == Precision::Auto {
+        let pixel_size = ((deep.1.re - deep.0.re) / DoubleDouble::new(bounds.0 as f64))
+            .to_f64()
+            .abs()
+            .min(
+                ((deep.0.im - deep.1.im) / DoubleDouble::new(bounds.1 as f64))
+                    .to_f64()
+                    .abs(),
+            );
+        let magnitude = [upper_left.re, upper_left.im, lower_right.re, lower_right.im]
+            .iter()
+            .fold(0.0, |max: f64, x| max.max(x.abs()));
+        // Perturbation is much faster, where it applies.
+        let perturbs = options.renderer.fractal == Fractal::Mandelbrot
+            && options.renderer.formula == Formula::Mandelbrot
+            && options.renderer.power == Power::default()
+            && options.renderer.interior == Interior::Flat;
+        options.precision = match precision::needs_arb(pixel_size, magnitude) {
+            false => Precision::F64,
+            true if perturbs => Precision::Perturb,
+            true => Precision::Arb,
+        };
+    }
+    let double_double = options.precision != Precision::F64;
+    // The real and imaginary parts of the corners as text, in the format asked for, or with as
+    // many digits as double-double holds when rendering at that precision.
+    let corner_parts = |f64_corner: Complex<f64>, deep_corner: Complex<DoubleDouble>| {
+        if double_double {
+            (deep_corner.re.to_string(), deep_corner.im.to_string())
+        } else {
+            let format = options.coord_format;
+            (format.format(f64_corner.re), format.format(f64_corner.im))
+        }
+    };
+    let (upper_left_parts, lower_right_parts) = (
+        corner_parts(upper_left, deep.0),
+        corner_parts(lower_right, deep.1),
+    );
+    let two = DoubleDouble::new(2.0);
+    let center_parts = corner_parts(
+        (upper_left + lower_right) / 2.0,
+        Complex {
+            re: (deep.0.re + deep.1.re) / two,
+            im: (deep.0.im + deep.1.im) / two,
+        },
+    );
+    let corner_text = |(re, im): &(String, String)| format!("{},{}", re, im);
+    let (upper_left_text, lower_right_text) = (
+        corner_text(&upper_left_parts),
+        corner_text(&lower_right_parts),
+    );
 
-    // Create a buffer of pixels.
-    let mut pixels = vec![0; bounds.0 * bounds.1];
+    // Sort the files to write by what they are written from.
+    let outputs = |format| {
+        options
+            .outputs
+            .iter()
+            .filter(move |&&(f, _)| f == format)
+            .map(|(_, filename)| filename)
+    };
+    let exr_files: Vec<_> = options.exr.iter().chain(outputs(Format::Exr)).collect();
+    let svg_files: Vec<_> = options.svg.iter().chain(outputs(Format::Svg)).collect();
+    let png_files: Vec<_> = (options.hdr.is_none() && options.tiles.is_none())
+        .then_some(&args[1])
+        .into_iter()
+        .chain(outputs(Format::Png))
+        .collect();
+    let tiff_files: Vec<_> = options.tiff.iter().chain(outputs(Format::Tiff)).collect();
+    let pdf_files: Vec<_> = options.pdf.iter().chain(outputs(Format::Pdf)).collect();
+    let raw_files: Vec<_> = outputs(Format::Raw).collect();
+    let csv_files: Vec<_> = outputs(Format::Csv).collect();
+    let parquet_files: Vec<_> = outputs(Format::Parquet).collect();
+    let hdr_files: Vec<_> = options.hdr.map(|_| &args[1]).into_iter().collect();
+    let pair_files: Vec<_> = options
+        .pair
+        .iter()
+        .flat_map(|&(first, second)| [first, second])
+        .map(|channel| (channel_filename(&args[1], channel), channel))
+        .collect();
+    let mut written: Vec<&str> = exr_files
+        .iter()
+        .chain(&svg_files)
+        .chain(&csv_files)
+        .chain(&parquet_files)
+        .chain(&hdr_files)
+        .chain(&png_files)
+        .chain(&raw_files)
+        .chain(&tiff_files)
+        .chain(&pdf_files)
+        .map(|filename| filename.as_str())
+        .collect();
+    written.extend(pair_files.iter().map(|(filename, _)| filename.as_str()));
+    let tile_files: Vec<_> = options
+        .tiles
+        .iter()
+        .flat_map(|&(across, down)| {
+            (0..down).flat_map(move |row| (0..across).map(move |column| (column, row)))
+        })
+        .map(|position| stitch::tile_filename(&args[1], position))
+        .collect();
+    written.extend(tile_files.iter().map(String::as_str));
 
-    // Render the Mandelbrot set into the buffer.
-    let threads = num_cpus::get();
-    let rows_per_band = bounds.1 / threads + 1;
+    // Refuse to start a render that won't fit in memory, rather than being killed halfway.
+    if let Some(limit) = options.max_memory {
+        let needed = estimate_memory(bounds, &options);
+        if needed > limit {
+            let (needed, limit, unit) = memory_sizes(needed, limit);
+            fail(
+                options.error_format,
+                options.lang,
+                ErrorKind::Memory,
+                &format!(
+                    "a {}x{} render needs about {} {}, more than the {} {} allowed by --max-memory",
+                    bounds.0, bounds.1, needed, unit, limit, unit
+                ),
+            );
+        }
+    }
+
+    let threads = options.threads;
+    let limit = options.renderer.limit;
+    let mut timings = stages::Timings::default();
+    let logger = log::Logger {
+        format: options.log_format,
+    };
+    let start = Instant::now();
+    logger.event(
+        "job_started",
+        &[
+            ("file", Field::Str(&args[1])),
+            ("width", Field::Int(bounds.0 as u64)),
+            ("height", Field::Int(bounds.1 as u64)),
+            ("upper_left", Field::Str(&upper_left_text)),
+            ("lower_right", Field::Str(&lower_right_text)),
+            ("precision", Field::Str(options.precision.name())),
+        ],
+    );
+
+    // Collect the iteration data for compositing, vector, HDR and tabular output, if asked to.
+    let data = if !exr_files.is_empty()
+        || !svg_files.is_empty()
+        || !csv_files.is_empty()
+        || !parquet_files.is_empty()
+        || options.hdr.is_some()
+        || options.pair.is_some()
     {
-        let bands: Vec<&mut [u8]> = pixels.chunks_mut(rows_per_band * bounds.0).collect();
-        crossbeam::scope(|spawner| {
-            for (i, band) in bands.into_iter().enumerate() {
-                let top = rows_per_band * i;
-                let height = band.len() / bounds.0;
-                let band_bounds = (bounds.0, height);
-                let band_upper_left = pixel_to_point(bounds, (0, top), upper_left, lower_right);
-                let band_lower_right =
-                    pixel_to_point(bounds, (bounds.0, top + height), upper_left, lower_right);
-                spawner.spawn(move |_| {
-                    render(band, band_bounds, band_upper_left, band_lower_right);
-                });
+        let mut data = vec![layers::PointData::default(); bounds.0 * bounds.1];
+        let bailout = options.renderer.bailout.unwrap_or(DEFAULT_BAILOUT);
+        let iterate_progress = progress::Progress::new(
+            options.progress,
+            Stage::Iterate.name(),
+            options.lang,
+            logger,
+            bounds,
+        );
+        let failed = timings.time(Stage::Iterate, || {
+            render_bands(
+                &mut data,
+                bounds,
+                upper_left,
+                lower_right,
+                threads.get(Stage::Iterate),
+                Some(&iterate_progress),
+                options.focus.clone(),
+                |band, band_bounds, band_upper_left, band_lower_right| {
+                    for (i, point) in band.iter_mut().enumerate() {
+                        let pixel = (i % band_bounds.0, i / band_bounds.0);
+                        let c =
+                            pixel_to_point(band_bounds, pixel, band_upper_left, band_lower_right);
+                        *point = layers::analyze_point(options.renderer.z0, c, limit, bailout);
+                    }
+                },
+            )
+        });
+        report_failures(Stage::Iterate, &failed, logger, options.lang);
+        data
+    } else {
+        Vec::new()
+    };
+    timings
+        .time(Stage::Encode, || {
+            // Trace the boundary half a pixel away from the set, using the distance estimate.
+            let pixel_size = (lower_right.re - upper_left.re) / bounds.0 as f64;
+            let field: Vec<f64> = if svg_files.is_empty() {
+                Vec::new()
+            } else {
+                data.iter()
+                    .map(|d| d.distance as f64 / pixel_size)
+                    .collect()
+            };
+            let mut jobs: Vec<Box<dyn FnOnce() -> Result<(), String> + Send>> = Vec::new();
+            for filename in exr_files {
+                let data = &data;
+                jobs.push(encode_job(logger, "EXR", filename, move || {
+                    layers::write_layers(filename, data, bounds)
+                }));
             }
+            for filename in svg_files {
+                let field = &field;
+                jobs.push(encode_job(logger, "SVG", filename, move || {
+                    svg::write_contours(filename, field, bounds, 0.5)
+                }));
+            }
+            for filename in csv_files {
+                let data = &data;
+                jobs.push(encode_job(logger, "CSV", filename, move || {
+                    let records = records::records(data, bounds, upper_left, lower_right, limit);
+                    records::write_csv(filename, records, options.coord_format)
+                }));
+            }
+            for filename in parquet_files {
+                let data = &data;
+                jobs.push(encode_job(logger, "Parquet", filename, move || {
+                    let records = records::records(data, bounds, upper_left, lower_right, limit);
+                    records::write_parquet(filename, records)
+                }));
+            }
+            let bailout = options.renderer.bailout.unwrap_or(DEFAULT_BAILOUT);
+            for (filename, channel) in &pair_files {
+                let data = &data;
+                jobs.push(encode_job(logger, "PNG", filename, move || {
+                    let scales = (limit, pixel_size, bailout);
+                    layers::write_channel_png(filename, data, bounds, *channel, scales)
+                }));
+            }
+            if let Some(transfer) = options.hdr {
+                let (filename, data) = (&args[1], &data);
+                jobs.push(encode_job(logger, "PNG", filename, move || {
+                    hdr::write_hdr_png(filename, data, bounds, limit, transfer)
+                }));
+            }
+            stages::run_parallel(jobs, threads.get(Stage::Encode))
         })
-        .expect("Failed to render");
+        .unwrap_or_else(|e| fail(options.error_format, options.lang, ErrorKind::Io, &e));
+
+    if !png_files.is_empty()
+        || !tiff_files.is_empty()
+        || !pdf_files.is_empty()
+        || !raw_files.is_empty()
+        || !tile_files.is_empty()
+    {
+        let channels = options.renderer.channels();
+        let text: Vec<_> = options
+            .renderer
+            .seed
+            .iter()
+            .map(|seed| ("Seed", seed.to_string()))
+            .collect();
+        let card_fields = options.card.then(|| {
+            let mut fields = vec![
+                format!("Center {}", corner_text(&center_parts)),
+                format!(
+                    "Zoom {}",
+                    card::format_zoom(
+                        annotations::FULL_WIDTH / (lower_right.re - upper_left.re).abs()
+                    )
+                ),
+                format!("Iterations {}", limit),
+            ];
+            if let Some(palette) = options.renderer.palette {
+                fields.push(format!("Palette {}", palette.name()));
+            }
+            fields.push(card::today());
+            fields
+        });
+        // Before the files are created, so that a checkpoint of another render is left alone.
+        let resume = options.resume.as_deref().map(|filename| {
+            let corners = (upper_left_text.as_str(), lower_right_text.as_str());
+            let parameters = with_parameters(bounds, corners, &options, manifest::parameters_json);
+            let checkpoint = checkpoint::Checkpoint::open(
+                filename,
+                &parameters,
+                (bounds.0 * channels, bounds.1),
+            )
+            .unwrap_or_else(|e| {
+                fail(
+                    options.error_format,
+                    options.lang,
+                    ErrorKind::Io,
+                    &format!("error reading checkpoint file {}: {}", filename, e),
+                )
+            });
+            logger.event(
+                "render_resumed",
+                &[
+                    ("file", Field::Str(filename)),
+                    ("rows", Field::Int(checkpoint.rows() as u64)),
+                ],
+            );
+            (filename, checkpoint)
+        });
+        let streams = (streams(&options) && options.tiles.is_none()).then(|| {
+            let mut card = Vec::new();
+            if let Some(fields) = &card_fields {
+                card::append(&mut card, channels, (bounds.0, 0), fields);
+            }
+            let icc = options.icc.as_deref();
+            Streams::create(&png_files, &raw_files, (bounds, channels), card, icc, &text)
+                .unwrap_or_else(|e| fail(options.error_format, options.lang, ErrorKind::Io, &e))
+        });
+        let streamed = streams.is_some() || options.tiles.is_some();
+        let Rendered {
+            mut pixels,
+            range,
+            refined,
+        } = if options.precision == Precision::Perturb {
+            // Place the reference orbits, and render the offsets of the points from the first.
+            let origin = Complex {
+                re: (deep.0.re + deep.1.re) / two,
+                im: (deep.0.im + deep.1.im) / two,
+            };
+            let offsets = (
+                precision::complex_to_f64(deep.0 - origin),
+                precision::complex_to_f64(deep.1 - origin),
+            );
+            let bailout = options.renderer.bailout.unwrap_or(DEFAULT_BAILOUT);
+            let z0 = options.renderer.z0;
+            let perturbation = timings.time(Stage::Iterate, || {
+                perturbation::Perturbation::for_view(
+                    origin, bounds, offsets.0, offsets.1, z0, limit, bailout,
+                )
+            });
+            logger.event(
+                "references_placed",
+                &[("references", Field::Int(perturbation.references() as u64))],
+            );
+            options.renderer.perturbation = Some(std::sync::Arc::new(perturbation));
+            render_view(
+                &args[1],
+                bounds,
+                offsets,
+                &options,
+                streams,
+                resume,
+                &mut timings,
+                logger,
+            )
+        } else if double_double {
+            render_view(
+                &args[1],
+                bounds,
+                deep,
+                &options,
+                streams,
+                resume,
+                &mut timings,
+                logger,
+            )
+        } else {
+            let corners = (upper_left, lower_right);
+            render_view(
+                &args[1],
+                bounds,
+                corners,
+                &options,
+                streams,
+                resume,
+                &mut timings,
+                logger,
+            )
+        };
+        options.renderer.range = range;
+        if let (Some(filename), Some(refined)) = (&options.refined, refined) {
+            let mask: Vec<u8> = refined.iter().map(|&edge| edge as u8 * 255).collect();
+            write_image(filename, &mask, bounds, None, &[]).unwrap_or_else(|e| {
+                fail(
+                    options.error_format,
+                    options.lang,
+                    ErrorKind::Io,
+                    &format!("error writing PNG file {}: {}", filename, e),
+                )
+            });
+        }
+
+        // Unless it was already written as it was rendered.
+        if !streamed {
+            if options.markers {
+                annotations::draw_markers(
+                    &mut pixels,
+                    channels,
+                    bounds,
+                    upper_left,
+                    lower_right,
+                    &options.annotations,
+                );
+            }
+
+            let bounds = match &card_fields {
+                Some(fields) => card::append(&mut pixels, channels, bounds, fields),
+                None => bounds,
+            };
+
+            // Write the buffer to every file that asked for it: as is, separated for print, or laid
+            // out on a page.
+            let mut layout = options.page.clone();
+            if options.caption {
+                let complex = |(re, im): &(String, String)| match im.strip_prefix('-') {
+                    Some(magnitude) => format!("{}-{}i", re, magnitude),
+                    None => format!("{}+{}i", re, im),
+                };
+                layout.caption = Some(format!(
+                    "Upper left {}, lower right {}",
+                    complex(&upper_left_parts),
+                    complex(&lower_right_parts)
+                ));
+            }
+            let (pixels, options, layout, text) = (&pixels, &options, &layout, &text);
+            let mut jobs: Vec<Box<dyn FnOnce() -> Result<(), String> + Send>> = Vec::new();
+            for filename in png_files {
+                jobs.push(encode_job(logger, "PNG", filename, move || {
+                    write_image(filename, pixels, bounds, options.icc.as_deref(), text)
+                }));
+            }
+            for filename in raw_files {
+                jobs.push(encode_job(logger, "raw", filename, move || {
+                    std::fs::write(filename, pixels)
+                }));
+            }
+            for filename in tiff_files {
+                jobs.push(encode_job(logger, "TIFF", filename, move || {
+                    cmyk::write_tiff(
+                        filename,
+                        pixels,
+                        bounds,
+                        options.cmyk,
+                        options.icc.as_deref(),
+                    )
+                }));
+            }
+            for filename in pdf_files {
+                jobs.push(encode_job(logger, "PDF", filename, move || {
+                    pdf::write_pdf(filename, pixels, bounds, layout)
+                }));
+            }
+            timings
+                .time(Stage::Encode, || {
+                    stages::run_parallel(jobs, threads.get(Stage::Encode))
+                })
+                .unwrap_or_else(|e| fail(options.error_format, options.lang, ErrorKind::Io, &e));
+        }
+        // The image is all written, so there is nothing left to resume.
+        if let Some(filename) = &options.resume {
+            std::fs::remove_file(filename).unwrap_or_else(|e| {
+                fail(
+                    options.error_format,
+                    options.lang,
+                    ErrorKind::Io,
+                    &format!("error removing checkpoint file {}: {}", filename, e),
+                )
+            });
+        }
     }
 
-    // Write the buffer as a PNG image.
-    write_image(&args[1], &pixels, bounds).expect("error writing PNG file");
+    // Re-check a sample of the pixels along the boundary, where rounding errors matter most.
+    if options.verify {
+        let (checked, uncertain) = timings.time(Stage::Iterate, || {
+            verify::uncertain_pixels(
+                bounds,
+                upper_left,
+                lower_right,
+                &options.renderer,
+                threads.get(Stage::Iterate),
+                verify::SAMPLES,
+            )
+        });
+        for &(x, y) in &uncertain {
+            logger.event(
+                "pixel_uncertain",
+                &[("x", Field::Int(x as u64)), ("y", Field::Int(y as u64))],
+            );
+            eprintln!(
+                "{}",
+                options.lang.translate(&format!(
+                    "warning: the escape time of pixel {},{} is uncertain",
+                    x, y
+                ))
+            );
+        }
+        logger.event(
+            "verify_finished",
+            &[
+                ("checked", Field::Int(checked as u64)),
+                ("uncertain", Field::Int(uncertain.len() as u64)),
+            ],
+        );
+        eprintln!(
+            "{}",
+            options.lang.translate(&format!(
+                "verified {} boundary pixels, {} uncertain",
+                checked,
+                uncertain.len()
+            ))
+        );
+    }
+
+    if let Some(filename) = &options.manifest {
+        let corners = (upper_left_text.as_str(), lower_right_text.as_str());
+        write_manifest(filename, &args, bounds, corners, &options, &written).unwrap_or_else(|e| {
+            fail(
+                options.error_format,
+                options.lang,
+                ErrorKind::Io,
+                &format!("error writing manifest file {}: {}", filename, e),
+            )
+        });
+    }
+
+    if options.timings {
+        eprint!("{}", timings.report());
+    }
+    logger.event(
+        "job_finished",
+        &[("ms", Field::Float(start.elapsed().as_secs_f64() * 1e3))],
+    );
 }
@@ -0,0 +1,283 @@
+use crate::errors::json_string;
+use crate::log::Field;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+
+/// Round constants of SHA-256: the fractional parts of the cube roots of the first 64 primes.
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Bytes read from a file at a time to hash it.
+const READ_CHUNK: usize = 1 << 16;
+
+/// A SHA-256 digest computed incrementally, from data handed to it in pieces of any size.
+pub struct Sha256 {
+    h: [u32; 8],
+    /// The start of a block not yet compressed, `buffered` bytes long.
+    block: [u8; 64],
+    buffered: usize,
+    /// Bytes hashed so far.
+    length: u64,
+}
+
+impl Sha256 {
+    /// Hash `data` after whatever came before it.
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.length += data.len() as u64;
+        while !data.is_empty() {
+            let taken = data.len().min(64 - self.buffered);
+            self.block[self.buffered..self.buffered + taken].copy_from_slice(&data[..taken]);
+            self.buffered += taken;
+            data = &data[taken..];
+            if self.buffered == 64 {
+                compress(&mut self.h, &self.block);
+                self.buffered = 0;
+            }
+        }
+    }
+
+    /// The digest of all the data hashed.
+    pub fn finish(mut self) -> [u8; 32] {
+        // Pad with a single one bit, zeros up to 8 bytes short of a whole block, and the length
+        // in bits.
+        let bits = self.length * 8;
+        self.update(&[0x80]);
+        while self.buffered != 56 {
+            self.update(&[0]);
+        }
+        self.update(&bits.to_be_bytes());
+
+        let mut digest = [0; 32];
+        for (bytes, word) in digest.chunks_mut(4).zip(self.h) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Sha256 {
+            h: [
+                0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+                0x5be0cd19,
+            ],
+            block: [0; 64],
+            buffered: 0,
+            length: 0,
+        }
+    }
+}
+
+/// Run the SHA-256 compression function on a 64-byte `block`, updating the hash state `h`.
+fn compress(h: &mut [u32; 8], block: &[u8; 64]) {
+    let mut w = [0u32; 64];
+    for (i, word) in block.chunks(4).enumerate() {
+        w[i] = u32::from_be_bytes(word.try_into().unwrap());
+    }
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = *h;
+    for i in 0..64 {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = hh
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(K[i])
+            .wrapping_add(w[i]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+        hh = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+    for (x, y) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+        *x = x.wrapping_add(y);
+    }
+}
+
+/// Compute the SHA-256 digest of `data`.
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::default();
+    hasher.update(data);
+    hasher.finish()
+}
+
+/// Compute the SHA-256 digest of the file `filename`, reading it a chunk at a time.
+pub fn sha256_file(filename: &str) -> Result<[u8; 32], std::io::Error> {
+    let mut file = BufReader::new(File::open(filename)?);
+    let mut hasher = Sha256::default();
+    let mut chunk = vec![0; READ_CHUNK];
+    loop {
+        match file.read(&mut chunk) {
+            Ok(0) => return Ok(hasher.finish()),
+            Ok(n) => hasher.update(&chunk[..n]),
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Write `bytes` as lowercase hexadecimal.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn test_sha256() {
+    assert_eq!(
+        hex(&sha256(b"")),
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+    assert_eq!(
+        hex(&sha256(b"abc")),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    // Two blocks, as the padding doesn't fit after 56 bytes.
+    assert_eq!(
+        hex(&sha256(
+            b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+        )),
+        "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+    );
+    // The same data, handed over in pieces that straddle the blocks.
+    let mut hasher = Sha256::default();
+    for piece in [
+        &b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomno"[..],
+        b"pnopq",
+    ] {
+        hasher.update(piece);
+    }
+    assert_eq!(
+        hex(&hasher.finish()),
+        "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+    );
+    let million = vec![b'a'; 1_000_000];
+    let mut hasher = Sha256::default();
+    for piece in million.chunks(100) {
+        hasher.update(piece);
+    }
+    assert_eq!(
+        hex(&hasher.finish()),
+        "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+    );
+
+    let filename = std::env::temp_dir().join("rust-experiments-test-sha256");
+    let filename = filename.to_str().unwrap();
+    std::fs::write(filename, b"abc").unwrap();
+    assert_eq!(sha256_file(filename).unwrap(), sha256(b"abc"));
+    std::fs::remove_file(filename).unwrap();
+}
+
+/// Commit the tool was built from, as found by the build script, if it was built from a git
+/// checkout.
+pub const GIT_HASH: Option<&str> = option_env!("GIT_HASH");
+
+/// Backend the image was computed on.
+pub const BACKEND: &str = "cpu";
+
+/// Write the `parameters` a render resolved its command line to as a JSON object, indented to
+/// sit inside the manifest.
+pub fn parameters_json(parameters: &[(&str, Field)]) -> String {
+    let value = |field: &Field| match field {
+        Field::Str(s) => json_string(s),
+        Field::Int(n) => n.to_string(),
+        Field::Float(x) if x.is_finite() => x.to_string(),
+        Field::Float(_) => "null".to_string(),
+    };
+    let parameters: Vec<_> = parameters
+        .iter()
+        .map(|(key, field)| format!("    {}: {}", json_string(key), value(field)))
+        .collect();
+    format!("{{\n{}\n  }}", parameters.join(",\n"))
+}
+
+/// Describe how a render was made as a JSON object: the tool that made it, the command line it
+/// was given, the `parameters` it resolved them to, and the `outputs` it wrote, as file names
+/// and SHA-256 digests.
+pub fn manifest(
+    args: &[String],
+    parameters: &[(&str, Field)],
+    outputs: &[(&str, [u8; 32])],
+) -> String {
+    let mut json = String::from("{\n");
+    json += &format!(
+        "  \"version\": {},\n",
+        json_string(env!("CARGO_PKG_VERSION"))
+    );
+    json += &format!(
+        "  \"git\": {},\n",
+        GIT_HASH.map_or("null".to_string(), json_string)
+    );
+    json += &format!("  \"backend\": {},\n", json_string(BACKEND));
+    let args: Vec<_> = args.iter().map(|arg| json_string(arg)).collect();
+    json += &format!("  \"args\": [{}],\n", args.join(", "));
+    json += &format!("  \"parameters\": {},\n", parameters_json(parameters));
+    let outputs: Vec<_> = outputs
+        .iter()
+        .map(|(filename, digest)| {
+            format!(
+                "    {{\"file\": {}, \"sha256\": \"{}\"}}",
+                json_string(filename),
+                hex(digest)
+            )
+        })
+        .collect();
+    json += &format!("  \"outputs\": [\n{}\n  ]\n}}\n", outputs.join(",\n"));
+    json
+}
+
+#[test]
+fn test_manifest() {
+    let json = manifest(
+        &["mandelbrot".to_string(), "a.png".to_string()],
+        &[
+            ("seed", Field::Int(42)),
+            ("fractal", Field::Str("mandelbrot")),
+        ],
+        &[("a.png", sha256(b"abc"))],
+    );
+    assert!(json.contains("  \"args\": [\"mandelbrot\", \"a.png\"],\n"));
+    assert!(json.contains("    \"seed\": 42,\n    \"fractal\": \"mandelbrot\"\n"));
+    assert!(json.contains(
+        "{\"file\": \"a.png\", \"sha256\": \"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad\"}"
+    ));
+    assert!(json.contains("\"backend\": \"cpu\""));
+}
+
+/// Write the manifest of a render to `filename`, reading back each of the `outputs` to hash it.
+pub fn write_manifest(
+    filename: &str,
+    args: &[String],
+    parameters: &[(&str, Field)],
+    outputs: &[&str],
+) -> Result<(), std::io::Error> {
+    let outputs = outputs
+        .iter()
+        .map(|&output| Ok((output, sha256_file(output)?)))
+        .collect::<Result<Vec<_>, std::io::Error>>()?;
+    let mut file = BufWriter::new(File::create(filename)?);
+    file.write_all(manifest(args, parameters, &outputs).as_bytes())?;
+    file.flush()
+}
@@ -0,0 +1,210 @@
+use crate::{palette, Fractal, Renderer, DEFAULT_LIMIT};
+use num::Complex;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Side of every tile, in pixels, as web maps expect.
+pub const TILE_SIZE: usize = 256;
+
+/// Deepest zoom level allowed, where the region is 2^30 tiles across, well past what `f64` can
+/// tell apart anyway.
+const MAX_ZOOM: u32 = 30;
+
+/// What pyramid of tiles to render and where to write it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MapTilesOptions {
+    /// Directory to write the tiles to, as `DIRECTORY/z/x/y.png`.
+    pub directory: String,
+    /// Corners of the region the map covers.
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+    /// Shallowest and deepest zoom level to render, both included.
+    pub zooms: (u32, u32),
+    /// Most iterations to wait for an orbit to escape.
+    pub limit: u32,
+    /// Render the Julia set of this `c`, in the dynamical plane, instead of the Mandelbrot set.
+    pub julia: Option<Complex<f64>>,
+    pub palette: Option<palette::Palette>,
+    pub threads: usize,
+}
+
+impl MapTilesOptions {
+    /// What to render in every tile.
+    pub fn renderer(&self) -> Renderer {
+        Renderer {
+            fractal: match self.julia {
+                Some(c) => Fractal::Julia(c),
+                None => Fractal::Mandelbrot,
+            },
+            palette: self.palette,
+            limit: self.limit,
+            ..Renderer::default()
+        }
+    }
+
+    /// The square zoom level 0 shows in a single tile: as wide as the longer side of the region,
+    /// with the upper left corner of the region as its own. Returns the corner and the signed
+    /// side across and down, so that a region given the other way round is mirrored as asked.
+    fn world(&self) -> (Complex<f64>, Complex<f64>) {
+        let span = self.lower_right - self.upper_left;
+        let side = span.re.abs().max(span.im.abs());
+        let sign = |x: f64| if x < 0.0 { -1.0 } else { 1.0 };
+        (
+            self.upper_left,
+            Complex {
+                re: side * sign(span.re),
+                im: side * sign(span.im),
+            },
+        )
+    }
+
+    /// Number of tiles across and down that zoom level `zoom` needs to cover the region.
+    pub fn grid(&self, zoom: u32) -> (usize, usize) {
+        let (_, side) = self.world();
+        let span = self.lower_right - self.upper_left;
+        let tiles = (1u64 << zoom) as f64;
+        let count = |length: f64, side: f64| ((length / side * tiles).ceil() as usize).max(1);
+        (count(span.re, side.re), count(span.im, side.im))
+    }
+
+    /// The upper left and lower right corners of the tile in column `x` and row `y` of zoom level
+    /// `zoom`, counted from 0 at the upper left.
+    pub fn corners(&self, zoom: u32, (x, y): (usize, usize)) -> (Complex<f64>, Complex<f64>) {
+        let (origin, side) = self.world();
+        let step = side / (1u64 << zoom) as f64;
+        let corner = |x: usize, y: usize| Complex {
+            re: origin.re + x as f64 * step.re,
+            im: origin.im + y as f64 * step.im,
+        };
+        (corner(x, y), corner(x + 1, y + 1))
+    }
+}
+
+#[test]
+fn test_grid() {
+    let options = MapTilesOptions {
+        directory: String::new(),
+        upper_left: Complex { re: -2.0, im: 1.0 },
+        lower_right: Complex { re: 2.0, im: -1.0 },
+        zooms: (0, 2),
+        limit: DEFAULT_LIMIT,
+        julia: None,
+        palette: None,
+        threads: 1,
+    };
+    // The region is half as tall as it is wide, so it fills the top half of the tiles.
+    assert_eq!(options.grid(0), (1, 1));
+    assert_eq!(options.grid(1), (2, 1));
+    assert_eq!(options.grid(2), (4, 2));
+    let (upper_left, lower_right) = options.corners(0, (0, 0));
+    assert_eq!(upper_left, Complex { re: -2.0, im: 1.0 });
+    assert_eq!(lower_right, Complex { re: 2.0, im: -3.0 });
+    let (upper_left, lower_right) = options.corners(2, (3, 1));
+    assert_eq!(upper_left, Complex { re: 1.0, im: 0.0 });
+    assert_eq!(lower_right, Complex { re: 2.0, im: -1.0 });
+}
+
+/// The file the tile in column `x` and row `y` of zoom level `zoom` is written to, in the
+/// `z/x/y` layout web maps fetch tiles by.
+pub fn tile_path(directory: &str, zoom: u32, (x, y): (usize, usize)) -> PathBuf {
+    Path::new(directory)
+        .join(zoom.to_string())
+        .join(x.to_string())
+        .join(format!("{}.png", y))
+}
+
+#[test]
+fn test_tile_path() {
+    assert_eq!(
+        tile_path("map", 3, (5, 2)),
+        Path::new("map").join("3").join("5").join("2.png")
+    );
+}
+
+/// Parse the arguments following `tiles`: the directory to write to, the corners of the region,
+/// the zoom levels to go from and to, and options.
+pub fn parse_args(args: &[String]) -> Result<MapTilesOptions, String> {
+    let (directory, upper_left, lower_right, from, to) = match args {
+        [directory, upper_left, lower_right, from, to, ..] if !to.starts_with("--") => {
+            (directory, upper_left, lower_right, from, to)
+        }
+        _ => return Err("expected DIRECTORY UPPERLEFT LOWERRIGHT FROM TO after tiles".to_string()),
+    };
+    let zoom = |s: &str| {
+        u32::from_str(s)
+            .ok()
+            .filter(|&zoom| zoom <= MAX_ZOOM)
+            .ok_or("error parsing zoom levels")
+    };
+    let zooms = (zoom(from)?, zoom(to)?);
+    if zooms.0 > zooms.1 {
+        return Err("error parsing zoom levels".to_string());
+    }
+    let mut options = MapTilesOptions {
+        directory: directory.clone(),
+        upper_left: crate::parse_complex(upper_left).ok_or("error parsing upper left corner")?,
+        lower_right: crate::parse_complex(lower_right).ok_or("error parsing lower right corner")?,
+        zooms,
+        limit: DEFAULT_LIMIT,
+        julia: None,
+        palette: None,
+        threads: num_cpus::get(),
+    };
+    let span = options.lower_right - options.upper_left;
+    if span.re == 0.0 || span.im == 0.0 {
+        return Err("the corners of the region have to differ in both coordinates".to_string());
+    }
+
+    let mut args = args[5..].iter();
+    while let Some(name) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {}", name))?;
+        match name.as_str() {
+            "--iterations" => {
+                options.limit = u32::from_str(value)
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or("error parsing --iterations")?
+            }
+            "--julia" => {
+                options.julia = Some(crate::parse_complex(value).ok_or("error parsing --julia")?)
+            }
+            "--palette" => {
+                options.palette = Some(
+                    palette::Palette::parse(value)
+                        .ok_or_else(|| format!("unknown palette {}", value))?,
+                )
+            }
+            "--threads" => {
+                options.threads = usize::from_str(value)
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or("error parsing --threads")?
+            }
+            // Read by `main` before the subcommand runs.
+            "--error-format" | "--lang" => {}
+            _ => return Err(format!("unknown option {}", name)),
+        }
+    }
+    Ok(options)
+}
+
+#[test]
+fn test_parse_args() {
+    let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+    let options = parse_args(&args(
+        "map -2,1.5 1,-1.5 0 4 --iterations 500 --palette fire",
+    ))
+    .unwrap();
+    assert_eq!(options.directory, "map");
+    assert_eq!(options.zooms, (0, 4));
+    assert_eq!(options.limit, 500);
+    assert_eq!(options.renderer().limit, 500);
+    assert!(options.palette.is_some());
+    assert!(parse_args(&args("map -2,1.5 1,-1.5 0")).is_err());
+    assert!(parse_args(&args("map -2,1.5 1,-1.5 4 2")).is_err());
+    assert!(parse_args(&args("map -2,1.5 1,-1.5 0 31")).is_err());
+    assert!(parse_args(&args("map -2,1.5 1,1.5 0 4")).is_err());
+    assert!(parse_args(&args("map -2,1.5 1,-1.5 0 4 --frames 2")).is_err());
+}
@@ -0,0 +1,237 @@
+use crate::color;
+use std::str::FromStr;
+
+/// Built-in palettes that gray levels can be mapped through to color the image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Palette {
+    Grayscale,
+    /// Black through red and orange to yellow and white.
+    Fire,
+    /// Deep navy through blue and teal to pale cyan.
+    Ocean,
+    /// Once around the color wheel, from red back to magenta.
+    Rainbow,
+}
+
+impl Palette {
+    pub fn parse(s: &str) -> Option<Palette> {
+        match s {
+            "grayscale" => Some(Palette::Grayscale),
+            "fire" => Some(Palette::Fire),
+            "ocean" => Some(Palette::Ocean),
+            "rainbow" => Some(Palette::Rainbow),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Palette::Grayscale => "grayscale",
+            Palette::Fire => "fire",
+            Palette::Ocean => "ocean",
+            Palette::Rainbow => "rainbow",
+        }
+    }
+
+    /// Colors spread evenly from gray level 0 to 255, to interpolate between.
+    fn stops(self) -> &'static [[u8; 3]] {
+        match self {
+            Palette::Grayscale => &[[0, 0, 0], [255, 255, 255]],
+            Palette::Fire => &[
+                [0, 0, 0],
+                [128, 0, 0],
+                [230, 80, 0],
+                [255, 200, 40],
+                [255, 255, 255],
+            ],
+            Palette::Ocean => &[
+                [0, 7, 30],
+                [0, 45, 120],
+                [0, 130, 170],
+                [90, 210, 210],
+                [230, 255, 255],
+            ],
+            Palette::Rainbow => &[
+                [255, 0, 0],
+                [255, 255, 0],
+                [0, 255, 0],
+                [0, 255, 255],
+                [0, 0, 255],
+                [255, 0, 255],
+            ],
+        }
+    }
+
+    /// Map the gray level `level` to a color.
+    pub fn color(self, level: u8) -> [u8; 3] {
+        interpolate(self.stops(), level as f64 / 255.0)
+    }
+}
+
+/// The color a fraction `t` of the way along `stops`, spread evenly from 0 to 1, blending the two
+/// stops on either side.
+fn interpolate(stops: &[[u8; 3]], t: f64) -> [u8; 3] {
+    let position = t * (stops.len() - 1) as f64;
+    let i = (position as usize).min(stops.len() - 2);
+    let t = position - i as f64;
+    let (from, to) = (stops[i], stops[i + 1]);
+    [0, 1, 2].map(|c| (from[c] as f64 + (to[c] as f64 - from[c] as f64) * t).round() as u8)
+}
+
+#[test]
+fn test_color() {
+    assert_eq!(Palette::Grayscale.color(0), [0, 0, 0]);
+    assert_eq!(Palette::Grayscale.color(77), [77, 77, 77]);
+    assert_eq!(Palette::Fire.color(255), [255, 255, 255]);
+    assert_eq!(Palette::Rainbow.color(0), [255, 0, 0]);
+    assert_eq!(Palette::Rainbow.color(51), [255, 255, 0]);
+    assert_eq!(Palette::Rainbow.color(255), [255, 0, 255]);
+    assert_eq!(Palette::parse("ocean"), Some(Palette::Ocean));
+    assert_eq!(Palette::parse(Palette::Fire.name()), Some(Palette::Fire));
+    assert_eq!(Palette::parse("sunset"), None);
+}
+
+/// How the values of colors are encoded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorSpace {
+    /// Gamma encoded, as images store them.
+    Srgb,
+    /// Proportional to the light, as blending and filtering need them.
+    Linear,
+}
+
+impl ColorSpace {
+    pub fn parse(s: &str) -> Option<ColorSpace> {
+        match s {
+            "srgb" => Some(ColorSpace::Srgb),
+            "linear" => Some(ColorSpace::Linear),
+            _ => None,
+        }
+    }
+}
+
+/// A palette of any colors, spread evenly from gray level 0 to 255 like those of the built-in
+/// palettes, kept in a palette file.
+///
+/// Palette files list the colors as `R,G,B` from 0 to 255, one per line. Blank lines and lines
+/// starting with `#` are skipped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gradient {
+    /// Two colors or more.
+    pub stops: Vec<[u8; 3]>,
+}
+
+impl Gradient {
+    /// Parse the contents of a palette file.
+    pub fn parse(text: &str) -> Result<Gradient, String> {
+        let mut stops = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let channels: Vec<_> = line.split(',').map(|c| u8::from_str(c.trim())).collect();
+            match channels[..] {
+                [Ok(r), Ok(g), Ok(b)] => stops.push([r, g, b]),
+                _ => return Err(format!("error parsing palette on line {}", number + 1)),
+            }
+        }
+        if stops.len() < 2 {
+            return Err("a palette needs at least two colors".to_string());
+        }
+        Ok(Gradient { stops })
+    }
+
+    /// The contents of a palette file holding this palette.
+    pub fn to_text(&self) -> String {
+        self.stops
+            .iter()
+            .map(|[r, g, b]| format!("{},{},{}\n", r, g, b))
+            .collect()
+    }
+
+    /// Map the gray level `level` to a color, as `Palette::color` does.
+    pub fn color(&self, level: u8) -> [u8; 3] {
+        interpolate(&self.stops, level as f64 / 255.0)
+    }
+
+    /// Run the colors the other way, from gray level 255 down to 0.
+    pub fn reverse(&mut self) {
+        self.stops.reverse();
+    }
+
+    /// Move each color `steps` stops down toward gray level 0, those falling off the bottom
+    /// coming back in at the top; negative steps move them up. Suits palettes that come back to
+    /// where they started, like the rainbow.
+    pub fn rotate(&mut self, steps: i64) {
+        let steps = steps.rem_euclid(self.stops.len() as i64) as usize;
+        self.stops.rotate_left(steps);
+    }
+
+    /// Replace the colors with `count` colors, at least two, taken evenly along the palette.
+    pub fn resample(&mut self, count: usize) {
+        self.stops = (0..count)
+            .map(|i| interpolate(&self.stops, i as f64 / (count - 1) as f64))
+            .collect();
+    }
+
+    /// Re-encode the colors, taken to be in the other color space, in `to`.
+    pub fn convert(&mut self, to: ColorSpace) {
+        for stop in &mut self.stops {
+            *stop = stop.map(|value| match to {
+                ColorSpace::Linear => (color::srgb_to_linear(value) * 255.0).round() as u8,
+                ColorSpace::Srgb => color::linear_to_srgb(value as f64 / 255.0),
+            });
+        }
+    }
+}
+
+impl From<Palette> for Gradient {
+    fn from(palette: Palette) -> Gradient {
+        Gradient {
+            stops: palette.stops().to_vec(),
+        }
+    }
+}
+
+#[test]
+fn test_gradient() {
+    let text = "# warm\n0,0,0\n\n255, 128, 0\n255,255,255\n";
+    let mut gradient = Gradient::parse(text).unwrap();
+    assert_eq!(gradient.stops, [[0, 0, 0], [255, 128, 0], [255, 255, 255]]);
+    assert_eq!(gradient.color(0), [0, 0, 0]);
+    assert_eq!(gradient.color(255), [255, 255, 255]);
+    assert_eq!(Gradient::parse(&gradient.to_text()), Ok(gradient.clone()));
+    assert!(Gradient::parse("0,0,0\n256,0,0\n").is_err());
+    assert!(Gradient::parse("0,0\n255,255,255\n").is_err());
+    assert!(Gradient::parse("0,0,0\n").is_err());
+
+    // The built-in palettes color the same as files with their colors.
+    let rainbow = Gradient::from(Palette::Rainbow);
+    for level in 0..=255 {
+        assert_eq!(rainbow.color(level), Palette::Rainbow.color(level));
+    }
+
+    gradient.reverse();
+    assert_eq!(gradient.stops[0], [255, 255, 255]);
+    gradient.rotate(1);
+    assert_eq!(gradient.stops, [[255, 128, 0], [0, 0, 0], [255, 255, 255]]);
+    gradient.rotate(-4);
+    assert_eq!(gradient.stops, [[255, 255, 255], [255, 128, 0], [0, 0, 0]]);
+    gradient.resample(5);
+    assert_eq!(
+        gradient.stops,
+        [
+            [255, 255, 255],
+            [255, 192, 128],
+            [255, 128, 0],
+            [128, 64, 0],
+            [0, 0, 0]
+        ]
+    );
+    gradient.convert(ColorSpace::Linear);
+    assert_eq!(gradient.stops[1], [255, 134, 55]);
+    gradient.convert(ColorSpace::Srgb);
+    assert_eq!(gradient.stops[0], [255, 255, 255]);
+    assert_eq!(gradient.stops[4], [0, 0, 0]);
+}
@@ -0,0 +1,181 @@
+use crate::palette::{ColorSpace, Gradient};
+use crate::{histogram_levels, pixel_to_point, Renderer};
+use num::Complex;
+use std::str::FromStr;
+
+/// Width of the preview, and dimensions of the gradient strip across its top, in pixels.
+const STRIP: (usize, usize) = (256, 32);
+
+/// Height of the test fractal below the strip, in pixels.
+const FRACTAL_HEIGHT: usize = 192;
+
+/// A change to a palette.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operation {
+    Reverse,
+    Rotate(i64),
+    Resample(usize),
+    Convert(ColorSpace),
+}
+
+impl Operation {
+    pub fn apply(self, gradient: &mut Gradient) {
+        match self {
+            Operation::Reverse => gradient.reverse(),
+            Operation::Rotate(steps) => gradient.rotate(steps),
+            Operation::Resample(count) => gradient.resample(count),
+            Operation::Convert(to) => gradient.convert(to),
+        }
+    }
+}
+
+/// Which palette to edit, how, and where to write the results.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EditOptions {
+    /// The palette file to edit, or the name of a built-in palette to start from.
+    pub palette: String,
+    /// PNG file to write the preview of the edited palette to.
+    pub preview: String,
+    /// Changes to make, in the order given.
+    pub operations: Vec<Operation>,
+    /// File to write the edited palette to, instead of back to the palette file.
+    pub output: Option<String>,
+}
+
+/// Parse the arguments following `palette`: `edit`, the palette and the preview file, and
+/// options.
+pub fn parse_args(args: &[String]) -> Result<EditOptions, String> {
+    let (palette, preview) = match args {
+        [edit, palette, preview, ..]
+            if edit == "edit" && !palette.starts_with("--") && !preview.starts_with("--") =>
+        {
+            (palette, preview)
+        }
+        _ => return Err("expected edit PALETTE PREVIEW after palette".to_string()),
+    };
+    let mut options = EditOptions {
+        palette: palette.clone(),
+        preview: preview.clone(),
+        operations: Vec::new(),
+        output: None,
+    };
+
+    let mut args = args[3..].iter();
+    while let Some(name) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {}", name))?;
+        match name.as_str() {
+            "--reverse" => match value.as_str() {
+                "on" => options.operations.push(Operation::Reverse),
+                "off" => {}
+                _ => return Err("error parsing --reverse".to_string()),
+            },
+            "--rotate" => options.operations.push(Operation::Rotate(
+                i64::from_str(value).map_err(|_| "error parsing --rotate")?,
+            )),
+            "--resample" => options.operations.push(Operation::Resample(
+                usize::from_str(value)
+                    .ok()
+                    .filter(|&count| (2..=256).contains(&count))
+                    .ok_or("error parsing --resample")?,
+            )),
+            "--convert" => options.operations.push(Operation::Convert(
+                ColorSpace::parse(value).ok_or_else(|| format!("unknown color space {}", value))?,
+            )),
+            "--output" => options.output = Some(value.clone()),
+            // Read by `main` before the subcommand runs.
+            "--error-format" | "--lang" => {}
+            _ => return Err(format!("unknown option {}", name)),
+        }
+    }
+    Ok(options)
+}
+
+#[test]
+fn test_parse_args() {
+    let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+    let options = parse_args(&args(
+        "edit warm.txt preview.png --reverse on --rotate -2 --resample 9 --convert linear",
+    ))
+    .unwrap();
+    assert_eq!(options.palette, "warm.txt");
+    assert_eq!(options.preview, "preview.png");
+    assert_eq!(
+        options.operations,
+        [
+            Operation::Reverse,
+            Operation::Rotate(-2),
+            Operation::Resample(9),
+            Operation::Convert(ColorSpace::Linear)
+        ]
+    );
+    assert_eq!(options.output, None);
+    let options = parse_args(&args("edit fire fire.png --output fire.txt")).unwrap();
+    assert!(options.operations.is_empty());
+    assert_eq!(options.output.as_deref(), Some("fire.txt"));
+    assert!(parse_args(&args("show fire fire.png")).is_err());
+    assert!(parse_args(&args("edit fire")).is_err());
+    assert!(parse_args(&args("edit --lang ru")).is_err());
+    assert!(parse_args(&args("edit fire fire.png --resample 1")).is_err());
+    assert!(parse_args(&args("edit fire fire.png --convert cmyk")).is_err());
+    assert!(parse_args(&args("edit fire fire.png --reverse")).is_err());
+}
+
+/// An RGB image showing `gradient`: a strip running through its colors from gray level 0 to
+/// 255, above the whole Mandelbrot set colored with it, its escape counts spread evenly over the
+/// palette by their histogram. Returns the pixels with the dimensions of the image.
+pub fn preview(gradient: &Gradient) -> (Vec<u8>, (usize, usize)) {
+    let bounds = (STRIP.0, STRIP.1 + FRACTAL_HEIGHT);
+    let mut pixels = Vec::with_capacity(bounds.0 * bounds.1 * 3);
+    for _ in 0..STRIP.1 {
+        for x in 0..STRIP.0 {
+            pixels.extend(gradient.color(x as u8));
+        }
+    }
+
+    let fractal = (STRIP.0, FRACTAL_HEIGHT);
+    let (upper_left, lower_right) = (Complex { re: -2.2, im: 1.2 }, Complex { re: 1.0, im: -1.2 });
+    let mut renderer = Renderer::default();
+    let points: Vec<Complex<f64>> = (0..fractal.0 * fractal.1)
+        .map(|i| {
+            pixel_to_point(
+                fractal,
+                (i % fractal.0, i / fractal.0),
+                upper_left,
+                lower_right,
+            )
+        })
+        .collect();
+    let counts: Vec<u32> = points
+        .iter()
+        .filter_map(|&point| renderer.escape_count(point))
+        .collect();
+    renderer.levels = Some(histogram_levels(&counts));
+    for point in points {
+        let (gray, _) = renderer.render_point(point);
+        pixels.extend(gradient.color(gray));
+    }
+    (pixels, bounds)
+}
+
+#[test]
+fn test_preview() {
+    let gradient = Gradient::from(crate::palette::Palette::Ocean);
+    let (pixels, bounds) = preview(&gradient);
+    assert_eq!(bounds, (256, 224));
+    assert_eq!(pixels.len(), 256 * 224 * 3);
+    // Every row of the strip runs through the palette.
+    for y in [0, 31] {
+        for x in [0, 100, 255] {
+            let index = (y * 256 + x) * 3;
+            assert_eq!(pixels[index..index + 3], gradient.color(x as u8));
+        }
+    }
+    // The fractal shows colors from all along the palette.
+    let fractal = &pixels[256 * 32 * 3..];
+    for level in [0, 255] {
+        let color = gradient.color(level);
+        assert!(fractal.chunks(3).any(|pixel| pixel == color));
+    }
+}
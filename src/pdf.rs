@@ -0,0 +1,276 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::str::FromStr;
+
+/// PDF user space units per inch.
+const POINTS_PER_INCH: f64 = 72.0;
+
+/// PDF user space units per millimeter.
+const POINTS_PER_MM: f64 = POINTS_PER_INCH / 25.4;
+
+/// Font size of the caption, in points.
+const CAPTION_SIZE: f64 = 9.0;
+
+/// How to lay out the raster on a PDF page.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PageLayout {
+    /// Page width and height, in points.
+    pub size: (f64, f64),
+    /// Blank space kept around all edges of the page, in points.
+    pub margin: f64,
+    /// Resolution the raster is printed at, in pixels per inch.
+    pub dpi: f64,
+    /// Line of text printed below the image, if any.
+    pub caption: Option<String>,
+}
+
+impl Default for PageLayout {
+    fn default() -> Self {
+        PageLayout {
+            size: parse_page_size("a4").unwrap(),
+            margin: 10.0 * POINTS_PER_MM,
+            dpi: 300.0,
+            caption: None,
+        }
+    }
+}
+
+/// Parse a page size, either a name like `"a4"` or `"letter"`, or dimensions in millimeters like
+/// `"210x297"`. Returns the width and height in points.
+pub fn parse_page_size(s: &str) -> Option<(f64, f64)> {
+    let (width, height) = match s.to_ascii_lowercase().as_str() {
+        "a5" => (148.0, 210.0),
+        "a4" => (210.0, 297.0),
+        "a3" => (297.0, 420.0),
+        "a2" => (420.0, 594.0),
+        "a1" => (594.0, 841.0),
+        "a0" => (841.0, 1189.0),
+        "letter" => (215.9, 279.4),
+        "legal" => (215.9, 355.6),
+        "tabloid" => (279.4, 431.8),
+        _ => match s.find('x') {
+            Some(index) => (
+                f64::from_str(&s[..index]).ok()?,
+                f64::from_str(&s[index + 1..]).ok()?,
+            ),
+            None => return None,
+        },
+    };
+    if !(width > 0.0 && height > 0.0 && width.is_finite() && height.is_finite()) {
+        return None;
+    }
+    Some((width * POINTS_PER_MM, height * POINTS_PER_MM))
+}
+
+#[test]
+fn test_parse_page_size() {
+    let a4 = parse_page_size("A4").unwrap();
+    assert!((a4.0 - 595.28).abs() < 0.01 && (a4.1 - 841.89).abs() < 0.01);
+    let letter = parse_page_size("letter").unwrap();
+    assert!((letter.0 - 612.0).abs() < 0.01 && (letter.1 - 792.0).abs() < 0.01);
+    let custom = parse_page_size("254x127").unwrap();
+    assert!((custom.0 - 720.0).abs() < 1e-9 && (custom.1 - 360.0).abs() < 1e-9);
+    assert_eq!(parse_page_size("b5"), None);
+    assert_eq!(parse_page_size("0x100"), None);
+}
+
+/// Escape `text` for use in a PDF literal string, dropping characters the standard Helvetica
+/// encoding can't show.
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars().filter(|c| (' '..='~').contains(c)) {
+        if matches!(c, '(' | ')' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[test]
+fn test_escape_text() {
+    assert_eq!(escape_text("a (b) \\ c"), "a \\(b\\) \\\\ c");
+    assert_eq!(escape_text("1.5×2"), "1.52");
+}
+
+/// Build the content stream of the page, placing the image and the caption, or explain why the
+/// image doesn't fit on the page.
+fn page_content(bounds: (usize, usize), layout: &PageLayout) -> Result<String, String> {
+    let width = bounds.0 as f64 / layout.dpi * POINTS_PER_INCH;
+    let height = bounds.1 as f64 / layout.dpi * POINTS_PER_INCH;
+    let caption_height = if layout.caption.is_some() {
+        CAPTION_SIZE * 2.0
+    } else {
+        0.0
+    };
+    let available = (
+        layout.size.0 - 2.0 * layout.margin,
+        layout.size.1 - 2.0 * layout.margin - caption_height,
+    );
+    if width > available.0 || height > available.1 {
+        return Err(format!(
+            "a {}x{} image at {} dpi needs {:.0}x{:.0} mm, but only {:.0}x{:.0} mm are available",
+            bounds.0,
+            bounds.1,
+            layout.dpi,
+            width / POINTS_PER_MM,
+            (height + caption_height) / POINTS_PER_MM,
+            available.0 / POINTS_PER_MM,
+            (available.1 + caption_height) / POINTS_PER_MM
+        ));
+    }
+
+    // Center the image and the caption below it in the area inside the margins.
+    let x = (layout.size.0 - width) / 2.0;
+    let y = (layout.size.1 - height + caption_height) / 2.0;
+    let mut content = format!(
+        "q {:.3} 0 0 {:.3} {:.3} {:.3} cm /Im0 Do Q\n",
+        width, height, x, y
+    );
+    if let Some(caption) = &layout.caption {
+        content += &format!(
+            "BT /F0 {} Tf {:.3} {:.3} Td ({}) Tj ET\n",
+            CAPTION_SIZE,
+            x,
+            y - CAPTION_SIZE * 1.5,
+            escape_text(caption)
+        );
+    }
+    Ok(content)
+}
+
+#[test]
+fn test_page_content() {
+    let layout = PageLayout {
+        size: (720.0, 720.0),
+        margin: 0.0,
+        dpi: 72.0,
+        caption: None,
+    };
+    assert_eq!(
+        page_content((360, 180), &layout).unwrap(),
+        "q 360.000 0 0 180.000 180.000 270.000 cm /Im0 Do Q\n"
+    );
+    assert!(page_content((800, 100), &layout).is_err());
+
+    let captioned = PageLayout {
+        caption: Some("z".to_string()),
+        ..layout
+    };
+    let content = page_content((360, 180), &captioned).unwrap();
+    assert!(content.contains("(z) Tj"));
+    assert!(page_content((360, 720), &captioned).is_err());
+}
+
+/// Write the buffer `pixels`, whose dimensions are given by `bounds`, to the file named
+/// `filename` as a single page PDF laid out according to `layout`. Pixels are either a gray level
+/// byte or red, green and blue bytes.
+pub fn write_pdf(
+    filename: &str,
+    pixels: &[u8],
+    bounds: (usize, usize),
+    layout: &PageLayout,
+) -> Result<(), std::io::Error> {
+    let content = page_content(bounds, layout).map_err(std::io::Error::other)?;
+    let mut compressor =
+        flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    compressor.write_all(pixels)?;
+    let image = compressor.finish()?;
+
+    let mut objects: Vec<Vec<u8>> = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_vec(),
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.3} {:.3}] /Contents 4 0 R \
+             /Resources << /XObject << /Im0 5 0 R >> /Font << /F0 6 0 R >> >> >>",
+            layout.size.0, layout.size.1
+        )
+        .into_bytes(),
+        format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            content.len(),
+            content
+        )
+        .into_bytes(),
+    ];
+    let mut image_object = format!(
+        "<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /{} \
+         /BitsPerComponent 8 /Filter /FlateDecode /Length {} >>\nstream\n",
+        bounds.0,
+        bounds.1,
+        if pixels.len() == bounds.0 * bounds.1 * 3 {
+            "DeviceRGB"
+        } else {
+            "DeviceGray"
+        },
+        image.len()
+    )
+    .into_bytes();
+    image_object.extend(image);
+    image_object.extend(b"\nendstream");
+    objects.push(image_object);
+    objects.push(b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_vec());
+
+    let mut output = BufWriter::new(File::create(filename)?);
+    let mut offset = 0;
+    let mut offsets = Vec::new();
+    let header = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n";
+    output.write_all(header)?;
+    offset += header.len();
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(offset);
+        let start = format!("{} 0 obj\n", i + 1);
+        output.write_all(start.as_bytes())?;
+        output.write_all(object)?;
+        output.write_all(b"\nendobj\n")?;
+        offset += start.len() + object.len() + b"\nendobj\n".len();
+    }
+    write!(
+        output,
+        "xref\n0 {}\n0000000000 65535 f \n",
+        objects.len() + 1
+    )?;
+    for offset in offsets {
+        writeln!(output, "{:010} 00000 n ", offset)?;
+    }
+    write!(
+        output,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        offset
+    )?;
+    output.flush()
+}
+
+#[test]
+fn test_write_pdf() {
+    let filename = std::env::temp_dir().join("rust-experiments-test.pdf");
+    let filename = filename.to_str().unwrap();
+    write_pdf(filename, &[0, 64, 128, 255], (2, 2), &PageLayout::default()).unwrap();
+    let pdf = std::fs::read(filename).unwrap();
+    assert!(pdf.starts_with(b"%PDF-1.4"));
+    assert!(pdf.ends_with(b"%%EOF\n"));
+
+    // The cross-reference table has to point at each object exactly.
+    let find = |needle: &[u8]| pdf.windows(needle.len()).position(|w| w == needle).unwrap();
+    let xref = find(b"\nxref\n") + 1;
+    let table = String::from_utf8(pdf[xref..].to_vec()).unwrap();
+    for (i, line) in table.lines().skip(3).take(6).enumerate() {
+        let offset: usize = line[..10].parse().unwrap();
+        assert!(pdf[offset..].starts_with(format!("{} 0 obj", i + 1).as_bytes()));
+    }
+    let startxref = table.find("startxref\n").unwrap();
+    let value: usize = table[startxref + 10..]
+        .lines()
+        .next()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert_eq!(value, xref);
+    assert!(find(b"/DeviceGray") > 0);
+
+    write_pdf(filename, &[0; 12], (2, 2), &PageLayout::default()).unwrap();
+    let pdf = std::fs::read(filename).unwrap();
+    assert!(pdf.windows(10).any(|w| w == b"/DeviceRGB"));
+    std::fs::remove_file(filename).unwrap();
+}
@@ -0,0 +1,236 @@
+use crate::precision::{self, DoubleDouble};
+use crate::{escape_time, pixel_to_point};
+use num::Complex;
+
+/// A point escaping to less than this fraction of its reference's magnitude, squared, has lost
+/// the digits that tell it apart from the reference (Pauldelbrot's criterion).
+const GLITCH_TOLERANCE: f64 = 1e-6;
+
+/// Largest number of reference orbits placed for a view.
+pub const MAX_REFERENCES: usize = 16;
+
+/// Columns and rows of the coarse grid searched for glitches when placing references.
+const PREVIEW: usize = 64;
+
+/// The orbit of a single point, iterated at high precision and kept rounded to f64, that the
+/// orbits of the points around it are computed relative to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReferenceOrbit {
+    /// `Z_0` up to the last iterate before escaping or reaching the limit.
+    orbit: Vec<Complex<f64>>,
+}
+
+impl ReferenceOrbit {
+    /// Iterate `z = z*z + c` from `z0` at double-double precision, at most `limit` times.
+    pub fn new(c: Complex<DoubleDouble>, z0: Complex<f64>, limit: u32, bailout: f64) -> Self {
+        let bailout_sqr = DoubleDouble::new(bailout * bailout);
+        let mut z: Complex<DoubleDouble> = precision::complex_from_f64(z0);
+        let mut orbit = vec![z0];
+        for _ in 0..limit {
+            z = z * z + c;
+            orbit.push(precision::complex_to_f64(z));
+            if z.norm_sqr() > bailout_sqr {
+                break;
+            }
+        }
+        ReferenceOrbit { orbit }
+    }
+
+    /// Iterate the point `delta_c` away from the reference's c, by the difference of its orbit
+    /// to the reference's, at most `limit` times. Returns its escape time as `escape_time` does,
+    /// or `Err` if the difference can't be trusted and the point needs another reference.
+    pub fn escape_time(
+        &self,
+        delta_c: Complex<f64>,
+        limit: u32,
+        bailout: f64,
+    ) -> Result<Option<u32>, Glitch> {
+        let bailout_sqr = bailout * bailout;
+        let mut delta = Complex { re: 0.0, im: 0.0 };
+        for i in 0..limit {
+            let reference = self.orbit[i as usize];
+            delta = (reference * 2.0 + delta) * delta + delta_c;
+            // The reference escaped first: its orbit doesn't go far enough.
+            let next = *self.orbit.get(i as usize + 1).ok_or(Glitch)?;
+            let z = next + delta;
+            if z.norm_sqr() > bailout_sqr {
+                return Ok(Some(i));
+            }
+            if z.norm_sqr() < GLITCH_TOLERANCE * next.norm_sqr() {
+                return Err(Glitch);
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// A point whose orbit can't be computed relative to a reference orbit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Glitch;
+
+/// The reference orbits for rendering a view of the Mandelbrot set around `origin`, with points
+/// given as f64 offsets from it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Perturbation {
+    origin: Complex<DoubleDouble>,
+    z0: Complex<f64>,
+    limit: u32,
+    bailout: f64,
+    /// The references, each with its c as an offset from `origin`, tried in order.
+    references: Vec<(Complex<f64>, ReferenceOrbit)>,
+}
+
+impl Perturbation {
+    /// Start with a single reference, at `origin`.
+    pub fn new(origin: Complex<DoubleDouble>, z0: Complex<f64>, limit: u32, bailout: f64) -> Self {
+        let reference = ReferenceOrbit::new(origin, z0, limit, bailout);
+        Perturbation {
+            origin,
+            z0,
+            limit,
+            bailout,
+            references: vec![(Complex { re: 0.0, im: 0.0 }, reference)],
+        }
+    }
+
+    /// Start with a reference at `origin` and add more, up to `MAX_REFERENCES`, at points found to
+    /// glitch on a coarse grid over the image with dimensions `bounds` between the offsets
+    /// `upper_left` and `lower_right`.
+    pub fn for_view(
+        origin: Complex<DoubleDouble>,
+        bounds: (usize, usize),
+        upper_left: Complex<f64>,
+        lower_right: Complex<f64>,
+        z0: Complex<f64>,
+        limit: u32,
+        bailout: f64,
+    ) -> Self {
+        let mut perturbation = Perturbation::new(origin, z0, limit, bailout);
+        let preview = (bounds.0.min(PREVIEW), bounds.1.min(PREVIEW));
+        let mut offsets: Vec<_> = (0..preview.0 * preview.1)
+            .map(|i| {
+                let pixel = (i % preview.0, i / preview.0);
+                pixel_to_point(preview, pixel, upper_left, lower_right)
+            })
+            .collect();
+        while perturbation.references.len() < MAX_REFERENCES {
+            offsets.retain(|&offset| perturbation.perturbed_escape_time(offset).is_err());
+            // Take the middle one of the glitched points, as likely as any to be near the
+            // feature they all miss.
+            match offsets.get(offsets.len() / 2) {
+                Some(&offset) => perturbation.add_reference(offset),
+                None => break,
+            }
+        }
+        perturbation
+    }
+
+    /// Add a reference at `offset` from the origin, to try after the others.
+    pub fn add_reference(&mut self, offset: Complex<f64>) {
+        let c = self.origin + precision::complex_from_f64(offset);
+        let reference = ReferenceOrbit::new(c, self.z0, self.limit, self.bailout);
+        self.references.push((offset, reference));
+    }
+
+    /// Number of reference orbits.
+    pub fn references(&self) -> usize {
+        self.references.len()
+    }
+
+    /// The escape time of the point `offset` from the origin, from the first reference that can
+    /// compute it.
+    fn perturbed_escape_time(&self, offset: Complex<f64>) -> Result<Option<u32>, Glitch> {
+        self.references
+            .iter()
+            .find_map(|(reference_offset, reference)| {
+                reference
+                    .escape_time(offset - reference_offset, self.limit, self.bailout)
+                    .ok()
+            })
+            .ok_or(Glitch)
+    }
+
+    /// The escape time of the point `offset` from the origin, computed relative to the first
+    /// reference that can, or at double-double precision if none can.
+    pub fn escape_time(&self, offset: Complex<f64>) -> Option<u32> {
+        self.perturbed_escape_time(offset).unwrap_or_else(|_| {
+            let c = self.origin + precision::complex_from_f64(offset);
+            escape_time(
+                precision::complex_from_f64(self.z0),
+                c,
+                self.limit,
+                self.bailout,
+            )
+        })
+    }
+}
+
+#[test]
+fn test_reference_orbit() {
+    let zero = Complex { re: 0.0, im: 0.0 };
+    // The cusp of the cardioid, whose orbit creeps towards 0.5 without escaping, while the points
+    // just right of it escape slowly.
+    let origin = Complex {
+        re: DoubleDouble::new(0.25),
+        im: DoubleDouble::new(0.0),
+    };
+    let reference = ReferenceOrbit::new(origin, zero, 255, 2.0);
+    // Close to the reference, perturbation agrees with iterating the point itself.
+    for offset in [(1e-3, 0.0), (2e-3, -2e-3), (5e-3, 5e-4), (-1e-3, 0.0)] {
+        let delta_c = Complex {
+            re: offset.0,
+            im: offset.1,
+        };
+        let c = precision::complex_to_f64(origin) + delta_c;
+        assert_eq!(
+            reference.escape_time(delta_c, 255, 2.0),
+            Ok(escape_time(zero, c, 255u32, 2.0))
+        );
+    }
+    // A reference that escapes at once can't stand in for points that don't.
+    let outside = ReferenceOrbit::new(
+        precision::complex_from_f64(Complex { re: 3.0, im: 0.0 }),
+        zero,
+        255,
+        2.0,
+    );
+    assert_eq!(
+        outside.escape_time(Complex { re: -3.0, im: 0.0 }, 255, 2.0),
+        Err(Glitch)
+    );
+}
+
+#[test]
+fn test_perturbation() {
+    // A deep view the f64 corners can't resolve, with the origin just outside the set, so that
+    // points inside it glitch against the first reference.
+    let dd = |s: &str| s.parse::<DoubleDouble>().unwrap();
+    let origin = Complex {
+        re: dd("-1.99999999999999999988"),
+        im: dd("0"),
+    };
+    let (upper_left, lower_right) = (
+        Complex {
+            re: -2e-20,
+            im: 3e-20,
+        },
+        Complex {
+            re: 2e-20,
+            im: -3e-20,
+        },
+    );
+    let zero = Complex { re: 0.0, im: 0.0 };
+    let perturbation =
+        Perturbation::for_view(origin, (40, 60), upper_left, lower_right, zero, 255, 2.0);
+    assert!(1 < perturbation.references() && perturbation.references() <= MAX_REFERENCES);
+    for pixel in [(0, 0), (20, 30), (39, 59), (10, 45)] {
+        let offset = pixel_to_point((40, 60), pixel, upper_left, lower_right);
+        let c = origin + precision::complex_from_f64(offset);
+        assert_eq!(
+            perturbation.escape_time(offset),
+            escape_time(precision::complex_from_f64(zero), c, 255u32, 2.0),
+            "{:?}",
+            pixel
+        );
+    }
+}
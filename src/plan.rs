@@ -0,0 +1,272 @@
+use crate::annotations::FULL_WIDTH;
+use crate::precision::{self, DoubleDouble, Precision};
+use crate::viewport;
+use num::Complex;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::str::FromStr;
+
+/// Frames planned when `--frames` isn't given.
+const DEFAULT_FRAMES: usize = 100;
+
+/// What zoom to plan.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlanOptions {
+    /// Dimensions of each frame, in pixels.
+    pub bounds: (usize, usize),
+    /// The point zoomed in on.
+    pub center: Complex<DoubleDouble>,
+    /// Magnification of the last frame relative to a view of the whole set.
+    pub zoom: f64,
+    /// Magnification of the first frame.
+    pub start_zoom: f64,
+    /// Number of frames, the first and last included.
+    pub frames: usize,
+}
+
+impl PlanOptions {
+    /// Width of a pixel in the plane at magnification `zoom`.
+    fn pixel_size(&self, zoom: f64) -> f64 {
+        FULL_WIDTH / zoom / self.bounds.0 as f64
+    }
+
+    /// Size of the coordinates near the center, which limits how small a pixel each precision
+    /// can tell apart.
+    fn magnitude(&self) -> f64 {
+        let center = precision::complex_to_f64(self.center);
+        center.re.abs().max(center.im.abs())
+    }
+}
+
+/// Parse the arguments following `plan-zoom`: the dimensions of the frames, the center, the
+/// final zoom, and options.
+pub fn parse_args(args: &[String]) -> Result<PlanOptions, String> {
+    let (pixels, center, zoom) = match args {
+        [pixels, center, zoom, ..] if !zoom.starts_with("--") => (pixels, center, zoom),
+        _ => return Err("expected PIXELS CENTER ZOOM after plan-zoom".to_string()),
+    };
+    let positive = |s: &str| f64::from_str(s).ok().filter(|&x| x > 0.0 && x.is_finite());
+    let mut options = PlanOptions {
+        bounds: crate::parse_pair(pixels, 'x')
+            .filter(|&(width, height)| width > 0 && height > 0)
+            .ok_or("error parsing image dimensions")?,
+        center: precision::parse_complex(center).ok_or("error parsing point")?,
+        zoom: positive(zoom).ok_or("error parsing zoom")?,
+        start_zoom: 1.0,
+        frames: DEFAULT_FRAMES,
+    };
+
+    let mut args = args[3..].iter();
+    while let Some(name) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {}", name))?;
+        match name.as_str() {
+            "--start-zoom" => {
+                options.start_zoom = positive(value).ok_or("error parsing --start-zoom")?
+            }
+            "--frames" => {
+                options.frames = usize::from_str(value)
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or("error parsing --frames")?
+            }
+            // Read by `main` before the subcommand runs.
+            "--error-format" | "--lang" => {}
+            _ => return Err(format!("unknown option {}", name)),
+        }
+    }
+    let deepest = options.zoom.max(options.start_zoom);
+    if precision::beyond_double_double(options.pixel_size(deepest), options.magnitude()) {
+        return Err(format!(
+            "a zoom of {:e} is too deep for double-double precision",
+            deepest
+        ));
+    }
+    Ok(options)
+}
+
+#[test]
+fn test_parse_args() {
+    let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+    let options = parse_args(&args("640x360 -0.75,0.1 1e6 --frames 30 --lang ru")).unwrap();
+    assert_eq!(options.bounds, (640, 360));
+    assert_eq!(
+        precision::complex_to_f64(options.center),
+        Complex { re: -0.75, im: 0.1 }
+    );
+    assert_eq!(options.zoom, 1e6);
+    assert_eq!(options.start_zoom, 1.0);
+    assert_eq!(options.frames, 30);
+    assert!(parse_args(&args("640x360 -0.75,0.1")).is_err());
+    assert!(parse_args(&args("640x360 -0.75,0.1 --frames 30")).is_err());
+    assert!(parse_args(&args("640x360 -0.75,0.1 0")).is_err());
+    assert!(parse_args(&args("640x360 -0.75,0.1 1e6 --frames 0")).is_err());
+    assert!(parse_args(&args("640x360 -0.75,0.1 1e40")).is_err());
+}
+
+/// One frame of a planned zoom.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    /// Magnification relative to a view of the whole set.
+    pub zoom: f64,
+    pub upper_left: Complex<DoubleDouble>,
+    pub lower_right: Complex<DoubleDouble>,
+    /// The precision `--precision auto` renders the frame with.
+    pub precision: Precision,
+    /// Estimated time to render, in megapixels computed at f64 speed.
+    pub cost: f64,
+}
+
+/// The frames of a zoom, in order. The zoom grows by the same factor from each frame to the
+/// next, so the motion looks steady. The precisions are those picked for the Mandelbrot set
+/// with the default flat interior.
+pub fn plan(options: &PlanOptions) -> Vec<Frame> {
+    let megapixels = (options.bounds.0 * options.bounds.1) as f64 / 1e6;
+    (0..options.frames)
+        .map(|i| {
+            let zoom = if i + 1 == options.frames {
+                options.zoom
+            } else {
+                let t = i as f64 / (options.frames - 1) as f64;
+                options.start_zoom * (options.zoom / options.start_zoom).powf(t)
+            };
+            let pixel_size = options.pixel_size(zoom);
+            let (upper_left, lower_right) = viewport(options.bounds, options.center, pixel_size);
+            let precision = if precision::needs_arb(pixel_size, options.magnitude()) {
+                Precision::Perturb
+            } else {
+                Precision::F64
+            };
+            Frame {
+                zoom,
+                upper_left,
+                lower_right,
+                precision,
+                cost: megapixels * precision.relative_cost(),
+            }
+        })
+        .collect()
+}
+
+/// A run of consecutive frames rendered with the same precision.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+    pub frames: Range<usize>,
+    pub precision: Precision,
+    /// Total estimated time to render the frames, as for `Frame::cost`.
+    pub cost: f64,
+}
+
+/// Split `frames` into runs with the same precision.
+pub fn segments(frames: &[Frame]) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = Vec::new();
+    for (i, frame) in frames.iter().enumerate() {
+        match segments.last_mut() {
+            Some(segment) if segment.precision == frame.precision => {
+                segment.frames.end = i + 1;
+                segment.cost += frame.cost;
+            }
+            _ => segments.push(Segment {
+                frames: i..i + 1,
+                precision: frame.precision,
+                cost: frame.cost,
+            }),
+        }
+    }
+    segments
+}
+
+#[test]
+fn test_plan() {
+    let options = PlanOptions {
+        bounds: (400, 200),
+        center: precision::complex_from_f64(Complex { re: -0.75, im: 0.1 }),
+        zoom: 1e20,
+        start_zoom: 1.0,
+        frames: 21,
+    };
+    let frames = plan(&options);
+    assert_eq!(frames.len(), 21);
+    assert_eq!(frames[0].zoom, 1.0);
+    assert_eq!(frames[20].zoom, 1e20);
+    assert!((frames[10].zoom / 1e10 - 1.0).abs() < 1e-9);
+    let first = (
+        precision::complex_to_f64(frames[0].upper_left),
+        precision::complex_to_f64(frames[0].lower_right),
+    );
+    assert_eq!(
+        first,
+        (
+            Complex {
+                re: -2.25,
+                im: 0.1 + 0.75
+            },
+            Complex {
+                re: 0.75,
+                im: 0.1 - 0.75
+            }
+        )
+    );
+    // Pixels narrower than 1e-13, with the center less than 1 away, are too small for f64.
+    let segments = segments(&frames);
+    assert_eq!(segments.len(), 2);
+    assert_eq!(segments[0].frames, 0..11);
+    assert_eq!(segments[0].precision, Precision::F64);
+    assert_eq!(segments[1].frames, 11..21);
+    assert_eq!(segments[1].precision, Precision::Perturb);
+    assert!((segments[1].cost - 10.0 * 0.08 * 2.0).abs() < 1e-9);
+}
+
+/// Write the frames of a plan, one per line with their zoom, corners as the command line takes
+/// them, precision and cost, followed by a summary of the segments and the total cost.
+pub fn write_plan(out: &mut impl Write, frames: &[Frame]) -> io::Result<()> {
+    writeln!(out, "# frame zoom upper_left lower_right precision cost")?;
+    for (i, frame) in frames.iter().enumerate() {
+        writeln!(
+            out,
+            "{} {:e} {},{} {},{} {} {:.3}",
+            i,
+            frame.zoom,
+            frame.upper_left.re,
+            frame.upper_left.im,
+            frame.lower_right.re,
+            frame.lower_right.im,
+            frame.precision.name(),
+            frame.cost
+        )?;
+    }
+    for segment in segments(frames) {
+        writeln!(
+            out,
+            "# frames {} to {}: {}, cost {:.3}",
+            segment.frames.start,
+            segment.frames.end - 1,
+            segment.precision.name(),
+            segment.cost
+        )?;
+    }
+    let total: f64 = frames.iter().map(|frame| frame.cost).sum();
+    writeln!(out, "# total cost {:.3} megapixels at f64 speed", total)
+}
+
+#[test]
+fn test_write_plan() {
+    let options = PlanOptions {
+        bounds: (100, 100),
+        center: precision::complex_from_f64(Complex { re: 0.0, im: 0.0 }),
+        zoom: 2.0,
+        start_zoom: 1.0,
+        frames: 2,
+    };
+    let mut out = Vec::new();
+    write_plan(&mut out, &plan(&options)).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "# frame zoom upper_left lower_right precision cost\n\
+         0 1e0 -1.5,1.5 1.5,-1.5 f64 0.010\n\
+         1 2e0 -0.75,0.75 0.75,-0.75 f64 0.010\n\
+         # frames 0 to 1: f64, cost 0.020\n\
+         # total cost 0.020 megapixels at f64 speed\n"
+    );
+}
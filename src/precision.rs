@@ -0,0 +1,503 @@
+use num::Complex;
+use num_traits::{Num, One, Zero};
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Rem, Sub};
+use std::str::FromStr;
+
+/// Real numbers that points of the plane can be computed with.
+pub trait Real:
+    Copy + Num + Neg<Output = Self> + PartialOrd + FromStr + fmt::Display + fmt::Debug + Send + Sync
+{
+    /// The gap between 1 and the next number this type can hold, as an f64.
+    const EPSILON: f64;
+
+    fn from_f64(x: f64) -> Self;
+    fn to_f64(self) -> f64;
+}
+
+impl Real for f64 {
+    const EPSILON: f64 = f64::EPSILON;
+
+    fn from_f64(x: f64) -> Self {
+        x
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+/// Round the parts of `z` to f64, for the computations that are only done in f64.
+pub fn complex_to_f64<R: Real>(z: Complex<R>) -> Complex<f64> {
+    Complex {
+        re: z.re.to_f64(),
+        im: z.im.to_f64(),
+    }
+}
+
+/// Widen the parts of `z` to `R`.
+pub fn complex_from_f64<R: Real>(z: Complex<f64>) -> Complex<R> {
+    Complex {
+        re: R::from_f64(z.re),
+        im: R::from_f64(z.im),
+    }
+}
+
+/// Parse a complex number as `parse_complex` does, keeping every digit of decimal parts that
+/// double-double precision can hold.
+pub fn parse_complex(s: &str) -> Option<Complex<DoubleDouble>> {
+    let parse = |part: &str| {
+        part.parse()
+            .ok()
+            .or_else(|| crate::coords::parse_real(part).map(DoubleDouble::new))
+    };
+    let (re, im) = s.split_once(',')?;
+    Some(Complex {
+        re: parse(re)?,
+        im: parse(im)?,
+    })
+}
+
+#[test]
+fn test_parse_complex() {
+    let z = parse_complex("-0.74364388703715870475,0x1p-4").unwrap();
+    assert_eq!(z.re.to_string(), "-0.74364388703715870475");
+    assert_eq!(z.im, DoubleDouble::new(0.0625));
+    assert_eq!(parse_complex("0.5"), None);
+}
+
+/// How precisely points of the plane are computed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Precision {
+    /// Use `Arb` when the pixels are too small for `F64` to tell them apart.
+    #[default]
+    Auto,
+    /// Hardware double precision, good to about 16 significant digits.
+    F64,
+    /// Software double-double precision, good to about 32 significant digits.
+    Arb,
+    /// Double-double precision for a few reference orbits, and f64 for each point's difference
+    /// to them, as `perturbation` computes it. Much faster than `Arb`, for the Mandelbrot set
+    /// only.
+    Perturb,
+}
+
+impl Precision {
+    pub fn parse(s: &str) -> Option<Precision> {
+        match s {
+            "auto" => Some(Precision::Auto),
+            "f64" => Some(Precision::F64),
+            "arb" => Some(Precision::Arb),
+            "perturb" => Some(Precision::Perturb),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Precision::Auto => "auto",
+            Precision::F64 => "f64",
+            Precision::Arb => "arb",
+            Precision::Perturb => "perturb",
+        }
+    }
+
+    /// Rough time to compute a point, relative to `F64`, as measured over views of the
+    /// Mandelbrot set on one thread.
+    pub fn relative_cost(self) -> f64 {
+        match self {
+            Precision::Auto | Precision::F64 => 1.0,
+            Precision::Arb => 12.0,
+            Precision::Perturb => 2.0,
+        }
+    }
+}
+
+/// Pixels smaller than this, relative to the size of their coordinates, are rendered with
+/// `Precision::Auto` at double-double precision, directly or by perturbation. Rounding errors
+/// grow during iteration, so the blocky artifacts start well before pixels reach the size of an
+/// f64's last digit.
+pub const AUTO_THRESHOLD: f64 = 1e-13;
+
+/// Pixels smaller than this, relative to the size of their coordinates, turn blocky even at
+/// double-double precision, with the same margin for rounding errors as `AUTO_THRESHOLD`.
+pub const DOUBLE_DOUBLE_THRESHOLD: f64 = 1e-28;
+
+/// Whether pixels of `pixel_size` at coordinates as large as `magnitude` need more precision
+/// than f64 has.
+pub fn needs_arb(pixel_size: f64, magnitude: f64) -> bool {
+    pixel_size < magnitude.max(1.0) * AUTO_THRESHOLD
+}
+
+/// Whether pixels of `pixel_size` at coordinates as large as `magnitude` need more precision
+/// than double-double has.
+pub fn beyond_double_double(pixel_size: f64, magnitude: f64) -> bool {
+    pixel_size < magnitude.max(1.0) * DOUBLE_DOUBLE_THRESHOLD
+}
+
+#[test]
+fn test_needs_arb() {
+    assert!(!needs_arb(3.0 / 1000.0, 2.0));
+    assert!(needs_arb(1e-16, 0.75));
+    assert!(!needs_arb(1e-12, 0.75));
+    assert!(!beyond_double_double(1e-16, 0.75));
+    assert!(beyond_double_double(1e-30, 0.75));
+}
+
+/// A number represented as the unevaluated sum of two f64s, the second at most half an ulp of
+/// the first, giving about 106 bits of mantissa.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DoubleDouble {
+    pub hi: f64,
+    pub lo: f64,
+}
+
+/// Sum of `a` and `b` as a rounded value and its exact error.
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    let v = s - a;
+    (s, (a - (s - v)) + (b - v))
+}
+
+/// Like `two_sum`, but only when `|a| >= |b|`.
+fn quick_two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    (s, b - (s - a))
+}
+
+/// Product of `a` and `b` as a rounded value and its exact error.
+fn two_prod(a: f64, b: f64) -> (f64, f64) {
+    let p = a * b;
+    (p, a.mul_add(b, -p))
+}
+
+impl DoubleDouble {
+    pub fn new(hi: f64) -> Self {
+        DoubleDouble { hi, lo: 0.0 }
+    }
+
+    fn renormalize(hi: f64, lo: f64) -> Self {
+        let (hi, lo) = quick_two_sum(hi, lo);
+        DoubleDouble { hi, lo }
+    }
+
+    fn trunc(self) -> Self {
+        let hi = self.hi.trunc();
+        if hi == self.hi {
+            DoubleDouble::renormalize(hi, self.lo.trunc())
+        } else {
+            DoubleDouble::new(hi)
+        }
+    }
+
+    fn floor(self) -> Self {
+        let hi = self.hi.floor();
+        if hi == self.hi {
+            DoubleDouble::renormalize(hi, self.lo.floor())
+        } else {
+            DoubleDouble::new(hi)
+        }
+    }
+}
+
+impl Add for DoubleDouble {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        let (s, e) = two_sum(self.hi, other.hi);
+        let (t, f) = two_sum(self.lo, other.lo);
+        let (s, e) = quick_two_sum(s, e + t);
+        DoubleDouble::renormalize(s, e + f)
+    }
+}
+
+impl Neg for DoubleDouble {
+    type Output = Self;
+    fn neg(self) -> Self {
+        DoubleDouble {
+            hi: -self.hi,
+            lo: -self.lo,
+        }
+    }
+}
+
+impl Sub for DoubleDouble {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        self + -other
+    }
+}
+
+impl Mul for DoubleDouble {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+        let (p, e) = two_prod(self.hi, other.hi);
+        DoubleDouble::renormalize(p, e + (self.hi * other.lo + self.lo * other.hi))
+    }
+}
+
+impl Div for DoubleDouble {
+    type Output = Self;
+    fn div(self, other: Self) -> Self {
+        // Long division, one f64 worth of quotient at a time.
+        let q1 = self.hi / other.hi;
+        let r = self - other * DoubleDouble::new(q1);
+        let q2 = r.hi / other.hi;
+        let r = r - other * DoubleDouble::new(q2);
+        let q3 = r.hi / other.hi;
+        DoubleDouble::renormalize(q1, q2) + DoubleDouble::new(q3)
+    }
+}
+
+impl Rem for DoubleDouble {
+    type Output = Self;
+    fn rem(self, other: Self) -> Self {
+        self - other * (self / other).trunc()
+    }
+}
+
+impl PartialOrd for DoubleDouble {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match self.hi.partial_cmp(&other.hi)? {
+            Ordering::Equal => self.lo.partial_cmp(&other.lo),
+            ordering => Some(ordering),
+        }
+    }
+}
+
+impl Zero for DoubleDouble {
+    fn zero() -> Self {
+        DoubleDouble::new(0.0)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.hi == 0.0
+    }
+}
+
+impl One for DoubleDouble {
+    fn one() -> Self {
+        DoubleDouble::new(1.0)
+    }
+}
+
+impl Num for DoubleDouble {
+    type FromStrRadixErr = ();
+
+    fn from_str_radix(s: &str, radix: u32) -> Result<Self, ()> {
+        match radix {
+            10 => s.parse(),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Real for DoubleDouble {
+    // The low part carries another 53 bits.
+    const EPSILON: f64 = f64::EPSILON * f64::EPSILON / 2.0;
+
+    fn from_f64(x: f64) -> Self {
+        DoubleDouble::new(x)
+    }
+
+    fn to_f64(self) -> f64 {
+        self.hi + self.lo
+    }
+}
+
+impl FromStr for DoubleDouble {
+    type Err = ();
+
+    /// Parse a decimal number like `"-0.7436438870371587047521915061"` or `"1.5e-20"`, keeping
+    /// every digit that fits.
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (mantissa, exponent) = match s.find(['e', 'E']) {
+            Some(i) => (&s[..i], s[i + 1..].parse::<i32>().map_err(|_| ())?),
+            None => (s, 0),
+        };
+        let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if whole.is_empty() && fraction.is_empty() {
+            return Err(());
+        }
+        let ten = DoubleDouble::new(10.0);
+        let mut value = DoubleDouble::zero();
+        for c in whole.chars().chain(fraction.chars()) {
+            let digit = c.to_digit(10).ok_or(())?;
+            value = value * ten + DoubleDouble::new(digit as f64);
+        }
+        let exponent = exponent - fraction.len() as i32;
+        for _ in 0..exponent.abs() {
+            value = if exponent > 0 {
+                value * ten
+            } else {
+                value / ten
+            };
+        }
+        Ok(if negative { -value } else { value })
+    }
+}
+
+/// Significant digits written by `Display`, as many as a double-double can be trusted with.
+const DISPLAY_DIGITS: usize = 31;
+
+impl fmt::Display for DoubleDouble {
+    /// Write the number in positional notation, with up to 31 significant digits.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.hi.is_finite() {
+            return write!(f, "{}", self.hi);
+        }
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        let ten = DoubleDouble::new(10.0);
+        let mut x = if self.hi < 0.0 { -*self } else { *self };
+        // Scale into [1, 10), correcting the estimate of the exponent if it's off by one.
+        let mut exponent = x.hi.log10().floor() as i32;
+        for _ in 0..exponent.abs() {
+            x = if exponent > 0 { x / ten } else { x * ten };
+        }
+        if x.hi >= 10.0 {
+            x = x / ten;
+            exponent += 1;
+        } else if x.hi < 1.0 {
+            x = x * ten;
+            exponent -= 1;
+        }
+        let mut digits = Vec::with_capacity(DISPLAY_DIGITS);
+        for _ in 0..DISPLAY_DIGITS {
+            let digit = x.floor().hi.clamp(0.0, 9.0);
+            digits.push(digit as u8);
+            x = (x - DoubleDouble::new(digit)) * ten;
+        }
+        // Round the last digit, carrying over.
+        if x.hi >= 5.0 {
+            let mut i = digits.len();
+            loop {
+                if i == 0 {
+                    digits.insert(0, 1);
+                    digits.pop();
+                    exponent += 1;
+                    break;
+                }
+                i -= 1;
+                if digits[i] == 9 {
+                    digits[i] = 0;
+                } else {
+                    digits[i] += 1;
+                    break;
+                }
+            }
+        }
+        while digits.len() > 1 && digits.last() == Some(&0) {
+            digits.pop();
+        }
+
+        let mut s = String::new();
+        if self.hi < 0.0 {
+            s.push('-');
+        }
+        let digit = |d: &u8| char::from(b'0' + d);
+        if exponent < 0 {
+            s.push_str("0.");
+            s.extend(std::iter::repeat_n('0', (-exponent - 1) as usize));
+            s.extend(digits.iter().map(digit));
+        } else {
+            let whole = exponent as usize + 1;
+            for i in 0..whole.max(digits.len()) {
+                if i == whole {
+                    s.push('.');
+                }
+                s.push(digits.get(i).map_or('0', digit));
+            }
+        }
+        f.pad(&s)
+    }
+}
+
+#[test]
+fn test_double_double_arithmetic() {
+    let dd = |s: &str| s.parse::<DoubleDouble>().unwrap();
+    // 1 + 2^-80 is lost in an f64, but not in a double-double.
+    let tiny = DoubleDouble::new(2f64.powi(-80));
+    let x = DoubleDouble::one() + tiny;
+    assert_eq!(x.hi, 1.0);
+    assert_eq!(x.lo, 2f64.powi(-80));
+    assert_eq!((x - DoubleDouble::one()).to_f64(), 2f64.powi(-80));
+    // (1 + e)^2 = 1 + 2e + e^2, exactly representable here for e = 2^-40.
+    let e = DoubleDouble::new(2f64.powi(-40));
+    let square = (DoubleDouble::one() + e) * (DoubleDouble::one() + e);
+    assert_eq!(square - DoubleDouble::one() - e - e, e * e);
+    // A third, to well beyond f64 precision.
+    let third = DoubleDouble::one() / DoubleDouble::new(3.0);
+    let error = third * DoubleDouble::new(3.0) - DoubleDouble::one();
+    assert!(error.to_f64().abs() < 1e-31);
+    assert_eq!(dd("7") % dd("3"), dd("1"));
+    assert!(dd("-0.5") < dd("0.25"));
+    assert!(dd("1") < x);
+}
+
+#[test]
+fn test_double_double_strings() {
+    let dd = |s: &str| s.parse::<DoubleDouble>().unwrap();
+    assert_eq!(dd("1.5").to_f64(), 1.5);
+    assert_eq!(dd("-2.5e-3").to_f64(), -2.5e-3);
+    assert_eq!(dd("25E1").to_f64(), 250.0);
+    assert!("".parse::<DoubleDouble>().is_err());
+    assert!("1.2.3".parse::<DoubleDouble>().is_err());
+    assert!("1e".parse::<DoubleDouble>().is_err());
+    assert_eq!(dd("0").to_string(), "0");
+    assert_eq!(dd("1.5").to_string(), "1.5");
+    assert_eq!(dd("-250").to_string(), "-250");
+    assert_eq!(dd("0.001").to_string(), "0.001");
+    // Digits past the precision of an f64 survive the round trip.
+    let deep = "-0.7436438870371587047521915061";
+    assert_eq!(dd(deep).to_string(), deep);
+    assert_eq!(
+        dd("0.1234567890123456789012345678912").to_string().len(),
+        33
+    );
+    assert_eq!(dd("9.99999999999999999999999999999999").to_string(), "10");
+}
+
+#[test]
+fn test_deep_zoom() {
+    // Two points 1e-20 apart near the boundary of the set: f64 can't tell them apart, but a
+    // double-double can, and the difference shows up in the escape times.
+    let dd = |s: &str| s.parse::<DoubleDouble>().unwrap();
+    let (upper_left, lower_right) = (
+        Complex {
+            re: dd("-0.74364388703715870475"),
+            im: dd("0.13182590420531197049"),
+        },
+        Complex {
+            re: dd("-0.74364388703715870465"),
+            im: dd("0.13182590420531197039"),
+        },
+    );
+    let narrow = (complex_to_f64(upper_left), complex_to_f64(lower_right));
+    assert_eq!(
+        crate::pixel_to_point((10, 10), (0, 0), narrow.0, narrow.1),
+        crate::pixel_to_point((10, 10), (1, 0), narrow.0, narrow.1)
+    );
+    let a = crate::pixel_to_point((10, 10), (0, 0), upper_left, lower_right);
+    let b = crate::pixel_to_point((10, 10), (1, 0), upper_left, lower_right);
+    assert!(a.re < b.re);
+    assert!(((b.re - a.re).to_f64() - 1e-20).abs() < 1e-30);
+    let zero = Complex::new(DoubleDouble::zero(), DoubleDouble::zero());
+    let (flat, deep): (Vec<_>, Vec<_>) = (0..10)
+        .map(|x| {
+            let narrow = crate::pixel_to_point((10, 10), (x, 5), narrow.0, narrow.1);
+            let deep = crate::pixel_to_point((10, 10), (x, 5), upper_left, lower_right);
+            (
+                crate::escape_time(Complex::new(0.0, 0.0), narrow, 100_000u32, 2.0),
+                crate::escape_time(zero, deep, 100_000u32, 2.0),
+            )
+        })
+        .unzip();
+    assert!(flat.windows(2).all(|w| w[0] == w[1]));
+    assert!(deep.windows(2).any(|w| w[0] != w[1]));
+}
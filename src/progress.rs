@@ -0,0 +1,215 @@
+use crate::i18n::Lang;
+use crate::log::{Field, Logger};
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Width of the redrawing progress bar, in characters.
+const BAR_WIDTH: usize = 30;
+
+/// Plain progress lines are printed at every tenth of the work, and at least this often.
+const PLAIN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How progress is shown on standard error.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ProgressMode {
+    /// A bar if standard error is a terminal, and nothing otherwise.
+    #[default]
+    Auto,
+    Off,
+    /// A bar redrawn in place, for terminals.
+    Bar,
+    /// A line of text now and then, for screen readers, dumb terminals and logs.
+    Plain,
+}
+
+impl ProgressMode {
+    pub fn parse(s: &str) -> Option<ProgressMode> {
+        match s {
+            "auto" => Some(ProgressMode::Auto),
+            "off" => Some(ProgressMode::Off),
+            "bar" => Some(ProgressMode::Bar),
+            "plain" => Some(ProgressMode::Plain),
+            _ => None,
+        }
+    }
+}
+
+/// Progress through the rows of one stage of the render, shared between the threads doing it.
+pub struct Progress {
+    mode: ProgressMode,
+    stage: &'static str,
+    lang: Lang,
+    log: Logger,
+    total: usize,
+    /// Number of pixels in each row, to tell how fast the render goes.
+    width: usize,
+    done: AtomicUsize,
+    start: Instant,
+    /// When the last line was printed, and at what percentage.
+    last: Mutex<(Instant, usize)>,
+}
+
+impl Progress {
+    pub fn new(
+        mode: ProgressMode,
+        stage: &'static str,
+        lang: Lang,
+        log: Logger,
+        (width, total): (usize, usize),
+    ) -> Self {
+        let mode = match mode {
+            ProgressMode::Auto if std::io::stderr().is_terminal() => ProgressMode::Bar,
+            ProgressMode::Auto => ProgressMode::Off,
+            mode => mode,
+        };
+        let start = Instant::now();
+        Progress {
+            mode,
+            stage,
+            lang,
+            log,
+            total,
+            width,
+            done: AtomicUsize::new(0),
+            start,
+            last: Mutex::new((start, 0)),
+        }
+    }
+
+    /// Count `rows` more rows, starting at row `top`, as done, and show the progress if it's
+    /// time to.
+    pub fn advance(&self, top: usize, rows: usize) {
+        let done = self.done.fetch_add(rows, Ordering::SeqCst) + rows;
+        self.log.event(
+            "band_completed",
+            &[
+                ("stage", Field::Str(self.stage)),
+                ("top", Field::Int(top as u64)),
+                ("rows", Field::Int(rows as u64)),
+            ],
+        );
+        if self.mode == ProgressMode::Off {
+            return;
+        }
+        let percent = done * 100 / self.total.max(1);
+        let elapsed = self.start.elapsed();
+        let eta = eta(elapsed, done, self.total);
+        let rate = (done * self.width) as f64 / elapsed.as_secs_f64().max(1e-3);
+        let mut last = self.last.lock().unwrap();
+        // Another thread may have reported more rows in the meantime.
+        if percent < last.1 {
+            return;
+        }
+        let line = match self.mode {
+            ProgressMode::Bar if percent != last.1 || done == self.total => {
+                format!("\r{}", bar_line(self.stage, percent, rate, eta))
+            }
+            ProgressMode::Plain
+                if percent / 10 != last.1 / 10
+                    || last.0.elapsed() >= PLAIN_INTERVAL
+                    || done == self.total =>
+            {
+                format!(
+                    "{}\n",
+                    self.lang
+                        .translate(&plain_line(self.stage, percent, rate, eta))
+                )
+            }
+            _ => return,
+        };
+        *last = (Instant::now(), percent);
+        let mut stderr = std::io::stderr().lock();
+        let _ = stderr.write_all(line.as_bytes());
+        if self.mode == ProgressMode::Bar && done == self.total {
+            let _ = stderr.write_all(b"\n");
+        }
+    }
+}
+
+/// Estimate the time left from the time taken for `done` out of `total` rows.
+fn eta(elapsed: Duration, done: usize, total: usize) -> Duration {
+    if done == 0 {
+        return Duration::ZERO;
+    }
+    elapsed.mul_f64((total - done.min(total)) as f64 / done as f64)
+}
+
+/// A line of text describing the progress of `stage`, going at `rate` pixels a second.
+fn plain_line(stage: &str, percent: usize, rate: f64, eta: Duration) -> String {
+    format!(
+        "{}: {}% done, {} pixels a second, about {} s left",
+        stage,
+        percent,
+        rate.round(),
+        eta.as_secs_f64().ceil()
+    )
+}
+
+/// A progress bar for `stage`, going at `rate` pixels a second, to be redrawn over the previous
+/// one.
+fn bar_line(stage: &str, percent: usize, rate: f64, eta: Duration) -> String {
+    let filled = percent.min(100) * BAR_WIDTH / 100;
+    format!(
+        "{:<8} [{}{}] {:>3}% {:>10} ETA {:>4} s",
+        stage,
+        "#".repeat(filled),
+        " ".repeat(BAR_WIDTH - filled),
+        percent,
+        format_rate(rate),
+        eta.as_secs_f64().ceil()
+    )
+}
+
+/// A rate of `rate` pixels a second, shortened with a metric prefix.
+fn format_rate(rate: f64) -> String {
+    match rate {
+        r if r >= 1e9 => format!("{:.1} Gpx/s", r / 1e9),
+        r if r >= 1e6 => format!("{:.1} Mpx/s", r / 1e6),
+        r if r >= 1e3 => format!("{:.1} kpx/s", r / 1e3),
+        r => format!("{:.0} px/s", r),
+    }
+}
+
+#[test]
+fn test_progress_lines() {
+    assert_eq!(
+        eta(Duration::from_secs(10), 25, 100),
+        Duration::from_secs(30)
+    );
+    assert_eq!(eta(Duration::from_secs(10), 0, 100), Duration::ZERO);
+    assert_eq!(
+        plain_line("color", 42, 1234.4, Duration::from_millis(2500)),
+        "color: 42% done, 1234 pixels a second, about 3 s left"
+    );
+    assert_eq!(
+        bar_line("color", 50, 2.5e6, Duration::from_secs(7)),
+        format!(
+            "color    [{}{}]  50%  2.5 Mpx/s ETA    7 s",
+            "#".repeat(15),
+            " ".repeat(15)
+        )
+    );
+    assert_eq!(format_rate(640.2), "640 px/s");
+    assert_eq!(format_rate(81_500.0), "81.5 kpx/s");
+    assert_eq!(format_rate(3e9), "3.0 Gpx/s");
+    assert_eq!(
+        Lang::Ru.translate(&plain_line("color", 42, 900.0, Duration::from_secs(3))),
+        "color: готово 42%, 900 пикселей в секунду, осталось около 3 с"
+    );
+}
+
+#[test]
+fn test_advance() {
+    let progress = Progress::new(
+        ProgressMode::Off,
+        "color",
+        Lang::En,
+        Logger::default(),
+        (8, 10),
+    );
+    progress.advance(0, 4);
+    progress.advance(4, 6);
+    assert_eq!(progress.done.load(Ordering::SeqCst), 10);
+}
@@ -0,0 +1,77 @@
+use num::Complex;
+
+/// The SplitMix64 generator: tiny, fast and good enough for sampling, and, unlike the thread
+/// local generators of crates like `rand`, the same on every platform for a given seed.
+#[derive(Clone, Debug)]
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        SplitMix64(seed)
+    }
+
+    /// A generator for the samples around `point`, so that every point of the image gets its
+    /// own stream of numbers no matter which thread renders it or in what order.
+    pub fn for_point(seed: u64, point: Complex<f64>) -> Self {
+        let mut mixer = SplitMix64(seed ^ point.re.to_bits());
+        let mixed = mixer.next_u64() ^ point.im.to_bits();
+        SplitMix64(SplitMix64(mixed).next_u64())
+    }
+
+    /// A generator for the numbered stream `stream` of the ones drawn from `seed`, so that work
+    /// split into numbered pieces gets the same numbers however the pieces are shared out.
+    pub fn for_stream(seed: u64, stream: u64) -> Self {
+        let mixed = SplitMix64(seed).next_u64() ^ stream;
+        SplitMix64(SplitMix64(mixed).next_u64())
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number uniformly distributed in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[test]
+fn test_split_mix() {
+    // First outputs of the reference implementation seeded with 0.
+    let mut rng = SplitMix64::new(0);
+    assert_eq!(rng.next_u64(), 0xe220a8397b1dcdaf);
+    assert_eq!(rng.next_u64(), 0x6e789e6aa1b965f4);
+
+    for _ in 0..1000 {
+        let x = rng.next_f64();
+        assert!((0.0..1.0).contains(&x));
+    }
+
+    let point = Complex { re: -0.5, im: 0.25 };
+    let mut a = SplitMix64::for_point(7, point);
+    let mut b = SplitMix64::for_point(7, point);
+    assert_eq!(a.next_u64(), b.next_u64());
+    let mut c = SplitMix64::for_point(8, point);
+    let mut d = SplitMix64::for_point(7, Complex { re: 0.25, im: -0.5 });
+    let first = a.next_u64();
+    assert_ne!(first, c.next_u64());
+    assert_ne!(first, d.next_u64());
+
+    let streams: Vec<u64> = (0..4)
+        .map(|stream| SplitMix64::for_stream(7, stream).next_u64())
+        .collect();
+    assert_eq!(streams[2], SplitMix64::for_stream(7, 2).next_u64());
+    assert!((1..4).all(|i| !streams[..i].contains(&streams[i])));
+}
+
+/// A seed for runs that weren't given one, taken from the clock.
+pub fn clock_seed() -> u64 {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    SplitMix64::new(now.as_nanos() as u64).next_u64()
+}
@@ -0,0 +1,380 @@
+use crate::coords::CoordFormat;
+use crate::layers::PointData;
+use num::Complex;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// What is known about a single pixel, as a row of a table.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Record {
+    pub x: u32,
+    pub y: u32,
+    pub re: f64,
+    pub im: f64,
+    /// Number of iterations before escaping, or the limit for points that don't escape.
+    pub iterations: u32,
+    pub smooth: f32,
+    pub escaped: bool,
+}
+
+/// The records of every pixel of `data`, row by row, for an image with dimensions `bounds`
+/// between `upper_left` and `lower_right`, iterated at most `limit` times.
+pub fn records(
+    data: &[PointData],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    limit: u32,
+) -> impl Iterator<Item = Record> + '_ {
+    data.iter().enumerate().map(move |(i, point)| {
+        let pixel = (i % bounds.0, i / bounds.0);
+        let c = crate::pixel_to_point(bounds, pixel, upper_left, lower_right);
+        Record {
+            x: pixel.0 as u32,
+            y: pixel.1 as u32,
+            re: c.re,
+            im: c.im,
+            iterations: point.iterations as u32,
+            smooth: point.smooth,
+            escaped: (point.iterations as u32) < limit,
+        }
+    })
+}
+
+/// Write `records` to `filename` as CSV, with a header row naming the columns and the
+/// coordinates written in `coords`.
+pub fn write_csv(
+    filename: &str,
+    records: impl Iterator<Item = Record>,
+    coords: CoordFormat,
+) -> Result<(), std::io::Error> {
+    let mut output = BufWriter::new(File::create(filename)?);
+    writeln!(output, "x,y,re,im,iterations,smooth,escaped")?;
+    for r in records {
+        writeln!(
+            output,
+            "{},{},{},{},{},{},{}",
+            r.x,
+            r.y,
+            coords.format(r.re),
+            coords.format(r.im),
+            r.iterations,
+            r.smooth,
+            r.escaped
+        )?;
+    }
+    output.flush()
+}
+
+#[test]
+fn test_write_csv() {
+    let filename = std::env::temp_dir().join("rust-experiments-test-records.csv");
+    let filename = filename.to_str().unwrap();
+    let data = [
+        PointData {
+            iterations: 3.0,
+            smooth: 2.5,
+            ..PointData::default()
+        },
+        PointData {
+            iterations: 255.0,
+            smooth: 255.0,
+            ..PointData::default()
+        },
+    ];
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.0 }, Complex { re: 0.0, im: 0.0 });
+    write_csv(
+        filename,
+        records(&data, (2, 1), upper_left, lower_right, 255),
+        CoordFormat::Shortest,
+    )
+    .unwrap();
+    assert_eq!(
+        std::fs::read_to_string(filename).unwrap(),
+        "x,y,re,im,iterations,smooth,escaped\n0,0,-2,1,3,2.5,true\n1,0,-1,1,255,255,false\n"
+    );
+    write_csv(
+        filename,
+        records(&data, (2, 1), upper_left, lower_right, 255),
+        CoordFormat::Hex,
+    )
+    .unwrap();
+    assert!(std::fs::read_to_string(filename)
+        .unwrap()
+        .ends_with("1,0,-0x1p+0,0x1p+0,255,255,false\n"));
+    std::fs::remove_file(filename).unwrap();
+}
+
+/// Largest number of rows in a Parquet row group, which keeps each column's page well below the
+/// 2 GiB a page can hold, and the buffers small.
+const ROWS_PER_GROUP: usize = 1 << 20;
+
+/// Parquet physical types of the columns.
+const BOOLEAN: i32 = 0;
+const INT32: i32 = 1;
+const FLOAT: i32 = 4;
+const DOUBLE: i32 = 5;
+
+/// Names and Parquet physical types of the columns, in the order of the fields of `Record`.
+const COLUMNS: [(&str, i32); 7] = [
+    ("x", INT32),
+    ("y", INT32),
+    ("re", DOUBLE),
+    ("im", DOUBLE),
+    ("iterations", INT32),
+    ("smooth", FLOAT),
+    ("escaped", BOOLEAN),
+];
+
+/// Thrift compact protocol type codes used by the Parquet metadata.
+const T_I32: u8 = 5;
+const T_I64: u8 = 6;
+const T_BINARY: u8 = 8;
+const T_LIST: u8 = 9;
+const T_STRUCT: u8 = 12;
+
+/// Just enough of the Thrift compact protocol to write Parquet metadata.
+#[derive(Default)]
+struct Thrift {
+    bytes: Vec<u8>,
+    /// Last field id written in each of the structs being written, innermost last.
+    last_field: Vec<i16>,
+}
+
+impl Thrift {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    fn zigzag(&mut self, value: i64) {
+        self.varint(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    fn field(&mut self, id: i16, kind: u8) {
+        let last = self
+            .last_field
+            .last_mut()
+            .expect("field outside of a struct");
+        let delta = id - std::mem::replace(last, id);
+        if (1..=15).contains(&delta) {
+            self.bytes.push((delta as u8) << 4 | kind);
+        } else {
+            self.bytes.push(kind);
+            self.zigzag(id as i64);
+        }
+    }
+
+    fn i32(&mut self, id: i16, value: i32) {
+        self.field(id, T_I32);
+        self.zigzag(value as i64);
+    }
+
+    fn i64(&mut self, id: i16, value: i64) {
+        self.field(id, T_I64);
+        self.zigzag(value);
+    }
+
+    fn string(&mut self, id: i16, value: &str) {
+        self.field(id, T_BINARY);
+        self.varint(value.len() as u64);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    /// Start a list of `len` elements of type `kind`, which are written without field headers.
+    fn list(&mut self, id: i16, kind: u8, len: usize) {
+        self.field(id, T_LIST);
+        if len < 15 {
+            self.bytes.push((len as u8) << 4 | kind);
+        } else {
+            self.bytes.push(0xf0 | kind);
+            self.varint(len as u64);
+        }
+    }
+
+    /// Start a struct that is a field of the current one.
+    fn begin_field_struct(&mut self, id: i16) {
+        self.field(id, T_STRUCT);
+        self.begin_struct();
+    }
+
+    /// Start a struct that is the whole message or an element of a list.
+    fn begin_struct(&mut self) {
+        self.last_field.push(0);
+    }
+
+    fn end_struct(&mut self) {
+        self.bytes.push(0);
+        self.last_field.pop();
+    }
+}
+
+#[test]
+fn test_thrift() {
+    let mut thrift = Thrift::default();
+    thrift.begin_struct();
+    thrift.i32(1, -1);
+    thrift.i64(3, 300);
+    thrift.string(20, "ab");
+    thrift.list(21, T_I32, 1);
+    thrift.zigzag(2);
+    thrift.end_struct();
+    assert_eq!(
+        thrift.bytes,
+        [0x15, 0x01, 0x26, 0xd8, 0x04, 0x08, 0x28, 2, b'a', b'b', 0x19, 0x15, 0x04, 0x00]
+    );
+}
+
+/// Encode the values of column `column` of `rows` with Parquet's plain encoding.
+fn encode_column(rows: &[Record], column: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for r in rows {
+        match column {
+            0 => bytes.extend_from_slice(&(r.x as i32).to_le_bytes()),
+            1 => bytes.extend_from_slice(&(r.y as i32).to_le_bytes()),
+            2 => bytes.extend_from_slice(&r.re.to_le_bytes()),
+            3 => bytes.extend_from_slice(&r.im.to_le_bytes()),
+            4 => bytes.extend_from_slice(&(r.iterations as i32).to_le_bytes()),
+            5 => bytes.extend_from_slice(&r.smooth.to_le_bytes()),
+            _ => {}
+        }
+    }
+    if column == 6 {
+        // Booleans are packed eight to a byte, the first in the lowest bit.
+        bytes = rows
+            .chunks(8)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0, |byte, (i, r)| byte | (r.escaped as u8) << i)
+            })
+            .collect();
+    }
+    bytes
+}
+
+/// Where a column chunk ended up in the file, for the footer.
+struct ColumnChunk {
+    offset: u64,
+    size: u64,
+    values: usize,
+}
+
+/// Write `records` to `filename` as an uncompressed Parquet file, with one required column per
+/// field of `Record`.
+pub fn write_parquet(
+    filename: &str,
+    records: impl Iterator<Item = Record>,
+) -> Result<(), std::io::Error> {
+    let mut output = BufWriter::new(File::create(filename)?);
+    output.write_all(b"PAR1")?;
+    let mut offset = 4;
+    let mut groups: Vec<Vec<ColumnChunk>> = Vec::new();
+    let mut records = records.peekable();
+    while records.peek().is_some() {
+        let rows: Vec<Record> = records.by_ref().take(ROWS_PER_GROUP).collect();
+        let mut chunks = Vec::new();
+        for column in 0..COLUMNS.len() {
+            let values = encode_column(&rows, column);
+            let mut header = Thrift::default();
+            header.begin_struct();
+            // A data page with plain encoded values, and RLE for the (absent) levels.
+            header.i32(1, 0);
+            header.i32(2, values.len() as i32);
+            header.i32(3, values.len() as i32);
+            header.begin_field_struct(5);
+            header.i32(1, rows.len() as i32);
+            header.i32(2, 0);
+            header.i32(3, 3);
+            header.i32(4, 3);
+            header.end_struct();
+            header.end_struct();
+            output.write_all(&header.bytes)?;
+            output.write_all(&values)?;
+            let size = (header.bytes.len() + values.len()) as u64;
+            chunks.push(ColumnChunk {
+                offset,
+                size,
+                values: rows.len(),
+            });
+            offset += size;
+        }
+        groups.push(chunks);
+    }
+
+    let mut footer = Thrift::default();
+    footer.begin_struct();
+    footer.i32(1, 1);
+    footer.list(2, T_STRUCT, COLUMNS.len() + 1);
+    footer.begin_struct();
+    footer.string(4, "schema");
+    footer.i32(5, COLUMNS.len() as i32);
+    footer.end_struct();
+    for (name, kind) in COLUMNS {
+        footer.begin_struct();
+        footer.i32(1, kind);
+        // Required: no definition or repetition levels.
+        footer.i32(3, 0);
+        footer.string(4, name);
+        footer.end_struct();
+    }
+    let rows: usize = groups.iter().map(|chunks| chunks[0].values).sum();
+    footer.i64(3, rows as i64);
+    footer.list(4, T_STRUCT, groups.len());
+    for chunks in &groups {
+        footer.begin_struct();
+        footer.list(1, T_STRUCT, chunks.len());
+        for (chunk, (name, kind)) in chunks.iter().zip(COLUMNS) {
+            footer.begin_struct();
+            footer.i64(2, chunk.offset as i64);
+            footer.begin_field_struct(3);
+            footer.i32(1, kind);
+            footer.list(2, T_I32, 1);
+            footer.zigzag(0);
+            footer.list(3, T_BINARY, 1);
+            footer.varint(name.len() as u64);
+            footer.bytes.extend_from_slice(name.as_bytes());
+            footer.i32(4, 0);
+            footer.i64(5, chunk.values as i64);
+            footer.i64(6, chunk.size as i64);
+            footer.i64(7, chunk.size as i64);
+            footer.i64(9, chunk.offset as i64);
+            footer.end_struct();
+            footer.end_struct();
+        }
+        footer.i64(2, chunks.iter().map(|chunk| chunk.size).sum::<u64>() as i64);
+        footer.i64(3, chunks[0].values as i64);
+        footer.end_struct();
+    }
+    footer.string(6, concat!("rust-experiments ", env!("CARGO_PKG_VERSION")));
+    footer.end_struct();
+    output.write_all(&footer.bytes)?;
+    output.write_all(&(footer.bytes.len() as u32).to_le_bytes())?;
+    output.write_all(b"PAR1")?;
+    output.flush()
+}
+
+#[test]
+fn test_write_parquet() {
+    let filename = std::env::temp_dir().join("rust-experiments-test-records.parquet");
+    let filename = filename.to_str().unwrap();
+    let data = vec![PointData::default(); 10];
+    let (upper_left, lower_right) = (Complex { re: 0.0, im: 1.0 }, Complex { re: 1.0, im: 0.0 });
+    write_parquet(
+        filename,
+        records(&data, (5, 2), upper_left, lower_right, 255),
+    )
+    .unwrap();
+    let bytes = std::fs::read(filename).unwrap();
+    assert_eq!(&bytes[..4], b"PAR1");
+    assert_eq!(&bytes[bytes.len() - 4..], b"PAR1");
+    let footer = u32::from_le_bytes(bytes[bytes.len() - 8..bytes.len() - 4].try_into().unwrap());
+    // The escaped column of the only row group is last before the footer.
+    let footer_start = bytes.len() - 8 - footer as usize;
+    assert_eq!(&bytes[footer_start - 2..footer_start], &[0xff, 0x03]);
+    std::fs::remove_file(filename).unwrap();
+}
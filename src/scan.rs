@@ -0,0 +1,235 @@
+use crate::{animation, palette, Fractal, Power, Renderer};
+use num::Complex;
+use std::str::FromStr;
+
+/// Frames of the scan when `--frames` isn't given.
+const DEFAULT_FRAMES: usize = 50;
+
+/// Frames per second of the scan when `--fps` isn't given.
+const DEFAULT_FPS: u16 = 25;
+
+/// A parameter of the iteration that a scan varies from frame to frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Parameter {
+    /// The exponent `d` of `z = z^d + c`.
+    Power,
+    /// The escape radius.
+    Bailout,
+}
+
+impl Parameter {
+    pub fn parse(s: &str) -> Option<Parameter> {
+        match s {
+            "power" => Some(Parameter::Power),
+            "bailout" => Some(Parameter::Bailout),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Parameter::Power => "power",
+            Parameter::Bailout => "bailout",
+        }
+    }
+
+    /// Whether the parameter can take `value`: exponents above 1, and any positive radius.
+    fn allows(self, value: f64) -> bool {
+        match self {
+            Parameter::Power => Power::from_value(value).is_some(),
+            Parameter::Bailout => value > 0.0 && value.is_finite(),
+        }
+    }
+}
+
+/// What scan to render and where to write it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScanOptions {
+    /// File name of the animation, a GIF or APNG file.
+    pub filename: String,
+    pub animation: animation::Container,
+    /// Dimensions of each frame, in pixels.
+    pub bounds: (usize, usize),
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+    pub parameter: Parameter,
+    /// Values of the parameter in the first and the last frame.
+    pub range: (f64, f64),
+    pub frames: usize,
+    /// Frames per second the animation plays at.
+    pub fps: u16,
+    /// Render the Julia set of this `c`, in the dynamical plane, instead of the Mandelbrot set.
+    pub julia: Option<Complex<f64>>,
+    pub palette: Option<palette::Palette>,
+    pub threads: usize,
+}
+
+impl ScanOptions {
+    /// The value of the parameter in frame number `frame`, in even steps from the start of the
+    /// range to its end.
+    pub fn value(&self, frame: usize) -> f64 {
+        let t = match self.frames {
+            1 => 0.0,
+            frames => frame as f64 / (frames - 1) as f64,
+        };
+        self.range.0 + (self.range.1 - self.range.0) * t
+    }
+
+    /// What to render in frame number `frame`.
+    pub fn renderer(&self, frame: usize) -> Renderer {
+        let mut renderer = Renderer {
+            fractal: match self.julia {
+                Some(c) => Fractal::Julia(c),
+                None => Fractal::Mandelbrot,
+            },
+            palette: self.palette,
+            ..Renderer::default()
+        };
+        let value = self.value(frame);
+        match self.parameter {
+            // Every value between two exponents above 1 is above 1 too.
+            Parameter::Power => renderer.power = Power::from_value(value).unwrap_or_default(),
+            Parameter::Bailout => renderer.bailout = Some(value),
+        }
+        renderer
+    }
+}
+
+/// Parse the arguments following `scan`: the animation file, the dimensions and corners of each
+/// frame, the parameter scanned and the values it goes from and to, and options.
+pub fn parse_args(args: &[String]) -> Result<ScanOptions, String> {
+    let (filename, pixels, upper_left, lower_right, parameter, from, to) = match args {
+        [filename, pixels, upper_left, lower_right, parameter, from, to, ..]
+            if !to.starts_with("--") =>
+        {
+            (
+                filename,
+                pixels,
+                upper_left,
+                lower_right,
+                parameter,
+                from,
+                to,
+            )
+        }
+        _ => {
+            return Err(
+                "expected FILE PIXELS UPPERLEFT LOWERRIGHT PARAMETER FROM TO after scan"
+                    .to_string(),
+            )
+        }
+    };
+    let parameter =
+        Parameter::parse(parameter).ok_or_else(|| format!("unknown parameter {}", parameter))?;
+    let value = |s: &str| {
+        f64::from_str(s)
+            .ok()
+            .filter(|&value| parameter.allows(value))
+            .ok_or_else(|| format!("error parsing {} {}", parameter.name(), s))
+    };
+    let mut options = ScanOptions {
+        filename: filename.clone(),
+        animation: animation::Container::from_filename(filename)
+            .ok_or_else(|| format!("{} isn't a GIF or APNG file", filename))?,
+        bounds: crate::parse_pair(pixels, 'x')
+            .filter(|&(width, height)| width > 0 && height > 0)
+            .ok_or("error parsing image dimensions")?,
+        upper_left: crate::parse_complex(upper_left).ok_or("error parsing upper left corner")?,
+        lower_right: crate::parse_complex(lower_right).ok_or("error parsing lower right corner")?,
+        parameter,
+        range: (value(from)?, value(to)?),
+        frames: DEFAULT_FRAMES,
+        fps: DEFAULT_FPS,
+        julia: None,
+        palette: None,
+        threads: num_cpus::get(),
+    };
+
+    let mut args = args[7..].iter();
+    while let Some(name) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {}", name))?;
+        match name.as_str() {
+            "--frames" => {
+                options.frames = usize::from_str(value)
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or("error parsing --frames")?
+            }
+            "--fps" => {
+                options.fps = u16::from_str(value)
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or("error parsing --fps")?
+            }
+            "--julia" => {
+                options.julia = Some(crate::parse_complex(value).ok_or("error parsing --julia")?)
+            }
+            "--palette" => {
+                options.palette = Some(
+                    palette::Palette::parse(value)
+                        .ok_or_else(|| format!("unknown palette {}", value))?,
+                )
+            }
+            "--threads" => {
+                options.threads = usize::from_str(value)
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or("error parsing --threads")?
+            }
+            // Read by `main` before the subcommand runs.
+            "--error-format" | "--lang" => {}
+            _ => return Err(format!("unknown option {}", name)),
+        }
+    }
+    Ok(options)
+}
+
+#[test]
+fn test_parse_args() {
+    let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+    let options = parse_args(&args(
+        "scan.gif 320x240 -2,1.5 2,-1.5 power 2 5 --frames 31 --julia -0.8,0.156",
+    ))
+    .unwrap();
+    assert_eq!(options.animation, animation::Container::Gif);
+    assert_eq!(options.bounds, (320, 240));
+    assert_eq!(options.parameter, Parameter::Power);
+    assert_eq!(options.range, (2.0, 5.0));
+    assert_eq!(options.frames, 31);
+    assert_eq!(
+        options.julia,
+        Some(Complex {
+            re: -0.8,
+            im: 0.156
+        })
+    );
+    let options = parse_args(&args("scan.png 32x24 -2,1 1,-1 bailout 10 2")).unwrap();
+    assert_eq!(options.range, (10.0, 2.0));
+    assert!(parse_args(&args("scan.gif 32x24 -2,1 1,-1 power 1 5")).is_err());
+    assert!(parse_args(&args("scan.gif 32x24 -2,1 1,-1 bailout 0 5")).is_err());
+    assert!(parse_args(&args("scan.gif 32x24 -2,1 1,-1 zoom 1 5")).is_err());
+    assert!(parse_args(&args("scan.gif 32x24 -2,1 1,-1 power 2")).is_err());
+    assert!(parse_args(&args("scan.mp4 32x24 -2,1 1,-1 power 2 5")).is_err());
+}
+
+#[test]
+fn test_renderer() {
+    let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+    let options = parse_args(&args("scan.gif 32x24 -2,1 1,-1 power 2 5 --frames 7")).unwrap();
+    assert_eq!(options.value(0), 2.0);
+    assert_eq!(options.value(6), 5.0);
+    assert_eq!(options.renderer(0).power, Power::Integer(2));
+    assert_eq!(options.renderer(1).power, Power::Real(2.5));
+    assert_eq!(options.renderer(6).power, Power::Integer(5));
+    assert_eq!(options.renderer(3).fractal, Fractal::Mandelbrot);
+    let options = ScanOptions {
+        parameter: Parameter::Bailout,
+        range: (2.0, 8.0),
+        frames: 1,
+        ..options
+    };
+    assert_eq!(options.renderer(0).bailout, Some(2.0));
+    assert_eq!(options.renderer(0).power, Power::default());
+}
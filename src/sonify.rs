@@ -0,0 +1,198 @@
+use num::Complex;
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::str::FromStr;
+
+/// Samples per second of the audio written.
+const SAMPLE_RATE: u32 = 44100;
+
+/// Loudness of each voice, as a fraction of full scale.
+const AMPLITUDE: f64 = 0.3;
+
+/// What to play and where to write it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SonifyOptions {
+    pub filename: String,
+    /// The point whose orbit is played: `c` for the Mandelbrot set, the start of the orbit for
+    /// a Julia set.
+    pub point: Complex<f64>,
+    /// The constant `c` of the Julia set to follow the orbit in, if any.
+    pub julia: Option<Complex<f64>>,
+    /// Most iterations to play.
+    pub iterations: usize,
+    /// How long each iteration sounds, in milliseconds.
+    pub note_ms: u32,
+}
+
+/// Parse the arguments following `sonify`: the WAV file to write, the point, and options.
+pub fn parse_args(args: &[String]) -> Result<SonifyOptions, String> {
+    let (filename, point) = match args {
+        [filename, point, ..] if !point.starts_with("--") => (filename, point),
+        _ => return Err("expected FILE POINT after sonify".to_string()),
+    };
+    let mut options = SonifyOptions {
+        filename: filename.clone(),
+        point: crate::parse_complex(point).ok_or("error parsing point")?,
+        julia: None,
+        iterations: 64,
+        note_ms: 150,
+    };
+
+    let mut args = args[2..].iter();
+    while let Some(name) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {}", name))?;
+        match name.as_str() {
+            "--julia" => {
+                options.julia = Some(crate::parse_complex(value).ok_or("error parsing --julia")?)
+            }
+            "--iterations" => {
+                options.iterations = usize::from_str(value)
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or("error parsing --iterations")?
+            }
+            "--note-ms" => {
+                options.note_ms = u32::from_str(value)
+                    .ok()
+                    .filter(|&ms| ms > 0)
+                    .ok_or("error parsing --note-ms")?
+            }
+            // Read by `main` before the subcommand runs.
+            "--error-format" | "--lang" => {}
+            _ => return Err(format!("unknown option {}", name)),
+        }
+    }
+    Ok(options)
+}
+
+#[test]
+fn test_parse_args() {
+    let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+    let options = parse_args(&args("orbit.wav -0.75,0.1 --note-ms 80 --lang ru")).unwrap();
+    assert_eq!(options.filename, "orbit.wav");
+    assert_eq!(options.point, Complex { re: -0.75, im: 0.1 });
+    assert_eq!(options.note_ms, 80);
+    assert_eq!(options.iterations, 64);
+    assert!(parse_args(&args("orbit.wav")).is_err());
+    assert!(parse_args(&args("orbit.wav --julia 0,1")).is_err());
+    assert!(parse_args(&args("orbit.wav 0,0 --iterations 0")).is_err());
+    assert!(parse_args(&args("orbit.wav 0,0 --speed 2")).is_err());
+}
+
+/// The orbit of `z = z*z + c` starting from `z0`, up to `limit` points, ending with the first
+/// point to leave the circle of radius 2 if it escapes.
+pub fn orbit(z0: Complex<f64>, c: Complex<f64>, limit: usize) -> Vec<Complex<f64>> {
+    let mut orbit = Vec::with_capacity(limit);
+    let mut z = z0;
+    while orbit.len() < limit {
+        z = z * z + c;
+        orbit.push(z);
+        if z.norm_sqr() > 4.0 {
+            break;
+        }
+    }
+    orbit
+}
+
+#[test]
+fn test_orbit() {
+    let zero = Complex { re: 0.0, im: 0.0 };
+    // 1, 2, 5
+    assert_eq!(
+        orbit(zero, Complex { re: 1.0, im: 0.0 }, 10),
+        vec![
+            Complex { re: 1.0, im: 0.0 },
+            Complex { re: 2.0, im: 0.0 },
+            Complex { re: 5.0, im: 0.0 }
+        ]
+    );
+    assert_eq!(orbit(zero, Complex { re: -1.0, im: 0.0 }, 10).len(), 10);
+}
+
+/// The pitches, in Hz, that a point of an orbit is played at: its distance from the origin
+/// spans four octaves up from A2, and its angle two octaves up from A3.
+fn frequencies(z: Complex<f64>) -> (f64, f64) {
+    let norm = (z.norm() / 2.0).min(1.0);
+    let turn = (z.arg() + PI) / (2.0 * PI);
+    (110.0 * 2f64.powf(4.0 * norm), 220.0 * 2f64.powf(2.0 * turn))
+}
+
+#[test]
+fn test_frequencies() {
+    assert_eq!(frequencies(Complex { re: 0.0, im: 0.0 }).0, 110.0);
+    assert_eq!(frequencies(Complex { re: 5.0, im: 0.0 }).0, 1760.0);
+    assert!((frequencies(Complex { re: 1.0, im: 0.0 }).1 - 440.0).abs() < 1e-9);
+}
+
+/// Play each point of `orbit` for `note_ms` milliseconds as interleaved stereo samples, with
+/// the pitch given by its distance from the origin on the left and by its angle on the right.
+pub fn synthesize(orbit: &[Complex<f64>], note_ms: u32) -> Vec<i16> {
+    let note_samples = (SAMPLE_RATE as u64 * note_ms as u64 / 1000) as usize;
+    let mut samples = Vec::with_capacity(orbit.len() * note_samples * 2);
+    // Carry the phases over from note to note so that the changes of pitch don't click.
+    let mut phases = (0.0f64, 0.0f64);
+    for &z in orbit {
+        let (left, right) = frequencies(z);
+        for _ in 0..note_samples {
+            for phase in [phases.0, phases.1] {
+                samples.push((phase.sin() * AMPLITUDE * i16::MAX as f64) as i16);
+            }
+            phases.0 = (phases.0 + 2.0 * PI * left / SAMPLE_RATE as f64) % (2.0 * PI);
+            phases.1 = (phases.1 + 2.0 * PI * right / SAMPLE_RATE as f64) % (2.0 * PI);
+        }
+    }
+    samples
+}
+
+#[test]
+fn test_synthesize() {
+    let samples = synthesize(&[Complex { re: 1.0, im: 0.0 }; 2], 10);
+    assert_eq!(samples.len(), 2 * 441 * 2);
+    assert_eq!(&samples[..2], &[0, 0]);
+    let peak = (AMPLITUDE * i16::MAX as f64) as i16;
+    assert!(samples.iter().all(|s| s.abs() <= peak));
+}
+
+/// Write interleaved stereo `samples` to `filename` as a 16-bit PCM WAV file.
+pub fn write_wav(filename: &str, samples: &[i16]) -> Result<(), std::io::Error> {
+    let mut output = BufWriter::new(File::create(filename)?);
+    let data_len = samples.len() as u32 * 2;
+    let (channels, bits) = (2u16, 16u16);
+    let block_align = channels * bits / 8;
+
+    output.write_all(b"RIFF")?;
+    output.write_all(&(36 + data_len).to_le_bytes())?;
+    output.write_all(b"WAVEfmt ")?;
+    output.write_all(&16u32.to_le_bytes())?;
+    output.write_all(&1u16.to_le_bytes())?;
+    output.write_all(&channels.to_le_bytes())?;
+    output.write_all(&SAMPLE_RATE.to_le_bytes())?;
+    output.write_all(&(SAMPLE_RATE * block_align as u32).to_le_bytes())?;
+    output.write_all(&block_align.to_le_bytes())?;
+    output.write_all(&bits.to_le_bytes())?;
+    output.write_all(b"data")?;
+    output.write_all(&data_len.to_le_bytes())?;
+    for sample in samples {
+        output.write_all(&sample.to_le_bytes())?;
+    }
+    output.flush()
+}
+
+#[test]
+fn test_write_wav() {
+    let filename = std::env::temp_dir().join("rust-experiments-test.wav");
+    let filename = filename.to_str().unwrap();
+    write_wav(filename, &[1, -1, 2, -2]).unwrap();
+    let bytes = std::fs::read(filename).unwrap();
+    assert_eq!(bytes.len(), 44 + 8);
+    assert_eq!(&bytes[..4], b"RIFF");
+    assert_eq!(&bytes[4..8], &44u32.to_le_bytes());
+    assert_eq!(&bytes[8..16], b"WAVEfmt ");
+    assert_eq!(&bytes[24..28], &SAMPLE_RATE.to_le_bytes());
+    assert_eq!(&bytes[36..44], b"data\x08\x00\x00\x00");
+    assert_eq!(&bytes[44..], &[1, 0, 255, 255, 2, 0, 254, 255]);
+    std::fs::remove_file(filename).unwrap();
+}
@@ -0,0 +1,171 @@
+use std::time::{Duration, Instant};
+
+/// The stages a render goes through, in order.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stage {
+    /// Following orbits to collect the iteration data for EXR, SVG and HDR output, and the
+    /// escape counts for `--auto-range`.
+    Iterate,
+    /// Turning samples into gray levels, including supersampling.
+    Color,
+    /// Writing the output files.
+    Encode,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 3] = [Stage::Iterate, Stage::Color, Stage::Encode];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Iterate => "iterate",
+            Stage::Color => "color",
+            Stage::Encode => "encode",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Stage> {
+        Stage::ALL.into_iter().find(|stage| stage.name() == name)
+    }
+}
+
+/// Number of threads each stage runs on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StageThreads([usize; 3]);
+
+impl Default for StageThreads {
+    fn default() -> Self {
+        StageThreads([num_cpus::get(); 3])
+    }
+}
+
+impl StageThreads {
+    pub fn get(&self, stage: Stage) -> usize {
+        self.0[stage as usize]
+    }
+
+    /// Override the thread counts of the stages named in `s`, given as `"stage=N,..."`.
+    pub fn parse(&mut self, s: &str) -> Option<()> {
+        for setting in s.split(',') {
+            let (name, threads) = setting.split_once('=')?;
+            let threads: usize = threads.parse().ok().filter(|&n| n > 0)?;
+            self.0[Stage::from_name(name)? as usize] = threads;
+        }
+        Some(())
+    }
+
+    /// Limit every stage to at most `threads` threads.
+    pub fn cap(&mut self, threads: usize) {
+        for n in &mut self.0 {
+            *n = (*n).min(threads);
+        }
+    }
+}
+
+#[test]
+fn test_stage_threads() {
+    let mut threads = StageThreads([4; 3]);
+    assert_eq!(threads.parse("color=2,encode=1"), Some(()));
+    assert_eq!(threads.get(Stage::Iterate), 4);
+    assert_eq!(threads.get(Stage::Color), 2);
+    assert_eq!(threads.get(Stage::Encode), 1);
+    assert_eq!(threads.parse("color=0"), None);
+    assert_eq!(threads.parse("post=2"), None);
+    assert_eq!(threads.parse("color"), None);
+    threads.cap(3);
+    assert_eq!(threads.get(Stage::Iterate), 3);
+    assert_eq!(threads.get(Stage::Color), 2);
+}
+
+/// Wall-clock time spent in each stage.
+#[derive(Debug, Default)]
+pub struct Timings(Vec<(Stage, Duration)>);
+
+impl Timings {
+    /// Run `f`, adding the time it takes to the total of `stage`.
+    pub fn time<R>(&mut self, stage: Stage, f: impl FnOnce() -> R) -> R {
+        let start = Instant::now();
+        let result = f();
+        self.0.push((stage, start.elapsed()));
+        result
+    }
+
+    /// Describe the total time spent in each stage, one line each, skipping stages that
+    /// didn't run.
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        for stage in Stage::ALL {
+            let runs: Vec<_> = self.0.iter().filter(|(s, _)| *s == stage).collect();
+            if runs.is_empty() {
+                continue;
+            }
+            let total: Duration = runs.iter().map(|(_, duration)| *duration).sum();
+            report += &format!(
+                "{:<8} {:>10.3} ms\n",
+                stage.name(),
+                total.as_secs_f64() * 1e3
+            );
+        }
+        report
+    }
+}
+
+#[test]
+fn test_timings() {
+    let mut timings = Timings::default();
+    assert_eq!(timings.time(Stage::Encode, || 7), 7);
+    timings.time(Stage::Iterate, || ());
+    timings.time(Stage::Encode, || ());
+    let report = timings.report();
+    let names: Vec<_> = report.lines().map(|line| &line[..8]).collect();
+    assert_eq!(names, ["iterate ", "encode  "]);
+}
+
+/// Run `jobs` on at most `threads` threads at a time, returning the first error any of them
+/// ran into.
+pub fn run_parallel<'a>(
+    jobs: Vec<Box<dyn FnOnce() -> Result<(), String> + Send + 'a>>,
+    threads: usize,
+) -> Result<(), String> {
+    let mut jobs = jobs.into_iter().peekable();
+    while jobs.peek().is_some() {
+        let batch: Vec<_> = jobs.by_ref().take(threads.max(1)).collect();
+        crossbeam::scope(|spawner| {
+            let handles: Vec<_> = batch
+                .into_iter()
+                .map(|job| spawner.spawn(move |_| job()))
+                .collect();
+            handles
+                .into_iter()
+                .try_for_each(|handle| handle.join().unwrap())
+        })
+        .unwrap()?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_run_parallel() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let done = AtomicUsize::new(0);
+    let jobs = |fail: usize| {
+        (0..5)
+            .map(|i| {
+                let done = &done;
+                Box::new(move || {
+                    done.fetch_add(1, Ordering::SeqCst);
+                    if i == fail {
+                        Err(format!("job {} failed", i))
+                    } else {
+                        Ok(())
+                    }
+                }) as Box<dyn FnOnce() -> Result<(), String> + Send>
+            })
+            .collect()
+    };
+    assert_eq!(run_parallel(jobs(5), 2), Ok(()));
+    assert_eq!(done.load(Ordering::SeqCst), 5);
+    // Jobs in later batches don't start once one has failed.
+    done.store(0, Ordering::SeqCst);
+    assert_eq!(run_parallel(jobs(0), 2), Err("job 0 failed".to_string()));
+    assert_eq!(done.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,246 @@
+use crate::PngStream;
+use std::fs::File;
+
+/// The name of the file the tile in `column` and `row`, counted from 0 at the upper left, of the
+/// image `filename` is written to: `mandel-tile-2-3.png` for `mandel.png`.
+pub fn tile_filename(filename: &str, (column, row): (usize, usize)) -> String {
+    let path = std::path::Path::new(filename);
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("");
+    path.with_file_name(format!("{}-tile-{}-{}.png", stem, column, row))
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[test]
+fn test_tile_filename() {
+    assert_eq!(tile_filename("mandel.png", (2, 3)), "mandel-tile-2-3.png");
+    assert_eq!(
+        tile_filename("out.d/deep.zoom.png", (0, 0)),
+        "out.d/deep.zoom-tile-0-0.png"
+    );
+}
+
+/// The pixels of an image with dimensions `bounds`, split into `tiles` across and down, that the
+/// tile in `column` and `row` covers: its upper left pixel, and the one past its lower right.
+/// Tiles differ in size by a pixel at most.
+pub fn tile_pixels(
+    bounds: (usize, usize),
+    tiles: (usize, usize),
+    (column, row): (usize, usize),
+) -> ((usize, usize), (usize, usize)) {
+    let edge = |length: usize, count: usize, i: usize| length * i / count;
+    (
+        (
+            edge(bounds.0, tiles.0, column),
+            edge(bounds.1, tiles.1, row),
+        ),
+        (
+            edge(bounds.0, tiles.0, column + 1),
+            edge(bounds.1, tiles.1, row + 1),
+        ),
+    )
+}
+
+#[test]
+fn test_tile_pixels() {
+    assert_eq!(tile_pixels((10, 6), (3, 2), (0, 0)), ((0, 0), (3, 3)));
+    assert_eq!(tile_pixels((10, 6), (3, 2), (1, 1)), ((3, 3), (6, 6)));
+    assert_eq!(tile_pixels((10, 6), (3, 2), (2, 0)), ((6, 0), (10, 3)));
+}
+
+/// What to stitch together, for the `stitch` subcommand.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StitchOptions {
+    /// The image the tiles were rendered for, and the file to write it to.
+    pub filename: String,
+    /// Number of tiles across and down.
+    pub tiles: (usize, usize),
+}
+
+/// Parse the arguments following `stitch`: the image file and the number of tiles.
+pub fn parse_args(args: &[String]) -> Result<StitchOptions, String> {
+    let (filename, tiles) = match args {
+        [filename, tiles, ..] if !filename.starts_with("--") && !tiles.starts_with("--") => {
+            (filename, tiles)
+        }
+        _ => return Err("expected FILE TILES after stitch".to_string()),
+    };
+    let options = StitchOptions {
+        filename: filename.clone(),
+        tiles: crate::parse_pair(tiles, 'x')
+            .filter(|&(across, down)| across > 0 && down > 0)
+            .ok_or("error parsing --tiles")?,
+    };
+
+    let mut args = args[2..].iter();
+    while let Some(name) = args.next() {
+        args.next()
+            .ok_or_else(|| format!("missing value for {}", name))?;
+        match name.as_str() {
+            // Read by `main` before the subcommand runs.
+            "--error-format" | "--lang" => {}
+            _ => return Err(format!("unknown option {}", name)),
+        }
+    }
+    Ok(options)
+}
+
+#[test]
+fn test_parse_args() {
+    let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+    let options = parse_args(&args("mandel.png 4x3 --lang ru")).unwrap();
+    assert_eq!(options.filename, "mandel.png");
+    assert_eq!(options.tiles, (4, 3));
+    assert!(parse_args(&args("mandel.png")).is_err());
+    assert!(parse_args(&args("mandel.png --lang ru")).is_err());
+    assert!(parse_args(&args("mandel.png 0x3")).is_err());
+    assert!(parse_args(&args("mandel.png 4x3 --frames 2")).is_err());
+}
+
+/// A tile read back from its file.
+struct Tile {
+    filename: String,
+    pixels: Vec<u8>,
+    bounds: (usize, usize),
+    color: png::ColorType,
+    text: Vec<(String, String)>,
+}
+
+/// Open the tile `filename` and read up to its pixels.
+fn open_tile(filename: &str) -> Result<png::Reader<File>, String> {
+    let error = |e: &dyn std::fmt::Display| format!("error reading tile {}: {}", filename, e);
+    let file = File::open(filename).map_err(|e| error(&e))?;
+    let reader = png::Decoder::new(file).read_info().map_err(|e| error(&e))?;
+    if reader.info().bit_depth != png::BitDepth::Eight {
+        return Err(format!("tile {} isn't 8 bits a sample", filename));
+    }
+    Ok(reader)
+}
+
+fn read_tile(filename: String) -> Result<Tile, String> {
+    let mut reader = open_tile(&filename)?;
+    let info = reader.info();
+    let bounds = (info.width as usize, info.height as usize);
+    let color = info.color_type;
+    let text = info
+        .uncompressed_latin1_text
+        .iter()
+        .map(|chunk| (chunk.keyword.clone(), chunk.text.clone()))
+        .collect();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    reader
+        .next_frame(&mut pixels)
+        .map_err(|e| format!("error reading tile {}: {}", filename, e))?;
+    Ok(Tile {
+        filename,
+        pixels,
+        bounds,
+        color,
+        text,
+    })
+}
+
+/// Assemble the tiles of the image `filename`, `tiles` across and down and written to the files
+/// `tile_filename` names, into the whole image in `filename`, carrying over the text of the first
+/// tile. Only a row of tiles is held in memory at a time. Returns the dimensions of the image.
+pub fn stitch(filename: &str, tiles: (usize, usize)) -> Result<(usize, usize), String> {
+    // The first tile of each row gives the height of the row, and the first row of tiles the
+    // width of each column.
+    let heights = (0..tiles.1)
+        .map(|row| {
+            open_tile(&tile_filename(filename, (0, row)))
+                .map(|reader| reader.info().height as usize)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut stream: Option<(PngStream, Vec<usize>, png::ColorType)> = None;
+    for (row, &height) in heights.iter().enumerate() {
+        let row_tiles = (0..tiles.0)
+            .map(|column| read_tile(tile_filename(filename, (column, row))))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (stream, widths, color) = match &mut stream {
+            Some(stream) => stream,
+            None => {
+                let widths: Vec<_> = row_tiles.iter().map(|tile| tile.bounds.0).collect();
+                let bounds = (widths.iter().sum(), heights.iter().sum());
+                let first = &row_tiles[0];
+                let text: Vec<_> = first
+                    .text
+                    .iter()
+                    .map(|(keyword, value)| (keyword.as_str(), value.clone()))
+                    .collect();
+                let created =
+                    PngStream::create(filename, bounds, first.color.samples(), None, &text)
+                        .map_err(|e| format!("error writing PNG file {}: {}", filename, e))?;
+                stream.insert((created, widths, first.color))
+            }
+        };
+        for (tile, &width) in row_tiles.iter().zip(widths.iter()) {
+            if tile.bounds != (width, height) || tile.color != *color {
+                return Err(format!(
+                    "tile {} doesn't line up with the others",
+                    tile.filename
+                ));
+            }
+        }
+        // Interleave the rows of the tiles into rows of the image.
+        let mut rows = Vec::with_capacity(widths.iter().sum::<usize>() * height * color.samples());
+        for y in 0..height {
+            for tile in &row_tiles {
+                let length = tile.bounds.0 * color.samples();
+                rows.extend_from_slice(&tile.pixels[y * length..(y + 1) * length]);
+            }
+        }
+        stream
+            .write_rows(&rows)
+            .map_err(|e| format!("error writing PNG file {}: {}", filename, e))?;
+    }
+    let (stream, widths, _) = stream.expect("there is at least one row of tiles");
+    stream
+        .finish()
+        .map_err(|e| format!("error writing PNG file {}: {}", filename, e))?;
+    Ok((widths.iter().sum(), heights.iter().sum()))
+}
+
+#[test]
+fn test_stitch() {
+    let directory = std::env::temp_dir();
+    let filename = directory.join("rust-experiments-test-stitch.png");
+    let filename = filename.to_str().unwrap();
+    let bounds = (7, 5);
+    let pixels: Vec<u8> = (0..bounds.0 * bounds.1 * 3).map(|i| i as u8).collect();
+    let tiles = (3, 2);
+    for row in 0..tiles.1 {
+        for column in 0..tiles.0 {
+            let ((left, top), (right, bottom)) = tile_pixels(bounds, tiles, (column, row));
+            let tile: Vec<u8> = (top..bottom)
+                .flat_map(|y| &pixels[(y * bounds.0 + left) * 3..(y * bounds.0 + right) * 3])
+                .copied()
+                .collect();
+            let text = [("Seed", "7".to_string())];
+            let tile_bounds = (right - left, bottom - top);
+            let tile_name = tile_filename(filename, (column, row));
+            crate::write_image(&tile_name, &tile, tile_bounds, None, &text).unwrap();
+        }
+    }
+    assert_eq!(stitch(filename, tiles), Ok(bounds));
+    let decoder = png::Decoder::new(File::open(filename).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let text = &reader.info().uncompressed_latin1_text[0];
+    assert_eq!((text.keyword.as_str(), text.text.as_str()), ("Seed", "7"));
+    let mut decoded = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut decoded).unwrap();
+    assert_eq!(decoded, pixels);
+
+    // Tiles that don't fit together, or aren't there, are an error.
+    let misfit = tile_filename(filename, (1, 1));
+    crate::write_image(&misfit, &[0; 4 * 3 * 3], (4, 3), None, &[]).unwrap();
+    assert!(stitch(filename, tiles)
+        .unwrap_err()
+        .contains("doesn't line up"));
+    assert!(stitch(filename, (4, 2))
+        .unwrap_err()
+        .contains("error reading tile"));
+}
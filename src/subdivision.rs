@@ -0,0 +1,155 @@
+use crate::precision::Real;
+use crate::{pixel_to_point, Renderer};
+use num::Complex;
+
+/// Render into `pixels` the image with dimensions `bounds` between `upper_left` and `lower_right`,
+/// as `render` does, but by the Mariani–Silver algorithm: render the border of a rectangle, fill
+/// it with the border's color if that is the same all around, and split it in two and do the
+/// same for each half otherwise.
+///
+/// The Mandelbrot set is connected, and so are the areas of each escape count around it, so a
+/// rectangle whose border is all one color is all that color inside, but for the odd feature
+/// smaller than the rectangle with no pixel on its border. That holds for the connected Julia
+/// sets too, and not for the others, nor for the formulas and maps besides `z = z*z + c`, whose
+/// rectangles may be filled over detail they would otherwise show.
+pub fn render<R: Real>(
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<R>,
+    lower_right: Complex<R>,
+    renderer: &Renderer,
+) {
+    let channels = renderer.channels();
+    assert!(pixels.len() == bounds.0 * bounds.1 * channels);
+    if bounds.0 == 0 || bounds.1 == 0 {
+        return;
+    }
+    let mut subdivision = Subdivision {
+        pixels,
+        rendered: vec![false; bounds.0 * bounds.1],
+        channels,
+        bounds,
+        upper_left,
+        lower_right,
+        renderer,
+    };
+    subdivision.subdivide((0, 0), (bounds.0 - 1, bounds.1 - 1));
+}
+
+/// The state of a render by `render`: the image so far, and which of its pixels are done.
+struct Subdivision<'a, R> {
+    pixels: &'a mut [u8],
+    rendered: Vec<bool>,
+    channels: usize,
+    bounds: (usize, usize),
+    upper_left: Complex<R>,
+    lower_right: Complex<R>,
+    renderer: &'a Renderer,
+}
+
+impl<R: Real> Subdivision<'_, R> {
+    /// The bytes of the pixel in column `x` and row `y`, rendering it first if it isn't yet.
+    fn pixel(&mut self, x: usize, y: usize) -> &[u8] {
+        let index = y * self.bounds.0 + x;
+        let range = index * self.channels..(index + 1) * self.channels;
+        if !self.rendered[index] {
+            let corner =
+                |x, y| pixel_to_point(self.bounds, (x, y), self.upper_left, self.lower_right);
+            let (upper_left, lower_right) = (corner(x, y), corner(x + 1, y + 1));
+            crate::render(
+                &mut self.pixels[range.clone()],
+                (1, 1),
+                upper_left,
+                lower_right,
+                self.renderer,
+            );
+            self.rendered[index] = true;
+        }
+        &self.pixels[range]
+    }
+
+    /// Render the rectangle from the pixel at `upper_left` to the one at `lower_right`, both
+    /// included, given as their columns and rows.
+    fn subdivide(&mut self, upper_left: (usize, usize), lower_right: (usize, usize)) {
+        let ((left, top), (right, bottom)) = (upper_left, lower_right);
+        let first = self.pixel(left, top).to_vec();
+        let mut uniform = true;
+        for x in left..=right {
+            uniform &= self.pixel(x, top) == first;
+            uniform &= self.pixel(x, bottom) == first;
+        }
+        for y in top..=bottom {
+            uniform &= self.pixel(left, y) == first;
+            uniform &= self.pixel(right, y) == first;
+        }
+        // Nothing is left inside a rectangle two pixels across or down.
+        if right - left < 2 || bottom - top < 2 {
+            return;
+        }
+        if uniform {
+            for y in top + 1..bottom {
+                for x in left + 1..right {
+                    let index = y * self.bounds.0 + x;
+                    self.pixels[index * self.channels..(index + 1) * self.channels]
+                        .copy_from_slice(&first);
+                    self.rendered[index] = true;
+                }
+            }
+            return;
+        }
+        // Split across the longer side, the halves sharing the middle line as their border.
+        if right - left >= bottom - top {
+            let middle = (left + right) / 2;
+            self.subdivide((left, top), (middle, bottom));
+            self.subdivide((middle, top), (right, bottom));
+        } else {
+            let middle = (top + bottom) / 2;
+            self.subdivide((left, top), (right, middle));
+            self.subdivide((left, middle), (right, bottom));
+        }
+    }
+}
+
+#[test]
+fn test_render() {
+    let bounds = (97, 61);
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.2 }, Complex { re: 1.0, im: -1.2 });
+    for renderer in [
+        Renderer::default(),
+        Renderer {
+            palette: Some(crate::palette::Palette::Fire),
+            ..Renderer::default()
+        },
+    ] {
+        let channels = renderer.channels();
+        let mut pixels = vec![0; bounds.0 * bounds.1 * channels];
+        render(&mut pixels, bounds, upper_left, lower_right, &renderer);
+        let mut full = vec![0; bounds.0 * bounds.1 * channels];
+        crate::render(&mut full, bounds, upper_left, lower_right, &renderer);
+        // Whole views of the set have little detail smaller than their rectangles to miss.
+        let differing = pixels
+            .chunks(channels)
+            .zip(full.chunks(channels))
+            .filter(|(a, b)| a != b)
+            .count();
+        assert!(differing * 200 < bounds.0 * bounds.1, "{}", differing);
+    }
+
+    // A view inside the main cardioid is one rectangle, filled from its border.
+    let mut pixels = vec![0; 16 * 8];
+    let renderer = Renderer::default();
+    let (upper_left, lower_right) = (Complex { re: -0.2, im: 0.1 }, Complex { re: 0.0, im: 0.0 });
+    render(&mut pixels, (16, 8), upper_left, lower_right, &renderer);
+    assert!(pixels.iter().all(|&p| p == 16));
+    render(&mut [], (0, 8), upper_left, lower_right, &renderer);
+
+    // Strips too thin to have an inside are rendered pixel by pixel.
+    let (upper_left, lower_right) = (Complex { re: -2.0, im: 0.6 }, Complex { re: 1.0, im: 0.5 });
+    for bounds in [(300, 1), (1, 300), (2, 300)] {
+        let mut pixels = vec![0; bounds.0 * bounds.1];
+        render(&mut pixels, bounds, upper_left, lower_right, &renderer);
+        let mut full = vec![0; bounds.0 * bounds.1];
+        crate::render(&mut full, bounds, upper_left, lower_right, &renderer);
+        assert_eq!(pixels, full);
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// A point in image coordinates, in pixels from the upper left corner.
+type Point = (f64, f64);
+
+/// Polylines are simplified until they deviate from the traced contour by at most this many
+/// pixels, before curves are fitted through the remaining points.
+const SIMPLIFY_TOLERANCE: f64 = 0.25;
+
+/// Trace the contour lines where `field`, sampled at the pixels of an image with dimensions
+/// `bounds`, crosses `level`, using marching squares. Each contour is returned as a list of
+/// points; closed contours end with their first point.
+pub fn trace_contours(field: &[f64], bounds: (usize, usize), level: f64) -> Vec<Vec<Point>> {
+    assert!(field.len() == bounds.0 * bounds.1);
+    let value = |x: usize, y: usize| field[y * bounds.0 + x] - level;
+
+    // Crossings are identified by the grid edge they lie on: horizontal edges by their left end,
+    // vertical edges by their upper end.
+    type Edge = (bool, usize, usize);
+    let crossing = |edge: Edge| -> Point {
+        let (horizontal, x, y) = edge;
+        let (x1, y1) = if horizontal { (x + 1, y) } else { (x, y + 1) };
+        let (a, b) = (value(x, y), value(x1, y1));
+        let t = a / (a - b);
+        (
+            x as f64 + t * (x1 - x) as f64,
+            y as f64 + t * (y1 - y) as f64,
+        )
+    };
+
+    // Collect the segments crossing each cell of the grid.
+    let mut segments: Vec<(Edge, Edge)> = Vec::new();
+    for y in 0..bounds.1.saturating_sub(1) {
+        for x in 0..bounds.0.saturating_sub(1) {
+            let corners = [
+                value(x, y),
+                value(x + 1, y),
+                value(x + 1, y + 1),
+                value(x, y + 1),
+            ];
+            let case = corners
+                .iter()
+                .enumerate()
+                .fold(0, |case, (i, &v)| case | ((v > 0.0) as usize) << i);
+            let (top, right, bottom, left) = (
+                (true, x, y),
+                (false, x + 1, y),
+                (true, x, y + 1),
+                (false, x, y),
+            );
+            let center_above = corners.iter().sum::<f64>() > 0.0;
+            match case {
+                0 | 15 => {}
+                1 | 14 => segments.push((left, top)),
+                2 | 13 => segments.push((top, right)),
+                3 | 12 => segments.push((left, right)),
+                4 | 11 => segments.push((right, bottom)),
+                6 | 9 => segments.push((top, bottom)),
+                7 | 8 => segments.push((left, bottom)),
+                // Saddles: opposite corners are above the level, and the center decides which
+                // pairs of edges are connected.
+                5 if center_above => {
+                    segments.push((left, bottom));
+                    segments.push((top, right));
+                }
+                5 => {
+                    segments.push((left, top));
+                    segments.push((right, bottom));
+                }
+                10 if center_above => {
+                    segments.push((left, top));
+                    segments.push((right, bottom));
+                }
+                _ => {
+                    segments.push((left, bottom));
+                    segments.push((top, right));
+                }
+            }
+        }
+    }
+
+    // Link segments sharing a crossing into polylines.
+    let mut by_edge: HashMap<Edge, Vec<usize>> = HashMap::new();
+    for (i, &(a, b)) in segments.iter().enumerate() {
+        by_edge.entry(a).or_default().push(i);
+        by_edge.entry(b).or_default().push(i);
+    }
+    let mut used = vec![false; segments.len()];
+    let mut contours = Vec::new();
+    for start in 0..segments.len() {
+        if used[start] {
+            continue;
+        }
+        used[start] = true;
+        let mut chain = vec![segments[start].0, segments[start].1];
+        // Extend the chain forwards, then reverse it and extend it forwards again.
+        for _ in 0..2 {
+            loop {
+                let end = *chain.last().unwrap();
+                let next = by_edge[&end].iter().copied().find(|&i| !used[i]);
+                match next {
+                    Some(i) => {
+                        used[i] = true;
+                        let (a, b) = segments[i];
+                        chain.push(if a == end { b } else { a });
+                    }
+                    None => break,
+                }
+            }
+            chain.reverse();
+        }
+        contours.push(chain.into_iter().map(crossing).collect());
+    }
+    contours
+}
+
+#[test]
+fn test_trace_contours() {
+    // A single bump in the middle of a 3x3 grid is surrounded by a closed diamond.
+    let field = [0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0];
+    let contours = trace_contours(&field, (3, 3), 0.5);
+    assert_eq!(contours.len(), 1);
+    let contour = &contours[0];
+    assert_eq!(contour.len(), 5);
+    assert_eq!(contour.first(), contour.last());
+    for &(x, y) in contour {
+        assert!(((x - 1.0).abs() + (y - 1.0).abs() - 0.5).abs() < 1e-12);
+    }
+
+    // A step from left to right gives one open vertical line.
+    let field = [0.0, 1.0, 0.0, 1.0, 0.0, 1.0];
+    let contours = trace_contours(&field, (2, 3), 0.25);
+    assert_eq!(contours.len(), 1);
+    assert_eq!(contours[0].len(), 3);
+    assert!(contours[0].iter().all(|&(x, _)| x == 0.25));
+
+    assert!(trace_contours(&[0.0; 4], (2, 2), 0.5).is_empty());
+}
+
+/// Drop points from `points` that deviate from the line through their neighbors by at most
+/// `tolerance`, with the Ramer-Douglas-Peucker algorithm.
+pub fn simplify(points: &[Point], tolerance: f64) -> Vec<Point> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let (first, last) = (points[0], points[points.len() - 1]);
+    let (dx, dy) = (last.0 - first.0, last.1 - first.1);
+    let length = (dx * dx + dy * dy).sqrt();
+    let distance = |p: &Point| {
+        if length == 0.0 {
+            ((p.0 - first.0).powi(2) + (p.1 - first.1).powi(2)).sqrt()
+        } else {
+            (dy * (p.0 - first.0) - dx * (p.1 - first.1)).abs() / length
+        }
+    };
+    let (index, max) = points[1..points.len() - 1]
+        .iter()
+        .map(distance)
+        .enumerate()
+        .fold(
+            (0, 0.0),
+            |best, (i, d)| if d > best.1 { (i + 1, d) } else { best },
+        );
+    if max <= tolerance {
+        return vec![first, last];
+    }
+    let mut left = simplify(&points[..=index], tolerance);
+    left.pop();
+    left.extend(simplify(&points[index..], tolerance));
+    left
+}
+
+#[test]
+fn test_simplify() {
+    let line = [(0.0, 0.0), (1.0, 0.01), (2.0, 0.0), (3.0, 0.0)];
+    assert_eq!(simplify(&line, 0.1), vec![(0.0, 0.0), (3.0, 0.0)]);
+    let corner = [(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (2.0, 1.0), (2.0, 2.0)];
+    assert_eq!(
+        simplify(&corner, 0.1),
+        vec![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0)]
+    );
+    // Closed loops start and end at the same point.
+    let square = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0), (0.0, 0.0)];
+    assert_eq!(simplify(&square, 0.1), square.to_vec());
+}
+
+/// Build SVG path data for a smooth curve through `points`, made of cubic Bézier segments
+/// following a Catmull-Rom spline. Closed polylines, ending with their first point, give closed
+/// curves.
+pub fn path_data(points: &[Point]) -> String {
+    let mut data = String::new();
+    if points.len() < 2 {
+        return data;
+    }
+    let closed = points.len() > 3 && points.first() == points.last();
+    let n = points.len();
+    // Neighbors before the first and after the last point, wrapping around closed curves.
+    let at = |i: isize| -> Point {
+        if closed {
+            points[i.rem_euclid(n as isize - 1) as usize]
+        } else {
+            points[i.clamp(0, n as isize - 1) as usize]
+        }
+    };
+    write!(data, "M{:.2},{:.2}", points[0].0, points[0].1).unwrap();
+    for i in 0..n as isize - 1 {
+        let (p0, p1, p2, p3) = (at(i - 1), at(i), at(i + 1), at(i + 2));
+        let c1 = (p1.0 + (p2.0 - p0.0) / 6.0, p1.1 + (p2.1 - p0.1) / 6.0);
+        let c2 = (p2.0 - (p3.0 - p1.0) / 6.0, p2.1 - (p3.1 - p1.1) / 6.0);
+        write!(
+            data,
+            "C{:.2},{:.2} {:.2},{:.2} {:.2},{:.2}",
+            c1.0, c1.1, c2.0, c2.1, p2.0, p2.1
+        )
+        .unwrap();
+    }
+    if closed {
+        data.push('Z');
+    }
+    data
+}
+
+#[test]
+fn test_path_data() {
+    assert_eq!(path_data(&[(0.0, 0.0)]), "");
+    // A straight line keeps its control points on the line.
+    assert_eq!(
+        path_data(&[(0.0, 0.0), (3.0, 0.0)]),
+        "M0.00,0.00C0.50,0.00 2.50,0.00 3.00,0.00"
+    );
+    let square = [(0.0, 0.0), (6.0, 0.0), (6.0, 6.0), (0.0, 6.0), (0.0, 0.0)];
+    let data = path_data(&square);
+    assert!(data.starts_with("M0.00,0.00C1.00,-1.00 "));
+    assert!(data.ends_with('Z'));
+    assert_eq!(data.matches('C').count(), 4);
+}
+
+/// Trace the contours where `field` crosses `level` and write them as smooth curves to the SVG
+/// file named `filename`, sized to an image with dimensions `bounds`.
+pub fn write_contours(
+    filename: &str,
+    field: &[f64],
+    bounds: (usize, usize),
+    level: f64,
+) -> Result<(), std::io::Error> {
+    let mut output = BufWriter::new(File::create(filename)?);
+    writeln!(
+        output,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+        bounds.0, bounds.1
+    )?;
+    for contour in trace_contours(field, bounds, level) {
+        let points = simplify(&contour, SIMPLIFY_TOLERANCE);
+        if points.len() < 2 {
+            continue;
+        }
+        writeln!(
+            output,
+            r#"<path d="{}" fill="none" stroke="black" stroke-width="1"/>"#,
+            path_data(&points)
+        )?;
+    }
+    writeln!(output, "</svg>")?;
+    output.flush()
+}
@@ -0,0 +1,242 @@
+use crate::{pixel_to_point, render_bands, Fractal, Renderer, DEFAULT_BAILOUT};
+use num::Complex;
+use std::ops::{Add, Mul, Sub};
+
+/// Largest number of boundary pixels checked by `uncertain_pixels`.
+pub const SAMPLES: usize = 1000;
+
+/// A closed interval of real numbers, guaranteed to contain the exact result of the operations
+/// that produced it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Interval {
+    pub lo: f64,
+    pub hi: f64,
+}
+
+impl Interval {
+    pub fn point(x: f64) -> Self {
+        Interval { lo: x, hi: x }
+    }
+
+    /// The interval between `lo` and `hi`, widened by an ulp on each side to cover the rounding
+    /// of whatever computed them to the nearest f64.
+    fn rounded(lo: f64, hi: f64) -> Self {
+        Interval {
+            lo: lo.next_down(),
+            hi: hi.next_up(),
+        }
+    }
+
+    /// The square, which unlike `self * self` is never negative.
+    pub fn sqr(self) -> Self {
+        let (lo, hi) = (self.lo * self.lo, self.hi * self.hi);
+        if self.lo >= 0.0 {
+            Interval::rounded(lo, hi)
+        } else if self.hi <= 0.0 {
+            Interval::rounded(hi, lo)
+        } else {
+            Interval::rounded(0.0, lo.max(hi)).max_zero()
+        }
+    }
+
+    fn max_zero(self) -> Self {
+        Interval {
+            lo: self.lo.max(0.0),
+            hi: self.hi,
+        }
+    }
+}
+
+impl Add for Interval {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Interval::rounded(self.lo + other.lo, self.hi + other.hi)
+    }
+}
+
+impl Sub for Interval {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Interval::rounded(self.lo - other.hi, self.hi - other.lo)
+    }
+}
+
+impl Mul for Interval {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+        let products = [
+            self.lo * other.lo,
+            self.lo * other.hi,
+            self.hi * other.lo,
+            self.hi * other.hi,
+        ];
+        // A NaN product, from zero times infinity, could be anything.
+        if products.iter().any(|p| p.is_nan()) {
+            return Interval::rounded(f64::NEG_INFINITY, f64::INFINITY);
+        }
+        Interval::rounded(
+            products.iter().copied().fold(f64::INFINITY, f64::min),
+            products.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        )
+    }
+}
+
+#[test]
+fn test_interval() {
+    let third = Interval::point(1.0) * Interval::point(1.0 / 3.0);
+    assert!(third.lo < 1.0 / 3.0 && 1.0 / 3.0 < third.hi);
+    let x = Interval { lo: -1.0, hi: 2.0 };
+    let square = x.sqr();
+    assert_eq!(square.lo, 0.0);
+    assert!(square.hi >= 4.0);
+    let product = x * x;
+    assert!(product.lo <= -2.0 && product.hi >= 4.0);
+    let difference = x - Interval::point(1.0);
+    assert!(difference.lo <= -2.0 && difference.hi >= 1.0);
+}
+
+/// Check with interval arithmetic that iterating `z = z*z + c` from `z0`, at most `limit` times,
+/// escapes the circle of radius `bailout` after `expected` iterations (or never, for `None`), as
+/// `escape_time` found in f64. Returns whether that holds despite the rounding errors piling up
+/// along the orbit; near the boundary of the set they often grow too large to tell.
+pub fn certify_escape_time(
+    z0: Complex<f64>,
+    c: Complex<f64>,
+    limit: u32,
+    bailout: f64,
+    expected: Option<u32>,
+) -> bool {
+    let bailout_sqr = bailout * bailout;
+    let (mut x, mut y) = (Interval::point(z0.re), Interval::point(z0.im));
+    let (cx, cy) = (Interval::point(c.re), Interval::point(c.im));
+    for i in 0..limit {
+        let (xx, yy) = (x.sqr(), y.sqr());
+        let xy = x * y;
+        x = xx - yy + cx;
+        y = xy + xy + cy;
+        let norm = x.sqr() + y.sqr();
+        if norm.lo > bailout_sqr {
+            return expected == Some(i);
+        }
+        if norm.hi > bailout_sqr {
+            return false;
+        }
+    }
+    expected.is_none()
+}
+
+#[test]
+fn test_certify_escape_time() {
+    let zero = Complex { re: 0.0, im: 0.0 };
+    // 0.5, 0.75, 1.0625, 1.62890625, 3.15...
+    let c = Complex { re: 0.5, im: 0.0 };
+    assert_eq!(crate::escape_time(zero, c, 100, 2.0), Some(4));
+    assert!(certify_escape_time(zero, c, 100, 2.0, Some(4)));
+    assert!(!certify_escape_time(zero, c, 100, 2.0, Some(5)));
+    assert!(certify_escape_time(zero, zero, 100, 2.0, None));
+    // The orbit of 1 lands on the bailout circle at 2, where rounding could go either way.
+    let one = Complex { re: 1.0, im: 0.0 };
+    assert!(!certify_escape_time(zero, one, 100, 2.0, Some(2)));
+}
+
+/// Pixels of an image with dimensions `bounds` whose escape status in `escaped` differs from one
+/// of their neighbors, row by row.
+pub fn boundary_pixels(escaped: &[bool], bounds: (usize, usize)) -> Vec<(usize, usize)> {
+    let at = |x: usize, y: usize| escaped[y * bounds.0 + x];
+    let mut pixels = Vec::new();
+    for y in 0..bounds.1 {
+        for x in 0..bounds.0 {
+            let differs = (x > 0 && at(x - 1, y) != at(x, y))
+                || (x + 1 < bounds.0 && at(x + 1, y) != at(x, y))
+                || (y > 0 && at(x, y - 1) != at(x, y))
+                || (y + 1 < bounds.1 && at(x, y + 1) != at(x, y));
+            if differs {
+                pixels.push((x, y));
+            }
+        }
+    }
+    pixels
+}
+
+#[test]
+fn test_boundary_pixels() {
+    let escaped = [true, true, false, true, true, true];
+    assert_eq!(boundary_pixels(&escaped, (3, 2)), [(1, 0), (2, 0), (2, 1)]);
+    assert!(boundary_pixels(&[true; 4], (2, 2)).is_empty());
+}
+
+/// Check the escape times of up to `samples` pixels, spread evenly along the boundary of the set
+/// in an image with dimensions `bounds` between `upper_left` and `lower_right`, as described by
+/// `renderer`, using `threads` threads. Returns the number of pixels checked and the pixels whose
+/// escape time interval arithmetic couldn't confirm.
+///
+/// Only the escape-time fractals, the Mandelbrot and Julia sets, can be checked; for others,
+/// nothing is.
+pub fn uncertain_pixels(
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    renderer: &Renderer,
+    threads: usize,
+    samples: usize,
+) -> (usize, Vec<(usize, usize)>) {
+    let orbit = |point: Complex<f64>| match renderer.fractal {
+        Fractal::Mandelbrot => Some((renderer.z0, point)),
+        Fractal::Julia(c) => Some((point, c)),
+        Fractal::Rational(_) | Fractal::Newton(_) => None,
+    };
+    if orbit(upper_left).is_none() {
+        return (0, Vec::new());
+    }
+    let bailout = renderer.bailout.unwrap_or(DEFAULT_BAILOUT);
+
+    // Keep the very point each escape time was found for, to check it for that same point.
+    let mut counts = vec![(Complex { re: 0.0, im: 0.0 }, None); bounds.0 * bounds.1];
+    render_bands(
+        &mut counts,
+        bounds,
+        upper_left,
+        lower_right,
+        threads,
+        None,
+        None,
+        |band, band_bounds, band_upper_left, band_lower_right| {
+            for (i, count) in band.iter_mut().enumerate() {
+                let pixel = (i % band_bounds.0, i / band_bounds.0);
+                let point = pixel_to_point(band_bounds, pixel, band_upper_left, band_lower_right);
+                let (z0, c) = orbit(point).unwrap();
+                *count = (point, crate::escape_time(z0, c, renderer.limit, bailout));
+            }
+        },
+    );
+    let escaped: Vec<bool> = counts.iter().map(|(_, count)| count.is_some()).collect();
+    let boundary = boundary_pixels(&escaped, bounds);
+    let step = boundary.len().div_ceil(samples.max(1)).max(1);
+    let checked: Vec<_> = boundary.into_iter().step_by(step).collect();
+    let uncertain = checked
+        .iter()
+        .copied()
+        .filter(|&(x, y)| {
+            let (point, count) = counts[y * bounds.0 + x];
+            let (z0, c) = orbit(point).unwrap();
+            !certify_escape_time(z0, c, renderer.limit, bailout, count)
+        })
+        .collect();
+    (checked.len(), uncertain)
+}
+
+#[test]
+fn test_uncertain_pixels() {
+    let renderer = Renderer::default();
+    let (upper_left, lower_right) = (Complex { re: -2.5, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let (checked, uncertain) =
+        uncertain_pixels((70, 40), upper_left, lower_right, &renderer, 1, 50);
+    assert!(0 < checked && checked <= 50);
+    assert!(uncertain.len() <= checked);
+    // Far from the set, every pixel escapes at once and there is no boundary to check.
+    let (far_left, far_right) = (Complex { re: 10.0, im: 1.0 }, Complex { re: 11.0, im: 0.0 });
+    assert_eq!(
+        uncertain_pixels((8, 8), far_left, far_right, &renderer, 1, 50),
+        (0, Vec::new())
+    );
+}
@@ -0,0 +1,282 @@
+use crate::plan::{self, PlanOptions};
+use crate::precision::Real;
+use crate::{animation, palette, pixel_to_point, progress, render, render_bands, Renderer};
+use num::Complex;
+use std::str::FromStr;
+
+/// Frames per second of animations when `--fps` isn't given.
+const DEFAULT_FPS: u16 = 25;
+
+/// What zoom to render and where to write its frames.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ZoomOptions {
+    /// File name of the frames, with a frame number like `%04d` in it, or of the single file
+    /// holding them all, when `animation` is set.
+    pub pattern: String,
+    pub animation: Option<animation::Container>,
+    /// Frames per second the animation plays at.
+    pub fps: u16,
+    pub plan: PlanOptions,
+    pub palette: Option<palette::Palette>,
+    pub threads: usize,
+}
+
+/// Parse the arguments following `zoom`: the pattern of the frames' file names or the animation
+/// file, then the dimensions, center and final zoom and the options as `plan-zoom` takes them,
+/// along with `--fps`, `--palette` and `--threads`.
+pub fn parse_args(args: &[String]) -> Result<ZoomOptions, String> {
+    let (pattern, positional) = match args {
+        [pattern, pixels, center, zoom, ..] if !zoom.starts_with("--") => {
+            (pattern, [pixels.clone(), center.clone(), zoom.clone()])
+        }
+        _ => return Err("expected PATTERN PIXELS CENTER ZOOM after zoom".to_string()),
+    };
+    let animation = match frame_filename(pattern, 0) {
+        Some(_) => None,
+        None => Some(animation::Container::from_filename(pattern).ok_or_else(|| {
+            format!(
+                "{} has no frame number like %04d and isn't a GIF or APNG file",
+                pattern
+            )
+        })?),
+    };
+    let mut fps = None;
+    let mut palette = None;
+    let mut threads = num_cpus::get();
+    let mut plan_args = positional.to_vec();
+    let mut args = args[4..].iter();
+    while let Some(name) = args.next() {
+        match (name.as_str(), args.next()) {
+            ("--palette", Some(value)) => {
+                palette = Some(
+                    palette::Palette::parse(value)
+                        .ok_or_else(|| format!("unknown palette {}", value))?,
+                )
+            }
+            ("--fps", Some(value)) => {
+                fps = Some(
+                    u16::from_str(value)
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or("error parsing --fps")?,
+                )
+            }
+            ("--threads", Some(value)) => {
+                threads = usize::from_str(value)
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or("error parsing --threads")?
+            }
+            (_, value) => plan_args.extend([name.clone()].into_iter().chain(value.cloned())),
+        }
+    }
+    if fps.is_some() && animation.is_none() {
+        return Err("--fps needs a GIF or APNG output".to_string());
+    }
+    Ok(ZoomOptions {
+        pattern: pattern.clone(),
+        animation,
+        fps: fps.unwrap_or(DEFAULT_FPS),
+        plan: plan::parse_args(&plan_args)?,
+        palette,
+        threads,
+    })
+}
+
+#[test]
+fn test_parse_args() {
+    let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+    let options = parse_args(&args(
+        "frame_%04d.png 320x240 -0.75,0.1 1e6 --frames 30 --palette fire --threads 2",
+    ))
+    .unwrap();
+    assert_eq!(options.pattern, "frame_%04d.png");
+    assert_eq!(options.plan.bounds, (320, 240));
+    assert_eq!(options.plan.frames, 30);
+    assert_eq!(options.palette, palette::Palette::parse("fire"));
+    assert_eq!(options.threads, 2);
+    assert_eq!(options.animation, None);
+    let options = parse_args(&args("zoom.gif 320x240 -0.75,0.1 1e6 --fps 12")).unwrap();
+    assert_eq!(options.animation, Some(animation::Container::Gif));
+    assert_eq!(options.fps, 12);
+    assert!(parse_args(&args("frame_%d.png 320x240 -0.75,0.1 1e6 --fps 12")).is_err());
+    assert!(parse_args(&args("zoom.gif 320x240 -0.75,0.1 1e6 --fps 0")).is_err());
+    assert!(parse_args(&args("frame.mp4 320x240 -0.75,0.1 1e6")).is_err());
+    assert!(parse_args(&args("frame_%d.png 320x240 -0.75,0.1")).is_err());
+    assert!(parse_args(&args("frame_%d.png 320x240 -0.75,0.1 1e6 --speed 2")).is_err());
+    assert!(parse_args(&args("frame_%d.png 320x240 -0.75,0.1 1e6 --frames")).is_err());
+}
+
+/// The file name of frame number `frame`, put in place of the `%d` in `pattern`, or of a `%0Nd`
+/// to pad it with zeros to `N` digits. `None` if `pattern` has neither.
+pub fn frame_filename(pattern: &str, frame: usize) -> Option<String> {
+    let start = pattern.find('%')?;
+    let rest = &pattern[start + 1..];
+    let end = rest.find('d')?;
+    let width = match &rest[..end] {
+        "" => 0,
+        digits => digits.strip_prefix('0')?.parse().ok()?,
+    };
+    Some(format!(
+        "{}{:0width$}{}",
+        &pattern[..start],
+        frame,
+        &rest[end + 1..],
+        width = width
+    ))
+}
+
+#[test]
+fn test_frame_filename() {
+    assert_eq!(
+        frame_filename("frame_%04d.png", 7).as_deref(),
+        Some("frame_0007.png")
+    );
+    assert_eq!(frame_filename("%d.png", 12).as_deref(), Some("12.png"));
+    assert_eq!(frame_filename("frame_%4d.png", 7), None);
+    assert_eq!(frame_filename("frame.png", 7), None);
+}
+
+/// The whole factor that a frame zooms in on the one before by, if it does by one, so that some
+/// of its pixels land on pixels of that frame. Zoom factors too far from a whole number to tell
+/// which pixels those are give `None`.
+pub fn whole_factor(previous_zoom: f64, zoom: f64) -> Option<usize> {
+    let factor = zoom / previous_zoom;
+    let whole = factor.round();
+    (whole >= 2.0 && (factor - whole).abs() < 1e-6).then_some(whole as usize)
+}
+
+#[test]
+fn test_whole_factor() {
+    assert_eq!(whole_factor(1.0, 2.0), Some(2));
+    assert_eq!(whole_factor(1e10, 3e10 * (1.0 + 1e-12)), Some(3));
+    assert_eq!(whole_factor(1.0, 1.05), None);
+    assert_eq!(whole_factor(1.0, 1.0), None);
+}
+
+/// For each pixel of a frame with dimensions `bounds` zoomed in by `factor` about its center
+/// from the frame before, the index of the pixel of the frame before at the same point, if there
+/// is one.
+pub fn reused_pixels(bounds: (usize, usize), factor: usize) -> Vec<Option<usize>> {
+    // Pixel `x` sits at `width / 2 + (x - width / 2) / factor` in the frame before; doubling both
+    // keeps the center whole for odd dimensions.
+    let source = |x: usize, width: usize| {
+        let (width, offset) = (width as i64, 2 * x as i64 - width as i64);
+        let doubled = width + offset / factor as i64;
+        (offset % factor as i64 == 0 && doubled % 2 == 0).then_some((doubled / 2) as usize)
+    };
+    (0..bounds.0 * bounds.1)
+        .map(|i| {
+            let x = source(i % bounds.0, bounds.0)?;
+            let y = source(i / bounds.0, bounds.1)?;
+            Some(y * bounds.0 + x)
+        })
+        .collect()
+}
+
+#[test]
+fn test_reused_pixels() {
+    // Zoomed in by two about the center of a 4x4 frame, the pixels at even offsets from the
+    // center, 0 and 2, land on pixels 1 and 2 of the frame before.
+    let reused = reused_pixels((4, 4), 2);
+    assert_eq!(reused[0], Some(5));
+    assert_eq!(reused[2], Some(6));
+    assert_eq!(reused[10], Some(10));
+    assert_eq!(reused[1], None);
+    assert_eq!(reused.iter().flatten().count(), 4);
+    // With odd dimensions, the center is the middle of pixel 1, and zooming in three times
+    // moves the corner of pixel 0 onto the corner of pixel 1.
+    let reused = reused_pixels((3, 3), 3);
+    assert_eq!(reused[0], Some(4));
+    assert_eq!(reused.iter().flatten().count(), 1);
+}
+
+/// Render a frame with dimensions `bounds` between `upper_left` and `lower_right`, as
+/// `Renderer::render_image` does, copying the pixels it shares with `previous`, the frame before
+/// along with the whole factor this one zooms in on it by, instead of computing them again. Only
+/// pixels taken as a single sample can be copied. Returns the pixels, the number copied, and the
+/// chunks of rows that failed to render.
+pub fn render_frame<R: Real>(
+    bounds: (usize, usize),
+    upper_left: Complex<R>,
+    lower_right: Complex<R>,
+    renderer: &Renderer,
+    threads: usize,
+    previous: Option<(&[u8], usize)>,
+    progress: Option<&progress::Progress>,
+) -> (Vec<u8>, usize, Vec<(usize, usize)>) {
+    let channels = renderer.channels();
+    let sources = match previous {
+        Some((_, factor)) if renderer.samples == (1, 1) => reused_pixels(bounds, factor),
+        _ => vec![None; bounds.0 * bounds.1],
+    };
+    let reused = sources.iter().flatten().count();
+    let mut slots: Vec<_> = sources.into_iter().map(|source| (source, [0; 4])).collect();
+    let failed = render_bands(
+        &mut slots,
+        bounds,
+        upper_left,
+        lower_right,
+        threads,
+        progress,
+        None,
+        |band, band_bounds, band_upper_left, band_lower_right| {
+            for (i, (source, pixel)) in band.iter_mut().enumerate() {
+                match (*source, previous) {
+                    (Some(source), Some((previous, _))) => pixel[..channels]
+                        .copy_from_slice(&previous[source * channels..][..channels]),
+                    _ => {
+                        let (x, y) = (i % band_bounds.0, i / band_bounds.0);
+                        let corner = |x, y| {
+                            pixel_to_point(band_bounds, (x, y), band_upper_left, band_lower_right)
+                        };
+                        render(
+                            &mut pixel[..channels],
+                            (1, 1),
+                            corner(x, y),
+                            corner(x + 1, y + 1),
+                            renderer,
+                        );
+                    }
+                }
+            }
+        },
+    );
+    let pixels = slots
+        .iter()
+        .flat_map(|(_, pixel)| pixel[..channels].iter().copied())
+        .collect();
+    (pixels, reused, failed)
+}
+
+#[test]
+fn test_render_frame() {
+    let renderer = Renderer::default();
+    let center = Complex { re: -0.5, im: 0.25 };
+    let bounds = (40, 30);
+    let (wide, _, _) = {
+        let (upper_left, lower_right) = crate::viewport(bounds, center, 0.1);
+        render_frame(bounds, upper_left, lower_right, &renderer, 2, None, None)
+    };
+    let (upper_left, lower_right) = crate::viewport(bounds, center, 0.05);
+    assert_eq!(
+        renderer.render_image(bounds, upper_left, lower_right, 2).0,
+        render_frame(bounds, upper_left, lower_right, &renderer, 2, None, None).0
+    );
+    let (close, reused, failed) = render_frame(
+        bounds,
+        upper_left,
+        lower_right,
+        &renderer,
+        2,
+        Some((&wide, 2)),
+        None,
+    );
+    assert_eq!(reused, 20 * 15);
+    assert!(failed.is_empty());
+    // The copied pixels are those a fresh render computes.
+    assert_eq!(
+        close,
+        renderer.render_image(bounds, upper_left, lower_right, 2).0
+    );
+}
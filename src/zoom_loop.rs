@@ -0,0 +1,327 @@
+use crate::annotations::FULL_WIDTH;
+use crate::{
+    animation, palette, pixel_to_point, progress, render, render_bands, turn_about, Renderer,
+};
+use num::Complex;
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+/// Frames in one cycle of the loop when `--frames` isn't given.
+const DEFAULT_FRAMES: usize = 50;
+
+/// Frames per second of the loop when `--fps` isn't given.
+const DEFAULT_FPS: u16 = 25;
+
+/// Directions sampled around the center when looking for the loop's period.
+pub const ANGLES: usize = 128;
+
+/// Rings sampled for each halving of the distance to the center.
+pub const RINGS_PER_OCTAVE: usize = 32;
+
+/// Smallest zoom factor of a cycle, in octaves: shorter cycles match only because neighboring
+/// rings look alike.
+pub const MIN_OCTAVES: usize = 1;
+
+/// Largest zoom factor of a cycle, in octaves.
+pub const MAX_OCTAVES: usize = 10;
+
+/// Octaves of rings nearest the center compared against the rings further out.
+pub const WINDOW_OCTAVES: usize = 2;
+
+/// A period with a larger `Period::mismatch` than this makes a loop that visibly jumps.
+pub const POOR_MATCH: f64 = 0.3;
+
+/// What loop to render and where to write it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoopOptions {
+    /// File name of the animation, a GIF or APNG file.
+    pub filename: String,
+    pub animation: animation::Container,
+    /// Dimensions of each frame, in pixels.
+    pub bounds: (usize, usize),
+    /// The point zoomed in on, which should be self-similar, like a Misiurewicz point.
+    pub center: Complex<f64>,
+    /// Magnification of the first frame relative to a view of the whole set.
+    pub zoom: f64,
+    /// Number of frames in one cycle.
+    pub frames: usize,
+    /// Frames per second the animation plays at.
+    pub fps: u16,
+    pub palette: Option<palette::Palette>,
+    pub threads: usize,
+}
+
+impl LoopOptions {
+    /// Half the width of the first frame in the plane.
+    pub fn radius(&self) -> f64 {
+        FULL_WIDTH / self.zoom / 2.0
+    }
+}
+
+/// Parse the arguments following `loop`: the animation file, the dimensions, center and zoom of
+/// the first frame, and options.
+pub fn parse_args(args: &[String]) -> Result<LoopOptions, String> {
+    let (filename, pixels, center, zoom) = match args {
+        [filename, pixels, center, zoom, ..] if !zoom.starts_with("--") => {
+            (filename, pixels, center, zoom)
+        }
+        _ => return Err("expected FILE PIXELS CENTER ZOOM after loop".to_string()),
+    };
+    let mut options = LoopOptions {
+        filename: filename.clone(),
+        animation: animation::Container::from_filename(filename)
+            .ok_or_else(|| format!("{} isn't a GIF or APNG file", filename))?,
+        bounds: crate::parse_pair(pixels, 'x')
+            .filter(|&(width, height)| width > 0 && height > 0)
+            .ok_or("error parsing image dimensions")?,
+        center: crate::parse_complex(center).ok_or("error parsing point")?,
+        zoom: f64::from_str(zoom)
+            .ok()
+            .filter(|&x| x > 0.0 && x.is_finite())
+            .ok_or("error parsing zoom")?,
+        frames: DEFAULT_FRAMES,
+        fps: DEFAULT_FPS,
+        palette: None,
+        threads: num_cpus::get(),
+    };
+
+    let mut args = args[4..].iter();
+    while let Some(name) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {}", name))?;
+        match name.as_str() {
+            "--frames" => {
+                options.frames = usize::from_str(value)
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or("error parsing --frames")?
+            }
+            "--fps" => {
+                options.fps = u16::from_str(value)
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or("error parsing --fps")?
+            }
+            "--palette" => {
+                options.palette = Some(
+                    palette::Palette::parse(value)
+                        .ok_or_else(|| format!("unknown palette {}", value))?,
+                )
+            }
+            "--threads" => {
+                options.threads = usize::from_str(value)
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or("error parsing --threads")?
+            }
+            // Read by `main` before the subcommand runs.
+            "--error-format" | "--lang" => {}
+            _ => return Err(format!("unknown option {}", name)),
+        }
+    }
+    Ok(options)
+}
+
+#[test]
+fn test_parse_args() {
+    let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+    let options = parse_args(&args("loop.gif 320x240 -0.1,0.65 100 --frames 30 --fps 12")).unwrap();
+    assert_eq!(options.animation, animation::Container::Gif);
+    assert_eq!(options.bounds, (320, 240));
+    assert_eq!(options.center, Complex { re: -0.1, im: 0.65 });
+    assert_eq!(options.radius(), 0.015);
+    assert_eq!(options.frames, 30);
+    assert_eq!(options.fps, 12);
+    assert!(parse_args(&args("loop.mp4 320x240 -0.1,0.65 100")).is_err());
+    assert!(parse_args(&args("loop.gif 320x240 -0.1,0.65")).is_err());
+    assert!(parse_args(&args("loop.gif 320x240 -0.1,0.65 100 --frames 0")).is_err());
+    assert!(parse_args(&args("loop.gif 320x240 -0.1,0.65 100 --start-zoom 2")).is_err());
+}
+
+/// The gray levels of the fractal described by `renderer` around `center` in log-polar
+/// coordinates: `rings` rows, from `radius` in towards the center, each halving of the distance
+/// taking `RINGS_PER_OCTAVE` of them, by `ANGLES` columns, going counterclockwise from the
+/// positive real direction. Zooming in about the center moves rows along the columns, and
+/// turning about it moves columns along the rows.
+pub fn log_polar_strip(
+    renderer: &Renderer,
+    center: Complex<f64>,
+    radius: f64,
+    rings: usize,
+) -> Vec<u8> {
+    (0..rings * ANGLES)
+        .map(|i| {
+            let (ring, angle) = (i / ANGLES, i % ANGLES);
+            let r = radius * 0.5f64.powf(ring as f64 / RINGS_PER_OCTAVE as f64);
+            let theta = 2.0 * PI * angle as f64 / ANGLES as f64;
+            renderer
+                .render_point(center + Complex::from_polar(r, theta))
+                .0
+        })
+        .collect()
+}
+
+/// How the picture about a self-similar point repeats: scaling it up by `factor` about the
+/// point and turning it counterclockwise by `angle` radians gives back the same picture.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Period {
+    pub factor: f64,
+    pub angle: f64,
+    /// Average difference in gray level between the picture and its repetition, as a fraction
+    /// of how much the picture itself varies: 0 for an exact repetition, 1 for one no closer
+    /// than a flat gray, and infinite for a flat picture, which repeats in any way at all.
+    pub mismatch: f64,
+}
+
+/// Find the period of a strip taken by `log_polar_strip`: the shift in rings and angles that
+/// best maps the innermost `WINDOW_OCTAVES` onto rings further out, trying zoom factors from
+/// `MIN_OCTAVES` up to as many octaves as the strip holds beyond the window. Points closer in
+/// take longer to escape by about the same number of iterations, so the gray levels are compared
+/// after taking away the difference of their averages.
+pub fn find_period(strip: &[u8]) -> Period {
+    let rings = strip.len() / ANGLES;
+    let window = WINDOW_OCTAVES * RINGS_PER_OCTAVE;
+    let inner = rings - window;
+    let average =
+        |rings: &[u8]| rings.iter().map(|&gray| gray as f64).sum::<f64>() / rings.len() as f64;
+    let near = &strip[inner * ANGLES..];
+    let near_average = average(near);
+    let mut best = (f64::INFINITY, 0, 0);
+    for shift in MIN_OCTAVES * RINGS_PER_OCTAVE..=inner {
+        let far = &strip[(inner - shift) * ANGLES..][..window * ANGLES];
+        let offset = near_average - average(far);
+        for turn in 0..ANGLES {
+            let mut total = 0.0;
+            for ring in 0..window {
+                let near = &near[ring * ANGLES..][..ANGLES];
+                let far = &far[ring * ANGLES..][..ANGLES];
+                for angle in 0..ANGLES {
+                    let far = far[(angle + turn) % ANGLES] as f64;
+                    total += (near[angle] as f64 - far - offset).abs();
+                }
+            }
+            let mismatch = total / (window * ANGLES) as f64;
+            if mismatch < best.0 {
+                best = (mismatch, shift, turn);
+            }
+        }
+    }
+    let (mismatch, shift, turn) = best;
+    let variation = near
+        .iter()
+        .map(|&gray| (gray as f64 - near_average).abs())
+        .sum::<f64>()
+        / near.len() as f64;
+    Period {
+        factor: 2.0f64.powf(shift as f64 / RINGS_PER_OCTAVE as f64),
+        angle: 2.0 * PI * turn as f64 / ANGLES as f64,
+        mismatch: if variation > 0.0 {
+            mismatch / variation
+        } else {
+            f64::INFINITY
+        },
+    }
+}
+
+#[test]
+fn test_find_period() {
+    // A pattern turning a quarter with each three octaves in, and telling apart the halves of
+    // those three octaves, so that nothing less than three octaves repeats it.
+    let cycle = 3 * RINGS_PER_OCTAVE;
+    let rings = cycle + WINDOW_OCTAVES * RINGS_PER_OCTAVE;
+    let strip: Vec<u8> = (0..rings * ANGLES)
+        .map(|i| {
+            let (ring, angle) = (i / ANGLES, i % ANGLES);
+            let turned = (angle + ring / cycle * ANGLES / 4) % ANGLES;
+            (turned + (ring % cycle < cycle / 2) as usize * ANGLES) as u8
+        })
+        .collect();
+    let period = find_period(&strip);
+    assert_eq!(period.mismatch, 0.0);
+    assert_eq!(period.factor, 8.0);
+    assert_eq!(period.angle, PI / 2.0);
+    assert_eq!(find_period(&vec![16; strip.len()]).mismatch, f64::INFINITY);
+}
+
+/// Render frame `frame` of a loop of `frames` frames around `center` with dimensions `bounds`,
+/// the first spanning `2 * radius` across, zooming in by `period.factor` and turning by
+/// `period.angle` over the whole loop, so that the frame after the last would be the first
+/// again. Pixels are taken as a single sample each. Returns the pixels and the chunks of rows
+/// that failed, as `render_bands` does.
+#[allow(clippy::too_many_arguments)]
+pub fn render_frame(
+    bounds: (usize, usize),
+    center: Complex<f64>,
+    radius: f64,
+    period: Period,
+    frame: usize,
+    frames: usize,
+    renderer: &Renderer,
+    threads: usize,
+    progress: Option<&progress::Progress>,
+) -> (Vec<u8>, Vec<(usize, usize)>) {
+    let t = frame as f64 / frames as f64;
+    let turn = Complex::from_polar(period.factor.powf(-t), -period.angle * t);
+    // The frame is rendered as if not turned, and each pixel's corner then moved to where it
+    // lands.
+    let pixel_size = 2.0 * radius / bounds.0 as f64;
+    let (upper_left, lower_right) = crate::viewport(bounds, center, pixel_size);
+    let channels = renderer.channels();
+    let mut slots = vec![[0; 4]; bounds.0 * bounds.1];
+    let failed = render_bands(
+        &mut slots,
+        bounds,
+        upper_left,
+        lower_right,
+        threads,
+        progress,
+        None,
+        |band, band_bounds, band_upper_left, band_lower_right| {
+            for (i, pixel) in band.iter_mut().enumerate() {
+                let (x, y) = (i % band_bounds.0, i / band_bounds.0);
+                let corner = pixel_to_point(band_bounds, (x, y), band_upper_left, band_lower_right);
+                let point = turn_about(corner, center, turn);
+                render(&mut pixel[..channels], (1, 1), point, point, renderer);
+            }
+        },
+    );
+    let pixels = slots
+        .iter()
+        .flat_map(|pixel| pixel[..channels].iter().copied())
+        .collect();
+    (pixels, failed)
+}
+
+#[test]
+fn test_render_frame() {
+    let renderer = Renderer::default();
+    let center = Complex { re: -0.75, im: 0.1 };
+    let bounds = (40, 30);
+    let still = Period {
+        factor: 1.0,
+        angle: 0.0,
+        mismatch: 0.0,
+    };
+    // The first frame is the plain view.
+    let (first, failed) = render_frame(bounds, center, 0.5, still, 0, 10, &renderer, 2, None);
+    assert!(failed.is_empty());
+    let (upper_left, lower_right) = crate::viewport(bounds, center, 1.0 / 40.0);
+    let (plain, _) = renderer.render_image(bounds, upper_left, lower_right, 2);
+    assert_eq!(first, plain);
+    // Halfway through a loop turning a whole turn, the view is upside down, the corner of each
+    // pixel landing on the opposite corner.
+    let turning = Period {
+        angle: 2.0 * PI,
+        ..still
+    };
+    let (half, _) = render_frame(bounds, center, 0.5, turning, 5, 10, &renderer, 2, None);
+    let mut mismatched = 0;
+    for y in 1..bounds.1 {
+        for x in 1..bounds.0 {
+            let opposite = (bounds.1 - y) * bounds.0 + bounds.0 - x;
+            mismatched += (half[y * bounds.0 + x] != first[opposite]) as usize;
+        }
+    }
+    assert!(mismatched * 20 < first.len());
+}